@@ -24,17 +24,26 @@ use axum::{
     response::IntoResponse,
     BoxError,
 };
+use chrono::{DateTime, Utc};
 use headers::{authorization::Basic, Authorization};
 use http::{Request, StatusCode};
 use mas_data_model::{Client, JwksOrJwksUri};
 use mas_http::HttpServiceExt;
 use mas_iana::oauth::OAuthClientAuthenticationMethod;
-use mas_jose::{jwk::PublicJsonWebKeySet, jwt::Jwt};
+use mas_jose::{
+    claims::{self, ClaimError, OneOrMany, TimeOptions},
+    jwk::PublicJsonWebKeySet,
+    jwt::Jwt,
+};
 use mas_keystore::Encrypter;
-use mas_storage::{oauth2::client::lookup_client_by_client_id, DatabaseError};
+use mas_storage::{
+    oauth2::client::{consume_client_assertion, lookup_client_by_client_id},
+    Clock, DatabaseError,
+};
 use serde::{de::DeserializeOwned, Deserialize};
 use serde_json::Value;
 use sqlx::PgExecutor;
+use subtle::ConstantTimeEq;
 use thiserror::Error;
 use tower::{Service, ServiceExt};
 
@@ -87,13 +96,25 @@ impl Credentials {
         lookup_client_by_client_id(executor, client_id).await
     }
 
+    /// Verify the credentials against the client configuration
+    ///
+    /// Client assertions must have at least one of the `audiences` in their
+    /// `aud` claim.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the credentials are invalid for this client
     #[tracing::instrument(skip_all, err)]
+    #[allow(clippy::too_many_arguments)]
     pub async fn verify(
         &self,
+        executor: impl PgExecutor<'_>,
+        clock: &Clock,
         http_client_factory: &HttpClientFactory,
         encrypter: &Encrypter,
         method: &OAuthClientAuthenticationMethod,
         client: &Client,
+        audiences: &[String],
     ) -> Result<(), CredentialsVerificationError> {
         match (self, method) {
             (Credentials::None { .. }, OAuthClientAuthenticationMethod::None) => {}
@@ -116,8 +137,8 @@ impl Credentials {
                     .decrypt_string(encrypted_client_secret)
                     .map_err(|_e| CredentialsVerificationError::DecryptionError)?;
 
-                // Check if the client_secret matches
-                if client_secret.as_bytes() != decrypted_client_secret {
+                // Check if the client_secret matches, in constant time
+                if !bool::from(client_secret.as_bytes().ct_eq(&decrypted_client_secret)) {
                     return Err(CredentialsVerificationError::ClientSecretMismatch);
                 }
             }
@@ -138,6 +159,10 @@ impl Credentials {
 
                 jwt.verify_with_jwks(&jwks)
                     .map_err(|_| CredentialsVerificationError::InvalidAssertionSignature)?;
+
+                validate_assertion_audience(jwt.payload(), audiences)?;
+                let exp = validate_assertion_times(jwt.payload(), &TimeOptions::new(clock.now()))?;
+                consume_assertion(executor, clock, client, jwt, exp).await?;
             }
 
             (
@@ -156,6 +181,10 @@ impl Credentials {
 
                 jwt.verify_with_shared_secret(decrypted_client_secret)
                     .map_err(|_| CredentialsVerificationError::InvalidAssertionSignature)?;
+
+                validate_assertion_audience(jwt.payload(), audiences)?;
+                let exp = validate_assertion_times(jwt.payload(), &TimeOptions::new(clock.now()))?;
+                consume_assertion(executor, clock, client, jwt, exp).await?;
             }
 
             (_, _) => {
@@ -166,6 +195,95 @@ impl Credentials {
     }
 }
 
+/// Validate the `exp`, `nbf` and `iat` claims of a client assertion.
+///
+/// The clock skew allowed between us and the client is the leeway of the
+/// given [`TimeOptions`]. The `exp` claim is required by RFC7523, the `nbf`
+/// and `iat` claims are checked if present.
+///
+/// Returns the expiration time of the assertion.
+///
+/// # Errors
+///
+/// Returns an error if the assertion is expired, not valid yet, has no `exp`
+/// claim, or if one of the claims is not a valid timestamp.
+fn validate_assertion_times(
+    claims: &HashMap<String, Value>,
+    time_options: &TimeOptions,
+) -> Result<DateTime<Utc>, CredentialsVerificationError> {
+    let mut claims = claims.clone();
+
+    let exp = claims::EXP
+        .extract_required_with_options(&mut claims, time_options)
+        .map_err(|e| match e {
+            ClaimError::ValidationError { .. } => CredentialsVerificationError::AssertionExpired,
+            _ => CredentialsVerificationError::InvalidAssertionClaims,
+        })?;
+
+    for claim in [claims::NBF, claims::IAT] {
+        claim
+            .extract_optional_with_options(&mut claims, time_options)
+            .map_err(|e| match e {
+                ClaimError::ValidationError { .. } => {
+                    CredentialsVerificationError::AssertionNotYetValid
+                }
+                _ => CredentialsVerificationError::InvalidAssertionClaims,
+            })?;
+    }
+
+    Ok(*exp)
+}
+
+/// Validate the `aud` claim of a client assertion.
+///
+/// The claim can either be a single string or an array of strings, and is
+/// valid if any of its values is one of the accepted `audiences`.
+///
+/// # Errors
+///
+/// Returns an error if the claim is missing, malformed or if none of its
+/// values is accepted.
+fn validate_assertion_audience(
+    claims: &HashMap<String, Value>,
+    audiences: &[String],
+) -> Result<(), CredentialsVerificationError> {
+    let aud = claims
+        .get("aud")
+        .ok_or(CredentialsVerificationError::AudienceMismatch)?;
+
+    let aud: OneOrMany<String> = serde_json::from_value(aud.clone())
+        .map_err(|_| CredentialsVerificationError::InvalidAssertionClaims)?;
+
+    if aud.iter().any(|aud| audiences.contains(aud)) {
+        Ok(())
+    } else {
+        Err(CredentialsVerificationError::AudienceMismatch)
+    }
+}
+
+/// Check that a client assertion was not used before and mark it as consumed.
+///
+/// Assertions without a `jti` claim can't be tracked, so they are rejected.
+async fn consume_assertion(
+    executor: impl PgExecutor<'_>,
+    clock: &Clock,
+    client: &Client,
+    jwt: &Jwt<'static, HashMap<String, Value>>,
+    exp: DateTime<Utc>,
+) -> Result<(), CredentialsVerificationError> {
+    let Some(Value::String(jti)) = jwt.payload().get("jti") else {
+        return Err(CredentialsVerificationError::InvalidAssertionClaims);
+    };
+
+    // Remember the assertion until it expires. Expired assertions are not
+    // accepted, so they can be forgotten after that.
+    if consume_client_assertion(executor, clock, client, jti, exp).await? {
+        Ok(())
+    } else {
+        Err(CredentialsVerificationError::AssertionReplayed)
+    }
+}
+
 async fn fetch_jwks(
     http_client_factory: &HttpClientFactory,
     jwks: &JwksOrJwksUri,
@@ -210,6 +328,24 @@ pub enum CredentialsVerificationError {
 
     #[error("failed to fetch jwks")]
     JwksFetchFailed,
+
+    #[error("client assertion is expired")]
+    AssertionExpired,
+
+    #[error("client assertion is not valid yet")]
+    AssertionNotYetValid,
+
+    #[error("client assertion has invalid time claims")]
+    InvalidAssertionClaims,
+
+    #[error("client assertion was already used")]
+    AssertionReplayed,
+
+    #[error("client assertion audience did not match")]
+    AudienceMismatch,
+
+    #[error("database error")]
+    Database(#[from] DatabaseError),
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -391,10 +527,61 @@ where
 #[cfg(test)]
 mod tests {
     use axum::body::{Bytes, Full};
+    use chrono::{Duration, TimeZone};
     use http::{Method, Request};
+    use mas_iana::jose::JsonWebSignatureAlg;
+    use mas_jose::{jwa::SymmetricKey, jwt::JsonWebSignatureHeader};
+    use mas_storage::oauth2::client::{
+        cleanup_consumed_client_assertions, insert_client_from_config, lookup_client,
+    };
+    use rand::SeedableRng;
+    use sqlx::PgPool;
+    use ulid::Ulid;
 
     use super::*;
 
+    /// Insert a `client_secret_jwt` client using `client-secret` as secret
+    async fn insert_client_secret_jwt_client(
+        pool: &PgPool,
+        clock: &Clock,
+        encrypter: &Encrypter,
+    ) -> Result<Client, DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut conn = pool.acquire().await?;
+
+        let client_id = Ulid::from_datetime_with_source(clock.now().into(), &mut rng);
+        let encrypted_client_secret = encrypter.encryt_to_string(b"client-secret").unwrap();
+        insert_client_from_config(
+            &mut conn,
+            &mut rng,
+            clock,
+            client_id,
+            OAuthClientAuthenticationMethod::ClientSecretJwt,
+            Some(&encrypted_client_secret),
+            None,
+            None,
+            &[],
+        )
+        .await?;
+
+        Ok(lookup_client(&mut conn, client_id).await?.unwrap())
+    }
+
+    /// Build client assertion credentials signed with `client-secret`
+    fn client_assertion(client: &Client, claims: serde_json::Value) -> Credentials {
+        let claims: HashMap<String, Value> = serde_json::from_value(claims).unwrap();
+        let key = SymmetricKey::new_for_alg(b"client-secret".to_vec(), &JsonWebSignatureAlg::Hs256)
+            .unwrap();
+        let header = JsonWebSignatureHeader::new(JsonWebSignatureAlg::Hs256);
+        let rng = rand::rngs::StdRng::seed_from_u64(42);
+        let jwt = Jwt::sign_with_rng(rng, header, claims, &key).unwrap();
+
+        Credentials::ClientAssertionJwtBearer {
+            client_id: client.client_id.clone(),
+            jwt: Box::new(jwt),
+        }
+    }
+
     #[tokio::test]
     async fn none_test() {
         let req = Request::builder()
@@ -571,4 +758,249 @@ mod tests {
         jwt.verify_with_shared_secret(b"client-secret".to_vec())
             .unwrap();
     }
+
+    #[test]
+    fn assertion_times_test() {
+        let claims: HashMap<String, Value> = serde_json::from_value(serde_json::json!({
+            "iat": 1_516_239_022,
+            "nbf": 1_516_239_022,
+            "exp": 1_516_239_322,
+        }))
+        .unwrap();
+        let issued_at = Utc.timestamp_opt(1_516_239_022, 0).unwrap();
+
+        // Valid token
+        let exp = validate_assertion_times(&claims, &TimeOptions::new(issued_at)).unwrap();
+        assert_eq!(exp, Utc.timestamp_opt(1_516_239_322, 0).unwrap());
+
+        // Expired token
+        let now = issued_at + Duration::minutes(6);
+        assert!(matches!(
+            validate_assertion_times(&claims, &TimeOptions::new(now).leeway(Duration::zero())),
+            Err(CredentialsVerificationError::AssertionExpired),
+        ));
+
+        // ...unless it is within the allowed clock skew
+        assert!(validate_assertion_times(
+            &claims,
+            &TimeOptions::new(now).leeway(Duration::minutes(2))
+        )
+        .is_ok());
+
+        // Not before in the future
+        let now = issued_at - Duration::minutes(1);
+        assert!(matches!(
+            validate_assertion_times(&claims, &TimeOptions::new(now).leeway(Duration::zero())),
+            Err(CredentialsVerificationError::AssertionNotYetValid),
+        ));
+
+        // Invalid timestamp
+        let claims: HashMap<String, Value> =
+            serde_json::from_value(serde_json::json!({ "exp": "tomorrow" })).unwrap();
+        assert!(matches!(
+            validate_assertion_times(&claims, &TimeOptions::new(issued_at)),
+            Err(CredentialsVerificationError::InvalidAssertionClaims),
+        ));
+
+        // Missing expiration
+        let claims: HashMap<String, Value> =
+            serde_json::from_value(serde_json::json!({ "iat": 1_516_239_022 })).unwrap();
+        assert!(matches!(
+            validate_assertion_times(&claims, &TimeOptions::new(issued_at)),
+            Err(CredentialsVerificationError::InvalidAssertionClaims),
+        ));
+    }
+
+    #[test]
+    fn assertion_audience_test() {
+        let audiences = [
+            "https://example.com/".to_owned(),
+            "https://example.com/oauth2/token".to_owned(),
+        ];
+
+        // Single audience
+        let claims: HashMap<String, Value> = serde_json::from_value(serde_json::json!({
+            "aud": "https://example.com/oauth2/token",
+        }))
+        .unwrap();
+        assert!(validate_assertion_audience(&claims, &audiences).is_ok());
+
+        // Array of audiences, with one of them matching
+        let claims: HashMap<String, Value> = serde_json::from_value(serde_json::json!({
+            "aud": ["https://other.example.com/", "https://example.com/"],
+        }))
+        .unwrap();
+        assert!(validate_assertion_audience(&claims, &audiences).is_ok());
+
+        // None of the audiences match
+        let claims: HashMap<String, Value> = serde_json::from_value(serde_json::json!({
+            "aud": ["https://other.example.com/", "https://example.com/oauth2/introspect"],
+        }))
+        .unwrap();
+        assert!(matches!(
+            validate_assertion_audience(&claims, &audiences),
+            Err(CredentialsVerificationError::AudienceMismatch),
+        ));
+
+        // Missing audience
+        let claims = HashMap::new();
+        assert!(matches!(
+            validate_assertion_audience(&claims, &audiences),
+            Err(CredentialsVerificationError::AudienceMismatch),
+        ));
+
+        // Invalid audience
+        let claims: HashMap<String, Value> =
+            serde_json::from_value(serde_json::json!({ "aud": 42 })).unwrap();
+        assert!(matches!(
+            validate_assertion_audience(&claims, &audiences),
+            Err(CredentialsVerificationError::InvalidAssertionClaims),
+        ));
+    }
+
+    #[sqlx::test(migrator = "mas_storage::MIGRATOR")]
+    async fn client_assertion_replay_test(pool: PgPool) -> Result<(), DatabaseError> {
+        let clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let encrypter = Encrypter::new(&[0x42; 32]);
+        let http_client_factory = HttpClientFactory::new(10);
+        let audiences = ["https://example.com/oauth2/token".to_owned()];
+        let client = insert_client_secret_jwt_client(&pool, &clock, &encrypter).await?;
+
+        let credentials = client_assertion(
+            &client,
+            serde_json::json!({
+                "iss": client.client_id,
+                "sub": client.client_id,
+                "aud": "https://example.com/oauth2/token",
+                "jti": "aabbcc",
+                "iat": 1_516_239_022,
+                "exp": 1_516_239_322,
+            }),
+        );
+
+        credentials
+            .verify(
+                &pool,
+                &clock,
+                &http_client_factory,
+                &encrypter,
+                &OAuthClientAuthenticationMethod::ClientSecretJwt,
+                &client,
+                &audiences,
+            )
+            .await
+            .unwrap();
+
+        // The same assertion can't be used twice
+        assert!(matches!(
+            credentials
+                .verify(
+                    &pool,
+                    &clock,
+                    &http_client_factory,
+                    &encrypter,
+                    &OAuthClientAuthenticationMethod::ClientSecretJwt,
+                    &client,
+                    &audiences,
+                )
+                .await,
+            Err(CredentialsVerificationError::AssertionReplayed),
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "mas_storage::MIGRATOR")]
+    async fn client_assertion_replay_after_cleanup_test(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let encrypter = Encrypter::new(&[0x42; 32]);
+        let http_client_factory = HttpClientFactory::new(10);
+        let audiences = ["https://example.com/oauth2/token".to_owned()];
+        let client = insert_client_secret_jwt_client(&pool, &clock, &encrypter).await?;
+
+        // An assertion valid for a week
+        let credentials = client_assertion(
+            &client,
+            serde_json::json!({
+                "iss": client.client_id,
+                "sub": client.client_id,
+                "aud": "https://example.com/oauth2/token",
+                "jti": "aabbcc",
+                "iat": 1_516_239_022,
+                "exp": 1_516_843_822,
+            }),
+        );
+
+        credentials
+            .verify(
+                &pool,
+                &clock,
+                &http_client_factory,
+                &encrypter,
+                &OAuthClientAuthenticationMethod::ClientSecretJwt,
+                &client,
+                &audiences,
+            )
+            .await
+            .unwrap();
+
+        // The consumed assertion is kept until the assertion itself expires
+        clock.advance(Duration::days(3));
+        cleanup_consumed_client_assertions(&pool, &clock).await?;
+
+        assert!(matches!(
+            credentials
+                .verify(
+                    &pool,
+                    &clock,
+                    &http_client_factory,
+                    &encrypter,
+                    &OAuthClientAuthenticationMethod::ClientSecretJwt,
+                    &client,
+                    &audiences,
+                )
+                .await,
+            Err(CredentialsVerificationError::AssertionReplayed),
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "mas_storage::MIGRATOR")]
+    async fn client_assertion_without_jti_test(pool: PgPool) -> Result<(), DatabaseError> {
+        let clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let encrypter = Encrypter::new(&[0x42; 32]);
+        let http_client_factory = HttpClientFactory::new(10);
+        let audiences = ["https://example.com/oauth2/token".to_owned()];
+        let client = insert_client_secret_jwt_client(&pool, &clock, &encrypter).await?;
+
+        // Assertions without a `jti` can't be tracked, and are rejected
+        let credentials = client_assertion(
+            &client,
+            serde_json::json!({
+                "iss": client.client_id,
+                "sub": client.client_id,
+                "aud": "https://example.com/oauth2/token",
+                "iat": 1_516_239_022,
+                "exp": 1_516_239_322,
+            }),
+        );
+
+        assert!(matches!(
+            credentials
+                .verify(
+                    &pool,
+                    &clock,
+                    &http_client_factory,
+                    &encrypter,
+                    &OAuthClientAuthenticationMethod::ClientSecretJwt,
+                    &client,
+                    &audiences,
+                )
+                .await,
+            Err(CredentialsVerificationError::InvalidAssertionClaims),
+        ));
+
+        Ok(())
+    }
 }
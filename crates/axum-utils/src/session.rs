@@ -13,8 +13,9 @@
 // limitations under the License.
 
 use axum_extra::extract::cookie::{Cookie, PrivateCookieJar};
+use chrono::Duration;
 use mas_data_model::BrowserSession;
-use mas_storage::{user::lookup_active_session, DatabaseError};
+use mas_storage::{user::lookup_active_session, Clock, DatabaseError};
 use serde::{Deserialize, Serialize};
 use sqlx::{Executor, Postgres};
 use ulid::Ulid;
@@ -25,6 +26,11 @@ use crate::CookieExt;
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct SessionInfo {
     current: Option<Ulid>,
+
+    /// How long the browser should keep the cookie, in seconds. If not set,
+    /// the cookie is a session cookie, discarded when the browser closes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_age: Option<i64>,
 }
 
 impl SessionInfo {
@@ -33,13 +39,24 @@ impl SessionInfo {
     pub fn from_session(session: &BrowserSession) -> Self {
         Self {
             current: Some(session.id),
+            max_age: None,
         }
     }
 
+    /// Set how long the browser should keep the session cookie.
+    ///
+    /// Passing `None` makes it a session cookie.
+    #[must_use]
+    pub fn with_max_age(mut self, max_age: Option<Duration>) -> Self {
+        self.max_age = max_age.map(|d| d.num_seconds());
+        self
+    }
+
     /// Mark the session as ended
     #[must_use]
     pub fn mark_session_ended(mut self) -> Self {
         self.current = None;
+        self.max_age = None;
         self
     }
 
@@ -47,6 +64,7 @@ impl SessionInfo {
     pub async fn load_session(
         &self,
         executor: impl Executor<'_, Database = Postgres>,
+        clock: &Clock,
     ) -> Result<Option<BrowserSession>, DatabaseError> {
         let session_id = if let Some(id) = self.current {
             id
@@ -54,11 +72,17 @@ impl SessionInfo {
             return Ok(None);
         };
 
-        let res = lookup_active_session(executor, session_id).await?;
+        let res = lookup_active_session(executor, clock, session_id).await?;
         Ok(res)
     }
 }
 
+fn set_max_age(cookie: &mut Cookie<'_>, info: &SessionInfo) {
+    if let Some(max_age) = info.max_age {
+        cookie.set_max_age(time::Duration::seconds(max_age));
+    }
+}
+
 pub trait SessionInfoExt {
     #[must_use]
     fn session_info(self) -> (SessionInfo, Self);
@@ -71,7 +95,17 @@ pub trait SessionInfoExt {
     where
         Self: Sized,
     {
-        let session_info = SessionInfo::from_session(session);
+        self.set_session_with_max_age(session, None)
+    }
+
+    /// Set the session cookie, asking the browser to keep it for the given
+    /// duration instead of discarding it when closed
+    #[must_use]
+    fn set_session_with_max_age(self, session: &BrowserSession, max_age: Option<Duration>) -> Self
+    where
+        Self: Sized,
+    {
+        let session_info = SessionInfo::from_session(session).with_max_age(max_age);
         self.update_session_info(&session_info)
     }
 }
@@ -84,8 +118,10 @@ impl<K> SessionInfoExt for PrivateCookieJar<K> {
             .unwrap_or_else(|| Cookie::new("session", ""));
         cookie.set_path("/");
         cookie.set_http_only(true);
-        let session_info = cookie.decode().unwrap_or_default();
+        let session_info: SessionInfo = cookie.decode().unwrap_or_default();
 
+        // Keep the lifetime the cookie was originally set with
+        set_max_age(&mut cookie, &session_info);
         let cookie = cookie.encode(&session_info);
         let jar = jar.add(cookie);
         (session_info, jar)
@@ -95,6 +131,7 @@ impl<K> SessionInfoExt for PrivateCookieJar<K> {
         let mut cookie = Cookie::new("session", "");
         cookie.set_path("/");
         cookie.set_http_only(true);
+        set_max_age(&mut cookie, info);
         let cookie = cookie.encode(&info);
         self.add(cookie)
     }
@@ -19,6 +19,7 @@ use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthClientAuthenticationMethod
 use mas_router::UrlBuilder;
 use mas_storage::{
     oauth2::client::{insert_client_from_config, lookup_client, truncate_clients},
+    upstream_oauth2::{add_provider, AddProviderParams},
     user::{
         add_user_password, lookup_user_by_username, lookup_user_email, mark_user_email_as_verified,
     },
@@ -161,6 +162,12 @@ enum Subcommand {
         /// Issuer URL
         issuer: String,
 
+        /// Name of the provider displayed to users, like "Google".
+        ///
+        /// Defaults to the issuer URL.
+        #[arg(long)]
+        human_name: Option<String>,
+
         /// Scope to ask for when authorizing with this upstream.
         ///
         /// This should include at least the `openid` scope.
@@ -178,6 +185,34 @@ enum Subcommand {
         #[arg(long, value_enum)]
         signing_alg: Option<SigningAlgorithm>,
 
+        /// JWT signing algorithm used by the upstream to sign ID tokens.
+        ///
+        /// Defaults to RS256.
+        #[arg(long, value_enum)]
+        id_token_signed_response_alg: Option<SigningAlgorithm>,
+
+        /// Query the userinfo endpoint of the upstream after the code exchange,
+        /// for providers which don't include all the claims in the ID token.
+        #[arg(long)]
+        fetch_userinfo: bool,
+
+        /// Claim of the upstream user used to suggest a username on
+        /// registration.
+        ///
+        /// Defaults to `preferred_username`.
+        #[arg(long)]
+        username_claim: Option<String>,
+
+        /// Fall back to the insecure `plain` PKCE method if the upstream doesn't
+        /// support `S256`.
+        #[arg(long)]
+        allow_plain_pkce: bool,
+
+        /// Trust the `email_verified` claim of the upstream to mark imported
+        /// email addresses as verified.
+        #[arg(long)]
+        trust_email_verified: bool,
+
         /// Client Secret
         #[arg(long)]
         client_secret: Option<String>,
@@ -298,11 +333,17 @@ impl Options {
 
             SC::AddOAuthUpstream {
                 issuer,
+                human_name,
                 scope,
                 token_endpoint_auth_method,
                 client_id,
                 client_secret,
                 signing_alg,
+                id_token_signed_response_alg,
+                fetch_userinfo,
+                username_claim,
+                allow_plain_pkce,
+                trust_email_verified,
             } => {
                 let config: RootConfig = root.load_config()?;
                 let encrypter = config.secrets.encrypter();
@@ -318,6 +359,9 @@ impl Options {
                 let token_endpoint_signing_alg: Option<JsonWebSignatureAlg> =
                     signing_alg.as_ref().map(Into::into);
 
+                let id_token_signed_response_alg: Option<JsonWebSignatureAlg> =
+                    id_token_signed_response_alg.as_ref().map(Into::into);
+
                 tracing::info!(%issuer, %scope, %token_endpoint_auth_method, %client_id, "Adding OAuth upstream");
 
                 if client_secret.is_none() && requires_client_secret {
@@ -329,18 +373,21 @@ impl Options {
                     .map(|client_secret| encrypter.encryt_to_string(client_secret.as_bytes()))
                     .transpose()?;
 
-                let provider = mas_storage::upstream_oauth2::add_provider(
-                    &mut conn,
-                    &mut rng,
-                    &clock,
-                    issuer.clone(),
-                    scope.clone(),
+                let params = AddProviderParams {
+                    issuer: issuer.clone(),
+                    human_name: human_name.clone(),
+                    scope: scope.clone(),
                     token_endpoint_auth_method,
                     token_endpoint_signing_alg,
-                    client_id.clone(),
+                    id_token_signed_response_alg,
+                    fetch_userinfo: *fetch_userinfo,
+                    username_claim: username_claim.clone(),
+                    allow_plain_pkce: *allow_plain_pkce,
+                    trust_email_verified: *trust_email_verified,
+                    client_id: client_id.clone(),
                     encrypted_client_secret,
-                )
-                .await?;
+                };
+                let provider = add_provider(&mut conn, &mut rng, &clock, params).await?;
 
                 let redirect_uri = url_builder.upstream_oauth_callback(provider.id);
                 let auth_uri = url_builder.upstream_oauth_authorize(provider.id);
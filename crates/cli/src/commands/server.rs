@@ -17,11 +17,15 @@ use std::{sync::Arc, time::Duration};
 use anyhow::Context;
 use clap::Parser;
 use itertools::Itertools;
-use mas_config::RootConfig;
-use mas_handlers::{AppState, HttpClientFactory, MatrixHomeserver};
+use mas_config::{RootConfig, VerificationCodeFormat};
+use mas_handlers::{
+    AppState, EmailVerificationCodeKind, HttpClientFactory, LoginLockout, LoginRateLimiter,
+    MatrixAccessTokenTtl, MatrixHomeserver, MaxSessionAge, MetadataCache, SessionCookieTtl,
+    SharedRng,
+};
 use mas_listener::{server::Server, shutdown::ShutdownStream};
 use mas_router::UrlBuilder;
-use mas_storage::MIGRATOR;
+use mas_storage::{user::CodeKind, Clock, MIGRATOR};
 use mas_tasks::TaskQueue;
 use tokio::signal::unix::SignalKind;
 use tracing::{info, warn};
@@ -88,10 +92,38 @@ impl Options {
 
         let homeserver = MatrixHomeserver::new(config.matrix.homeserver.clone());
 
+        let compat_access_token_ttl = MatrixAccessTokenTtl::new(config.compat.access_token_ttl);
+
+        let session_cookie_ttl =
+            SessionCookieTtl::new(config.session.ttl, config.session.remember_me_ttl);
+
+        let max_session_age = MaxSessionAge::new(config.session.max_age);
+
+        let login_lockout = LoginLockout::new(
+            config.passwords.lockout_max_attempts,
+            config.passwords.lockout_window,
+        );
+
+        let login_rate_limiter = LoginRateLimiter::new(
+            config.passwords.rate_limit_burst,
+            config.passwords.rate_limit_period,
+        )
+        .with_trusted_proxies(config.http.trusted_proxies.clone());
+
+        let len = config.email.verification_code_length.get().into();
+        let email_verification_code_kind =
+            EmailVerificationCodeKind::new(match config.email.verification_code_format {
+                VerificationCodeFormat::Numeric => CodeKind::Numeric { len },
+                VerificationCodeFormat::Alphanumeric => CodeKind::Alphanumeric { len },
+            });
+
         let listeners_config = config.http.listeners.clone();
 
         let password_manager = password_manager_from_config(&config.passwords).await?;
 
+        let upstream_oauth2_metadata_cache =
+            MetadataCache::new(config.upstream_oauth2.metadata_cache_ttl);
+
         // Explicitely the config to properly zeroize secret keys
         drop(config);
 
@@ -113,10 +145,19 @@ impl Options {
             url_builder,
             mailer,
             homeserver,
+            compat_access_token_ttl,
+            session_cookie_ttl,
+            max_session_age,
+            login_lockout,
+            login_rate_limiter,
+            email_verification_code_kind,
+            clock: Clock::default(),
+            rng: SharedRng::from_entropy(),
             policy_factory,
             graphql_schema,
             http_client_factory,
             password_manager,
+            upstream_oauth2_metadata_cache,
         };
 
         let mut fd_manager = listenfd::ListenFd::from_env();
@@ -92,15 +92,35 @@ pub async fn policy_factory_from_config(
         .await
         .context("failed to open OPA WASM policy file")?;
 
-    PolicyFactory::load(
-        policy_file,
-        config.data.clone().unwrap_or_default(),
-        config.register_entrypoint.clone(),
-        config.client_registration_entrypoint.clone(),
-        config.authorization_grant_entrypoint.clone(),
-    )
-    .await
-    .context("failed to load the policy")
+    // OPA bundles are gzipped tarballs containing the module and its data
+    let is_bundle = config.wasm_module.as_str().ends_with(".tar.gz");
+
+    let factory = if is_bundle {
+        // Only override the bundled data if some is set in the config
+        PolicyFactory::load_bundle(
+            policy_file,
+            config.data.clone(),
+            config.register_entrypoint.clone(),
+            config.client_registration_entrypoint.clone(),
+            config.authorization_grant_entrypoint.clone(),
+            config.email_entrypoint.clone(),
+            config.evaluation_timeout,
+        )
+        .await
+    } else {
+        PolicyFactory::load(
+            policy_file,
+            config.data.clone().unwrap_or_default(),
+            config.register_entrypoint.clone(),
+            config.client_registration_entrypoint.clone(),
+            config.authorization_grant_entrypoint.clone(),
+            config.email_entrypoint.clone(),
+            config.evaluation_timeout,
+        )
+        .await
+    };
+
+    factory.context("failed to load the policy")
 }
 
 pub async fn templates_from_config(
@@ -0,0 +1,90 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::Duration;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+use super::ConfigurationSection;
+
+fn default_access_token_ttl() -> Duration {
+    Duration::minutes(5)
+}
+
+/// Configuration related to the Matrix compatibility layer
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CompatConfig {
+    /// How long the access tokens issued through the compatibility layer are
+    /// valid, in seconds
+    #[schemars(with = "u64", range(min = 60, max = 86400))]
+    #[serde(default = "default_access_token_ttl")]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub access_token_ttl: Duration,
+}
+
+impl Default for CompatConfig {
+    fn default() -> Self {
+        Self {
+            access_token_ttl: default_access_token_ttl(),
+        }
+    }
+}
+
+#[async_trait]
+impl ConfigurationSection<'_> for CompatConfig {
+    fn path() -> &'static str {
+        "compat"
+    }
+
+    async fn generate<R>(_rng: R) -> anyhow::Result<Self>
+    where
+        R: Rng + Send,
+    {
+        Ok(Self::default())
+    }
+
+    fn test() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                    compat:
+                      access_token_ttl: 3600
+                "#,
+            )?;
+
+            let config = CompatConfig::load_from_file("config.yaml")?;
+
+            assert_eq!(config.access_token_ttl, Duration::hours(1));
+
+            Ok(())
+        });
+    }
+}
@@ -12,12 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::num::NonZeroU16;
+use std::num::{NonZeroU16, NonZeroU8};
 
 use async_trait::async_trait;
 use rand::Rng;
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 
 use super::ConfigurationSection;
 
@@ -87,6 +87,23 @@ impl Default for EmailTransportConfig {
     }
 }
 
+/// Characters used in the codes sent to verify email addresses
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationCodeFormat {
+    /// Decimal digits
+    Numeric,
+
+    /// Uppercase letters and digits, without the ones which are easy to mix up
+    Alphanumeric,
+}
+
+impl Default for VerificationCodeFormat {
+    fn default() -> Self {
+        Self::Numeric
+    }
+}
+
 fn default_email() -> String {
     r#""Authentication Service" <root@localhost>"#.to_owned()
 }
@@ -95,6 +112,27 @@ fn default_sendmail_command() -> String {
     "sendmail".to_owned()
 }
 
+/// Shorter verification codes could be guessed by brute force
+const MIN_VERIFICATION_CODE_LENGTH: u8 = 6;
+
+fn default_verification_code_length() -> NonZeroU8 {
+    NonZeroU8::new(MIN_VERIFICATION_CODE_LENGTH).unwrap()
+}
+
+fn deserialize_verification_code_length<'de, D>(deserializer: D) -> Result<NonZeroU8, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let length = NonZeroU8::deserialize(deserializer)?;
+    if length.get() < MIN_VERIFICATION_CODE_LENGTH {
+        return Err(D::Error::custom(format!(
+            "verification codes must be at least {MIN_VERIFICATION_CODE_LENGTH} characters long"
+        )));
+    }
+
+    Ok(length)
+}
+
 /// Configuration related to sending emails
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct EmailConfig {
@@ -111,6 +149,18 @@ pub struct EmailConfig {
     /// What backend should be used when sending emails
     #[serde(flatten, default)]
     pub transport: EmailTransportConfig,
+
+    /// Characters used in the codes sent to verify email addresses
+    #[serde(default)]
+    pub verification_code_format: VerificationCodeFormat,
+
+    /// Number of characters in the codes sent to verify email addresses
+    #[serde(
+        default = "default_verification_code_length",
+        deserialize_with = "deserialize_verification_code_length"
+    )]
+    #[schemars(range(min = 6))]
+    pub verification_code_length: NonZeroU8,
 }
 
 impl Default for EmailConfig {
@@ -119,6 +169,8 @@ impl Default for EmailConfig {
             from: default_email(),
             reply_to: default_email(),
             transport: EmailTransportConfig::Blackhole,
+            verification_code_format: VerificationCodeFormat::default(),
+            verification_code_length: default_verification_code_length(),
         }
     }
 }
@@ -140,3 +192,53 @@ impl ConfigurationSection<'_> for EmailConfig {
         Self::default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                    email:
+                      transport: blackhole
+                      verification_code_format: alphanumeric
+                      verification_code_length: 8
+                "#,
+            )?;
+
+            let config = EmailConfig::load_from_file("config.yaml")?;
+
+            assert_eq!(
+                config.verification_code_format,
+                VerificationCodeFormat::Alphanumeric
+            );
+            assert_eq!(config.verification_code_length.get(), 8);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn reject_short_verification_codes() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                    email:
+                      transport: blackhole
+                      verification_code_length: 4
+                "#,
+            )?;
+
+            assert!(EmailConfig::load_from_file("config.yaml").is_err());
+
+            Ok(())
+        });
+    }
+}
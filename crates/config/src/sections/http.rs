@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{borrow::Cow, io::Cursor, ops::Deref};
+use std::{borrow::Cow, io::Cursor, net::IpAddr, ops::Deref};
 
 use anyhow::bail;
 use async_trait::async_trait;
@@ -326,6 +326,11 @@ pub struct HttpConfig {
 
     /// Public URL base from where the authentication service is reachable
     pub public_base: Url,
+
+    /// IP addresses of the reverse proxies in front of the service. The
+    /// `X-Forwarded-For` header is only trusted on requests coming from them.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 impl Default for HttpConfig {
@@ -364,6 +369,7 @@ impl Default for HttpConfig {
                 },
             ],
             public_base: default_public_base(),
+            trusted_proxies: Vec::new(),
         }
     }
 }
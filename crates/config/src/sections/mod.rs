@@ -18,6 +18,7 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 mod clients;
+mod compat;
 mod csrf;
 mod database;
 mod email;
@@ -26,14 +27,17 @@ mod matrix;
 mod passwords;
 mod policy;
 mod secrets;
+mod session;
 mod telemetry;
 mod templates;
+mod upstream_oauth2;
 
 pub use self::{
     clients::{ClientAuthMethodConfig, ClientConfig, ClientsConfig},
+    compat::CompatConfig,
     csrf::CsrfConfig,
     database::{ConnectConfig as DatabaseConnectConfig, DatabaseConfig},
-    email::{EmailConfig, EmailSmtpMode, EmailTransportConfig},
+    email::{EmailConfig, EmailSmtpMode, EmailTransportConfig, VerificationCodeFormat},
     http::{
         BindConfig as HttpBindConfig, HttpConfig, ListenerConfig as HttpListenerConfig,
         Resource as HttpResource, TlsConfig as HttpTlsConfig, UnixOrTcp,
@@ -42,11 +46,13 @@ pub use self::{
     passwords::{Algorithm as PasswordAlgorithm, PasswordsConfig},
     policy::PolicyConfig,
     secrets::SecretsConfig,
+    session::SessionConfig,
     telemetry::{
         JaegerExporterProtocolConfig, MetricsConfig, MetricsExporterConfig, Propagator,
         TelemetryConfig, TracingConfig, TracingExporterConfig,
     },
     templates::TemplatesConfig,
+    upstream_oauth2::UpstreamOAuth2Config,
 };
 use crate::util::ConfigurationSection;
 
@@ -95,6 +101,18 @@ pub struct RootConfig {
     /// Configuration related to the OPA policies
     #[serde(default)]
     pub policy: PolicyConfig,
+
+    /// Configuration related to upstream OAuth 2.0 providers
+    #[serde(default)]
+    pub upstream_oauth2: UpstreamOAuth2Config,
+
+    /// Configuration related to the Matrix compatibility layer
+    #[serde(default)]
+    pub compat: CompatConfig,
+
+    /// Configuration related to the browser sessions
+    #[serde(default)]
+    pub session: SessionConfig,
 }
 
 #[async_trait]
@@ -119,6 +137,9 @@ impl ConfigurationSection<'_> for RootConfig {
             secrets: SecretsConfig::generate(&mut rng).await?,
             matrix: MatrixConfig::generate(&mut rng).await?,
             policy: PolicyConfig::generate(&mut rng).await?,
+            upstream_oauth2: UpstreamOAuth2Config::generate(&mut rng).await?,
+            compat: CompatConfig::generate(&mut rng).await?,
+            session: SessionConfig::generate(&mut rng).await?,
         })
     }
 
@@ -135,6 +156,9 @@ impl ConfigurationSection<'_> for RootConfig {
             secrets: SecretsConfig::test(),
             matrix: MatrixConfig::test(),
             policy: PolicyConfig::test(),
+            upstream_oauth2: UpstreamOAuth2Config::test(),
+            compat: CompatConfig::test(),
+            session: SessionConfig::test(),
         }
     }
 }
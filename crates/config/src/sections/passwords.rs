@@ -12,14 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::borrow::Cow;
+use std::{borrow::Cow, num::NonZeroU16};
 
 use anyhow::bail;
 use async_trait::async_trait;
 use camino::Utf8PathBuf;
+use chrono::Duration;
 use rand::Rng;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
 
 use crate::ConfigurationSection;
 
@@ -31,17 +33,62 @@ fn default_schemes() -> Vec<HashingScheme> {
     }]
 }
 
+fn default_lockout_max_attempts() -> u32 {
+    5
+}
+
+fn default_lockout_window() -> Duration {
+    Duration::minutes(15)
+}
+
+fn default_rate_limit_burst() -> NonZeroU16 {
+    NonZeroU16::new(10).unwrap()
+}
+
+fn default_rate_limit_period() -> Duration {
+    Duration::minutes(1)
+}
+
 /// User password hashing config
+#[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PasswordsConfig {
     #[serde(default = "default_schemes")]
     schemes: Vec<HashingScheme>,
+
+    /// How many failed login attempts against a username are allowed within
+    /// the lockout window before the account gets locked
+    #[schemars(range(min = 1))]
+    #[serde(default = "default_lockout_max_attempts")]
+    pub lockout_max_attempts: u32,
+
+    /// The window in which failed login attempts are counted, in seconds
+    #[schemars(with = "u64", range(min = 1))]
+    #[serde(default = "default_lockout_window")]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub lockout_window: Duration,
+
+    /// How many failed login attempts can be made at once from a single IP
+    /// address or against a single username before being rate limited
+    #[serde(default = "default_rate_limit_burst")]
+    pub rate_limit_burst: NonZeroU16,
+
+    /// How often an extra failed login attempt is allowed once rate limited,
+    /// in seconds
+    #[schemars(with = "u64", range(min = 1))]
+    #[serde(default = "default_rate_limit_period")]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub rate_limit_period: Duration,
 }
 
 impl Default for PasswordsConfig {
     fn default() -> Self {
         Self {
             schemes: default_schemes(),
+            lockout_max_attempts: default_lockout_max_attempts(),
+            lockout_window: default_lockout_window(),
+            rate_limit_burst: default_rate_limit_burst(),
+            rate_limit_period: default_rate_limit_period(),
         }
     }
 }
@@ -154,3 +201,35 @@ pub enum Algorithm {
     /// PBKDF2
     Pbkdf2,
 }
+
+#[cfg(test)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                    passwords:
+                      lockout_max_attempts: 3
+                      lockout_window: 3600
+                      rate_limit_burst: 20
+                      rate_limit_period: 30
+                "#,
+            )?;
+
+            let config = PasswordsConfig::load_from_file("config.yaml")?;
+
+            assert_eq!(config.lockout_max_attempts, 3);
+            assert_eq!(config.lockout_window, Duration::hours(1));
+            assert_eq!(config.rate_limit_burst.get(), 20);
+            assert_eq!(config.rate_limit_period, Duration::seconds(30));
+
+            Ok(())
+        });
+    }
+}
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
 use async_trait::async_trait;
 use camino::Utf8PathBuf;
 use rand::Rng;
@@ -43,11 +45,15 @@ fn default_authorization_grant_endpoint() -> String {
     "authorization_grant/violation".to_owned()
 }
 
+fn default_email_endpoint() -> String {
+    "email/violation".to_owned()
+}
+
 /// Application secrets
 #[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PolicyConfig {
-    /// Path to the WASM module
+    /// Path to the WASM module, or to an OPA bundle if it ends with `.tar.gz`
     #[serde(default = "default_policy_path")]
     #[schemars(with = "String")]
     pub wasm_module: Utf8PathBuf,
@@ -64,9 +70,20 @@ pub struct PolicyConfig {
     #[serde(default = "default_authorization_grant_endpoint")]
     pub authorization_grant_entrypoint: String,
 
+    /// Entrypoint to use when evaluating email changes
+    #[serde(default = "default_email_endpoint")]
+    pub email_entrypoint: String,
+
     /// Arbitrary data to pass to the policy
     #[serde(default)]
     pub data: Option<serde_json::Value>,
+
+    /// Maximum time a single policy evaluation can take, in milliseconds.
+    /// Evaluations are not limited if unset.
+    #[schemars(with = "Option<u64>")]
+    #[serde(default)]
+    #[serde_as(as = "Option<serde_with::DurationMilliSeconds<u64>>")]
+    pub evaluation_timeout: Option<Duration>,
 }
 
 impl Default for PolicyConfig {
@@ -76,7 +93,9 @@ impl Default for PolicyConfig {
             client_registration_entrypoint: default_client_registration_endpoint(),
             register_entrypoint: default_register_endpoint(),
             authorization_grant_entrypoint: default_authorization_grant_endpoint(),
+            email_entrypoint: default_email_endpoint(),
             data: None,
+            evaluation_timeout: None,
         }
     }
 }
@@ -213,9 +213,25 @@ impl ConfigurationSection<'_> for SecretsConfig {
             key: KeyOrFile::Key(ec_k256_key.to_pem(pem_rfc7468::LineEnding::LF)?.to_string()),
         };
 
+        let span = tracing::info_span!("ed25519");
+        let key_rng = rand_chacha::ChaChaRng::from_rng(&mut rng)?;
+        let ed25519_key = task::spawn_blocking(move || {
+            let _entered = span.enter();
+            let ret = PrivateKey::generate_ed25519(key_rng);
+            info!("Done generating Ed25519 key");
+            ret
+        })
+        .await
+        .context("could not join blocking task")?;
+        let ed25519_key = KeyConfig {
+            kid: Alphanumeric.sample_string(&mut rng, 10),
+            password: None,
+            key: KeyOrFile::Key(ed25519_key.to_pem(pem_rfc7468::LineEnding::LF)?.to_string()),
+        };
+
         Ok(Self {
             encryption: rng.gen(),
-            keys: vec![rsa_key, ec_p256_key, ec_p384_key, ec_k256_key],
+            keys: vec![rsa_key, ec_p256_key, ec_p384_key, ec_k256_key, ed25519_key],
         })
     }
 
@@ -0,0 +1,110 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::Duration;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+use super::ConfigurationSection;
+
+fn default_remember_me_ttl() -> Duration {
+    Duration::days(30)
+}
+
+/// Configuration related to the browser sessions
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SessionConfig {
+    /// How long the browser keeps the session cookie after a regular login, in
+    /// seconds. The cookie is discarded when the browser closes if unset.
+    #[schemars(with = "Option<u64>")]
+    #[serde(default)]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub ttl: Option<Duration>,
+
+    /// How long the browser keeps the session cookie when the user asked to be
+    /// remembered, in seconds
+    #[schemars(with = "u64", range(min = 60))]
+    #[serde(default = "default_remember_me_ttl")]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub remember_me_ttl: Duration,
+
+    /// How long a browser session stays valid after it started, in seconds.
+    /// Sessions never expire if unset.
+    #[schemars(with = "Option<u64>")]
+    #[serde(default)]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub max_age: Option<Duration>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            ttl: None,
+            remember_me_ttl: default_remember_me_ttl(),
+            max_age: None,
+        }
+    }
+}
+
+#[async_trait]
+impl ConfigurationSection<'_> for SessionConfig {
+    fn path() -> &'static str {
+        "session"
+    }
+
+    async fn generate<R>(_rng: R) -> anyhow::Result<Self>
+    where
+        R: Rng + Send,
+    {
+        Ok(Self::default())
+    }
+
+    fn test() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                    session:
+                      ttl: 3600
+                      remember_me_ttl: 604800
+                      max_age: 2592000
+                "#,
+            )?;
+
+            let config = SessionConfig::load_from_file("config.yaml")?;
+
+            assert_eq!(config.ttl, Some(Duration::hours(1)));
+            assert_eq!(config.remember_me_ttl, Duration::days(7));
+            assert_eq!(config.max_age, Some(Duration::days(30)));
+
+            Ok(())
+        });
+    }
+}
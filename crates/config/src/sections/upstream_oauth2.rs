@@ -0,0 +1,90 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::Duration;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+use super::ConfigurationSection;
+
+fn default_metadata_cache_ttl() -> Duration {
+    Duration::hours(1)
+}
+
+/// Configuration related to upstream OAuth 2.0 providers
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UpstreamOAuth2Config {
+    /// How long the discovered metadata and keys of upstream providers are
+    /// cached, in seconds
+    #[schemars(with = "u64", range(min = 0, max = 86400))]
+    #[serde(default = "default_metadata_cache_ttl")]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub metadata_cache_ttl: Duration,
+}
+
+impl Default for UpstreamOAuth2Config {
+    fn default() -> Self {
+        Self {
+            metadata_cache_ttl: default_metadata_cache_ttl(),
+        }
+    }
+}
+
+#[async_trait]
+impl ConfigurationSection<'_> for UpstreamOAuth2Config {
+    fn path() -> &'static str {
+        "upstream_oauth2"
+    }
+
+    async fn generate<R>(_rng: R) -> anyhow::Result<Self>
+    where
+        R: Rng + Send,
+    {
+        Ok(Self::default())
+    }
+
+    fn test() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                    upstream_oauth2:
+                      metadata_cache_ttl: 300
+                "#,
+            )?;
+
+            let config = UpstreamOAuth2Config::load_from_file("config.yaml")?;
+
+            assert_eq!(config.metadata_cache_ttl, Duration::minutes(5));
+
+            Ok(())
+        });
+    }
+}
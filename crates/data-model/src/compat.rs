@@ -104,6 +104,14 @@ pub struct CompatRefreshToken {
     pub id: Ulid,
     pub token: String,
     pub created_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+}
+
+impl CompatRefreshToken {
+    #[must_use]
+    pub const fn consumed(&self) -> bool {
+        self.consumed_at.is_some()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -43,7 +43,7 @@ pub use self::{
         UpstreamOAuthAuthorizationSession, UpstreamOAuthLink, UpstreamOAuthProvider,
     },
     users::{
-        Authentication, BrowserSession, Password, User, UserEmail, UserEmailVerification,
-        UserEmailVerificationState,
+        Authentication, BrowserSession, Password, SessionMetadata, User, UserEmail,
+        UserEmailVerification, UserEmailVerificationLookup, UserEmailVerificationState,
     },
 };
@@ -108,6 +108,33 @@ pub enum InvalidRedirectUriError {
 }
 
 impl Client {
+    /// Check that the given `redirect_uri` is registered for this client
+    ///
+    /// As per RFC6749 section 3.1.2.3, URIs are compared with a simple string
+    /// comparison: the only normalization applied is the one done when
+    /// parsing the [`Url`], e.g. an empty path being equivalent to `/`. A
+    /// trailing slash or a different query string makes it a different URI.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the client has no redirect URI registered, or if
+    /// the given one isn't part of them
+    pub fn validate_redirect_uri(&self, redirect_uri: &Url) -> Result<(), InvalidRedirectUriError> {
+        if self.redirect_uris.is_empty() {
+            return Err(InvalidRedirectUriError::NoneRegistered);
+        }
+
+        if self
+            .redirect_uris
+            .iter()
+            .any(|registered| registered.as_str() == redirect_uri.as_str())
+        {
+            Ok(())
+        } else {
+            Err(InvalidRedirectUriError::NotAllowed)
+        }
+    }
+
     pub fn resolve_redirect_uri<'a>(
         &'a self,
         redirect_uri: &'a Option<Url>,
@@ -116,8 +143,95 @@ impl Client {
             ([], _) => Err(InvalidRedirectUriError::NoneRegistered),
             ([one], None) => Ok(one),
             (_, None) => Err(InvalidRedirectUriError::MultipleRegistered),
-            (uris, Some(uri)) if uris.contains(uri) => Ok(uri),
-            _ => Err(InvalidRedirectUriError::NotAllowed),
+            (_, Some(uri)) => {
+                self.validate_redirect_uri(uri)?;
+                Ok(uri)
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(redirect_uris: &[&str]) -> Client {
+        Client {
+            id: Ulid::nil(),
+            client_id: "client".to_owned(),
+            encrypted_client_secret: None,
+            redirect_uris: redirect_uris
+                .iter()
+                .map(|uri| uri.parse().unwrap())
+                .collect(),
+            response_types: Vec::new(),
+            grant_types: Vec::new(),
+            contacts: Vec::new(),
+            client_name: None,
+            logo_uri: None,
+            client_uri: None,
+            policy_uri: None,
+            tos_uri: None,
+            jwks: None,
+            id_token_signed_response_alg: None,
+            userinfo_signed_response_alg: None,
+            token_endpoint_auth_method: None,
+            token_endpoint_auth_signing_alg: None,
+            initiate_login_uri: None,
+        }
+    }
+
+    #[test]
+    fn validate_redirect_uri_exact_match() {
+        let client = client(&["https://example.com/callback", "https://example.org/"]);
+
+        assert!(client
+            .validate_redirect_uri(&"https://example.com/callback".parse().unwrap())
+            .is_ok());
+        // An empty path is the same as `/` once parsed
+        assert!(client
+            .validate_redirect_uri(&"https://example.org".parse().unwrap())
+            .is_ok());
+        assert!(matches!(
+            client.validate_redirect_uri(&"https://example.net/callback".parse().unwrap()),
+            Err(InvalidRedirectUriError::NotAllowed)
+        ));
+    }
+
+    #[test]
+    fn validate_redirect_uri_trailing_slash() {
+        let client = client(&["https://example.com/callback"]);
+
+        assert!(matches!(
+            client.validate_redirect_uri(&"https://example.com/callback/".parse().unwrap()),
+            Err(InvalidRedirectUriError::NotAllowed)
+        ));
+    }
+
+    #[test]
+    fn validate_redirect_uri_query() {
+        let client = client(&["https://example.com/callback?foo=bar"]);
+
+        assert!(client
+            .validate_redirect_uri(&"https://example.com/callback?foo=bar".parse().unwrap())
+            .is_ok());
+        assert!(matches!(
+            client.validate_redirect_uri(&"https://example.com/callback".parse().unwrap()),
+            Err(InvalidRedirectUriError::NotAllowed)
+        ));
+        assert!(matches!(
+            client.validate_redirect_uri(&"https://example.com/callback?foo=baz".parse().unwrap()),
+            Err(InvalidRedirectUriError::NotAllowed)
+        ));
+    }
+
+    #[test]
+    fn validate_redirect_uri_none_registered() {
+        let client = client(&[]);
+
+        assert!(matches!(
+            client.validate_redirect_uri(&"https://example.com/callback".parse().unwrap()),
+            Err(InvalidRedirectUriError::NoneRegistered)
+        ));
+    }
+}
@@ -22,11 +22,17 @@ use ulid::Ulid;
 pub struct UpstreamOAuthProvider {
     pub id: Ulid,
     pub issuer: String,
+    pub human_name: Option<String>,
     pub scope: Scope,
     pub client_id: String,
     pub encrypted_client_secret: Option<String>,
     pub token_endpoint_signing_alg: Option<JsonWebSignatureAlg>,
     pub token_endpoint_auth_method: OAuthClientAuthenticationMethod,
+    pub id_token_signed_response_alg: Option<JsonWebSignatureAlg>,
+    pub fetch_userinfo: bool,
+    pub username_claim: Option<String>,
+    pub allow_plain_pkce: bool,
+    pub trust_email_verified: bool,
     pub created_at: DateTime<Utc>,
 }
 
@@ -51,6 +57,7 @@ pub struct UpstreamOAuthAuthorizationSession {
     pub completed_at: Option<DateTime<Utc>>,
     pub consumed_at: Option<DateTime<Utc>>,
     pub id_token: Option<String>,
+    pub userinfo: Option<serde_json::Value>,
 }
 
 impl UpstreamOAuthAuthorizationSession {
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::net::IpAddr;
+
 use chrono::{DateTime, Duration, Utc};
 use rand::{Rng, SeedableRng};
 use serde::Serialize;
@@ -21,18 +23,34 @@ use ulid::Ulid;
 pub struct User {
     pub id: Ulid,
     pub username: String,
+    pub display_name: Option<String>,
     pub sub: String,
     pub primary_email: Option<UserEmail>,
+    pub deactivated_at: Option<DateTime<Utc>>,
+    pub admin: bool,
 }
 
 impl User {
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.deactivated_at.is_none()
+    }
+
+    #[must_use]
+    pub fn is_admin(&self) -> bool {
+        self.admin
+    }
+
     #[must_use]
     pub fn samples(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self> {
         vec![User {
             id: Ulid::from_datetime_with_source(now.into(), rng),
             username: "john".to_owned(),
+            display_name: None,
             sub: "123-456".to_owned(),
             primary_email: None,
+            deactivated_at: None,
+            admin: false,
         }]
     }
 }
@@ -52,12 +70,20 @@ pub struct Authentication {
     pub created_at: DateTime<Utc>,
 }
 
+/// Informations about the client which started a [`BrowserSession`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SessionMetadata {
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct BrowserSession {
     pub id: Ulid,
     pub user: User,
     pub created_at: DateTime<Utc>,
     pub last_authentication: Option<Authentication>,
+    pub metadata: SessionMetadata,
 }
 
 impl BrowserSession {
@@ -69,6 +95,17 @@ impl BrowserSession {
             false
         }
     }
+
+    /// Whether the user should authenticate again before doing a sensitive
+    /// operation, because they never authenticated in this session or did it
+    /// more than `max_age` ago
+    #[must_use]
+    pub fn requires_reauth(&self, max_age: Duration, now: DateTime<Utc>) -> bool {
+        match &self.last_authentication {
+            Some(auth) => now - auth.created_at > max_age,
+            None => true,
+        }
+    }
 }
 
 impl BrowserSession {
@@ -81,6 +118,7 @@ impl BrowserSession {
                 user,
                 created_at: now,
                 last_authentication: None,
+                metadata: SessionMetadata::default(),
             })
             .collect()
     }
@@ -121,6 +159,16 @@ pub enum UserEmailVerificationState {
     Valid,
 }
 
+/// The outcome of looking up a verification code for an email
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum UserEmailVerificationLookup {
+    /// No such code was sent for this email
+    CodeNotFound,
+
+    /// The code was found, and its state tells whether it can still be used
+    Found(UserEmailVerification),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct UserEmailVerification {
     pub id: Ulid,
@@ -161,3 +209,30 @@ impl UserEmailVerification {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn requires_reauth() {
+        let now = Utc.timestamp_opt(1_516_239_022, 0).unwrap();
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let mut session = BrowserSession::samples(now, &mut rng).remove(0);
+        let max_age = Duration::minutes(5);
+
+        // Never authenticated
+        assert!(session.requires_reauth(max_age, now));
+
+        session.last_authentication = Some(Authentication {
+            id: Ulid::from_datetime_with_source(now.into(), &mut rng),
+            created_at: now,
+        });
+        assert!(!session.requires_reauth(max_age, now));
+        assert!(!session.requires_reauth(max_age, now + max_age));
+        assert!(session.requires_reauth(max_age, now + max_age + Duration::seconds(1)));
+    }
+}
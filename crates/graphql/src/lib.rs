@@ -120,11 +120,13 @@ impl RootQuery {
         let session = ctx.data_opt::<mas_data_model::BrowserSession>().cloned();
         let database = ctx.data::<PgPool>()?;
         let mut conn = database.acquire().await?;
+        let clock = mas_storage::Clock::default();
 
         let Some(session) = session else { return Ok(None) };
         let current_user = session.user;
 
-        let browser_session = mas_storage::user::lookup_active_session(&mut conn, id).await?;
+        let browser_session =
+            mas_storage::user::lookup_active_session(&mut conn, &clock, id).await?;
 
         let ret = browser_session.and_then(|browser_session| {
             if browser_session.user.id == current_user.id {
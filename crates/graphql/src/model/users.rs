@@ -126,6 +126,7 @@ impl User {
             last,
             |after, before, first, last| async move {
                 let mut conn = database.acquire().await?;
+                let clock = mas_storage::Clock::default();
                 let after_id = after
                     .map(|x: OpaqueCursor<NodeCursor>| x.extract_for_type(NodeType::BrowserSession))
                     .transpose()?;
@@ -135,7 +136,14 @@ impl User {
 
                 let (has_previous_page, has_next_page, edges) =
                     mas_storage::user::get_paginated_user_sessions(
-                        &mut conn, &self.0, before_id, after_id, first, last,
+                        &mut conn,
+                        &clock,
+                        &self.0,
+                        mas_storage::PaginationOrder::Ascending,
+                        before_id,
+                        after_id,
+                        first,
+                        last,
                     )
                     .await?;
 
@@ -183,7 +191,13 @@ impl User {
 
                 let (has_previous_page, has_next_page, edges) =
                     mas_storage::user::get_paginated_user_emails(
-                        &mut conn, &self.0, before_id, after_id, first, last,
+                        &mut conn,
+                        &self.0,
+                        mas_storage::PaginationOrder::Ascending,
+                        before_id,
+                        after_id,
+                        first,
+                        last,
                     )
                     .await?;
 
@@ -226,6 +240,7 @@ impl User {
             last,
             |after, before, first, last| async move {
                 let mut conn = database.acquire().await?;
+                let clock = mas_storage::Clock::default();
                 let after_id = after
                     .map(|x: OpaqueCursor<NodeCursor>| x.extract_for_type(NodeType::OAuth2Session))
                     .transpose()?;
@@ -235,7 +250,7 @@ impl User {
 
                 let (has_previous_page, has_next_page, edges) =
                     mas_storage::oauth2::get_paginated_user_oauth_sessions(
-                        &mut conn, &self.0, before_id, after_id, first, last,
+                        &mut conn, &clock, &self.0, before_id, after_id, first, last,
                     )
                     .await?;
 
@@ -20,10 +20,15 @@ use mas_email::Mailer;
 use mas_keystore::{Encrypter, Keystore};
 use mas_policy::PolicyFactory;
 use mas_router::UrlBuilder;
+use mas_storage::Clock;
 use mas_templates::Templates;
 use sqlx::PgPool;
 
-use crate::{passwords::PasswordManager, MatrixHomeserver};
+use crate::{
+    passwords::PasswordManager, EmailVerificationCodeKind, LoginLockout, LoginRateLimiter,
+    MatrixAccessTokenTtl, MatrixHomeserver, MaxSessionAge, MetadataCache, SessionCookieTtl,
+    SharedRng,
+};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -34,10 +39,19 @@ pub struct AppState {
     pub url_builder: UrlBuilder,
     pub mailer: Mailer,
     pub homeserver: MatrixHomeserver,
+    pub compat_access_token_ttl: MatrixAccessTokenTtl,
+    pub session_cookie_ttl: SessionCookieTtl,
+    pub max_session_age: MaxSessionAge,
+    pub login_lockout: LoginLockout,
+    pub login_rate_limiter: LoginRateLimiter,
+    pub email_verification_code_kind: EmailVerificationCodeKind,
+    pub clock: Clock,
+    pub rng: SharedRng,
     pub policy_factory: Arc<PolicyFactory>,
     pub graphql_schema: mas_graphql::Schema,
     pub http_client_factory: HttpClientFactory,
     pub password_manager: PasswordManager,
+    pub upstream_oauth2_metadata_cache: MetadataCache,
 }
 
 impl FromRef<AppState> for PgPool {
@@ -88,6 +102,54 @@ impl FromRef<AppState> for MatrixHomeserver {
     }
 }
 
+impl FromRef<AppState> for MatrixAccessTokenTtl {
+    fn from_ref(input: &AppState) -> Self {
+        input.compat_access_token_ttl
+    }
+}
+
+impl FromRef<AppState> for SessionCookieTtl {
+    fn from_ref(input: &AppState) -> Self {
+        input.session_cookie_ttl
+    }
+}
+
+impl FromRef<AppState> for MaxSessionAge {
+    fn from_ref(input: &AppState) -> Self {
+        input.max_session_age
+    }
+}
+
+impl FromRef<AppState> for LoginLockout {
+    fn from_ref(input: &AppState) -> Self {
+        input.login_lockout
+    }
+}
+
+impl FromRef<AppState> for LoginRateLimiter {
+    fn from_ref(input: &AppState) -> Self {
+        input.login_rate_limiter.clone()
+    }
+}
+
+impl FromRef<AppState> for EmailVerificationCodeKind {
+    fn from_ref(input: &AppState) -> Self {
+        input.email_verification_code_kind
+    }
+}
+
+impl FromRef<AppState> for Clock {
+    fn from_ref(input: &AppState) -> Self {
+        input.clock
+    }
+}
+
+impl FromRef<AppState> for SharedRng {
+    fn from_ref(input: &AppState) -> Self {
+        input.rng.clone()
+    }
+}
+
 impl FromRef<AppState> for Arc<PolicyFactory> {
     fn from_ref(input: &AppState) -> Self {
         input.policy_factory.clone()
@@ -105,3 +167,9 @@ impl FromRef<AppState> for PasswordManager {
         input.password_manager.clone()
     }
 }
+
+impl FromRef<AppState> for MetadataCache {
+    fn from_ref(input: &AppState) -> Self {
+        input.upstream_oauth2_metadata_cache.clone()
+    }
+}
@@ -30,7 +30,7 @@ use sqlx::{PgPool, Postgres, Transaction};
 use thiserror::Error;
 use zeroize::Zeroizing;
 
-use super::{MatrixError, MatrixHomeserver};
+use super::{MatrixAccessTokenTtl, MatrixError, MatrixHomeserver};
 use crate::{impl_from_error_for_route, passwords::PasswordManager};
 
 #[derive(Debug, Serialize)]
@@ -143,6 +143,9 @@ pub enum RouteError {
     #[error("password verification failed")]
     PasswordVerificationFailed(#[source] anyhow::Error),
 
+    #[error("user is deactivated")]
+    UserDeactivated,
+
     #[error("login took too long")]
     LoginTookTooLong,
 
@@ -166,13 +169,14 @@ impl IntoResponse for RouteError {
                 error: "Invalid login type",
                 status: StatusCode::BAD_REQUEST,
             },
-            Self::UserNotFound | Self::NoPassword | Self::PasswordVerificationFailed(_) => {
-                MatrixError {
-                    errcode: "M_UNAUTHORIZED",
-                    error: "Invalid username/password",
-                    status: StatusCode::FORBIDDEN,
-                }
-            }
+            Self::UserNotFound
+            | Self::NoPassword
+            | Self::PasswordVerificationFailed(_)
+            | Self::UserDeactivated => MatrixError {
+                errcode: "M_UNAUTHORIZED",
+                error: "Invalid username/password",
+                status: StatusCode::FORBIDDEN,
+            },
             Self::LoginTookTooLong => MatrixError {
                 errcode: "M_UNAUTHORIZED",
                 error: "Login token expired",
@@ -193,6 +197,7 @@ pub(crate) async fn post(
     State(password_manager): State<PasswordManager>,
     State(pool): State<PgPool>,
     State(homeserver): State<MatrixHomeserver>,
+    State(MatrixAccessTokenTtl(access_token_ttl)): State<MatrixAccessTokenTtl>,
     Json(input): Json<RequestBody>,
 ) -> Result<impl IntoResponse, RouteError> {
     let (clock, mut rng) = crate::clock_and_rng();
@@ -214,8 +219,7 @@ pub(crate) async fn post(
 
     // If the client asked for a refreshable token, make it expire
     let expires_in = if input.refresh_token {
-        // TODO: this should be configurable
-        Some(Duration::minutes(5))
+        Some(access_token_ttl)
     } else {
         None
     };
@@ -278,11 +282,16 @@ async fn token_login(
         }
         CompatSsoLoginState::Fulfilled {
             fulfilled_at: fullfilled_at,
-            ..
+            ref session,
         } => {
             if now > fullfilled_at + Duration::seconds(30) {
                 return Err(RouteError::LoginTookTooLong);
             }
+
+            // The user might have been deactivated since the login was fulfilled
+            if !session.user.is_active() {
+                return Err(RouteError::InvalidLoginToken);
+            }
         }
         CompatSsoLoginState::Exchanged { exchanged_at, .. } => {
             if now > exchanged_at + Duration::seconds(30) {
@@ -336,6 +345,12 @@ async fn user_password_login(
         .await
         .map_err(RouteError::PasswordVerificationFailed)?;
 
+    // Deactivated users can't log in. This is checked after verifying the
+    // password to avoid leaking the account state
+    if !user.is_active() {
+        return Err(RouteError::UserDeactivated);
+    }
+
     if let Some((version, hashed_password)) = new_password_hash {
         // Save the upgraded password if needed
         add_user_password(
@@ -356,3 +371,79 @@ async fn user_password_login(
 
     Ok(session)
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::FromRef;
+    use hyper::{header::CONTENT_TYPE, Body, Request};
+    use mas_storage::user::{add_user, deactivate_user};
+    use rand::SeedableRng;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn password_login_request(username: &str, password: &str) -> Request<Body> {
+        let body = serde_json::json!({
+            "type": "m.login.password",
+            "identifier": { "type": "m.id.user", "user": username },
+            "password": password,
+        });
+        Request::post("/_matrix/client/v3/login")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[sqlx::test(migrator = "mas_storage::MIGRATOR")]
+    async fn test_password_login_deactivated_user(pool: PgPool) -> Result<(), anyhow::Error> {
+        let state = crate::test_state(pool.clone()).await?;
+        let password_manager = PasswordManager::from_ref(&state);
+
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let (version, hashed_password) = password_manager
+            .hash(&mut rng, Zeroizing::new(b"hunter2".to_vec()))
+            .await?;
+        add_user_password(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &user,
+            version,
+            hashed_password,
+            None,
+        )
+        .await?;
+
+        let app = crate::compat_router().with_state(state);
+
+        let response = app
+            .clone()
+            .oneshot(password_login_request("alice", "hunter2"))
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        deactivate_user(&mut conn, &clock, user).await?;
+
+        // The response is the same as with a wrong password
+        let response = app
+            .clone()
+            .oneshot(password_login_request("alice", "hunter2"))
+            .await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let deactivated = hyper::body::to_bytes(response.into_body()).await?;
+
+        let response = app
+            .oneshot(password_login_request("alice", "wrong"))
+            .await?;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let wrong_password = hyper::body::to_bytes(response.into_body()).await?;
+
+        assert_eq!(deactivated, wrong_password);
+
+        Ok(())
+    }
+}
@@ -62,7 +62,7 @@ pub async fn get(
     let (session_info, cookie_jar) = cookie_jar.session_info();
     let (csrf_token, cookie_jar) = cookie_jar.csrf_token(clock.now(), &mut rng);
 
-    let maybe_session = session_info.load_session(&mut conn).await?;
+    let maybe_session = session_info.load_session(&mut conn, &clock).await?;
 
     let session = if let Some(session) = maybe_session {
         session
@@ -130,7 +130,7 @@ pub async fn post(
     let (session_info, cookie_jar) = cookie_jar.session_info();
     cookie_jar.verify_form(clock.now(), form)?;
 
-    let maybe_session = session_info.load_session(&mut txn).await?;
+    let maybe_session = session_info.load_session(&mut txn, &clock).await?;
 
     let session = if let Some(session) = maybe_session {
         session
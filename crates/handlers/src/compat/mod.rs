@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use axum::{response::IntoResponse, Json};
+use chrono::Duration;
 use hyper::StatusCode;
 use serde::Serialize;
 
@@ -38,6 +39,24 @@ impl std::fmt::Display for MatrixHomeserver {
     }
 }
 
+/// How long the refreshable access tokens issued through the compatibility
+/// layer are valid
+#[derive(Debug, Clone, Copy)]
+pub struct MatrixAccessTokenTtl(Duration);
+
+impl MatrixAccessTokenTtl {
+    #[must_use]
+    pub const fn new(ttl: Duration) -> Self {
+        Self(ttl)
+    }
+}
+
+impl Default for MatrixAccessTokenTtl {
+    fn default() -> Self {
+        Self(Duration::minutes(5))
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct MatrixError {
     errcode: &'static str,
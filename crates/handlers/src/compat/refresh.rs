@@ -18,14 +18,14 @@ use hyper::StatusCode;
 use mas_data_model::{TokenFormatError, TokenType};
 use mas_storage::compat::{
     add_compat_access_token, add_compat_refresh_token, consume_compat_refresh_token,
-    expire_compat_access_token, lookup_active_compat_refresh_token,
+    expire_compat_access_token, lookup_compat_refresh_token, revoke_compat_session,
 };
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DurationMilliSeconds};
 use sqlx::PgPool;
 use thiserror::Error;
 
-use super::MatrixError;
+use super::{MatrixAccessTokenTtl, MatrixError};
 use crate::impl_from_error_for_route;
 
 #[derive(Debug, Deserialize)]
@@ -80,6 +80,7 @@ pub struct ResponseBody {
 
 pub(crate) async fn post(
     State(pool): State<PgPool>,
+    State(MatrixAccessTokenTtl(expires_in)): State<MatrixAccessTokenTtl>,
     Json(input): Json<RequestBody>,
 ) -> Result<impl IntoResponse, RouteError> {
     let (clock, mut rng) = crate::clock_and_rng();
@@ -92,14 +93,25 @@ pub(crate) async fn post(
     }
 
     let (refresh_token, access_token, session) =
-        lookup_active_compat_refresh_token(&mut txn, &input.refresh_token)
+        lookup_compat_refresh_token(&mut txn, &input.refresh_token)
             .await?
             .ok_or(RouteError::InvalidToken)?;
 
+    if session.finished_at.is_some() || !session.user.is_active() {
+        return Err(RouteError::InvalidToken);
+    }
+
+    if refresh_token.consumed() {
+        // The refresh token was already used: it probably leaked, so end the
+        // whole session to lock out whoever else is using it
+        revoke_compat_session(&mut txn, &clock, session).await?;
+        txn.commit().await?;
+        return Err(RouteError::InvalidToken);
+    }
+
     let new_refresh_token_str = TokenType::CompatRefreshToken.generate(&mut rng);
     let new_access_token_str = TokenType::CompatAccessToken.generate(&mut rng);
 
-    let expires_in = Duration::minutes(5);
     let new_access_token = add_compat_access_token(
         &mut txn,
         &mut rng,
@@ -130,3 +142,91 @@ pub(crate) async fn post(
         expires_in_ms: expires_in,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::CONTENT_TYPE, Body, Request};
+    use mas_data_model::Device;
+    use mas_storage::{
+        compat::{lookup_active_compat_access_token, start_compat_session},
+        user::add_user,
+        Clock,
+    };
+    use rand::SeedableRng;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn refresh_request(refresh_token: &str) -> Request<Body> {
+        let body = serde_json::json!({ "refresh_token": refresh_token });
+        Request::post("/_matrix/client/v3/refresh")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[sqlx::test(migrator = "mas_storage::MIGRATOR")]
+    async fn test_refresh_token_reuse(pool: PgPool) -> Result<(), anyhow::Error> {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let device = Device::generate(&mut rng);
+        let session = start_compat_session(&mut conn, &mut rng, &clock, user, device).await?;
+        let access_token = add_compat_access_token(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &session,
+            TokenType::CompatAccessToken.generate(&mut rng),
+            None,
+        )
+        .await?;
+        let refresh_token = add_compat_refresh_token(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &session,
+            &access_token,
+            TokenType::CompatRefreshToken.generate(&mut rng),
+        )
+        .await?;
+
+        let state = crate::test_state(pool).await?;
+        let app = crate::compat_router().with_state(state);
+
+        let response = app
+            .clone()
+            .oneshot(refresh_request(&refresh_token.token))
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        let new_access_token = body["access_token"].as_str().unwrap().to_owned();
+        let new_refresh_token = body["refresh_token"].as_str().unwrap().to_owned();
+
+        // Replaying the consumed refresh token ends the session
+        let response = app
+            .clone()
+            .oneshot(refresh_request(&refresh_token.token))
+            .await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(body["errcode"], "M_UNKNOWN_TOKEN");
+
+        assert!(
+            lookup_active_compat_access_token(&mut conn, &clock, &new_access_token)
+                .await?
+                .is_none()
+        );
+
+        // Which means the tokens issued by the legitimate refresh can't be used
+        // anymore either
+        let response = app.oneshot(refresh_request(&new_refresh_token)).await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+}
@@ -64,10 +64,11 @@ pub async fn post(
     content_type: Option<TypedHeader<ContentType>>,
     body: BodyStream,
 ) -> Result<impl IntoResponse, FancyError> {
+    let clock = mas_storage::Clock::default();
     let content_type = content_type.map(|TypedHeader(h)| h.to_string());
 
     let (session_info, _cookie_jar) = cookie_jar.session_info();
-    let maybe_session = session_info.load_session(&pool).await?;
+    let maybe_session = session_info.load_session(&pool, &clock).await?;
 
     let mut request = async_graphql::http::receive_batch_body(
         content_type,
@@ -115,8 +116,9 @@ pub async fn get(
     cookie_jar: PrivateCookieJar<Encrypter>,
     RawQuery(query): RawQuery,
 ) -> Result<impl IntoResponse, FancyError> {
+    let clock = mas_storage::Clock::default();
     let (session_info, _cookie_jar) = cookie_jar.session_info();
-    let maybe_session = session_info.load_session(&pool).await?;
+    let maybe_session = session_info.load_session(&pool, &clock).await?;
 
     let mut request = async_graphql::http::parse_query_string(&query.unwrap_or_default())?;
 
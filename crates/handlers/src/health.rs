@@ -12,12 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::Arc;
+
 use axum::{extract::State, response::IntoResponse};
 use mas_axum_utils::FancyError;
+use mas_policy::PolicyFactory;
 use sqlx::PgPool;
 use tracing::{info_span, Instrument};
 
-pub async fn get(State(pool): State<PgPool>) -> Result<impl IntoResponse, FancyError> {
+pub async fn get(
+    State(pool): State<PgPool>,
+    State(policy_factory): State<Arc<PolicyFactory>>,
+) -> Result<impl IntoResponse, FancyError> {
     let mut conn = pool.acquire().await?;
 
     sqlx::query("SELECT $1")
@@ -26,6 +32,11 @@ pub async fn get(State(pool): State<PgPool>) -> Result<impl IntoResponse, FancyE
         .instrument(info_span!("DB health"))
         .await?;
 
+    policy_factory
+        .healthcheck()
+        .instrument(info_span!("Policy health"))
+        .await?;
+
     Ok("ok")
 }
 
@@ -40,6 +40,7 @@ use mas_http::CorsLayerExt;
 use mas_keystore::{Encrypter, Keystore};
 use mas_policy::PolicyFactory;
 use mas_router::{Route, UrlBuilder};
+use mas_storage::Clock;
 use mas_templates::{ErrorContext, Templates};
 use passwords::PasswordManager;
 use rand::SeedableRng;
@@ -53,6 +54,8 @@ mod graphql;
 mod health;
 mod oauth2;
 pub mod passwords;
+mod rate_limit;
+mod rng;
 mod upstream_oauth2;
 mod views;
 
@@ -74,7 +77,18 @@ macro_rules! impl_from_error_for_route {
 
 pub use mas_axum_utils::http_client_factory::HttpClientFactory;
 
-pub use self::{app_state::AppState, compat::MatrixHomeserver, graphql::schema as graphql_schema};
+pub use self::{
+    app_state::AppState,
+    compat::{MatrixAccessTokenTtl, MatrixHomeserver},
+    graphql::schema as graphql_schema,
+    rate_limit::LoginRateLimiter,
+    rng::SharedRng,
+    upstream_oauth2::discovery::MetadataCache,
+    views::{
+        login::{LoginLockout, MaxSessionAge, SessionCookieTtl},
+        shared::EmailVerificationCodeKind,
+    },
+};
 
 #[must_use]
 pub fn healthcheck_router<S, B>() -> Router<S, B>
@@ -82,6 +96,7 @@ where
     B: HttpBody + Send + 'static,
     S: Clone + Send + Sync + 'static,
     PgPool: FromRef<S>,
+    Arc<PolicyFactory>: FromRef<S>,
 {
     Router::new().route(mas_router::Healthcheck::route(), get(self::health::get))
 }
@@ -207,6 +222,7 @@ where
     UrlBuilder: FromRef<S>,
     PgPool: FromRef<S>,
     MatrixHomeserver: FromRef<S>,
+    MatrixAccessTokenTtl: FromRef<S>,
     PasswordManager: FromRef<S>,
 {
     Router::new()
@@ -255,6 +271,14 @@ where
     Keystore: FromRef<S>,
     HttpClientFactory: FromRef<S>,
     PasswordManager: FromRef<S>,
+    MetadataCache: FromRef<S>,
+    SessionCookieTtl: FromRef<S>,
+    MaxSessionAge: FromRef<S>,
+    LoginLockout: FromRef<S>,
+    LoginRateLimiter: FromRef<S>,
+    EmailVerificationCodeKind: FromRef<S>,
+    Clock: FromRef<S>,
+    SharedRng: FromRef<S>,
 {
     Router::new()
         .route(
@@ -280,6 +304,10 @@ where
             mas_router::AccountPassword::route(),
             get(self::views::account::password::get).post(self::views::account::password::post),
         )
+        .route(
+            mas_router::AccountSession::route(),
+            get(self::views::account::session::get),
+        )
         .route(
             mas_router::AccountEmails::route(),
             get(self::views::account::emails::get).post(self::views::account::emails::post),
@@ -384,6 +412,8 @@ async fn test_state(pool: PgPool) -> Result<AppState, anyhow::Error> {
         "register/violation".to_owned(),
         "client_registration/violation".to_owned(),
         "authorization_grant/violation".to_owned(),
+        "email/violation".to_owned(),
+        None,
     )
     .await?;
 
@@ -393,6 +423,8 @@ async fn test_state(pool: PgPool) -> Result<AppState, anyhow::Error> {
 
     let http_client_factory = HttpClientFactory::new(10);
 
+    let upstream_oauth2_metadata_cache = MetadataCache::new(chrono::Duration::hours(1));
+
     Ok(AppState {
         pool,
         templates,
@@ -401,10 +433,19 @@ async fn test_state(pool: PgPool) -> Result<AppState, anyhow::Error> {
         url_builder,
         mailer,
         homeserver,
+        compat_access_token_ttl: MatrixAccessTokenTtl::default(),
+        session_cookie_ttl: SessionCookieTtl::default(),
+        max_session_age: MaxSessionAge::default(),
+        login_lockout: LoginLockout::default(),
+        login_rate_limiter: LoginRateLimiter::default(),
+        email_verification_code_kind: EmailVerificationCodeKind::default(),
+        clock: Clock::default(),
+        rng: SharedRng::seed_from_u64(42),
         policy_factory,
         graphql_schema,
         http_client_factory,
         password_manager,
+        upstream_oauth2_metadata_cache,
     })
 }
 
@@ -84,11 +84,12 @@ pub(crate) async fn get(
     cookie_jar: PrivateCookieJar<Encrypter>,
     Path(grant_id): Path<Ulid>,
 ) -> Result<Response, RouteError> {
+    let clock = mas_storage::Clock::default();
     let mut txn = pool.begin().await?;
 
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut txn).await?;
+    let maybe_session = session_info.load_session(&mut txn, &clock).await?;
 
     let grant = get_grant_by_id(&mut txn, grant_id)
         .await?
@@ -184,6 +185,7 @@ pub(crate) async fn complete(
     let lacks_consent = grant
         .scope
         .difference(&current_consent)
+        .iter()
         .any(|scope| !scope.starts_with("urn:matrix:org.matrix.msc2967.client:device:"));
 
     // Check if the client lacks consent *or* if consent was explicitely asked
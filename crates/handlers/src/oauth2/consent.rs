@@ -82,7 +82,7 @@ pub(crate) async fn get(
 
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut conn).await?;
+    let maybe_session = session_info.load_session(&mut conn, &clock).await?;
 
     let grant = get_grant_by_id(&mut conn, grant_id)
         .await?
@@ -137,7 +137,7 @@ pub(crate) async fn post(
 
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut txn).await?;
+    let maybe_session = session_info.load_session(&mut txn, &clock).await?;
 
     let grant = get_grant_by_id(&mut txn, grant_id)
         .await?
@@ -74,6 +74,13 @@ impl IntoResponse for RouteError {
                 Json(ClientError::from(ClientErrorCode::InvalidClient)),
             )
                 .into_response(),
+            Self::ClientCredentialsVerification(e @ CredentialsVerificationError::Database(_)) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(
+                    ClientError::from(ClientErrorCode::ServerError).with_description(e.to_string()),
+                ),
+            )
+                .into_response(),
             Self::ClientCredentialsVerification(e) => (
                 StatusCode::UNAUTHORIZED,
                 Json(
@@ -126,6 +133,7 @@ const API_SCOPE: ScopeToken = ScopeToken::from_static("urn:matrix:org.matrix.msc
 #[allow(clippy::too_many_lines)]
 pub(crate) async fn post(
     State(http_client_factory): State<HttpClientFactory>,
+    State(url_builder): State<UrlBuilder>,
     State(pool): State<PgPool>,
     State(encrypter): State<Encrypter>,
     client_authorization: ClientAuthorization<IntrospectionRequest>,
@@ -148,7 +156,19 @@ pub(crate) async fn post(
 
     client_authorization
         .credentials
-        .verify(&http_client_factory, &encrypter, method, &client)
+        .verify(
+            &mut conn,
+            &clock,
+            &http_client_factory,
+            &encrypter,
+            method,
+            &client,
+            &[
+                url_builder.oidc_issuer().to_string(),
+                url_builder.oauth_token_endpoint().to_string(),
+                url_builder.oauth_introspection_endpoint().to_string(),
+            ],
+        )
         .await?;
 
     let form = if let Some(form) = client_authorization.form {
@@ -257,3 +277,93 @@ pub(crate) async fn post(
 
     Ok(Json(reply))
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::FromRef;
+    use hyper::{header::CONTENT_TYPE, Body, Request};
+    use mas_data_model::Device;
+    use mas_storage::{
+        compat::{
+            add_compat_access_token, add_compat_refresh_token, consume_compat_refresh_token,
+            start_compat_session,
+        },
+        oauth2::client::insert_client_from_config,
+        user::add_user,
+    };
+    use rand::SeedableRng;
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+    use ulid::Ulid;
+
+    use super::*;
+
+    #[sqlx::test(migrator = "mas_storage::MIGRATOR")]
+    async fn test_introspect_consumed_compat_refresh_token(
+        pool: PgPool,
+    ) -> Result<(), anyhow::Error> {
+        let state = crate::test_state(pool.clone()).await?;
+        let encrypter = Encrypter::from_ref(&state);
+
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let client_id = Ulid::from_datetime_with_source(clock.now().into(), &mut rng);
+        let client_secret = encrypter.encryt_to_string(b"secret")?;
+        insert_client_from_config(
+            &mut conn,
+            &mut rng,
+            &clock,
+            client_id,
+            OAuthClientAuthenticationMethod::ClientSecretPost,
+            Some(&client_secret),
+            None,
+            None,
+            &[],
+        )
+        .await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let device = Device::generate(&mut rng);
+        let session = start_compat_session(&mut conn, &mut rng, &clock, user, device).await?;
+        let access_token = add_compat_access_token(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &session,
+            TokenType::CompatAccessToken.generate(&mut rng),
+            None,
+        )
+        .await?;
+        let refresh_token = add_compat_refresh_token(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &session,
+            &access_token,
+            TokenType::CompatRefreshToken.generate(&mut rng),
+        )
+        .await?;
+        let token = refresh_token.token.clone();
+        consume_compat_refresh_token(&mut conn, &clock, refresh_token).await?;
+
+        let app = crate::api_router().with_state(state);
+        let body = serde_urlencoded::to_string([
+            ("client_id", client_id.to_string()),
+            ("client_secret", "secret".to_owned()),
+            ("token", token),
+        ])?;
+        let request = Request::post("/oauth2/introspect")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(body))?;
+
+        let response = app.oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(body, serde_json::json!({ "active": false }));
+
+        Ok(())
+    }
+}
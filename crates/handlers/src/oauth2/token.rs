@@ -107,7 +107,9 @@ pub(crate) enum RouteError {
 impl IntoResponse for RouteError {
     fn into_response(self) -> axum::response::Response {
         match self {
-            Self::Internal(_) | Self::InvalidSigningKey => (
+            Self::Internal(_)
+            | Self::InvalidSigningKey
+            | Self::ClientCredentialsVerification(CredentialsVerificationError::Database(_)) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(ClientError::from(ClientErrorCode::ServerError)),
             ),
@@ -155,6 +157,7 @@ pub(crate) async fn post(
     State(encrypter): State<Encrypter>,
     client_authorization: ClientAuthorization<AccessTokenRequest>,
 ) -> Result<impl IntoResponse, RouteError> {
+    let clock = mas_storage::Clock::default();
     let mut txn = pool.begin().await?;
 
     let client = client_authorization
@@ -170,7 +173,18 @@ pub(crate) async fn post(
 
     client_authorization
         .credentials
-        .verify(&http_client_factory, &encrypter, method, &client)
+        .verify(
+            &mut txn,
+            &clock,
+            &http_client_factory,
+            &encrypter,
+            method,
+            &client,
+            &[
+                url_builder.oidc_issuer().to_string(),
+                url_builder.oauth_token_endpoint().to_string(),
+            ],
+        )
         .await?;
 
     let form = client_authorization.form.ok_or(RouteError::BadRequest)?;
@@ -246,6 +260,11 @@ async fn authorization_code_grant(
                 return Err(RouteError::InvalidGrant);
             }
 
+            if !session.browser_session.user.is_active() {
+                debug!("User was deactivated since the grant was fulfilled");
+                return Err(RouteError::InvalidGrant);
+            }
+
             session
         }
     };
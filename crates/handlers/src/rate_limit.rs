@@ -0,0 +1,248 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In-memory rate limiting of the failed password login attempts
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    num::NonZeroU16,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use hyper::HeaderMap;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Ip(IpAddr),
+    Username(String),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("too many failed login attempts")]
+pub struct RateLimited;
+
+/// Limits how many failed login attempts can be made from a single IP
+/// address or against a single username.
+///
+/// It uses the generic cell rate algorithm: each key can do `burst` attempts
+/// at once, and gets an extra attempt back every `period`. It is cheap to
+/// clone, and all clones share the same state.
+///
+/// Usernames are compared case-insensitively, so that changing the case of a
+/// username doesn't give extra attempts.
+#[derive(Debug, Clone)]
+pub struct LoginRateLimiter {
+    burst: i32,
+    period: Duration,
+
+    /// The reverse proxies trusted to set the `X-Forwarded-For` header
+    trusted_proxies: Arc<[IpAddr]>,
+
+    /// The theoretical arrival time of the next attempt for each key
+    state: Arc<Mutex<HashMap<Key, DateTime<Utc>>>>,
+}
+
+impl LoginRateLimiter {
+    /// Create a new [`LoginRateLimiter`], allowing `burst` attempts at once,
+    /// and an extra one every `period`
+    #[must_use]
+    pub fn new(burst: NonZeroU16, period: Duration) -> Self {
+        Self {
+            burst: burst.get().into(),
+            period,
+            trusted_proxies: Arc::new([]),
+            state: Arc::default(),
+        }
+    }
+
+    /// Set the IP addresses of the reverse proxies trusted to set the
+    /// `X-Forwarded-For` header
+    #[must_use]
+    pub fn with_trusted_proxies(mut self, trusted_proxies: Vec<IpAddr>) -> Self {
+        self.trusted_proxies = trusted_proxies.into();
+        self
+    }
+
+    /// Figure out the IP address of the client from the address of the peer
+    /// and the `X-Forwarded-For` header.
+    ///
+    /// The header is only honoured on requests coming from a trusted proxy. The
+    /// client is then the last address of the chain which is not a trusted
+    /// proxy itself.
+    #[must_use]
+    pub fn client_ip(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        let mut client = peer?;
+
+        let forwarded: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect();
+
+        for address in forwarded.into_iter().rev() {
+            if !self.trusted_proxies.contains(&client) {
+                break;
+            }
+
+            let Ok(address) = address.trim().parse() else { break };
+            client = address;
+        }
+
+        Some(client)
+    }
+
+    fn keys(ip: Option<IpAddr>, username: &str) -> impl Iterator<Item = Key> {
+        ip.map(Key::Ip)
+            .into_iter()
+            .chain(std::iter::once(Key::Username(username.to_lowercase())))
+    }
+
+    /// Check whether a login attempt is allowed, without counting it
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RateLimited`] if either the IP address or the username made
+    /// too many failed attempts recently
+    ///
+    /// # Panics
+    ///
+    /// If the internal lock was poisoned
+    pub fn check(
+        &self,
+        now: DateTime<Utc>,
+        ip: Option<IpAddr>,
+        username: &str,
+    ) -> Result<(), RateLimited> {
+        let state = self.state.lock().unwrap();
+        let tolerance = self.period * (self.burst - 1);
+
+        for key in Self::keys(ip, username) {
+            if let Some(tat) = state.get(&key) {
+                if *tat - now > tolerance {
+                    return Err(RateLimited);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Count a failed login attempt against the IP address and the username
+    ///
+    /// # Panics
+    ///
+    /// If the internal lock was poisoned
+    pub fn record_failure(&self, now: DateTime<Utc>, ip: Option<IpAddr>, username: &str) {
+        let mut state = self.state.lock().unwrap();
+
+        // Forget about the keys which are back to their full burst capacity
+        state.retain(|_, tat| *tat > now);
+
+        for key in Self::keys(ip, username) {
+            let tat = state.entry(key).or_insert(now);
+            *tat = std::cmp::max(*tat, now) + self.period;
+        }
+    }
+}
+
+impl Default for LoginRateLimiter {
+    fn default() -> Self {
+        Self::new(NonZeroU16::new(10).unwrap(), Duration::minutes(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn limits_failed_attempts() {
+        let limiter = LoginRateLimiter::new(NonZeroU16::new(3).unwrap(), Duration::minutes(1));
+        let now = Utc.timestamp_opt(1_516_239_022, 0).unwrap();
+        let ip = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        for _ in 0..3 {
+            assert_eq!(limiter.check(now, ip, "alice"), Ok(()));
+            limiter.record_failure(now, ip, "alice");
+        }
+
+        // Both the IP and the username are now limited
+        assert_eq!(limiter.check(now, ip, "bob"), Err(RateLimited));
+        assert_eq!(limiter.check(now, None, "alice"), Err(RateLimited));
+        assert_eq!(limiter.check(now, None, "bob"), Ok(()));
+
+        // An attempt is allowed again after a period
+        let later = now + Duration::minutes(1);
+        assert_eq!(limiter.check(later, ip, "alice"), Ok(()));
+    }
+
+    #[test]
+    fn shared_between_clones() {
+        let limiter = LoginRateLimiter::new(NonZeroU16::new(1).unwrap(), Duration::minutes(1));
+        let now = Utc.timestamp_opt(1_516_239_022, 0).unwrap();
+
+        limiter.clone().record_failure(now, None, "alice");
+        assert_eq!(limiter.check(now, None, "alice"), Err(RateLimited));
+    }
+
+    #[test]
+    fn usernames_are_case_insensitive() {
+        let limiter = LoginRateLimiter::new(NonZeroU16::new(1).unwrap(), Duration::minutes(1));
+        let now = Utc.timestamp_opt(1_516_239_022, 0).unwrap();
+
+        limiter.record_failure(now, None, "alice");
+        assert_eq!(limiter.check(now, None, "Alice"), Err(RateLimited));
+        assert_eq!(limiter.check(now, None, "ALICE"), Err(RateLimited));
+    }
+
+    #[test]
+    fn client_ip_from_trusted_proxies() {
+        let proxy = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let client = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let spoofed = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 1));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            format!("{spoofed}, {client}").parse().unwrap(),
+        );
+        headers.append("x-forwarded-for", "10.0.0.1".parse().unwrap());
+
+        // Without any trusted proxy, the header is ignored
+        let limiter = LoginRateLimiter::default();
+        assert_eq!(limiter.client_ip(Some(proxy), &headers), Some(proxy));
+
+        // The client is the last address which is not a trusted proxy
+        let limiter = LoginRateLimiter::default().with_trusted_proxies(vec![proxy]);
+        assert_eq!(limiter.client_ip(Some(proxy), &headers), Some(client));
+
+        // The header is ignored on requests not coming from a trusted proxy
+        assert_eq!(limiter.client_ip(Some(client), &headers), Some(client));
+
+        // Invalid addresses stop the chain
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "not-an-ip".parse().unwrap());
+        assert_eq!(limiter.client_ip(Some(proxy), &headers), Some(proxy));
+
+        assert_eq!(limiter.client_ip(None, &headers), None);
+    }
+}
@@ -0,0 +1,91 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A random number generator shared by all the requests
+
+use std::sync::{Arc, Mutex};
+
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaChaRng;
+
+/// A cryptographically secure random number generator, seeded once and shared
+/// between all the handlers through the application state.
+///
+/// It is cheap to clone, and all clones draw from the same stream. Tests can
+/// create one from a fixed seed to get deterministic results.
+#[derive(Debug, Clone)]
+pub struct SharedRng {
+    inner: Arc<Mutex<ChaChaRng>>,
+}
+
+impl SharedRng {
+    /// Create a [`SharedRng`] seeded from the operating system entropy source
+    #[must_use]
+    pub fn from_entropy() -> Self {
+        Self::new(ChaChaRng::from_entropy())
+    }
+
+    /// Create a deterministic [`SharedRng`] from a fixed seed
+    #[must_use]
+    pub fn seed_from_u64(seed: u64) -> Self {
+        Self::new(ChaChaRng::seed_from_u64(seed))
+    }
+
+    fn new(rng: ChaChaRng) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(rng)),
+        }
+    }
+}
+
+impl RngCore for SharedRng {
+    fn next_u32(&mut self) -> u32 {
+        self.inner.lock().unwrap().next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.inner.lock().unwrap().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.inner.lock().unwrap().fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.inner.lock().unwrap().try_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for SharedRng {}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn deterministic_and_shared() {
+        let mut a = SharedRng::seed_from_u64(42);
+        let mut b = SharedRng::seed_from_u64(42);
+        assert_eq!(a.gen::<u64>(), b.gen::<u64>());
+
+        // Clones draw from the same stream
+        let mut c = a.clone();
+        let from_clone: u64 = c.gen();
+        let from_b: u64 = b.gen();
+        assert_eq!(from_clone, from_b);
+        assert_ne!(a.gen::<u64>(), from_clone);
+    }
+}
@@ -22,13 +22,13 @@ use mas_axum_utils::http_client_factory::HttpClientFactory;
 use mas_keystore::Encrypter;
 use mas_oidc_client::requests::authorization_code::AuthorizationRequestData;
 use mas_router::UrlBuilder;
-use mas_storage::upstream_oauth2::lookup_provider;
+use mas_storage::{upstream_oauth2::lookup_provider, Clock};
 use sqlx::PgPool;
 use thiserror::Error;
 use ulid::Ulid;
 
-use super::UpstreamSessionsCookie;
-use crate::{impl_from_error_for_route, views::shared::OptionalPostAuthAction};
+use super::{discovery::MetadataCache, UpstreamSessionsCookie};
+use crate::{impl_from_error_for_route, views::shared::OptionalPostAuthAction, SharedRng};
 
 #[derive(Debug, Error)]
 pub(crate) enum RouteError {
@@ -41,7 +41,7 @@ pub(crate) enum RouteError {
 
 impl_from_error_for_route!(sqlx::Error);
 impl_from_error_for_route!(mas_http::ClientInitError);
-impl_from_error_for_route!(mas_oidc_client::error::DiscoveryError);
+impl_from_error_for_route!(super::discovery::MetadataCacheError);
 impl_from_error_for_route!(mas_oidc_client::error::AuthorizationError);
 impl_from_error_for_route!(mas_storage::DatabaseError);
 
@@ -54,16 +54,18 @@ impl IntoResponse for RouteError {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn get(
+    State(clock): State<Clock>,
+    State(mut rng): State<SharedRng>,
     State(http_client_factory): State<HttpClientFactory>,
+    State(metadata_cache): State<MetadataCache>,
     State(pool): State<PgPool>,
     State(url_builder): State<UrlBuilder>,
     cookie_jar: PrivateCookieJar<Encrypter>,
     Path(provider_id): Path<Ulid>,
     Query(query): Query<OptionalPostAuthAction>,
 ) -> Result<impl IntoResponse, RouteError> {
-    let (clock, mut rng) = crate::clock_and_rng();
-
     let mut txn = pool.begin().await?;
 
     let provider = lookup_provider(&mut txn, provider_id)
@@ -75,8 +77,10 @@ pub(crate) async fn get(
         .await?;
 
     // First, discover the provider
-    let metadata =
-        mas_oidc_client::requests::discovery::discover(&http_service, &provider.issuer).await?;
+    let metadata = metadata_cache
+        .get(&http_service, clock.now(), &provider.issuer)
+        .await?
+        .metadata;
 
     let redirect_uri = url_builder.upstream_oauth_callback(provider.id);
 
@@ -84,8 +88,15 @@ pub(crate) async fn get(
         client_id: &provider.client_id,
         scope: &provider.scope,
         prompt: None,
+        max_age: None,
+        response_mode: None,
+        login_hint: None,
+        ui_locales: None,
+        acr_values: None,
+        resource: None,
         redirect_uri: &redirect_uri,
         code_challenge_methods_supported: metadata.code_challenge_methods_supported.as_deref(),
+        allow_plain_pkce: provider.allow_plain_pkce,
     };
 
     // Build an authorization request for it
@@ -14,28 +14,39 @@
 
 use axum::{
     extract::{Path, Query, State},
-    response::IntoResponse,
+    response::{Html, IntoResponse},
 };
 use axum_extra::extract::PrivateCookieJar;
 use hyper::StatusCode;
 use mas_axum_utils::http_client_factory::HttpClientFactory;
-use mas_jose::claims::ClaimError;
+use mas_iana::jose::JsonWebSignatureAlg;
+use mas_jose::{claims::ClaimError, jwk::PublicJsonWebKeySet};
 use mas_keystore::{Encrypter, Keystore};
-use mas_oidc_client::requests::{
-    authorization_code::AuthorizationValidationData, jose::JwtVerificationData,
+use mas_oidc_client::{
+    error::{IdTokenError, JwtVerificationError},
+    requests::{
+        authorization_code::{verify_authorization_code_id_token, AuthorizationValidationData},
+        jose::JwtVerificationData,
+        userinfo::fetch_userinfo,
+    },
 };
 use mas_router::{Route, UrlBuilder};
-use mas_storage::upstream_oauth2::{
-    add_link, complete_session, lookup_link_by_subject, lookup_session,
+use mas_storage::{
+    upstream_oauth2::{
+        complete_session, lookup_or_add_link, lookup_provider, lookup_session,
+        lookup_session_by_state, store_upstream_tokens,
+    },
+    Clock,
 };
+use mas_templates::{Templates, UpstreamCancelledContext};
 use oauth2_types::errors::ClientErrorCode;
 use serde::Deserialize;
 use sqlx::PgPool;
 use thiserror::Error;
 use ulid::Ulid;
 
-use super::{client_credentials_for_provider, UpstreamSessionsCookie};
-use crate::impl_from_error_for_route;
+use super::{client_credentials_for_provider, discovery::MetadataCache, UpstreamSessionsCookie};
+use crate::{impl_from_error_for_route, SharedRng};
 
 #[derive(Deserialize)]
 pub struct QueryParams {
@@ -61,6 +72,9 @@ enum CodeOrError {
 
 #[derive(Debug, Error)]
 pub(crate) enum RouteError {
+    #[error("Provider not found")]
+    ProviderNotFound,
+
     #[error("Session not found")]
     SessionNotFound,
 
@@ -79,15 +93,18 @@ pub(crate) enum RouteError {
     #[error("Invalid ID token")]
     InvalidIdToken(#[from] ClaimError),
 
+    #[error("Provider doesn't have a userinfo endpoint")]
+    MissingUserinfoEndpoint,
+
+    #[error("ID token was not signed with the expected algorithm {expected}")]
+    IdTokenSignatureAlgMismatch { expected: JsonWebSignatureAlg },
+
     #[error("Error from the provider: {error}")]
     ClientError {
         error: ClientErrorCode,
         error_description: Option<String>,
     },
 
-    #[error("Missing session cookie")]
-    MissingCookie,
-
     #[error(transparent)]
     Internal(Box<dyn std::error::Error>),
 }
@@ -95,15 +112,18 @@ pub(crate) enum RouteError {
 impl_from_error_for_route!(mas_storage::DatabaseError);
 impl_from_error_for_route!(mas_http::ClientInitError);
 impl_from_error_for_route!(sqlx::Error);
-impl_from_error_for_route!(mas_oidc_client::error::DiscoveryError);
-impl_from_error_for_route!(mas_oidc_client::error::JwksError);
 impl_from_error_for_route!(mas_oidc_client::error::TokenAuthorizationCodeError);
+impl_from_error_for_route!(mas_oidc_client::error::UserInfoError);
+impl_from_error_for_route!(super::discovery::MetadataCacheError);
 impl_from_error_for_route!(super::ProviderCredentialsError);
 impl_from_error_for_route!(super::cookie::UpstreamSessionNotFound);
+impl_from_error_for_route!(mas_templates::TemplateError);
+impl_from_error_for_route!(mas_keystore::aead::Error);
 
 impl IntoResponse for RouteError {
     fn into_response(self) -> axum::response::Response {
         match self {
+            Self::ProviderNotFound => (StatusCode::NOT_FOUND, "Provider not found").into_response(),
             Self::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found").into_response(),
             Self::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
             e => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
@@ -113,30 +133,53 @@ impl IntoResponse for RouteError {
 
 #[allow(clippy::too_many_lines, clippy::too_many_arguments)]
 pub(crate) async fn get(
+    State(clock): State<Clock>,
+    State(mut rng): State<SharedRng>,
     State(http_client_factory): State<HttpClientFactory>,
+    State(metadata_cache): State<MetadataCache>,
     State(pool): State<PgPool>,
     State(url_builder): State<UrlBuilder>,
     State(encrypter): State<Encrypter>,
     State(keystore): State<Keystore>,
+    State(templates): State<Templates>,
     cookie_jar: PrivateCookieJar<Encrypter>,
     Path(provider_id): Path<Ulid>,
     Query(params): Query<QueryParams>,
 ) -> Result<impl IntoResponse, RouteError> {
-    let (clock, mut rng) = crate::clock_and_rng();
-
     let mut txn = pool.begin().await?;
 
-    let sessions_cookie = UpstreamSessionsCookie::load(&cookie_jar);
-    let (session_id, _post_auth_action) = sessions_cookie
-        .find_session(provider_id, &params.state)
-        .map_err(|_| RouteError::MissingCookie)?;
-
-    let (provider, session) = lookup_session(&mut txn, session_id)
+    let provider = lookup_provider(&mut txn, provider_id)
         .await?
-        .ok_or(RouteError::SessionNotFound)?;
+        .ok_or(RouteError::ProviderNotFound)?;
 
-    if provider.id != provider_id {
-        // The provider in the session cookie should match the one from the URL
+    let mut sessions_cookie = UpstreamSessionsCookie::load(&cookie_jar);
+    let cookie_session = sessions_cookie
+        .find_session(provider_id, &params.state)
+        .ok()
+        .map(|(session_id, post_auth_action)| (session_id, post_auth_action.cloned()));
+
+    let (session_provider_id, session, post_auth_action) =
+        if let Some((session_id, post_auth_action)) = cookie_session {
+            let (session_provider, session) = lookup_session(&mut txn, session_id)
+                .await?
+                .ok_or(RouteError::SessionNotFound)?;
+
+            (session_provider.id, session, post_auth_action)
+        } else {
+            // The cookie might be missing, e.g. if the authorization was
+            // finished on another device, so find the session from the state
+            // and remember it for the link step
+            let session = lookup_session_by_state(&mut txn, &provider, &params.state)
+                .await?
+                .ok_or(RouteError::SessionNotFound)?;
+            sessions_cookie =
+                sessions_cookie.add(session.id, provider.id, session.state.clone(), None);
+
+            (session.provider_id, session, None)
+        };
+
+    if session_provider_id != provider.id {
+        // The provider in the session should match the one from the URL
         return Err(RouteError::ProviderMismatch);
     }
 
@@ -152,6 +195,20 @@ pub(crate) async fn get(
 
     // Let's extract the code from the params, and return if there was an error
     let code = match params.code_or_error {
+        CodeOrError::Error {
+            error,
+            error_description,
+            ..
+        } if error == ClientErrorCode::AccessDenied || error.is_interaction_required() => {
+            // The user cancelled the authorization on the provider side, or the
+            // provider needs them to interact with it, so show them a way back
+            // to the login page instead of a bare error
+            let ctx = UpstreamCancelledContext::new(post_auth_action)
+                .with_error_description(error_description);
+            let content = templates.render_upstream_oauth2_cancelled(&ctx).await?;
+
+            return Ok(Html(content).into_response());
+        }
         CodeOrError::Error {
             error,
             error_description,
@@ -169,18 +226,11 @@ pub(crate) async fn get(
         .http_service("upstream-discover")
         .await?;
 
-    // XXX: we shouldn't discover on-the-fly
-    // Discover the provider
-    let metadata =
-        mas_oidc_client::requests::discovery::discover(&http_service, &provider.issuer).await?;
-
-    let http_service = http_client_factory
-        .http_service("upstream-fetch-jwks")
+    // Get the provider metadata and keys, which are cached
+    let provider_metadata = metadata_cache
+        .get(&http_service, clock.now(), &provider.issuer)
         .await?;
-
-    // Fetch the JWKS
-    let jwks =
-        mas_oidc_client::requests::jose::fetch_jwks(&http_service, metadata.jwks_uri()).await?;
+    let metadata = &provider_metadata.metadata;
 
     // Figure out the client credentials
     let client_credentials = client_credentials_for_provider(
@@ -198,48 +248,132 @@ pub(crate) async fn get(
         nonce: session.nonce.clone(),
         code_challenge_verifier: session.code_challenge_verifier.clone(),
         redirect_uri,
-    };
-
-    let id_token_verification_data = JwtVerificationData {
-        issuer: &provider.issuer,
-        jwks: &jwks,
-        // TODO: make that configurable
-        signing_algorithm: &mas_iana::jose::JsonWebSignatureAlg::Rs256,
-        client_id: &provider.client_id,
+        resource: None,
     };
 
     let http_service = http_client_factory
         .http_service("upstream-exchange-code")
         .await?;
 
-    let (response, id_token) =
+    let (response, _) =
         mas_oidc_client::requests::authorization_code::access_token_with_authorization_code(
             &http_service,
             client_credentials,
             metadata.token_endpoint(),
-            code,
+            code.clone(),
             validation_data,
-            Some(id_token_verification_data),
+            None,
+            false,
             clock.now(),
             &mut rng,
         )
         .await?;
 
-    let (_header, mut id_token) = id_token.ok_or(RouteError::MissingIDToken)?.into_parts();
+    let id_token_signed_response_alg = provider
+        .id_token_signed_response_alg
+        .as_ref()
+        .unwrap_or(&JsonWebSignatureAlg::Rs256);
+
+    let verify_id_token = |jwks: &PublicJsonWebKeySet| {
+        verify_authorization_code_id_token(
+            &response,
+            &code,
+            &session.nonce,
+            JwtVerificationData {
+                issuer: &provider.issuer,
+                jwks,
+                signing_algorithm: id_token_signed_response_alg,
+                client_id: &provider.client_id,
+                leeway: JwtVerificationData::default_leeway(),
+            },
+            clock.now(),
+        )
+    };
 
-    // Extract the subject from the id_token
-    let subject = mas_jose::claims::SUB.extract_required(&mut id_token)?;
+    let id_token = match verify_id_token(&provider_metadata.jwks) {
+        Err(IdTokenError::Jwt(JwtVerificationError::JwtSignature(_))) => {
+            // None of the cached keys worked, the provider might have rotated them
+            let http_service = http_client_factory
+                .http_service("upstream-fetch-jwks")
+                .await?;
+            let provider_metadata = metadata_cache
+                .refresh(&http_service, clock.now(), &provider.issuer)
+                .await?;
+            verify_id_token(&provider_metadata.jwks)
+        }
+        res => res,
+    }
+    .map_err(|e| match e {
+        IdTokenError::MissingIdToken => RouteError::MissingIDToken,
+        IdTokenError::Jwt(JwtVerificationError::WrongSignatureAlg) => {
+            RouteError::IdTokenSignatureAlgMismatch {
+                expected: id_token_signed_response_alg.clone(),
+            }
+        }
+        e => RouteError::Internal(Box::new(e)),
+    })?;
 
-    // Look for an existing link
-    let maybe_link = lookup_link_by_subject(&mut txn, &provider, &subject).await?;
+    // Some providers only give out profile claims through the userinfo endpoint
+    let userinfo = if provider.fetch_userinfo {
+        let userinfo_endpoint = metadata
+            .userinfo_endpoint
+            .as_ref()
+            .ok_or(RouteError::MissingUserinfoEndpoint)?;
 
-    let link = if let Some(link) = maybe_link {
-        link
+        let http_service = http_client_factory
+            .http_service("upstream-fetch-userinfo")
+            .await?;
+
+        let claims = fetch_userinfo(
+            &http_service,
+            userinfo_endpoint,
+            &response.access_token,
+            None,
+            &id_token,
+        )
+        .await?;
+
+        Some(serde_json::Value::Object(claims.into_iter().collect()))
     } else {
-        add_link(&mut txn, &mut rng, &clock, &provider, subject).await?
+        None
     };
 
-    let session = complete_session(&mut txn, &clock, session, &link, response.id_token).await?;
+    let (_header, mut id_token) = id_token.into_parts();
+
+    // Extract the subject from the id_token
+    let subject = mas_jose::claims::SUB.extract_required(&mut id_token)?;
+
+    // Look for an existing link, or create one
+    let link = lookup_or_add_link(&mut txn, &mut rng, &clock, &provider, subject).await?;
+
+    // Keep the tokens, so that we can call the provider APIs later on
+    let encrypted_access_token = encrypter.encryt_to_string(response.access_token.as_bytes())?;
+    let encrypted_refresh_token = response
+        .refresh_token
+        .as_ref()
+        .map(|refresh_token| encrypter.encryt_to_string(refresh_token.as_bytes()))
+        .transpose()?;
+    let expires_at = response.expires_at(clock.now());
+
+    store_upstream_tokens(
+        &mut txn,
+        &clock,
+        &link,
+        &encrypted_access_token,
+        encrypted_refresh_token.as_deref(),
+        expires_at,
+    )
+    .await?;
+
+    let session = complete_session(
+        &mut txn,
+        &clock,
+        session,
+        &link,
+        response.id_token,
+        userinfo,
+    )
+    .await?;
     let cookie_jar = sessions_cookie
         .add_link_to_session(session.id, link.id)?
         .save(cookie_jar, clock.now());
@@ -249,5 +383,6 @@ pub(crate) async fn get(
     Ok((
         cookie_jar,
         mas_router::UpstreamOAuth2Link::new(link.id).go(),
-    ))
+    )
+        .into_response())
 }
@@ -0,0 +1,283 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use mas_http::HttpService;
+use mas_jose::jwk::PublicJsonWebKeySet;
+use mas_oidc_client::error::{DiscoveryError, JwksError};
+use oauth2_types::oidc::VerifiedProviderMetadata;
+use thiserror::Error;
+
+/// The metadata and keys of an upstream provider
+#[derive(Debug, Clone)]
+pub struct CachedMetadata {
+    pub metadata: Arc<VerifiedProviderMetadata>,
+    pub jwks: Arc<PublicJsonWebKeySet>,
+    fetched_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Error)]
+pub enum MetadataCacheError {
+    #[error(transparent)]
+    Discovery(#[from] DiscoveryError),
+
+    #[error(transparent)]
+    Jwks(#[from] JwksError),
+}
+
+/// A cache of the discovered metadata and keys of upstream providers, keyed by
+/// issuer
+///
+/// Only one fetch per issuer runs at a time: concurrent lookups of the same
+/// issuer wait for the running fetch and reuse its result.
+#[derive(Debug, Clone)]
+pub struct MetadataCache {
+    ttl: Duration,
+    cache: Arc<RwLock<HashMap<String, CachedMetadata>>>,
+    fetch_locks: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl MetadataCache {
+    #[must_use]
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cache: Arc::default(),
+            fetch_locks: Arc::default(),
+        }
+    }
+
+    fn cached(&self, issuer: &str) -> Option<CachedMetadata> {
+        self.cache
+            .read()
+            .expect("metadata cache lock poisoned")
+            .get(issuer)
+            .cloned()
+    }
+
+    fn fetch_lock(&self, issuer: &str) -> Arc<tokio::sync::Mutex<()>> {
+        self.fetch_locks
+            .lock()
+            .expect("metadata cache lock poisoned")
+            .entry(issuer.to_owned())
+            .or_default()
+            .clone()
+    }
+
+    /// Get the metadata and keys of an issuer, discovering them if they are
+    /// not in the cache or if they expired
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the discovery or the JWKS fetch failed
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock on the cache was poisoned
+    pub async fn get(
+        &self,
+        http_service: &HttpService,
+        now: DateTime<Utc>,
+        issuer: &str,
+    ) -> Result<CachedMetadata, MetadataCacheError> {
+        let fresh = |cached: &CachedMetadata| now < cached.fetched_at + self.ttl;
+
+        if let Some(cached) = self.cached(issuer).filter(fresh) {
+            return Ok(cached);
+        }
+
+        let lock = self.fetch_lock(issuer);
+        let _guard = lock.lock().await;
+
+        // Another lookup might have fetched it while we were waiting for the lock
+        if let Some(cached) = self.cached(issuer).filter(fresh) {
+            return Ok(cached);
+        }
+
+        self.fetch(http_service, now, issuer).await
+    }
+
+    /// Discover the metadata and keys of an issuer, even if they are in the
+    /// cache
+    ///
+    /// This is useful when the keys of the issuer might have been rotated.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the discovery or the JWKS fetch failed
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock on the cache was poisoned
+    pub async fn refresh(
+        &self,
+        http_service: &HttpService,
+        now: DateTime<Utc>,
+        issuer: &str,
+    ) -> Result<CachedMetadata, MetadataCacheError> {
+        let stale = self.cached(issuer);
+
+        let lock = self.fetch_lock(issuer);
+        let _guard = lock.lock().await;
+
+        // If another refresh replaced the entry while we were waiting for the
+        // lock, use its result instead of fetching everything again
+        if let Some(cached) = self.cached(issuer) {
+            let replaced = stale.map_or(true, |stale| !Arc::ptr_eq(&stale.jwks, &cached.jwks));
+            if replaced {
+                return Ok(cached);
+            }
+        }
+
+        self.fetch(http_service, now, issuer).await
+    }
+
+    /// Fetch the metadata and keys of an issuer and store them in the cache
+    ///
+    /// Callers must hold the fetch lock of the issuer.
+    #[tracing::instrument(skip(self, http_service, now), err)]
+    async fn fetch(
+        &self,
+        http_service: &HttpService,
+        now: DateTime<Utc>,
+        issuer: &str,
+    ) -> Result<CachedMetadata, MetadataCacheError> {
+        let metadata = mas_oidc_client::requests::discovery::discover(http_service, issuer).await?;
+        let jwks =
+            mas_oidc_client::requests::jose::fetch_jwks(http_service, metadata.jwks_uri()).await?;
+
+        let cached = CachedMetadata {
+            metadata: Arc::new(metadata),
+            jwks: Arc::new(jwks),
+            fetched_at: now,
+        };
+
+        self.cache
+            .write()
+            .expect("metadata cache lock poisoned")
+            .insert(issuer.to_owned(), cached.clone());
+
+        Ok(cached)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::TimeZone;
+    use hyper::{body::Bytes, Request, Response};
+    use tower::{service_fn, BoxError};
+
+    use super::*;
+
+    /// A fake HTTP service serving the discovery document and the JWKS of
+    /// `https://example.com/`, which counts how many requests it got
+    fn http_service(calls: Arc<AtomicUsize>) -> HttpService {
+        HttpService::new(service_fn(move |request: Request<Bytes>| {
+            calls.fetch_add(1, Ordering::SeqCst);
+
+            let body = match request.uri().path() {
+                "/.well-known/openid-configuration" => serde_json::json!({
+                    "issuer": "https://example.com/",
+                    "authorization_endpoint": "https://example.com/authorize",
+                    "token_endpoint": "https://example.com/token",
+                    "jwks_uri": "https://example.com/jwks.json",
+                    "response_types_supported": ["code"],
+                    "subject_types_supported": ["public"],
+                    "id_token_signing_alg_values_supported": ["RS256"],
+                }),
+                "/jwks.json" => serde_json::json!({ "keys": [] }),
+                path => panic!("unexpected request to {path}"),
+            };
+
+            let response = Response::new(Bytes::from(body.to_string()));
+            async move {
+                // Let concurrent lookups run while this request is in flight
+                tokio::task::yield_now().await;
+                Ok::<_, BoxError>(response)
+            }
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_metadata_cache() {
+        let now = Utc.with_ymd_and_hms(2018, 1, 18, 1, 30, 22).unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let http_service = http_service(calls.clone());
+        let cache = MetadataCache::new(Duration::minutes(10));
+
+        // The first lookup discovers the provider and fetches its keys
+        let provider = cache
+            .get(&http_service, now, "https://example.com/")
+            .await
+            .unwrap();
+        assert_eq!(provider.metadata.issuer(), "https://example.com/");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // The second one hits the cache
+        let now = now + Duration::minutes(5);
+        cache
+            .get(&http_service, now, "https://example.com/")
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Forcing a refresh fetches everything again
+        cache
+            .refresh(&http_service, now, "https://example.com/")
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        // Once the entry expired, it is fetched again
+        let now = now + Duration::minutes(11);
+        cache
+            .get(&http_service, now, "https://example.com/")
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn test_metadata_cache_concurrent_fetches() {
+        let now = Utc.with_ymd_and_hms(2018, 1, 18, 1, 30, 22).unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let http_service = http_service(calls.clone());
+        let cache = MetadataCache::new(Duration::minutes(10));
+
+        // Concurrent lookups of a missing issuer share a single fetch
+        let (first, second) = tokio::join!(
+            cache.get(&http_service, now, "https://example.com/"),
+            cache.get(&http_service, now, "https://example.com/"),
+        );
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert!(Arc::ptr_eq(&first.jwks, &second.jwks));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // So do concurrent refreshes
+        let (first, second) = tokio::join!(
+            cache.refresh(&http_service, now, "https://example.com/"),
+            cache.refresh(&http_service, now, "https://example.com/"),
+        );
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert!(Arc::ptr_eq(&first.jwks, &second.jwks));
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+}
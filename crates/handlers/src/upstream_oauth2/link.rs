@@ -12,35 +12,49 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+
 use axum::{
     extract::{Path, State},
     response::{Html, IntoResponse},
-    Form,
+    Extension, Form,
 };
 use axum_extra::extract::PrivateCookieJar;
-use hyper::StatusCode;
+use hyper::{HeaderMap, StatusCode};
 use mas_axum_utils::{
     csrf::{CsrfExt, ProtectedForm},
     SessionInfoExt,
 };
+use mas_data_model::{UpstreamOAuthAuthorizationSession, UpstreamOAuthProvider};
+use mas_jose::jwt::Jwt;
 use mas_keystore::Encrypter;
+use mas_listener::ConnectionInfo;
 use mas_storage::{
     upstream_oauth2::{
-        associate_link_to_user, consume_session, lookup_link, lookup_session_on_link,
+        authenticate_with_link, link_to_session, login_and_link, lookup_link, lookup_provider,
+        lookup_session_on_link, register_and_link,
     },
-    user::{add_user, authenticate_session_with_upstream, lookup_user, start_session},
+    user::{add_user_email, lookup_user, mark_user_email_as_verified, set_user_email_as_primary},
 };
 use mas_templates::{
     EmptyContext, TemplateContext, Templates, UpstreamExistingLinkContext, UpstreamRegister,
     UpstreamSuggestLink,
 };
 use serde::Deserialize;
+use serde_json::Value;
 use sqlx::PgPool;
 use thiserror::Error;
 use ulid::Ulid;
 
 use super::UpstreamSessionsCookie;
-use crate::{impl_from_error_for_route, views::shared::OptionalPostAuthAction};
+use crate::{
+    impl_from_error_for_route,
+    rate_limit::LoginRateLimiter,
+    views::{
+        login::MaxSessionAge,
+        shared::{session_metadata, OptionalPostAuthAction},
+    },
+};
 
 #[derive(Debug, Error)]
 pub(crate) enum RouteError {
@@ -56,12 +70,20 @@ pub(crate) enum RouteError {
     #[error("Session already consumed")]
     SessionConsumed,
 
+    /// Couldn't find the provider of the link
+    #[error("Provider not found")]
+    ProviderNotFound,
+
     #[error("Missing session cookie")]
     MissingCookie,
 
     #[error("Invalid form action")]
     InvalidFormAction,
 
+    /// The user associated to the link is deactivated
+    #[error("User is deactivated")]
+    UserDeactivated,
+
     #[error(transparent)]
     Internal(Box<dyn std::error::Error>),
 }
@@ -71,17 +93,78 @@ impl_from_error_for_route!(mas_templates::TemplateError);
 impl_from_error_for_route!(mas_axum_utils::csrf::CsrfError);
 impl_from_error_for_route!(super::cookie::UpstreamSessionNotFound);
 impl_from_error_for_route!(mas_storage::DatabaseError);
+impl_from_error_for_route!(mas_jose::jwt::JwtDecodeError);
 
 impl IntoResponse for RouteError {
     fn into_response(self) -> axum::response::Response {
         match self {
             Self::LinkNotFound => (StatusCode::NOT_FOUND, "Link not found").into_response(),
+            Self::UserDeactivated => (StatusCode::FORBIDDEN, "User is deactivated").into_response(),
             Self::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
             e => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
         }
     }
 }
 
+/// Gather the claims the upstream provider returned about the user, from the
+/// ID token and, if it was queried, from the userinfo endpoint
+fn upstream_claims(
+    upstream_session: &UpstreamOAuthAuthorizationSession,
+) -> Result<HashMap<String, Value>, RouteError> {
+    let mut claims = if let Some(id_token) = &upstream_session.id_token {
+        // The ID token signature was already verified in the callback
+        let id_token: Jwt<'_, HashMap<String, Value>> = Jwt::try_from(id_token.as_str())?;
+        id_token.into_parts().1
+    } else {
+        HashMap::new()
+    };
+
+    if let Some(Value::Object(userinfo)) = &upstream_session.userinfo {
+        claims.extend(userinfo.clone());
+    }
+
+    Ok(claims)
+}
+
+/// The claims from the upstream provider used to pre-fill the registration
+#[derive(Debug, PartialEq, Eq)]
+struct UpstreamSuggestions {
+    username: Option<String>,
+    email: Option<String>,
+    email_verified: bool,
+}
+
+impl UpstreamSuggestions {
+    fn new(
+        provider: &UpstreamOAuthProvider,
+        upstream_session: &UpstreamOAuthAuthorizationSession,
+    ) -> Result<Self, RouteError> {
+        let claims = upstream_claims(upstream_session)?;
+        let claim_as_string = |name: &str| {
+            claims
+                .get(name)
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned)
+        };
+
+        let username_claim = provider
+            .username_claim
+            .as_deref()
+            .unwrap_or("preferred_username");
+
+        Ok(Self {
+            username: claim_as_string(username_claim),
+            email: claim_as_string("email"),
+            // Only trust the upstream to verify emails if configured to do so
+            email_verified: provider.trust_email_verified
+                && claims
+                    .get("email_verified")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false),
+        })
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "lowercase", tag = "action")]
 pub(crate) enum FormData {
@@ -114,21 +197,22 @@ pub(crate) async fn get(
         .await?
         .ok_or(RouteError::SessionNotFound)?;
 
-    if upstream_session.consumed() {
-        return Err(RouteError::SessionConsumed);
-    }
-
     let (user_session_info, cookie_jar) = cookie_jar.session_info();
     let (csrf_token, mut cookie_jar) = cookie_jar.csrf_token(clock.now(), &mut rng);
-    let maybe_user_session = user_session_info.load_session(&mut txn).await?;
+    let maybe_user_session = user_session_info.load_session(&mut txn, &clock).await?;
 
     let render = match (maybe_user_session, link.user_id) {
-        (Some(mut session), Some(user_id)) if session.user.id == user_id => {
+        (Some(session), Some(user_id)) if session.user.id == user_id => {
             // Session already linked, and link matches the currently logged
-            // user. Mark the session as consumed and renew the authentication.
-            consume_session(&mut txn, &clock, upstream_session).await?;
-            authenticate_session_with_upstream(&mut txn, &mut rng, &clock, &mut session, &link)
-                .await?;
+            // user. Mark the session as consumed and renew the authentication,
+            // unless that was already done, e.g. because the page was
+            // refreshed.
+            let session = if upstream_session.consumed() {
+                session
+            } else {
+                authenticate_with_link(&mut txn, &mut rng, &clock, session, &link, upstream_session)
+                    .await?
+            };
 
             cookie_jar = cookie_jar.set_session(&session);
 
@@ -143,6 +227,9 @@ pub(crate) async fn get(
                 .await?
         }
 
+        // The upstream session can't be used for anything else once consumed
+        _ if upstream_session.consumed() => return Err(RouteError::SessionConsumed),
+
         (Some(user_session), Some(user_id)) => {
             // Session already linked, but link doesn't match the currently
             // logged user. Suggest logging out of the current user
@@ -177,7 +264,15 @@ pub(crate) async fn get(
         (None, None) => {
             // Session not linked and used not logged in: suggest creating an
             // account or logging in an existing user
-            let ctx = UpstreamRegister::new(&link).with_csrf(csrf_token.form_value());
+            let provider = lookup_provider(&mut txn, link.provider_id)
+                .await?
+                .ok_or(RouteError::ProviderNotFound)?;
+            let suggestions = UpstreamSuggestions::new(&provider, &upstream_session)?;
+
+            let ctx = UpstreamRegister::new(&link)
+                .with_suggested_username(suggestions.username)
+                .with_suggested_email(suggestions.email)
+                .with_csrf(csrf_token.form_value());
 
             templates.render_upstream_oauth2_do_register(&ctx).await?
         }
@@ -186,8 +281,13 @@ pub(crate) async fn get(
     Ok((cookie_jar, Html(render)))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn post(
     State(pool): State<PgPool>,
+    State(MaxSessionAge(max_session_age)): State<MaxSessionAge>,
+    State(rate_limiter): State<LoginRateLimiter>,
+    connection: Option<Extension<ConnectionInfo>>,
+    headers: HeaderMap,
     cookie_jar: PrivateCookieJar<Encrypter>,
     Path(link_id): Path<Ulid>,
     Form(form): Form<ProtectedForm<FormData>>,
@@ -220,32 +320,68 @@ pub(crate) async fn post(
     }
 
     let (user_session_info, cookie_jar) = cookie_jar.session_info();
-    let maybe_user_session = user_session_info.load_session(&mut txn).await?;
+    let maybe_user_session = user_session_info.load_session(&mut txn, &clock).await?;
+    let metadata = session_metadata(&rate_limiter, connection.as_deref(), &headers);
 
-    let mut session = match (maybe_user_session, link.user_id, form) {
+    let session = match (maybe_user_session, link.user_id, form) {
         (Some(session), None, FormData::Link) => {
-            associate_link_to_user(&mut txn, &link, &session.user).await?;
-            session
+            link_to_session(&mut txn, &mut rng, &clock, session, &link, upstream_session).await?
         }
 
         (None, Some(user_id), FormData::Login) => {
             let user = lookup_user(&mut txn, user_id).await?;
-            start_session(&mut txn, &mut rng, &clock, user).await?
+            if !user.is_active() {
+                return Err(RouteError::UserDeactivated);
+            }
+
+            login_and_link(
+                &mut txn,
+                &mut rng,
+                &clock,
+                &link,
+                upstream_session,
+                Some(metadata),
+                max_session_age,
+            )
+            .await?
         }
 
         (None, None, FormData::Register { username }) => {
-            let user = add_user(&mut txn, &mut rng, &clock, &username).await?;
-            associate_link_to_user(&mut txn, &link, &user).await?;
+            let provider = lookup_provider(&mut txn, link.provider_id)
+                .await?
+                .ok_or(RouteError::ProviderNotFound)?;
+            let suggestions = UpstreamSuggestions::new(&provider, &upstream_session)?;
+
+            let session = register_and_link(
+                &mut txn,
+                &mut rng,
+                &clock,
+                &username,
+                &link,
+                upstream_session,
+                Some(metadata),
+                max_session_age,
+            )
+            .await?;
+
+            // Import the email address given by the upstream provider
+            if let Some(email) = suggestions.email {
+                let user_email =
+                    add_user_email(&mut txn, &mut rng, &clock, &session.user, email).await?;
+                // Only a verified email can be used as the primary email
+                if suggestions.email_verified {
+                    let user_email =
+                        mark_user_email_as_verified(&mut txn, &clock, user_email).await?;
+                    set_user_email_as_primary(&mut txn, &user_email).await?;
+                }
+            }
 
-            start_session(&mut txn, &mut rng, &clock, user).await?
+            session
         }
 
         _ => return Err(RouteError::InvalidFormAction),
     };
 
-    consume_session(&mut txn, &clock, upstream_session).await?;
-    authenticate_session_with_upstream(&mut txn, &mut rng, &clock, &mut session, &link).await?;
-
     let cookie_jar = sessions_cookie
         .consume_link(link_id)?
         .save(cookie_jar, clock.now());
@@ -255,3 +391,133 @@ pub(crate) async fn post(
 
     Ok((cookie_jar, post_auth_action.go_next()))
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthClientAuthenticationMethod};
+    use mas_jose::{jwa::SymmetricKey, jwt::JsonWebSignatureHeader};
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    use super::*;
+
+    fn provider(username_claim: Option<&str>, trust_email_verified: bool) -> UpstreamOAuthProvider {
+        UpstreamOAuthProvider {
+            id: Ulid::nil(),
+            issuer: "https://example.com/".to_owned(),
+            human_name: None,
+            scope: "openid".parse().unwrap(),
+            client_id: "client".to_owned(),
+            encrypted_client_secret: None,
+            token_endpoint_signing_alg: None,
+            token_endpoint_auth_method: OAuthClientAuthenticationMethod::None,
+            id_token_signed_response_alg: None,
+            fetch_userinfo: false,
+            username_claim: username_claim.map(ToOwned::to_owned),
+            allow_plain_pkce: false,
+            trust_email_verified,
+            created_at: Utc.timestamp_opt(1_516_239_022, 0).unwrap(),
+        }
+    }
+
+    fn upstream_session(
+        id_token: Option<Value>,
+        userinfo: Option<Value>,
+    ) -> UpstreamOAuthAuthorizationSession {
+        let id_token = id_token.map(|claims| {
+            let claims: HashMap<String, Value> = serde_json::from_value(claims).unwrap();
+            let key =
+                SymmetricKey::new_for_alg(b"secret".to_vec(), &JsonWebSignatureAlg::Hs256).unwrap();
+            let header = JsonWebSignatureHeader::new(JsonWebSignatureAlg::Hs256);
+            let rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+            Jwt::sign_with_rng(rng, header, claims, &key)
+                .unwrap()
+                .into_string()
+        });
+
+        UpstreamOAuthAuthorizationSession {
+            id: Ulid::nil(),
+            provider_id: Ulid::nil(),
+            link_id: None,
+            state: "state".to_owned(),
+            code_challenge_verifier: None,
+            nonce: "nonce".to_owned(),
+            created_at: Utc.timestamp_opt(1_516_239_022, 0).unwrap(),
+            completed_at: None,
+            consumed_at: None,
+            id_token,
+            userinfo,
+        }
+    }
+
+    #[test]
+    fn test_upstream_claims() {
+        // Nothing was returned by the upstream
+        let claims = upstream_claims(&upstream_session(None, None)).unwrap();
+        assert!(claims.is_empty());
+
+        // Claims from the userinfo endpoint take precedence over the ID token
+        let session = upstream_session(
+            Some(json!({ "sub": "subject", "email": "id-token@example.com" })),
+            Some(json!({ "email": "userinfo@example.com", "name": "Alice" })),
+        );
+        let claims = upstream_claims(&session).unwrap();
+        assert_eq!(claims["sub"], "subject");
+        assert_eq!(claims["email"], "userinfo@example.com");
+        assert_eq!(claims["name"], "Alice");
+
+        // Userinfo responses which aren't objects are ignored
+        let session = upstream_session(Some(json!({ "sub": "subject" })), Some(json!("invalid")));
+        let claims = upstream_claims(&session).unwrap();
+        assert_eq!(claims.len(), 1);
+    }
+
+    #[test]
+    fn test_upstream_suggestions() {
+        let session = upstream_session(
+            Some(json!({
+                "sub": "subject",
+                "preferred_username": "alice",
+                "nickname": "ally",
+                "email": "alice@example.com",
+                "email_verified": true,
+            })),
+            None,
+        );
+
+        // The email is not considered verified unless the provider is trusted
+        let suggestions = UpstreamSuggestions::new(&provider(None, false), &session).unwrap();
+        assert_eq!(
+            suggestions,
+            UpstreamSuggestions {
+                username: Some("alice".to_owned()),
+                email: Some("alice@example.com".to_owned()),
+                email_verified: false,
+            }
+        );
+
+        let suggestions = UpstreamSuggestions::new(&provider(None, true), &session).unwrap();
+        assert!(suggestions.email_verified);
+
+        // The username can be taken from another claim
+        let suggestions =
+            UpstreamSuggestions::new(&provider(Some("nickname"), false), &session).unwrap();
+        assert_eq!(suggestions.username.as_deref(), Some("ally"));
+
+        // Missing or invalid claims don't give any suggestion
+        let session = upstream_session(
+            Some(json!({ "sub": "subject", "email": 42 })),
+            Some(json!({ "email_verified": "yes" })),
+        );
+        let suggestions = UpstreamSuggestions::new(&provider(None, true), &session).unwrap();
+        assert_eq!(
+            suggestions,
+            UpstreamSuggestions {
+                username: None,
+                email: None,
+                email_verified: false,
+            }
+        );
+    }
+}
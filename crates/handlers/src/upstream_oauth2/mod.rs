@@ -24,6 +24,7 @@ use url::Url;
 pub(crate) mod authorize;
 pub(crate) mod callback;
 mod cookie;
+pub(crate) mod discovery;
 pub(crate) mod link;
 
 use self::cookie::UpstreamSessions as UpstreamSessionsCookie;
@@ -30,7 +30,7 @@ use serde::Deserialize;
 use sqlx::PgPool;
 
 use super::start_email_verification;
-use crate::views::shared::OptionalPostAuthAction;
+use crate::views::shared::{EmailVerificationCodeKind, OptionalPostAuthAction};
 
 #[derive(Deserialize, Debug)]
 pub struct EmailForm {
@@ -48,7 +48,7 @@ pub(crate) async fn get(
     let (csrf_token, cookie_jar) = cookie_jar.csrf_token(clock.now(), &mut rng);
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut conn).await?;
+    let maybe_session = session_info.load_session(&mut conn, &clock).await?;
 
     let session = if let Some(session) = maybe_session {
         session
@@ -69,6 +69,7 @@ pub(crate) async fn get(
 pub(crate) async fn post(
     State(pool): State<PgPool>,
     State(mailer): State<Mailer>,
+    State(code_kind): State<EmailVerificationCodeKind>,
     cookie_jar: PrivateCookieJar<Encrypter>,
     Query(query): Query<OptionalPostAuthAction>,
     Form(form): Form<ProtectedForm<EmailForm>>,
@@ -79,7 +80,7 @@ pub(crate) async fn post(
     let form = cookie_jar.verify_form(clock.now(), form)?;
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut txn).await?;
+    let maybe_session = session_info.load_session(&mut txn, &clock).await?;
 
     let session = if let Some(session) = maybe_session {
         session
@@ -102,6 +103,7 @@ pub(crate) async fn post(
         &clock,
         &session.user,
         user_email,
+        code_kind,
     )
     .await?;
 
@@ -29,17 +29,20 @@ use mas_keystore::Encrypter;
 use mas_router::Route;
 use mas_storage::{
     user::{
-        add_user_email, add_user_email_verification_code, get_user_email, get_user_emails,
-        remove_user_email, set_user_email_as_primary,
+        add_user_email, add_user_email_verification_code, can_send_verification_code,
+        generate_verification_code, get_user_email, get_user_emails, remove_user_email,
+        set_user_email_as_primary,
     },
     Clock,
 };
 use mas_templates::{AccountEmailsContext, EmailVerificationContext, TemplateContext, Templates};
-use rand::{distributions::Uniform, Rng};
+use rand::Rng;
 use serde::Deserialize;
 use sqlx::{PgExecutor, PgPool};
 use tracing::info;
 
+use crate::views::shared::EmailVerificationCodeKind;
+
 pub mod add;
 pub mod verify;
 
@@ -63,7 +66,7 @@ pub(crate) async fn get(
 
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut conn).await?;
+    let maybe_session = session_info.load_session(&mut conn, &clock).await?;
 
     if let Some(session) = maybe_session {
         render(&mut rng, &clock, templates, session, cookie_jar, &mut conn).await
@@ -101,10 +104,10 @@ async fn start_email_verification(
     clock: &Clock,
     user: &User,
     user_email: UserEmail,
+    EmailVerificationCodeKind(code_kind): EmailVerificationCodeKind,
 ) -> anyhow::Result<()> {
     // First, generate a code
-    let range = Uniform::<u32>::from(0..1_000_000);
-    let code = rng.sample(range).to_string();
+    let code = generate_verification_code(&mut rng, code_kind);
 
     let address: Address = user_email.email.parse()?;
 
@@ -114,7 +117,7 @@ async fn start_email_verification(
         clock,
         user_email,
         Duration::hours(8),
-        code,
+        (*code).clone(),
     )
     .await?;
 
@@ -136,6 +139,7 @@ pub(crate) async fn post(
     State(templates): State<Templates>,
     State(pool): State<PgPool>,
     State(mailer): State<Mailer>,
+    State(code_kind): State<EmailVerificationCodeKind>,
     cookie_jar: PrivateCookieJar<Encrypter>,
     Form(form): Form<ProtectedForm<ManagementForm>>,
 ) -> Result<Response, FancyError> {
@@ -144,7 +148,7 @@ pub(crate) async fn post(
 
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut txn).await?;
+    let maybe_session = session_info.load_session(&mut txn, &clock).await?;
 
     let mut session = if let Some(session) = maybe_session {
         session
@@ -167,6 +171,7 @@ pub(crate) async fn post(
                 &clock,
                 &session.user,
                 user_email,
+                code_kind,
             )
             .await?;
             txn.commit().await?;
@@ -177,16 +182,27 @@ pub(crate) async fn post(
 
             let user_email = get_user_email(&mut txn, &session.user, id).await?;
             let next = mas_router::AccountVerifyEmail::new(user_email.id);
-            start_email_verification(
-                &mailer,
-                &mut txn,
-                &mut rng,
-                &clock,
-                &session.user,
-                user_email,
-            )
-            .await?;
-            txn.commit().await?;
+
+            // Don't send a new code if one was sent in the last minute, the user can
+            // still use it
+            if can_send_verification_code(&mut txn, &clock, &user_email, Duration::minutes(1))
+                .await?
+            {
+                start_email_verification(
+                    &mailer,
+                    &mut txn,
+                    &mut rng,
+                    &clock,
+                    &session.user,
+                    user_email,
+                    code_kind,
+                )
+                .await?;
+                txn.commit().await?;
+            } else {
+                info!(email.id = %user_email.id, "Verification email sent recently, not resending");
+            }
+
             return Ok((cookie_jar, next.go()).into_response());
         }
         ManagementForm::Remove { id } => {
@@ -22,27 +22,34 @@ use mas_axum_utils::{
     csrf::{CsrfExt, ProtectedForm},
     FancyError, SessionInfoExt,
 };
+use mas_data_model::{
+    UserEmailVerification, UserEmailVerificationLookup, UserEmailVerificationState,
+};
 use mas_keystore::Encrypter;
 use mas_router::Route;
-use mas_storage::{
-    user::{
-        consume_email_verification, lookup_user_email_by_id, lookup_user_email_verification_code,
-        mark_user_email_as_verified, set_user_email_as_primary,
-    },
-    Clock,
+use mas_storage::user::{
+    consume_email_verification, lookup_user_email_by_id, lookup_user_email_verification_code,
+    mark_user_email_as_verified, set_user_email_as_primary,
+};
+use mas_templates::{
+    EmailVerificationFormField, EmailVerificationPageContext, FormError, TemplateContext,
+    Templates, ToFormState,
 };
-use mas_templates::{EmailVerificationPageContext, TemplateContext, Templates};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use ulid::Ulid;
 
 use crate::views::shared::OptionalPostAuthAction;
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct CodeForm {
     code: String,
 }
 
+impl ToFormState for CodeForm {
+    type Field = EmailVerificationFormField;
+}
+
 pub(crate) async fn get(
     State(templates): State<Templates>,
     State(pool): State<PgPool>,
@@ -56,7 +63,7 @@ pub(crate) async fn get(
     let (csrf_token, cookie_jar) = cookie_jar.csrf_token(clock.now(), &mut rng);
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut conn).await?;
+    let maybe_session = session_info.load_session(&mut conn, &clock).await?;
 
     let session = if let Some(session) = maybe_session {
         session
@@ -85,19 +92,20 @@ pub(crate) async fn get(
 }
 
 pub(crate) async fn post(
+    State(templates): State<Templates>,
     State(pool): State<PgPool>,
     cookie_jar: PrivateCookieJar<Encrypter>,
     Query(query): Query<OptionalPostAuthAction>,
     Path(id): Path<Ulid>,
     Form(form): Form<ProtectedForm<CodeForm>>,
 ) -> Result<Response, FancyError> {
-    let clock = Clock::default();
+    let (clock, mut rng) = crate::clock_and_rng();
     let mut txn = pool.begin().await?;
 
     let form = cookie_jar.verify_form(clock.now(), form)?;
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut txn).await?;
+    let maybe_session = session_info.load_session(&mut txn, &clock).await?;
 
     let session = if let Some(session) = maybe_session {
         session
@@ -110,22 +118,50 @@ pub(crate) async fn post(
         .await?
         .context("Could not find user email")?;
 
-    if session.user.primary_email.is_none() {
-        set_user_email_as_primary(&mut txn, &email).await?;
-    }
-
     // TODO: make those 8 hours configurable
-    let verification = lookup_user_email_verification_code(&mut txn, &clock, email, &form.code)
-        .await?
-        .context("Invalid code")?;
-
-    // TODO: display nice errors if the code was already consumed or expired
-    let verification = consume_email_verification(&mut txn, &clock, verification).await?;
+    let lookup =
+        lookup_user_email_verification_code(&mut txn, &clock, email.clone(), &form.code).await?;
+
+    let error = match lookup {
+        UserEmailVerificationLookup::Found(
+            verification @ UserEmailVerification {
+                state: UserEmailVerificationState::Valid,
+                ..
+            },
+        ) => {
+            let verification = consume_email_verification(&mut txn, &clock, verification).await?;
+
+            let email = mark_user_email_as_verified(&mut txn, &clock, verification.email).await?;
+
+            if session.user.primary_email.is_none() {
+                set_user_email_as_primary(&mut txn, &email).await?;
+            }
+
+            txn.commit().await?;
+
+            let destination = query.go_next_or_default(&mas_router::AccountEmails);
+            return Ok((cookie_jar, destination).into_response());
+        }
+        UserEmailVerificationLookup::CodeNotFound => FormError::InvalidCode,
+        UserEmailVerificationLookup::Found(UserEmailVerification {
+            state: UserEmailVerificationState::Expired { .. },
+            ..
+        }) => FormError::CodeExpired,
+        UserEmailVerificationLookup::Found(UserEmailVerification {
+            state: UserEmailVerificationState::AlreadyUsed { .. },
+            ..
+        }) => FormError::CodeAlreadyUsed,
+    };
 
-    let _email = mark_user_email_as_verified(&mut txn, &clock, verification.email).await?;
+    // Show the verification page again, explaining why the code was refused
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(clock.now(), &mut rng);
+    let state = form.to_form_state().with_error_on_form(error);
+    let ctx = EmailVerificationPageContext::new(email)
+        .with_form_state(state)
+        .with_session(session)
+        .with_csrf(csrf_token.form_value());
 
-    txn.commit().await?;
+    let content = templates.render_account_verify_email(&ctx).await?;
 
-    let destination = query.go_next_or_default(&mas_router::AccountEmails);
-    Ok((cookie_jar, destination).into_response())
+    Ok((cookie_jar, Html(content)).into_response())
 }
@@ -14,6 +14,7 @@
 
 pub mod emails;
 pub mod password;
+pub mod session;
 
 use axum::{
     extract::State,
@@ -38,7 +39,7 @@ pub(crate) async fn get(
     let (csrf_token, cookie_jar) = cookie_jar.csrf_token(clock.now(), &mut rng);
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut conn).await?;
+    let maybe_session = session_info.load_session(&mut conn, &clock).await?;
 
     let session = if let Some(session) = maybe_session {
         session
@@ -47,7 +48,7 @@ pub(crate) async fn get(
         return Ok((cookie_jar, login.go()).into_response());
     };
 
-    let active_sessions = count_active_sessions(&mut conn, &session.user).await?;
+    let active_sessions = count_active_sessions(&mut conn, &clock, &session.user).await?;
 
     let emails = get_user_emails(&mut conn, &session.user).await?;
 
@@ -0,0 +1,214 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Machine-readable information about the current browser session
+
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::PrivateCookieJar;
+use chrono::{DateTime, Utc};
+use hyper::StatusCode;
+use mas_axum_utils::SessionInfoExt;
+use mas_keystore::Encrypter;
+use serde::Serialize;
+use sqlx::PgPool;
+use thiserror::Error;
+use ulid::Ulid;
+
+use crate::impl_from_error_for_route;
+
+#[derive(Serialize)]
+struct SessionInfo {
+    user_id: Ulid,
+    username: String,
+    last_authentication_at: Option<DateTime<Utc>>,
+    primary_email: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("no active session")]
+    NoActiveSession,
+}
+
+impl_from_error_for_route!(sqlx::Error);
+impl_from_error_for_route!(mas_storage::DatabaseError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Internal(e) => {
+                // Don't leak the details of the error to the client
+                tracing::error!(
+                    error = &*e as &dyn std::error::Error,
+                    "Failed to load session"
+                );
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({ "error": "internal server error" })),
+                )
+                    .into_response()
+            }
+            Self::NoActiveSession => StatusCode::UNAUTHORIZED.into_response(),
+        }
+    }
+}
+
+pub(crate) async fn get(
+    State(pool): State<PgPool>,
+    cookie_jar: PrivateCookieJar<Encrypter>,
+) -> Result<Response, RouteError> {
+    let (clock, _rng) = crate::clock_and_rng();
+    let mut conn = pool.acquire().await?;
+
+    let (session_info, _cookie_jar) = cookie_jar.session_info();
+    let session = session_info
+        .load_session(&mut conn, &clock)
+        .await?
+        .ok_or(RouteError::NoActiveSession)?;
+
+    let user = session.user;
+    let session_info = SessionInfo {
+        user_id: user.id,
+        username: user.username,
+        last_authentication_at: session.last_authentication.map(|auth| auth.created_at),
+        primary_email: user.primary_email.map(|email| email.email),
+    };
+
+    Ok(Json(session_info).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::FromRef;
+    use axum_extra::extract::cookie::Key;
+    use chrono::TimeZone;
+    use hyper::{
+        header::{COOKIE, SET_COOKIE},
+        Body, Request,
+    };
+    use mas_storage::{
+        user::{
+            add_user, add_user_email, add_user_password, authenticate_session_with_password,
+            mark_user_email_as_verified, set_user_email_as_primary, start_session,
+        },
+        Clock,
+    };
+    use mas_templates::Templates;
+    use rand::SeedableRng;
+    use tower::ServiceExt;
+    use zeroize::Zeroizing;
+
+    use super::*;
+
+    #[sqlx::test(migrator = "mas_storage::MIGRATOR")]
+    async fn test_get_without_session(pool: PgPool) -> Result<(), anyhow::Error> {
+        let state = crate::test_state(pool).await?;
+        let templates = Templates::from_ref(&state);
+        let app = crate::human_router(templates).with_state(state);
+
+        let request = Request::builder()
+            .uri("/account/session")
+            .body(Body::empty())?;
+
+        let response = app.oneshot(request).await?;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "mas_storage::MIGRATOR")]
+    async fn test_get_with_session(pool: PgPool) -> Result<(), anyhow::Error> {
+        let state = crate::test_state(pool.clone()).await?;
+        let templates = Templates::from_ref(&state);
+        let encrypter = Encrypter::from_ref(&state);
+        let password_manager = crate::passwords::PasswordManager::from_ref(&state);
+        let app = crate::human_router(templates).with_state(state);
+
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let user_email = add_user_email(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &user,
+            "alice@example.com".to_owned(),
+        )
+        .await?;
+        let user_email = mark_user_email_as_verified(&mut conn, &clock, user_email).await?;
+        set_user_email_as_primary(&mut conn, &user_email).await?;
+
+        let (version, hashed_password) = password_manager
+            .hash(&mut rng, Zeroizing::new(b"hunter2".to_vec()))
+            .await?;
+        let user_password = add_user_password(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &user,
+            version,
+            hashed_password,
+            None,
+        )
+        .await?;
+        let mut session = start_session(&mut conn, &mut rng, &clock, user, None, None).await?;
+        authenticate_session_with_password(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &mut session,
+            &user_password,
+        )
+        .await?;
+
+        // Pass the encrypted session cookie the same way a browser would
+        let response = PrivateCookieJar::new(Key::from(encrypter))
+            .set_session(&session)
+            .into_response();
+        let cookie = response.headers()[SET_COOKIE].to_str()?;
+        let cookie = cookie.split(';').next().unwrap_or_default();
+
+        let request = Request::builder()
+            .uri("/account/session")
+            .header(COOKIE, cookie)
+            .body(Body::empty())?;
+
+        let response = app.oneshot(request).await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let body: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "user_id": session.user.id,
+                "username": "alice",
+                "last_authentication_at": "2018-01-18T01:30:22Z",
+                "primary_email": "alice@example.com",
+            })
+        );
+
+        Ok(())
+    }
+}
@@ -20,23 +20,30 @@ use axum_extra::extract::PrivateCookieJar;
 use mas_axum_utils::{csrf::CsrfExt, FancyError, SessionInfoExt};
 use mas_keystore::Encrypter;
 use mas_router::UrlBuilder;
+use mas_storage::Clock;
 use mas_templates::{IndexContext, TemplateContext, Templates};
 use sqlx::PgPool;
 
+use crate::SharedRng;
+
 pub async fn get(
     State(templates): State<Templates>,
     State(url_builder): State<UrlBuilder>,
     State(pool): State<PgPool>,
+    State(clock): State<Clock>,
+    State(mut rng): State<SharedRng>,
     cookie_jar: PrivateCookieJar<Encrypter>,
 ) -> Result<impl IntoResponse, FancyError> {
-    let (clock, mut rng) = crate::clock_and_rng();
     let mut conn = pool.acquire().await?;
 
     let (csrf_token, cookie_jar) = cookie_jar.csrf_token(clock.now(), &mut rng);
     let (session_info, cookie_jar) = cookie_jar.session_info();
-    let session = session_info.load_session(&mut conn).await?;
+    let session = session_info.load_session(&mut conn, &clock).await?;
+
+    let providers = mas_storage::upstream_oauth2::get_providers(&mut conn).await?;
 
     let ctx = IndexContext::new(url_builder.oidc_discovery())
+        .with_upstream_providers(providers)
         .maybe_with_session(session)
         .with_csrf(csrf_token.form_value());
 
@@ -15,20 +15,25 @@
 use axum::{
     extract::{Form, Query, State},
     response::{Html, IntoResponse, Response},
+    Extension,
 };
 use axum_extra::extract::PrivateCookieJar;
+use chrono::Duration;
+use hyper::{HeaderMap, StatusCode};
 use mas_axum_utils::{
     csrf::{CsrfExt, CsrfToken, ProtectedForm},
     FancyError, SessionInfoExt,
 };
-use mas_data_model::BrowserSession;
+use mas_data_model::{BrowserSession, SessionMetadata};
 use mas_keystore::Encrypter;
+use mas_listener::ConnectionInfo;
 use mas_storage::{
+    acquire_with_timeout,
     user::{
-        add_user_password, authenticate_session_with_password, lookup_user_by_username,
-        lookup_user_password, start_session,
+        add_user_password, authenticate_session_with_password, failed_login_count_since,
+        lookup_user_by_username, lookup_user_password, record_failed_login, start_session,
     },
-    Clock,
+    Clock, DatabaseError,
 };
 use mas_templates::{
     FieldError, FormError, LoginContext, LoginFormField, TemplateContext, Templates, ToFormState,
@@ -38,32 +43,112 @@ use serde::{Deserialize, Serialize};
 use sqlx::{PgConnection, PgPool};
 use zeroize::Zeroizing;
 
-use super::shared::OptionalPostAuthAction;
-use crate::passwords::PasswordManager;
+use super::shared::{session_metadata, OptionalPostAuthAction};
+use crate::{passwords::PasswordManager, rate_limit::LoginRateLimiter, SharedRng};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct LoginForm {
     username: String,
     password: String,
+    #[serde(default, skip_serializing)]
+    remember_me: bool,
 }
 
 impl ToFormState for LoginForm {
     type Field = LoginFormField;
 }
 
+/// How long the browser keeps the session cookie after logging in
+#[derive(Debug, Clone, Copy)]
+pub struct SessionCookieTtl {
+    ttl: Option<Duration>,
+    remember_me_ttl: Duration,
+}
+
+impl SessionCookieTtl {
+    /// Create a new [`SessionCookieTtl`]. If `ttl` is `None`, a regular login
+    /// sets a cookie which is discarded when the browser closes.
+    #[must_use]
+    pub const fn new(ttl: Option<Duration>, remember_me_ttl: Duration) -> Self {
+        Self {
+            ttl,
+            remember_me_ttl,
+        }
+    }
+
+    fn max_age(self, remember_me: bool) -> Option<Duration> {
+        if remember_me {
+            Some(self.remember_me_ttl)
+        } else {
+            self.ttl
+        }
+    }
+}
+
+impl Default for SessionCookieTtl {
+    fn default() -> Self {
+        Self::new(None, Duration::days(30))
+    }
+}
+
+/// How many failed login attempts against a username are allowed within a
+/// window before the account gets locked
+#[derive(Debug, Clone, Copy)]
+pub struct LoginLockout {
+    max_attempts: u32,
+    window: Duration,
+}
+
+impl LoginLockout {
+    #[must_use]
+    pub const fn new(max_attempts: u32, window: Duration) -> Self {
+        Self {
+            max_attempts,
+            window,
+        }
+    }
+}
+
+impl Default for LoginLockout {
+    fn default() -> Self {
+        Self::new(5, Duration::minutes(15))
+    }
+}
+
+/// How long a browser session stays valid after it started. Sessions never
+/// expire if unset.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaxSessionAge(pub(crate) Option<Duration>);
+
+impl MaxSessionAge {
+    #[must_use]
+    pub const fn new(max_age: Option<Duration>) -> Self {
+        Self(max_age)
+    }
+}
+
+/// How long to wait for a database connection before giving up with a 503
+const DATABASE_ACQUIRE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 pub(crate) async fn get(
     State(templates): State<Templates>,
     State(pool): State<PgPool>,
+    State(clock): State<Clock>,
+    State(mut rng): State<SharedRng>,
     Query(query): Query<OptionalPostAuthAction>,
     cookie_jar: PrivateCookieJar<Encrypter>,
 ) -> Result<Response, FancyError> {
-    let (clock, mut rng) = crate::clock_and_rng();
-    let mut conn = pool.acquire().await?;
+    let mut conn = match acquire_with_timeout(&pool, DATABASE_ACQUIRE_TIMEOUT).await {
+        Err(DatabaseError::AcquireTimeout { .. }) => {
+            return Ok(StatusCode::SERVICE_UNAVAILABLE.into_response())
+        }
+        res => res?,
+    };
 
     let (csrf_token, cookie_jar) = cookie_jar.csrf_token(clock.now(), &mut rng);
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut conn).await?;
+    let maybe_session = session_info.load_session(&mut conn, &clock).await?;
 
     if maybe_session.is_some() {
         let reply = query.go_next();
@@ -83,16 +168,29 @@ pub(crate) async fn get(
     }
 }
 
+#[allow(clippy::too_many_lines, clippy::too_many_arguments)]
 pub(crate) async fn post(
     State(password_manager): State<PasswordManager>,
     State(templates): State<Templates>,
     State(pool): State<PgPool>,
+    State(session_cookie_ttl): State<SessionCookieTtl>,
+    State(MaxSessionAge(max_session_age)): State<MaxSessionAge>,
+    State(lockout): State<LoginLockout>,
+    State(rate_limiter): State<LoginRateLimiter>,
+    State(clock): State<Clock>,
+    State(mut rng): State<SharedRng>,
     Query(query): Query<OptionalPostAuthAction>,
+    connection: Option<Extension<ConnectionInfo>>,
+    headers: HeaderMap,
     cookie_jar: PrivateCookieJar<Encrypter>,
     Form(form): Form<ProtectedForm<LoginForm>>,
 ) -> Result<Response, FancyError> {
-    let (clock, mut rng) = crate::clock_and_rng();
-    let mut conn = pool.acquire().await?;
+    let mut conn = match acquire_with_timeout(&pool, DATABASE_ACQUIRE_TIMEOUT).await {
+        Err(DatabaseError::AcquireTimeout { .. }) => {
+            return Ok(StatusCode::SERVICE_UNAVAILABLE.into_response())
+        }
+        res => res?,
+    };
 
     let form = cookie_jar.verify_form(clock.now(), form)?;
 
@@ -129,6 +227,23 @@ pub(crate) async fn post(
         return Ok((cookie_jar, Html(content)).into_response());
     }
 
+    let metadata = session_metadata(&rate_limiter, connection.as_deref(), &headers);
+    let ip = metadata.ip;
+
+    if rate_limiter.check(clock.now(), ip, &form.username).is_err() {
+        let state = state.with_error_on_form(FormError::RateLimited);
+        let content = render(
+            LoginContext::default().with_form_state(state),
+            query,
+            csrf_token,
+            &mut conn,
+            &templates,
+        )
+        .await?;
+
+        return Ok((cookie_jar, StatusCode::TOO_MANY_REQUESTS, Html(content)).into_response());
+    }
+
     lookup_user_by_username(&mut conn, &form.username).await?;
 
     match login(
@@ -138,15 +253,23 @@ pub(crate) async fn post(
         &clock,
         &form.username,
         &form.password,
+        metadata,
+        max_session_age,
+        lockout,
     )
     .await
     {
         Ok(session_info) => {
-            let cookie_jar = cookie_jar.set_session(&session_info);
+            let max_age = session_cookie_ttl.max_age(form.remember_me);
+            let cookie_jar = cookie_jar.set_session_with_max_age(&session_info, max_age);
             let reply = query.go_next();
             Ok((cookie_jar, reply).into_response())
         }
         Err(e) => {
+            if !matches!(e, FormError::Internal) {
+                rate_limiter.record_failure(clock.now(), ip, &form.username);
+            }
+
             let state = state.with_error_on_form(e);
 
             let content = render(
@@ -164,6 +287,7 @@ pub(crate) async fn post(
 }
 
 // TODO: move that logic elsewhere?
+#[allow(clippy::too_many_arguments)]
 async fn login(
     password_manager: PasswordManager,
     conn: &mut PgConnection,
@@ -171,32 +295,70 @@ async fn login(
     clock: &Clock,
     username: &str,
     password: &str,
+    metadata: SessionMetadata,
+    max_session_age: Option<Duration>,
+    lockout: LoginLockout,
 ) -> Result<BrowserSession, FormError> {
+    // Refuse to even check the password if there were too many failed attempts.
+    // This is checked before looking up the user, so that unknown usernames
+    // get the same answer
+    let failed_attempts =
+        failed_login_count_since(&mut *conn, username, clock.now() - lockout.window)
+            .await
+            .map_err(|_e| FormError::Internal)?;
+
+    if failed_attempts >= i64::from(lockout.max_attempts) {
+        return Err(FormError::AccountLocked);
+    }
+
     // XXX: we're loosing the error context here
     // First, lookup the user
     let user = lookup_user_by_username(&mut *conn, username)
         .await
-        .map_err(|_e| FormError::Internal)?
-        .ok_or(FormError::InvalidCredentials)?;
+        .map_err(|_e| FormError::Internal)?;
 
     // And its password
-    let user_password = lookup_user_password(&mut *conn, &user)
-        .await
-        .map_err(|_e| FormError::Internal)?
-        .ok_or(FormError::InvalidCredentials)?;
+    let user_password = if let Some(user) = &user {
+        lookup_user_password(&mut *conn, user)
+            .await
+            .map_err(|_e| FormError::Internal)?
+    } else {
+        None
+    };
 
     let password = Zeroizing::new(password.as_bytes().to_vec());
 
     // Verify the password, and upgrade it on-the-fly if needed
-    let new_password_hash = password_manager
-        .verify_and_upgrade(
-            &mut rng,
-            user_password.version,
-            password,
-            user_password.hashed_password.clone(),
-        )
-        .await
-        .map_err(|_| FormError::InvalidCredentials)?;
+    let verified = if let (Some(user), Some(user_password)) = (user, user_password) {
+        password_manager
+            .verify_and_upgrade(
+                &mut rng,
+                user_password.version,
+                password,
+                user_password.hashed_password.clone(),
+            )
+            .await
+            .ok()
+            .map(|new_password_hash| (user, user_password, new_password_hash))
+    } else {
+        None
+    };
+
+    // Unknown users, users without a password and wrong passwords all count as
+    // a failed attempt
+    let Some((user, user_password, new_password_hash)) = verified else {
+        record_failed_login(&mut *conn, &mut rng, clock, username)
+            .await
+            .map_err(|_e| FormError::Internal)?;
+
+        return Err(FormError::InvalidCredentials);
+    };
+
+    // Deactivated users can't log in. This is checked after verifying the
+    // password to avoid leaking the account state
+    if !user.is_active() {
+        return Err(FormError::InvalidCredentials);
+    }
 
     let user_password = if let Some((version, new_password_hash)) = new_password_hash {
         // Save the upgraded password
@@ -216,14 +378,27 @@ async fn login(
     };
 
     // Start a new session
-    let mut user_session = start_session(&mut *conn, &mut rng, clock, user)
-        .await
-        .map_err(|_| FormError::Internal)?;
+    let mut user_session = start_session(
+        &mut *conn,
+        &mut rng,
+        clock,
+        user,
+        Some(metadata),
+        max_session_age,
+    )
+    .await
+    .map_err(|_| FormError::Internal)?;
 
     // And mark it as authenticated by the password
-    authenticate_session_with_password(&mut *conn, rng, clock, &mut user_session, &user_password)
-        .await
-        .map_err(|_| FormError::Internal)?;
+    authenticate_session_with_password(
+        &mut *conn,
+        &mut rng,
+        clock,
+        &mut user_session,
+        &user_password,
+    )
+    .await
+    .map_err(|_| FormError::Internal)?;
 
     Ok(user_session)
 }
@@ -246,3 +421,207 @@ async fn render(
     let content = templates.render_login(&ctx).await?;
     Ok(content)
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::FromRef;
+    use chrono::{TimeZone, Utc};
+    use mas_storage::user::{
+        add_user, get_session_events, lookup_active_session, SessionEventKind,
+    };
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[sqlx::test(migrator = "mas_storage::MIGRATOR")]
+    async fn test_login_lockout(pool: PgPool) -> Result<(), anyhow::Error> {
+        let state = crate::test_state(pool.clone()).await?;
+        let password_manager = PasswordManager::from_ref(&state);
+        let lockout = LoginLockout::new(3, Duration::minutes(15));
+
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let mut clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let (version, hashed_password) = password_manager
+            .hash(&mut rng, Zeroizing::new(b"hunter2".to_vec()))
+            .await?;
+        add_user_password(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &user,
+            version,
+            hashed_password,
+            None,
+        )
+        .await?;
+
+        // Existing and unknown users are locked out the same way
+        for username in ["alice", "bob"] {
+            for _ in 0..3 {
+                let res = login(
+                    password_manager.clone(),
+                    &mut conn,
+                    &mut rng,
+                    &clock,
+                    username,
+                    "wrong",
+                    SessionMetadata::default(),
+                    SessionMetadata::default(),
+                    None,
+                    lockout,
+                )
+                .await;
+                assert!(matches!(res, Err(FormError::InvalidCredentials)));
+            }
+
+            // Even the right password is refused once locked
+            let res = login(
+                password_manager.clone(),
+                &mut conn,
+                &mut rng,
+                &clock,
+                username,
+                "hunter2",
+                SessionMetadata::default(),
+                None,
+                lockout,
+            )
+            .await;
+            assert!(matches!(res, Err(FormError::AccountLocked)));
+        }
+
+        // The failed attempts are forgotten once they are out of the window
+        clock.advance(Duration::minutes(16));
+        let res = login(
+            password_manager,
+            &mut conn,
+            &mut rng,
+            &clock,
+            "alice",
+            "hunter2",
+            SessionMetadata::default(),
+            None,
+            lockout,
+        )
+        .await;
+        assert!(res.is_ok());
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "mas_storage::MIGRATOR")]
+    async fn test_login_records_session_events(pool: PgPool) -> Result<(), anyhow::Error> {
+        let state = crate::test_state(pool.clone()).await?;
+        let password_manager = PasswordManager::from_ref(&state);
+        let lockout = LoginLockout::new(3, Duration::minutes(15));
+
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let (version, hashed_password) = password_manager
+            .hash(&mut rng, Zeroizing::new(b"hunter2".to_vec()))
+            .await?;
+        add_user_password(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &user,
+            version,
+            hashed_password,
+            None,
+        )
+        .await?;
+
+        let session = login(
+            password_manager,
+            &mut conn,
+            &mut rng,
+            &clock,
+            "alice",
+            "hunter2",
+            SessionMetadata::default(),
+            None,
+            lockout,
+        )
+        .await
+        .unwrap();
+
+        // The session is recorded as started, then authenticated by the password
+        let (_, _, events) =
+            get_session_events(&mut conn, &user, None, None, Some(10), None).await?;
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|event| event.session_id == Some(session.id)));
+        assert!(events
+            .iter()
+            .any(|event| event.kind == SessionEventKind::Started));
+        let authenticated = events
+            .iter()
+            .find(|event| event.kind == SessionEventKind::Authenticated)
+            .unwrap();
+        assert_eq!(
+            authenticated.metadata,
+            serde_json::json!({ "method": "password" })
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "mas_storage::MIGRATOR")]
+    async fn test_login_records_session_metadata(pool: PgPool) -> Result<(), anyhow::Error> {
+        let state = crate::test_state(pool.clone()).await?;
+        let password_manager = PasswordManager::from_ref(&state);
+        let lockout = LoginLockout::new(3, Duration::minutes(15));
+
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let (version, hashed_password) = password_manager
+            .hash(&mut rng, Zeroizing::new(b"hunter2".to_vec()))
+            .await?;
+        add_user_password(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &user,
+            version,
+            hashed_password,
+            None,
+        )
+        .await?;
+
+        let metadata = SessionMetadata {
+            ip: Some("192.0.2.1".parse().unwrap()),
+            user_agent: Some("Mozilla/5.0".to_owned()),
+        };
+        let session = login(
+            password_manager,
+            &mut conn,
+            &mut rng,
+            &clock,
+            "alice",
+            "hunter2",
+            metadata.clone(),
+            None,
+            lockout,
+        )
+        .await
+        .unwrap();
+
+        // The client informations are stored along the session
+        let session = lookup_active_session(&mut conn, &clock, session.id)
+            .await?
+            .unwrap();
+        assert_eq!(session.metadata, metadata);
+
+        Ok(())
+    }
+}
@@ -23,25 +23,33 @@ use mas_axum_utils::{
 };
 use mas_keystore::Encrypter;
 use mas_router::{PostAuthAction, Route};
-use mas_storage::{user::end_session, Clock};
+use mas_storage::{user::end_session, Clock, DatabaseError};
 use sqlx::PgPool;
 
+use crate::SharedRng;
+
 pub(crate) async fn post(
     State(pool): State<PgPool>,
+    State(clock): State<Clock>,
+    State(mut rng): State<SharedRng>,
     cookie_jar: PrivateCookieJar<Encrypter>,
     Form(form): Form<ProtectedForm<Option<PostAuthAction>>>,
 ) -> Result<impl IntoResponse, FancyError> {
-    let clock = Clock::default();
     let mut txn = pool.begin().await?;
 
     let form = cookie_jar.verify_form(clock.now(), form)?;
 
     let (session_info, mut cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut txn).await?;
+    let maybe_session = session_info.load_session(&mut txn, &clock).await?;
 
     if let Some(session) = maybe_session {
-        end_session(&mut txn, &clock, &session).await?;
+        match end_session(&mut txn, &mut rng, &clock, &session).await {
+            // The session was ended concurrently by another request
+            Ok(()) | Err(DatabaseError::RowsAffected { actual: 0, .. }) => {}
+            Err(e) => return Err(e.into()),
+        }
+
         cookie_jar = cookie_jar.update_session_info(&session_info.mark_session_ended());
     }
 
@@ -52,7 +52,7 @@ pub(crate) async fn get(
     let (csrf_token, cookie_jar) = cookie_jar.csrf_token(clock.now(), &mut rng);
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut conn).await?;
+    let maybe_session = session_info.load_session(&mut conn, &clock).await?;
 
     let session = if let Some(session) = maybe_session {
         session
@@ -91,7 +91,7 @@ pub(crate) async fn post(
 
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut txn).await?;
+    let maybe_session = session_info.load_session(&mut txn, &clock).await?;
 
     let mut session = if let Some(session) = maybe_session {
         session
@@ -137,7 +137,8 @@ pub(crate) async fn post(
     };
 
     // Mark the session as authenticated by the password
-    authenticate_session_with_password(&mut txn, rng, &clock, &mut session, &user_password).await?;
+    authenticate_session_with_password(&mut txn, &mut rng, &clock, &mut session, &user_password)
+        .await?;
 
     let cookie_jar = cookie_jar.set_session(&session);
     txn.commit().await?;
@@ -19,9 +19,11 @@ use std::{str::FromStr, sync::Arc};
 use axum::{
     extract::{Form, Query, State},
     response::{Html, IntoResponse, Response},
+    Extension,
 };
 use axum_extra::extract::PrivateCookieJar;
 use chrono::Duration;
+use hyper::HeaderMap;
 use lettre::{message::Mailbox, Address};
 use mas_axum_utils::{
     csrf::{CsrfExt, CsrfToken, ProtectedForm},
@@ -29,23 +31,29 @@ use mas_axum_utils::{
 };
 use mas_email::Mailer;
 use mas_keystore::Encrypter;
+use mas_listener::ConnectionInfo;
 use mas_policy::PolicyFactory;
 use mas_router::Route;
 use mas_storage::user::{
     add_user, add_user_email, add_user_email_verification_code, add_user_password,
-    authenticate_session_with_password, start_session, username_exists,
+    authenticate_session_with_password, generate_verification_code, start_session, username_exists,
 };
 use mas_templates::{
     EmailVerificationContext, FieldError, FormError, RegisterContext, RegisterFormField,
     TemplateContext, Templates, ToFormState,
 };
-use rand::{distributions::Uniform, Rng};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgConnection, PgPool};
 use zeroize::Zeroizing;
 
-use super::shared::OptionalPostAuthAction;
-use crate::passwords::PasswordManager;
+use super::{
+    login::MaxSessionAge,
+    shared::{
+        session_metadata, violations_to_form_state, EmailVerificationCodeKind,
+        OptionalPostAuthAction,
+    },
+};
+use crate::{passwords::PasswordManager, rate_limit::LoginRateLimiter};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct RegisterForm {
@@ -71,7 +79,7 @@ pub(crate) async fn get(
     let (csrf_token, cookie_jar) = cookie_jar.csrf_token(clock.now(), &mut rng);
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
-    let maybe_session = session_info.load_session(&mut conn).await?;
+    let maybe_session = session_info.load_session(&mut conn, &clock).await?;
 
     if maybe_session.is_some() {
         let reply = query.go_next();
@@ -97,7 +105,12 @@ pub(crate) async fn post(
     State(policy_factory): State<Arc<PolicyFactory>>,
     State(templates): State<Templates>,
     State(pool): State<PgPool>,
+    State(MaxSessionAge(max_session_age)): State<MaxSessionAge>,
+    State(EmailVerificationCodeKind(code_kind)): State<EmailVerificationCodeKind>,
+    State(rate_limiter): State<LoginRateLimiter>,
     Query(query): Query<OptionalPostAuthAction>,
+    connection: Option<Extension<ConnectionInfo>>,
+    headers: HeaderMap,
     cookie_jar: PrivateCookieJar<Encrypter>,
     Form(form): Form<ProtectedForm<RegisterForm>>,
 ) -> Result<Response, FancyError> {
@@ -140,34 +153,10 @@ pub(crate) async fn post(
 
         let mut policy = policy_factory.instantiate().await?;
         let res = policy
-            .evaluate_register(&form.username, &form.password, &form.email)
+            .evaluate_register(&form.username, &form.password, &form.email, None)
             .await?;
 
-        for violation in res.violations {
-            match violation.field.as_deref() {
-                Some("email") => state.add_error_on_field(
-                    RegisterFormField::Email,
-                    FieldError::Policy {
-                        message: violation.msg,
-                    },
-                ),
-                Some("username") => state.add_error_on_field(
-                    RegisterFormField::Username,
-                    FieldError::Policy {
-                        message: violation.msg,
-                    },
-                ),
-                Some("password") => state.add_error_on_field(
-                    RegisterFormField::Password,
-                    FieldError::Policy {
-                        message: violation.msg,
-                    },
-                ),
-                _ => state.add_error_on_form(FormError::Policy {
-                    message: violation.msg,
-                }),
-            }
-        }
+        state.add_errors_from(violations_to_form_state(&res));
 
         state
     };
@@ -185,7 +174,7 @@ pub(crate) async fn post(
         return Ok((cookie_jar, Html(content)).into_response());
     }
 
-    let user = add_user(&mut txn, &mut rng, &clock, &form.username).await?;
+    let user = add_user(&mut txn, &mut rng, &clock, &form.username, None).await?;
     let password = Zeroizing::new(form.password.into_bytes());
     let (version, hashed_password) = password_manager.hash(&mut rng, password).await?;
     let user_password = add_user_password(
@@ -202,9 +191,7 @@ pub(crate) async fn post(
     let user_email = add_user_email(&mut txn, &mut rng, &clock, &user, form.email).await?;
 
     // First, generate a code
-    let range = Uniform::<u32>::from(0..1_000_000);
-    let code = rng.sample(range);
-    let code = format!("{code:06}");
+    let code = generate_verification_code(&mut rng, code_kind);
 
     let address: Address = user_email.email.parse()?;
 
@@ -214,7 +201,7 @@ pub(crate) async fn post(
         &clock,
         user_email,
         Duration::hours(8),
-        code,
+        (*code).clone(),
     )
     .await?;
 
@@ -228,7 +215,17 @@ pub(crate) async fn post(
     let next = mas_router::AccountVerifyEmail::new(verification.email.id)
         .and_maybe(query.post_auth_action);
 
-    let mut session = start_session(&mut txn, &mut rng, &clock, user).await?;
+    let metadata = session_metadata(&rate_limiter, connection.as_deref(), &headers);
+    let mut session = start_session(
+        &mut txn,
+        &mut rng,
+        &clock,
+        user,
+        Some(metadata),
+        max_session_age,
+    )
+    .await?;
+
     authenticate_session_with_password(&mut txn, &mut rng, &clock, &mut session, &user_password)
         .await?;
 
@@ -12,15 +12,37 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::net::IpAddr;
+
 use anyhow::Context;
+use hyper::{header::USER_AGENT, HeaderMap};
+use mas_data_model::SessionMetadata;
+use mas_listener::{proxy_protocol::ProxyProtocolV1Info, ConnectionInfo};
+use mas_policy::EvaluationResult;
 use mas_router::{PostAuthAction, Route};
 use mas_storage::{
     compat::get_compat_sso_login_by_id, oauth2::authorization_grant::get_grant_by_id,
+    user::CodeKind,
+};
+use mas_templates::{
+    FieldError, FormError, FormField, FormState, PostAuthContext, PostAuthContextInner,
 };
-use mas_templates::{PostAuthContext, PostAuthContextInner};
 use serde::{Deserialize, Serialize};
 use sqlx::PgConnection;
 
+use crate::rate_limit::LoginRateLimiter;
+
+/// The format of the codes sent to verify email addresses
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmailVerificationCodeKind(pub(crate) CodeKind);
+
+impl EmailVerificationCodeKind {
+    #[must_use]
+    pub const fn new(kind: CodeKind) -> Self {
+        Self(kind)
+    }
+}
+
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
 pub(crate) struct OptionalPostAuthAction {
     #[serde(flatten)]
@@ -84,3 +106,110 @@ impl OptionalPostAuthAction {
         }))
     }
 }
+
+/// Turn the violations of a policy evaluation into a [`FormState`].
+///
+/// Violations on a field known to the form are added as errors on that field,
+/// the others are added as errors on the whole form.
+pub(crate) fn violations_to_form_state<K: FormField>(result: &EvaluationResult) -> FormState<K> {
+    let mut state = FormState::default();
+
+    for violation in &result.violations {
+        let field = violation
+            .field
+            .as_deref()
+            .and_then(|field| serde_json::from_value(field.into()).ok());
+
+        let message = violation.msg.clone();
+        if let Some(field) = field {
+            state.add_error_on_field(field, FieldError::Policy { message });
+        } else {
+            state.add_error_on_form(FormError::Policy { message });
+        }
+    }
+
+    state
+}
+
+/// Figure out the IP address of the peer, preferring the one advertised
+/// through the proxy protocol if any
+pub(crate) fn peer_ip(connection: &ConnectionInfo) -> Option<IpAddr> {
+    match connection.get_proxy_ref() {
+        Some(ProxyProtocolV1Info::Tcp { source, .. } | ProxyProtocolV1Info::Udp { source, .. }) => {
+            Some(source.ip())
+        }
+        _ => connection.get_peer_addr().map(|addr| addr.ip()),
+    }
+}
+
+/// Gather the informations about the client to record on a new browser
+/// session.
+///
+/// The client IP honours the `X-Forwarded-For` header only from the trusted
+/// proxies of the rate limiter.
+pub(crate) fn session_metadata(
+    rate_limiter: &LoginRateLimiter,
+    connection: Option<&ConnectionInfo>,
+    headers: &HeaderMap,
+) -> SessionMetadata {
+    let peer = connection.and_then(peer_ip);
+    let ip = rate_limiter.client_ip(peer, headers);
+    let user_agent = headers
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(ToOwned::to_owned);
+
+    SessionMetadata { ip, user_agent }
+}
+
+#[cfg(test)]
+mod tests {
+    use mas_policy::Violation;
+    use mas_templates::RegisterFormField;
+
+    use super::*;
+
+    #[test]
+    fn test_violations_to_form_state() {
+        let violation = |msg: &str, field: Option<&str>| Violation {
+            msg: msg.to_owned(),
+            field: field.map(ToOwned::to_owned),
+            code: None,
+        };
+
+        let result = EvaluationResult {
+            violations: vec![
+                violation("username too short", Some("username")),
+                violation("unknown field", Some("not_a_field")),
+                violation("registration is closed", None),
+            ],
+        };
+
+        let state: FormState<RegisterFormField> = violations_to_form_state(&result);
+        assert!(!state.is_valid());
+
+        // Violations on known fields are attached to the field, the others to the
+        // whole form
+        let state = serde_json::to_value(state).unwrap();
+        assert_eq!(
+            state,
+            serde_json::json!({
+                "errors": [
+                    {"kind": "policy", "message": "unknown field"},
+                    {"kind": "policy", "message": "registration is closed"},
+                ],
+                "fields": {
+                    "username": {
+                        "errors": [{"kind": "policy", "message": "username too short"}],
+                        "value": null,
+                    },
+                },
+            })
+        );
+
+        // No violation gives a valid form state
+        let result = EvaluationResult { violations: vec![] };
+        let state: FormState<RegisterFormField> = violations_to_form_state(&result);
+        assert!(state.is_valid());
+    }
+}
@@ -1,7 +1,9 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
+use http::header::{HeaderName, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION};
 use opentelemetry::{
     metrics::{Counter, Histogram, UpDownCounter},
+    trace::SpanKind,
     KeyValue,
 };
 use tower::Layer;
@@ -9,7 +11,8 @@ use tower::Layer;
 use super::{
     extract_context::DefaultExtractContext, inject_context::DefaultInjectContext,
     make_metrics_labels::DefaultMakeMetricsLabels, make_span_builder::DefaultMakeSpanBuilder,
-    on_error::DefaultOnError, on_response::DefaultOnResponse, service::Trace,
+    on_error::DefaultOnError, on_request::DefaultOnRequest, on_response::DefaultOnResponse,
+    service::Trace,
 };
 
 #[derive(Debug, Clone)]
@@ -18,6 +21,7 @@ pub struct TraceLayer<
     InjectContext = DefaultInjectContext,
     MakeSpanBuilder = DefaultMakeSpanBuilder,
     MakeMetricsLabels = DefaultMakeMetricsLabels,
+    OnRequest = DefaultOnRequest,
     OnResponse = DefaultOnResponse,
     OnError = DefaultOnError,
 > {
@@ -26,13 +30,20 @@ pub struct TraceLayer<
     inject_context: InjectContext,
     make_span_builder: MakeSpanBuilder,
     make_metrics_labels: MakeMetricsLabels,
+    on_request: OnRequest,
     on_response: OnResponse,
     on_error: OnError,
 
     inflight_requests: UpDownCounter<i64>,
     request_counter: Counter<u64>,
     request_histogram: Histogram<f64>,
+    request_body_size: Histogram<u64>,
+    response_body_size: Histogram<u64>,
     static_attributes: Vec<KeyValue>,
+    record_headers: HashSet<HeaderName>,
+    redact_headers: HashSet<HeaderName>,
+    span_kind: Option<SpanKind>,
+    enabled: bool,
 }
 
 impl Default for TraceLayer {
@@ -59,22 +70,49 @@ impl TraceLayer {
             .f64_histogram(format!("{namespace}.request_duration_seconds"))
             .with_description("The request latencies in seconds.")
             .init();
+        let request_body_size = meter
+            .u64_histogram(format!("{namespace}.request_body_size"))
+            .with_description("The size of the request bodies in bytes.")
+            .init();
+        let response_body_size = meter
+            .u64_histogram(format!("{namespace}.response_body_size"))
+            .with_description("The size of the response bodies in bytes.")
+            .init();
 
         Self::new(
             tracer,
             inflight_requests,
             request_counter,
             request_histogram,
+            request_body_size,
+            response_body_size,
         )
     }
+
+    /// Create a layer which lets the requests through without creating spans
+    /// or recording metrics, for the code paths where the overhead matters
+    /// more than the telemetry
+    #[must_use]
+    pub fn noop() -> Self {
+        Self::default().disabled()
+    }
 }
 
-impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnResponse, OnError>
+impl<
+        ExtractContext,
+        InjectContext,
+        MakeSpanBuilder,
+        MakeMetricsLabels,
+        OnRequest,
+        OnResponse,
+        OnError,
+    >
     TraceLayer<
         ExtractContext,
         InjectContext,
         MakeSpanBuilder,
         MakeMetricsLabels,
+        OnRequest,
         OnResponse,
         OnError,
     >
@@ -85,12 +123,15 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
         inflight_requests: UpDownCounter<i64>,
         request_counter: Counter<u64>,
         request_histogram: Histogram<f64>,
+        request_body_size: Histogram<u64>,
+        response_body_size: Histogram<u64>,
     ) -> Self
     where
         ExtractContext: Default,
         InjectContext: Default,
         MakeSpanBuilder: Default,
         MakeMetricsLabels: Default,
+        OnRequest: Default,
         OnResponse: Default,
         OnError: Default,
     {
@@ -100,12 +141,19 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
             inject_context: InjectContext::default(),
             make_span_builder: MakeSpanBuilder::default(),
             make_metrics_labels: MakeMetricsLabels::default(),
+            on_request: OnRequest::default(),
             on_response: OnResponse::default(),
             on_error: OnError::default(),
             inflight_requests,
             request_counter,
             request_histogram,
+            request_body_size,
+            response_body_size,
             static_attributes: Vec::new(),
+            record_headers: HashSet::new(),
+            redact_headers: HashSet::from([AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION]),
+            span_kind: None,
+            enabled: true,
         }
     }
 
@@ -124,6 +172,51 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
         self
     }
 
+    /// Add request headers to record on the spans.
+    ///
+    /// No header is recorded by default.
+    #[must_use]
+    pub fn record_headers(mut self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        self.record_headers.extend(headers);
+        self
+    }
+
+    /// Add headers whose values should not end up in the spans.
+    ///
+    /// Those headers are recorded with a `[redacted]` value, even if they were
+    /// not added with [`TraceLayer::record_headers`]. The `Authorization`,
+    /// `Cookie` and `Proxy-Authorization` headers are always redacted.
+    #[must_use]
+    pub fn redact_headers(mut self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        self.redact_headers.extend(headers);
+        self
+    }
+
+    /// Let the requests through without creating spans or recording metrics
+    #[must_use]
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+
+    /// Mark the requests going through this layer as outbound requests.
+    ///
+    /// The metrics will get a `span.kind` label with the `client` value.
+    #[must_use]
+    pub fn as_client(mut self) -> Self {
+        self.span_kind = Some(SpanKind::Client);
+        self
+    }
+
+    /// Mark the requests going through this layer as inbound requests.
+    ///
+    /// The metrics will get a `span.kind` label with the `server` value.
+    #[must_use]
+    pub fn as_server(mut self) -> Self {
+        self.span_kind = Some(SpanKind::Server);
+        self
+    }
+
     #[must_use]
     pub fn extract_context<NewExtractContext>(
         self,
@@ -133,6 +226,7 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
         InjectContext,
         MakeSpanBuilder,
         MakeMetricsLabels,
+        OnRequest,
         OnResponse,
         OnError,
     > {
@@ -142,12 +236,19 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
             inject_context: self.inject_context,
             make_span_builder: self.make_span_builder,
             make_metrics_labels: self.make_metrics_labels,
+            on_request: self.on_request,
             on_response: self.on_response,
             on_error: self.on_error,
             inflight_requests: self.inflight_requests,
             request_counter: self.request_counter,
             request_histogram: self.request_histogram,
+            request_body_size: self.request_body_size,
+            response_body_size: self.response_body_size,
             static_attributes: self.static_attributes,
+            record_headers: self.record_headers,
+            redact_headers: self.redact_headers,
+            span_kind: self.span_kind,
+            enabled: self.enabled,
         }
     }
 
@@ -160,6 +261,7 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
         NewInjectContext,
         MakeSpanBuilder,
         MakeMetricsLabels,
+        OnRequest,
         OnResponse,
         OnError,
     > {
@@ -169,12 +271,19 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
             inject_context,
             make_span_builder: self.make_span_builder,
             make_metrics_labels: self.make_metrics_labels,
+            on_request: self.on_request,
             on_response: self.on_response,
             on_error: self.on_error,
             inflight_requests: self.inflight_requests,
             request_counter: self.request_counter,
             request_histogram: self.request_histogram,
+            request_body_size: self.request_body_size,
+            response_body_size: self.response_body_size,
             static_attributes: self.static_attributes,
+            record_headers: self.record_headers,
+            redact_headers: self.redact_headers,
+            span_kind: self.span_kind,
+            enabled: self.enabled,
         }
     }
 
@@ -187,6 +296,7 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
         InjectContext,
         NewMakeSpanBuilder,
         MakeMetricsLabels,
+        OnRequest,
         OnResponse,
         OnError,
     > {
@@ -196,12 +306,19 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
             inject_context: self.inject_context,
             make_span_builder,
             make_metrics_labels: self.make_metrics_labels,
+            on_request: self.on_request,
             on_response: self.on_response,
             on_error: self.on_error,
             inflight_requests: self.inflight_requests,
             request_counter: self.request_counter,
             request_histogram: self.request_histogram,
+            request_body_size: self.request_body_size,
+            response_body_size: self.response_body_size,
             static_attributes: self.static_attributes,
+            record_headers: self.record_headers,
+            redact_headers: self.redact_headers,
+            span_kind: self.span_kind,
+            enabled: self.enabled,
         }
     }
 
@@ -214,6 +331,7 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
         InjectContext,
         MakeSpanBuilder,
         NewMakeMetricsLabels,
+        OnRequest,
         OnResponse,
         OnError,
     > {
@@ -223,12 +341,54 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
             inject_context: self.inject_context,
             make_span_builder: self.make_span_builder,
             make_metrics_labels,
+            on_request: self.on_request,
             on_response: self.on_response,
             on_error: self.on_error,
             inflight_requests: self.inflight_requests,
             request_counter: self.request_counter,
             request_histogram: self.request_histogram,
+            request_body_size: self.request_body_size,
+            response_body_size: self.response_body_size,
             static_attributes: self.static_attributes,
+            record_headers: self.record_headers,
+            redact_headers: self.redact_headers,
+            span_kind: self.span_kind,
+            enabled: self.enabled,
+        }
+    }
+
+    #[must_use]
+    pub fn on_request<NewOnRequest>(
+        self,
+        on_request: NewOnRequest,
+    ) -> TraceLayer<
+        ExtractContext,
+        InjectContext,
+        MakeSpanBuilder,
+        MakeMetricsLabels,
+        NewOnRequest,
+        OnResponse,
+        OnError,
+    > {
+        TraceLayer {
+            tracer: self.tracer,
+            extract_context: self.extract_context,
+            inject_context: self.inject_context,
+            make_span_builder: self.make_span_builder,
+            make_metrics_labels: self.make_metrics_labels,
+            on_request,
+            on_response: self.on_response,
+            on_error: self.on_error,
+            inflight_requests: self.inflight_requests,
+            request_counter: self.request_counter,
+            request_histogram: self.request_histogram,
+            request_body_size: self.request_body_size,
+            response_body_size: self.response_body_size,
+            static_attributes: self.static_attributes,
+            record_headers: self.record_headers,
+            redact_headers: self.redact_headers,
+            span_kind: self.span_kind,
+            enabled: self.enabled,
         }
     }
 
@@ -241,6 +401,7 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
         InjectContext,
         MakeSpanBuilder,
         MakeMetricsLabels,
+        OnRequest,
         NewOnResponse,
         OnError,
     > {
@@ -250,12 +411,19 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
             inject_context: self.inject_context,
             make_span_builder: self.make_span_builder,
             make_metrics_labels: self.make_metrics_labels,
+            on_request: self.on_request,
             on_response,
             on_error: self.on_error,
             inflight_requests: self.inflight_requests,
             request_counter: self.request_counter,
             request_histogram: self.request_histogram,
+            request_body_size: self.request_body_size,
+            response_body_size: self.response_body_size,
             static_attributes: self.static_attributes,
+            record_headers: self.record_headers,
+            redact_headers: self.redact_headers,
+            span_kind: self.span_kind,
+            enabled: self.enabled,
         }
     }
 
@@ -268,6 +436,7 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
         InjectContext,
         MakeSpanBuilder,
         MakeMetricsLabels,
+        OnRequest,
         OnResponse,
         NewOnError,
     > {
@@ -277,23 +446,39 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
             inject_context: self.inject_context,
             make_span_builder: self.make_span_builder,
             make_metrics_labels: self.make_metrics_labels,
+            on_request: self.on_request,
             on_response: self.on_response,
             on_error,
             inflight_requests: self.inflight_requests,
             request_counter: self.request_counter,
             request_histogram: self.request_histogram,
+            request_body_size: self.request_body_size,
+            response_body_size: self.response_body_size,
             static_attributes: self.static_attributes,
+            record_headers: self.record_headers,
+            redact_headers: self.redact_headers,
+            span_kind: self.span_kind,
+            enabled: self.enabled,
         }
     }
 }
 
-impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnResponse, OnError, S>
-    Layer<S>
+impl<
+        ExtractContext,
+        InjectContext,
+        MakeSpanBuilder,
+        MakeMetricsLabels,
+        OnRequest,
+        OnResponse,
+        OnError,
+        S,
+    > Layer<S>
     for TraceLayer<
         ExtractContext,
         InjectContext,
         MakeSpanBuilder,
         MakeMetricsLabels,
+        OnRequest,
         OnResponse,
         OnError,
     >
@@ -302,6 +487,7 @@ where
     InjectContext: Clone,
     MakeSpanBuilder: Clone,
     MakeMetricsLabels: Clone,
+    OnRequest: Clone,
     OnResponse: Clone,
     OnError: Clone,
 {
@@ -310,6 +496,7 @@ where
         InjectContext,
         MakeSpanBuilder,
         MakeMetricsLabels,
+        OnRequest,
         OnResponse,
         OnError,
         S,
@@ -323,12 +510,19 @@ where
             self.inject_context.clone(),
             self.make_span_builder.clone(),
             self.make_metrics_labels.clone(),
+            self.on_request.clone(),
             self.on_response.clone(),
             self.on_error.clone(),
             self.inflight_requests.clone(),
             self.request_counter.clone(),
             self.request_histogram.clone(),
+            self.request_body_size.clone(),
+            self.response_body_size.clone(),
             self.static_attributes.clone(),
+            self.record_headers.clone(),
+            self.redact_headers.clone(),
+            self.span_kind.clone(),
+            self.enabled,
         )
     }
 }
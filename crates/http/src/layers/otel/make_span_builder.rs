@@ -18,16 +18,27 @@ use std::borrow::Cow;
 #[cfg(feature = "axum")]
 use axum::extract::{ConnectInfo, MatchedPath};
 use headers::{ContentLength, HeaderMapExt, Host, UserAgent};
-use http::Request;
+use http::{HeaderMap, Request};
 #[cfg(feature = "client")]
 use hyper::client::connect::dns::Name;
 use opentelemetry::trace::{SpanBuilder, SpanKind};
 use opentelemetry_semantic_conventions::trace as SC;
 
-use super::utils::{http_flavor, http_method_str};
+use super::utils::{content_length, http_flavor, http_method_str};
 
 pub trait MakeSpanBuilder<R> {
     fn make_span_builder(&self, request: &R) -> SpanBuilder;
+
+    /// The size of the request body in bytes, if known
+    fn request_body_size(&self, _request: &R) -> Option<u64> {
+        None
+    }
+
+    /// The headers of the request, if any, from which the allowed headers get
+    /// recorded on the span
+    fn request_headers<'a>(&self, _request: &'a R) -> Option<&'a HeaderMap> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -116,6 +127,14 @@ impl<B> MakeSpanBuilder<Request<B>> for SpanFromHttpRequest {
             .with_kind(self.span_kind.clone())
             .with_attributes(attributes)
     }
+
+    fn request_body_size(&self, request: &Request<B>) -> Option<u64> {
+        content_length(request.headers())
+    }
+
+    fn request_headers<'a>(&self, request: &'a Request<B>) -> Option<&'a HeaderMap> {
+        Some(request.headers())
+    }
 }
 
 #[cfg(feature = "axum")]
@@ -170,6 +189,14 @@ impl<B> MakeSpanBuilder<Request<B>> for SpanFromAxumRequest {
             .with_kind(SpanKind::Server)
             .with_attributes(attributes)
     }
+
+    fn request_body_size(&self, request: &Request<B>) -> Option<u64> {
+        content_length(request.headers())
+    }
+
+    fn request_headers<'a>(&self, request: &'a Request<B>) -> Option<&'a HeaderMap> {
+        Some(request.headers())
+    }
 }
 
 #[cfg(feature = "client")]
@@ -232,4 +259,15 @@ impl MakeSpanBuilder<aws_smithy_http::operation::Request> for SpanFromAwsRequest
             .with_kind(SpanKind::Client)
             .with_attributes(attributes)
     }
+
+    fn request_body_size(&self, request: &aws_smithy_http::operation::Request) -> Option<u64> {
+        content_length(request.http().headers())
+    }
+
+    fn request_headers<'a>(
+        &self,
+        request: &'a aws_smithy_http::operation::Request,
+    ) -> Option<&'a HeaderMap> {
+        Some(request.http().headers())
+    }
 }
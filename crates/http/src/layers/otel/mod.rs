@@ -18,6 +18,7 @@ mod layer;
 mod make_metrics_labels;
 mod make_span_builder;
 mod on_error;
+mod on_request;
 mod on_response;
 mod service;
 mod utils;
@@ -27,6 +28,7 @@ pub type TraceHttpServerLayer = TraceLayer<
     DefaultInjectContext,
     SpanFromHttpRequest,
     MetricsLabelsFromHttpRequest,
+    DefaultOnRequest,
     OnHttpResponse,
     DefaultOnError,
 >;
@@ -36,6 +38,7 @@ pub type TraceHttpServer<S> = Trace<
     DefaultInjectContext,
     SpanFromHttpRequest,
     MetricsLabelsFromHttpRequest,
+    DefaultOnRequest,
     OnHttpResponse,
     DefaultOnError,
     S,
@@ -47,6 +50,7 @@ pub type TraceAxumServerLayer = TraceLayer<
     DefaultInjectContext,
     SpanFromAxumRequest,
     MetricsLabelsFromAxumRequest,
+    DefaultOnRequest,
     OnHttpResponse,
     DefaultOnError,
 >;
@@ -57,6 +61,7 @@ pub type TraceAxumServer<S> = Trace<
     DefaultInjectContext,
     SpanFromAxumRequest,
     MetricsLabelsFromAxumRequest,
+    DefaultOnRequest,
     OnHttpResponse,
     DefaultOnError,
     S,
@@ -67,6 +72,7 @@ pub type TraceHttpClientLayer = TraceLayer<
     InjectInHttpRequest,
     SpanFromHttpRequest,
     MetricsLabelsFromHttpRequest,
+    DefaultOnRequest,
     OnHttpResponse,
     DefaultOnError,
 >;
@@ -76,6 +82,7 @@ pub type TraceHttpClient<S> = Trace<
     InjectInHttpRequest,
     SpanFromHttpRequest,
     MetricsLabelsFromHttpRequest,
+    DefaultOnRequest,
     OnHttpResponse,
     DefaultOnError,
     S,
@@ -87,6 +94,7 @@ pub type TraceDnsLayer = TraceLayer<
     DefaultInjectContext,
     SpanFromDnsRequest,
     DefaultMakeMetricsLabels,
+    DefaultOnRequest,
     DefaultOnResponse,
     DefaultOnError,
 >;
@@ -97,6 +105,7 @@ pub type TraceDns<S> = Trace<
     DefaultInjectContext,
     SpanFromDnsRequest,
     DefaultMakeMetricsLabels,
+    DefaultOnRequest,
     DefaultOnResponse,
     DefaultOnError,
     S,
@@ -108,6 +117,7 @@ pub type TraceAwsSdkClientLayer = TraceLayer<
     InjectInAwsRequest,
     SpanFromAwsRequest,
     DefaultMakeMetricsLabels,
+    DefaultOnRequest,
     OnAwsResponse,
     DebugOnError,
 >;
@@ -118,6 +128,7 @@ pub type TraceAwsSdkClient<S> = Trace<
     InjectInAwsRequest,
     SpanFromAwsRequest,
     DefaultMakeMetricsLabels,
+    DefaultOnRequest,
     OnAwsResponse,
     DebugOnError,
     S,
@@ -131,6 +142,7 @@ impl TraceHttpServerLayer {
             .make_metrics_labels(MetricsLabelsFromHttpRequest::default())
             .on_response(OnHttpResponse)
             .extract_context(ExtractFromHttpRequest)
+            .as_server()
     }
 }
 
@@ -143,6 +155,7 @@ impl TraceAxumServerLayer {
             .make_metrics_labels(MetricsLabelsFromAxumRequest::default())
             .on_response(OnHttpResponse)
             .extract_context(ExtractFromHttpRequest)
+            .as_server()
     }
 }
 
@@ -154,6 +167,7 @@ impl TraceHttpClientLayer {
             .make_metrics_labels(MetricsLabelsFromHttpRequest::default())
             .on_response(OnHttpResponse)
             .inject_context(InjectInHttpRequest)
+            .as_client()
     }
 
     #[must_use]
@@ -163,6 +177,7 @@ impl TraceHttpClientLayer {
             .make_metrics_labels(MetricsLabelsFromHttpRequest::default())
             .on_response(OnHttpResponse)
             .inject_context(InjectInHttpRequest)
+            .as_client()
     }
 }
 
@@ -170,7 +185,9 @@ impl TraceHttpClientLayer {
 impl TraceDnsLayer {
     #[must_use]
     pub fn dns() -> Self {
-        TraceLayer::with_namespace("dns").make_span_builder(SpanFromDnsRequest)
+        TraceLayer::with_namespace("dns")
+            .make_span_builder(SpanFromDnsRequest)
+            .as_client()
     }
 }
 
@@ -183,6 +200,7 @@ impl TraceAwsSdkClientLayer {
             .on_response(OnAwsResponse)
             .on_error(DebugOnError)
             .inject_context(InjectInAwsRequest)
+            .as_client()
     }
 }
 
@@ -193,5 +211,5 @@ use self::make_metrics_labels::MetricsLabelsFromAxumRequest;
 use self::make_metrics_labels::MetricsLabelsFromHttpRequest;
 pub use self::{
     extract_context::*, inject_context::*, layer::*, make_span_builder::*, on_error::*,
-    on_response::*, service::*,
+    on_request::*, on_response::*, service::*,
 };
@@ -0,0 +1,35 @@
+// Copyright 2022 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use opentelemetry::{trace::SpanRef, KeyValue};
+
+pub trait OnRequest<R> {
+    fn on_request(&self, span: &SpanRef<'_>, metrics_labels: &mut Vec<KeyValue>, request: &R);
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultOnRequest;
+
+impl<R> OnRequest<R> for DefaultOnRequest {
+    fn on_request(&self, _span: &SpanRef<'_>, _metrics_labels: &mut Vec<KeyValue>, _request: &R) {}
+}
+
+impl<R, F> OnRequest<R> for F
+where
+    F: Fn(&SpanRef<'_>, &mut Vec<KeyValue>, &R),
+{
+    fn on_request(&self, span: &SpanRef<'_>, metrics_labels: &mut Vec<KeyValue>, request: &R) {
+        self(span, metrics_labels, request);
+    }
+}
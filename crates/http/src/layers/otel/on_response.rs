@@ -19,10 +19,19 @@ use hyper::client::connect::HttpInfo;
 use opentelemetry::{trace::SpanRef, KeyValue};
 use opentelemetry_semantic_conventions::trace as SC;
 
+use super::utils::{content_length, status_class_str};
+
 pub trait OnResponse<R> {
     fn on_response(&self, span: &SpanRef<'_>, metrics_labels: &mut Vec<KeyValue>, response: &R);
+
+    /// The size of the response body in bytes, if known
+    fn response_body_size(&self, _response: &R) -> Option<u64> {
+        None
+    }
 }
 
+/// Doesn't record anything, as it works with any kind of response. Use
+/// [`OnHttpResponse`] to get the status code and status class as labels.
 #[derive(Debug, Clone, Copy, Default)]
 pub struct DefaultOnResponse;
 
@@ -44,23 +53,67 @@ impl<B> OnResponse<Response<B>> for OnHttpResponse {
         let status_code = i64::from(response.status().as_u16());
         span.set_attribute(SC::HTTP_STATUS_CODE.i64(status_code));
         metrics_labels.push(KeyValue::new("status_code", status_code));
+        metrics_labels.push(KeyValue::new(
+            "http.status_class",
+            status_class_str(response.status()),
+        ));
 
-        if let Some(ContentLength(content_length)) = response.headers().typed_get() {
-            if let Ok(content_length) = content_length.try_into() {
-                span.set_attribute(SC::HTTP_RESPONSE_CONTENT_LENGTH.i64(content_length));
-            }
+        record_http_response_details(span, response);
+    }
+
+    fn response_body_size(&self, response: &Response<B>) -> Option<u64> {
+        content_length(response.headers())
+    }
+}
+
+/// Like [`OnHttpResponse`], but only records the detailed attributes of the
+/// response if the span is sampled, or if the response is an error.
+///
+/// The status code is always recorded. This avoids the cost of the other
+/// attributes on busy endpoints, where most spans are not sampled.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SampledOnResponse;
+
+impl<B> OnResponse<Response<B>> for SampledOnResponse {
+    fn on_response(
+        &self,
+        span: &SpanRef<'_>,
+        metrics_labels: &mut Vec<KeyValue>,
+        response: &Response<B>,
+    ) {
+        let status = response.status();
+        let status_code = i64::from(status.as_u16());
+        span.set_attribute(SC::HTTP_STATUS_CODE.i64(status_code));
+        metrics_labels.push(KeyValue::new("status_code", status_code));
+        metrics_labels.push(KeyValue::new("http.status_class", status_class_str(status)));
+
+        if span.span_context().is_sampled() || status.is_client_error() || status.is_server_error()
+        {
+            record_http_response_details(span, response);
         }
+    }
 
-        #[cfg(feature = "client")]
-        // Get local and remote address from hyper's HttpInfo injected by the
-        // HttpConnector
-        if let Some(info) = response.extensions().get::<HttpInfo>() {
-            span.set_attribute(SC::NET_PEER_IP.string(info.remote_addr().ip().to_string()));
-            span.set_attribute(SC::NET_PEER_PORT.i64(info.remote_addr().port().into()));
-            span.set_attribute(SC::NET_HOST_IP.string(info.local_addr().ip().to_string()));
-            span.set_attribute(SC::NET_HOST_PORT.i64(info.local_addr().port().into()));
+    fn response_body_size(&self, response: &Response<B>) -> Option<u64> {
+        content_length(response.headers())
+    }
+}
+
+fn record_http_response_details<B>(span: &SpanRef<'_>, response: &Response<B>) {
+    if let Some(ContentLength(content_length)) = response.headers().typed_get() {
+        if let Ok(content_length) = content_length.try_into() {
+            span.set_attribute(SC::HTTP_RESPONSE_CONTENT_LENGTH.i64(content_length));
         }
     }
+
+    #[cfg(feature = "client")]
+    // Get local and remote address from hyper's HttpInfo injected by the
+    // HttpConnector
+    if let Some(info) = response.extensions().get::<HttpInfo>() {
+        span.set_attribute(SC::NET_PEER_IP.string(info.remote_addr().ip().to_string()));
+        span.set_attribute(SC::NET_PEER_PORT.i64(info.remote_addr().port().into()));
+        span.set_attribute(SC::NET_HOST_IP.string(info.local_addr().ip().to_string()));
+        span.set_attribute(SC::NET_HOST_PORT.i64(info.local_addr().port().into()));
+    }
 }
 
 #[cfg(feature = "aws-sdk")]
@@ -79,6 +132,10 @@ impl OnResponse<aws_smithy_http::operation::Response> for OnAwsResponse {
         let status_code = i64::from(response.status().as_u16());
         span.set_attribute(SC::HTTP_STATUS_CODE.i64(status_code));
         metrics_labels.push(KeyValue::new("status_code", status_code));
+        metrics_labels.push(KeyValue::new(
+            "http.status_class",
+            status_class_str(response.status()),
+        ));
 
         if let Some(ContentLength(content_length)) = response.headers().typed_get() {
             if let Ok(content_length) = content_length.try_into() {
@@ -96,4 +153,8 @@ impl OnResponse<aws_smithy_http::operation::Response> for OnAwsResponse {
             span.set_attribute(SC::NET_HOST_PORT.i64(info.local_addr().port().into()));
         }
     }
+
+    fn response_body_size(&self, response: &aws_smithy_http::operation::Response) -> Option<u64> {
+        content_length(response.http().headers())
+    }
 }
@@ -12,20 +12,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{sync::Arc, task::Poll, time::SystemTime};
+use std::{collections::HashSet, sync::Arc, task::Poll, time::SystemTime};
 
 use futures_util::{future::BoxFuture, FutureExt as _};
+use http::header::HeaderName;
 use opentelemetry::{
     metrics::{Counter, Histogram, UpDownCounter},
-    trace::{FutureExt as _, TraceContextExt},
+    trace::{FutureExt as _, SpanKind, TraceContextExt},
     Context, KeyValue,
 };
 use tower::Service;
 
 use super::{
-    extract_context::ExtractContext, inject_context::InjectContext,
-    make_metrics_labels::MakeMetricsLabels, make_span_builder::MakeSpanBuilder, on_error::OnError,
+    extract_context::ExtractContext,
+    inject_context::InjectContext,
+    make_metrics_labels::MakeMetricsLabels,
+    make_span_builder::MakeSpanBuilder,
+    on_error::OnError,
+    on_request::OnRequest,
     on_response::OnResponse,
+    utils::{request_header_attributes, span_kind_str},
 };
 
 #[derive(Debug, Clone)]
@@ -34,6 +40,7 @@ pub struct Trace<
     InjectContext,
     MakeSpanBuilder,
     MakeMetricsLabels,
+    OnRequest,
     OnResponse,
     OnError,
     S,
@@ -44,17 +51,42 @@ pub struct Trace<
     inject_context: InjectContext,
     make_span_builder: MakeSpanBuilder,
     make_metrics_labels: MakeMetricsLabels,
+    on_request: OnRequest,
     on_response: OnResponse,
     on_error: OnError,
 
     inflight_requests: UpDownCounter<i64>,
     request_counter: Counter<u64>,
     request_histogram: Histogram<f64>,
+    request_body_size: Histogram<u64>,
+    response_body_size: Histogram<u64>,
     static_attributes: Vec<KeyValue>,
+    record_headers: HashSet<HeaderName>,
+    redact_headers: HashSet<HeaderName>,
+    span_kind: Option<SpanKind>,
+    enabled: bool,
 }
 
-impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnResponse, OnError, S>
-    Trace<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnResponse, OnError, S>
+impl<
+        ExtractContext,
+        InjectContext,
+        MakeSpanBuilder,
+        MakeMetricsLabels,
+        OnRequest,
+        OnResponse,
+        OnError,
+        S,
+    >
+    Trace<
+        ExtractContext,
+        InjectContext,
+        MakeSpanBuilder,
+        MakeMetricsLabels,
+        OnRequest,
+        OnResponse,
+        OnError,
+        S,
+    >
 {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -64,12 +96,19 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
         inject_context: InjectContext,
         make_span_builder: MakeSpanBuilder,
         make_metrics_labels: MakeMetricsLabels,
+        on_request: OnRequest,
         on_response: OnResponse,
         on_error: OnError,
         inflight_requests: UpDownCounter<i64>,
         request_counter: Counter<u64>,
         request_histogram: Histogram<f64>,
+        request_body_size: Histogram<u64>,
+        response_body_size: Histogram<u64>,
         static_attributes: Vec<KeyValue>,
+        record_headers: HashSet<HeaderName>,
+        redact_headers: HashSet<HeaderName>,
+        span_kind: Option<SpanKind>,
+        enabled: bool,
     ) -> Self {
         Self {
             inner: service,
@@ -79,13 +118,20 @@ impl<ExtractContext, InjectContext, MakeSpanBuilder, MakeMetricsLabels, OnRespon
             inject_context,
             make_span_builder,
             make_metrics_labels,
+            on_request,
             on_response,
             on_error,
 
             inflight_requests,
             request_counter,
             request_histogram,
+            request_body_size,
+            response_body_size,
             static_attributes,
+            record_headers,
+            redact_headers,
+            span_kind,
+            enabled,
         }
     }
 }
@@ -120,6 +166,7 @@ impl<
         InjectContextT,
         MakeSpanBuilderT,
         MakeMetricsLabelsT,
+        OnRequestT,
         OnResponseT,
         OnErrorT,
     > Service<Req>
@@ -128,6 +175,7 @@ impl<
         InjectContextT,
         MakeSpanBuilderT,
         MakeMetricsLabelsT,
+        OnRequestT,
         OnResponseT,
         OnErrorT,
         S,
@@ -136,6 +184,7 @@ where
     ExtractContextT: ExtractContext<Req> + Send,
     InjectContextT: InjectContext<Req> + Send,
     S: Service<InjectContextT::Output> + Send,
+    OnRequestT: OnRequest<Req> + Send,
     OnResponseT: OnResponse<S::Response> + Send + Clone + 'static,
     OnErrorT: OnError<S::Error> + Send + Clone + 'static,
     MakeSpanBuilderT: MakeSpanBuilder<Req> + Send,
@@ -151,16 +200,29 @@ where
     }
 
     fn call(&mut self, request: Req) -> Self::Future {
+        if !self.enabled {
+            let request = self
+                .inject_context
+                .inject_context(&Context::current(), request);
+            return self.inner.call(request).boxed();
+        }
+
         let request_counter = self.request_counter.clone();
         let request_histogram = self.request_histogram.clone();
+        let request_body_size = self.request_body_size.clone();
+        let response_body_size = self.response_body_size.clone();
         let start_time = SystemTime::now();
 
         let cx = self.extract_context.extract_context(&request);
         let mut span_builder = self.make_span_builder.make_span_builder(&request);
         let mut metrics_labels = self.make_metrics_labels.make_metrics_labels(&request);
+        let request_size = self.make_span_builder.request_body_size(&request);
 
         // Add the static attributes to the metrics and the span
         metrics_labels.extend_from_slice(&self.static_attributes[..]);
+        if let Some(span_kind) = &self.span_kind {
+            metrics_labels.push(KeyValue::new("span.kind", span_kind_str(span_kind)));
+        }
         let mut span_attributes = span_builder.attributes.unwrap_or_default();
         span_attributes.extend(self.static_attributes.iter().cloned());
         span_builder.attributes = Some(span_attributes);
@@ -168,6 +230,19 @@ where
         let span = span_builder.start_with_context(self.tracer.as_ref(), &cx);
 
         let cx = cx.with_span(span);
+        self.on_request
+            .on_request(&cx.span(), &mut metrics_labels, &request);
+
+        // The headers are recorded last, so that the redacted values can't be
+        // overwritten by the span builder or the request hook
+        if let Some(headers) = self.make_span_builder.request_headers(&request) {
+            let span = cx.span();
+            for attribute in
+                request_header_attributes(headers, &self.record_headers, &self.redact_headers)
+            {
+                span.set_attribute(attribute);
+            }
+        }
         let request = self.inject_context.inject_context(&cx, request);
 
         let guard = InFlightGuard::increment(&cx, &self.inflight_requests, &metrics_labels);
@@ -185,9 +260,15 @@ where
                 let _guard = guard;
 
                 let span = cx.span();
-                match r {
-                    Ok(response) => on_response.on_response(&span, &mut metrics_labels, response),
-                    Err(err) => on_error.on_error(&span, &mut metrics_labels, err),
+                let response_size = match r {
+                    Ok(response) => {
+                        on_response.on_response(&span, &mut metrics_labels, response);
+                        on_response.response_body_size(response)
+                    }
+                    Err(err) => {
+                        on_error.on_error(&span, &mut metrics_labels, err);
+                        None
+                    }
                 };
 
                 request_counter.add(&cx, 1, &metrics_labels);
@@ -197,6 +278,15 @@ where
                     &metrics_labels,
                 );
 
+                // Body sizes are only known when a Content-Length header is set
+                if let Some(request_size) = request_size {
+                    request_body_size.record(&cx, request_size, &metrics_labels);
+                }
+
+                if let Some(response_size) = response_size {
+                    response_body_size.record(&cx, response_size, &metrics_labels);
+                }
+
                 span.end();
             })
             .boxed();
@@ -12,9 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashSet};
 
-use http::{Method, Version};
+use headers::{ContentLength, HeaderMapExt};
+use http::{header::HeaderName, HeaderMap, Method, StatusCode, Version};
+use opentelemetry::{trace::SpanKind, KeyValue};
+
+const REQUEST_HEADER_PREFIX: &str = "http.request.header.";
+const REDACTED: &str = "[redacted]";
 
 #[inline]
 pub(super) fn http_method_str(method: &Method) -> Cow<'static, str> {
@@ -43,3 +48,122 @@ pub(super) fn http_flavor(version: Version) -> Cow<'static, str> {
         other => format!("{other:?}").into(),
     }
 }
+
+/// Get the class of a status code (`2xx`, `4xx`, etc.), to be used as a
+/// low-cardinality metrics label
+#[inline]
+pub(super) fn status_class_str(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        500..=599 => "5xx",
+        _ => "unknown",
+    }
+}
+
+/// Get the body size from the `Content-Length` header, if set
+#[inline]
+pub(super) fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .typed_get()
+        .map(|ContentLength(content_length)| content_length)
+}
+
+/// Get the attributes recording the request headers, following the
+/// OpenTelemetry semantic conventions for the attribute names
+///
+/// Only the headers in `record_headers` are recorded. Headers in
+/// `redact_headers` are always recorded, with their value replaced by
+/// `[redacted]`.
+pub(super) fn request_header_attributes(
+    headers: &HeaderMap,
+    record_headers: &HashSet<HeaderName>,
+    redact_headers: &HashSet<HeaderName>,
+) -> Vec<KeyValue> {
+    headers
+        .keys()
+        .filter(|name| record_headers.contains(*name) || redact_headers.contains(*name))
+        .map(|name| {
+            let key = format!("{REQUEST_HEADER_PREFIX}{}", name.as_str().replace('-', "_"));
+            if redact_headers.contains(name) {
+                return KeyValue::new(key, REDACTED);
+            }
+
+            let values: Vec<&str> = headers
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .collect();
+            KeyValue::new(key, values.join(", "))
+        })
+        .collect()
+}
+
+#[inline]
+pub(super) fn span_kind_str(span_kind: &SpanKind) -> &'static str {
+    match span_kind {
+        SpanKind::Client => "client",
+        SpanKind::Server => "server",
+        SpanKind::Producer => "producer",
+        SpanKind::Consumer => "consumer",
+        SpanKind::Internal => "internal",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::{AUTHORIZATION, CONTENT_TYPE, COOKIE, USER_AGENT};
+    use opentelemetry::Value;
+
+    use super::*;
+
+    #[test]
+    fn test_request_header_attributes() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+        headers.insert(USER_AGENT, "curl/7.87.0".parse().unwrap());
+        headers.insert(AUTHORIZATION, "Bearer secret".parse().unwrap());
+        headers.append("x-forwarded-for", "10.0.0.1".parse().unwrap());
+        headers.append("x-forwarded-for", "10.0.0.2".parse().unwrap());
+        headers.insert("x_secret", "hunter2".parse().unwrap());
+
+        let record_headers = HashSet::from([
+            CONTENT_TYPE,
+            AUTHORIZATION,
+            HeaderName::from_static("x-forwarded-for"),
+        ]);
+        let redact_headers =
+            HashSet::from([AUTHORIZATION, COOKIE, HeaderName::from_static("x_secret")]);
+
+        let mut attributes: Vec<(String, Value)> =
+            request_header_attributes(&headers, &record_headers, &redact_headers)
+                .into_iter()
+                .map(|kv| (kv.key.as_str().to_owned(), kv.value))
+                .collect();
+        attributes.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(
+            attributes,
+            vec![
+                (
+                    "http.request.header.authorization".to_owned(),
+                    "[redacted]".into()
+                ),
+                (
+                    "http.request.header.content_type".to_owned(),
+                    "application/json".into()
+                ),
+                (
+                    "http.request.header.x_forwarded_for".to_owned(),
+                    "10.0.0.1, 10.0.0.2".into()
+                ),
+                (
+                    "http.request.header.x_secret".to_owned(),
+                    "[redacted]".into()
+                ),
+            ]
+        );
+    }
+}
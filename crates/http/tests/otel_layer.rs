@@ -0,0 +1,66 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use mas_http::otel::{DefaultExtractContext, DefaultInjectContext, MakeSpanBuilder, TraceLayer};
+use opentelemetry::trace::SpanBuilder;
+use tower::{service_fn, Layer, Service, ServiceExt};
+
+/// Counts how many spans the layer starts
+#[derive(Debug, Clone, Default)]
+struct CountingMakeSpanBuilder(Arc<AtomicUsize>);
+
+impl<R> MakeSpanBuilder<R> for CountingMakeSpanBuilder {
+    fn make_span_builder(&self, _request: &R) -> SpanBuilder {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        SpanBuilder::from_name("test")
+    }
+}
+
+type CountingTraceLayer =
+    TraceLayer<DefaultExtractContext, DefaultInjectContext, CountingMakeSpanBuilder>;
+
+async fn run(layer: CountingTraceLayer, requests: usize) {
+    let mut svc = layer.layer(service_fn(|request: usize| async move {
+        Ok::<_, Infallible>(request * 2)
+    }));
+
+    for request in 0..requests {
+        let response = svc.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response, request * 2);
+    }
+}
+
+#[tokio::test]
+async fn test_noop_layer_creates_no_span() {
+    let counter = CountingMakeSpanBuilder::default();
+
+    run(
+        TraceLayer::with_namespace("test").make_span_builder(counter.clone()),
+        10,
+    )
+    .await;
+    assert_eq!(counter.0.load(Ordering::Relaxed), 10);
+
+    // The requests go straight through the disabled layer
+    run(TraceLayer::noop().make_span_builder(counter.clone()), 1000).await;
+    assert_eq!(counter.0.load(Ordering::Relaxed), 10);
+}
@@ -173,6 +173,21 @@ pub enum {} {{"#,
             }
 
             writeln!(f, "}}")?;
+
+            // List all the known variants, so that they can be iterated over
+            writeln!(f)?;
+            writeln!(f, "impl {} {{", section.key)?;
+            writeln!(f, "    /// All the known values of this enum")?;
+            writeln!(f, "    #[must_use]")?;
+            writeln!(f, "    pub fn all() -> &'static [Self] {{")?;
+            writeln!(f, "        const ALL: &[{}] = &[", section.key)?;
+            for member in list {
+                writeln!(f, "            {}::{},", section.key, member.enum_name)?;
+            }
+            writeln!(f, "        ];")?;
+            writeln!(f, "        ALL")?;
+            writeln!(f, "    }}")?;
+            writeln!(f, "}}")?;
         }
 
         Ok(())
@@ -121,6 +121,31 @@ pub enum JsonWebSignatureAlg {
     Unknown(String),
 }
 
+impl JsonWebSignatureAlg {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[JsonWebSignatureAlg] = &[
+            JsonWebSignatureAlg::Hs256,
+            JsonWebSignatureAlg::Hs384,
+            JsonWebSignatureAlg::Hs512,
+            JsonWebSignatureAlg::Rs256,
+            JsonWebSignatureAlg::Rs384,
+            JsonWebSignatureAlg::Rs512,
+            JsonWebSignatureAlg::Es256,
+            JsonWebSignatureAlg::Es384,
+            JsonWebSignatureAlg::Es512,
+            JsonWebSignatureAlg::Ps256,
+            JsonWebSignatureAlg::Ps384,
+            JsonWebSignatureAlg::Ps512,
+            JsonWebSignatureAlg::None,
+            JsonWebSignatureAlg::EdDsa,
+            JsonWebSignatureAlg::Es256K,
+        ];
+        ALL
+    }
+}
+
 /// JSON Web Encryption "alg" parameter
 ///
 /// Source: <https://www.iana.org/assignments/jose/web-signature-encryption-algorithms.csv>
@@ -241,6 +266,35 @@ pub enum JsonWebEncryptionAlg {
     Unknown(String),
 }
 
+impl JsonWebEncryptionAlg {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[JsonWebEncryptionAlg] = &[
+            JsonWebEncryptionAlg::Rsa15,
+            JsonWebEncryptionAlg::RsaOaep,
+            JsonWebEncryptionAlg::RsaOaep256,
+            JsonWebEncryptionAlg::A128Kw,
+            JsonWebEncryptionAlg::A192Kw,
+            JsonWebEncryptionAlg::A256Kw,
+            JsonWebEncryptionAlg::Dir,
+            JsonWebEncryptionAlg::EcdhEs,
+            JsonWebEncryptionAlg::EcdhEsA128Kw,
+            JsonWebEncryptionAlg::EcdhEsA192Kw,
+            JsonWebEncryptionAlg::EcdhEsA256Kw,
+            JsonWebEncryptionAlg::A128Gcmkw,
+            JsonWebEncryptionAlg::A192Gcmkw,
+            JsonWebEncryptionAlg::A256Gcmkw,
+            JsonWebEncryptionAlg::Pbes2Hs256A128Kw,
+            JsonWebEncryptionAlg::Pbes2Hs384A192Kw,
+            JsonWebEncryptionAlg::Pbes2Hs512A256Kw,
+            JsonWebEncryptionAlg::RsaOaep384,
+            JsonWebEncryptionAlg::RsaOaep512,
+        ];
+        ALL
+    }
+}
+
 /// JSON Web Encryption "enc" parameter
 ///
 /// Source: <https://www.iana.org/assignments/jose/web-signature-encryption-algorithms.csv>
@@ -296,6 +350,22 @@ pub enum JsonWebEncryptionEnc {
     Unknown(String),
 }
 
+impl JsonWebEncryptionEnc {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[JsonWebEncryptionEnc] = &[
+            JsonWebEncryptionEnc::A128CbcHs256,
+            JsonWebEncryptionEnc::A192CbcHs384,
+            JsonWebEncryptionEnc::A256CbcHs512,
+            JsonWebEncryptionEnc::A128Gcm,
+            JsonWebEncryptionEnc::A192Gcm,
+            JsonWebEncryptionEnc::A256Gcm,
+        ];
+        ALL
+    }
+}
+
 /// JSON Web Encryption Compression Algorithm
 ///
 /// Source: <https://www.iana.org/assignments/jose/web-encryption-compression-algorithms.csv>
@@ -326,6 +396,16 @@ pub enum JsonWebEncryptionCompressionAlgorithm {
     Unknown(String),
 }
 
+impl JsonWebEncryptionCompressionAlgorithm {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[JsonWebEncryptionCompressionAlgorithm] =
+            &[JsonWebEncryptionCompressionAlgorithm::Def];
+        ALL
+    }
+}
+
 /// JSON Web Key Type
 ///
 /// Source: <https://www.iana.org/assignments/jose/web-key-types.csv>
@@ -371,6 +451,20 @@ pub enum JsonWebKeyType {
     Unknown(String),
 }
 
+impl JsonWebKeyType {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[JsonWebKeyType] = &[
+            JsonWebKeyType::Ec,
+            JsonWebKeyType::Rsa,
+            JsonWebKeyType::Oct,
+            JsonWebKeyType::Okp,
+        ];
+        ALL
+    }
+}
+
 /// JSON Web Key EC Elliptic Curve
 ///
 /// Source: <https://www.iana.org/assignments/jose/web-key-elliptic-curve.csv>
@@ -416,6 +510,20 @@ pub enum JsonWebKeyEcEllipticCurve {
     Unknown(String),
 }
 
+impl JsonWebKeyEcEllipticCurve {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[JsonWebKeyEcEllipticCurve] = &[
+            JsonWebKeyEcEllipticCurve::P256,
+            JsonWebKeyEcEllipticCurve::P384,
+            JsonWebKeyEcEllipticCurve::P521,
+            JsonWebKeyEcEllipticCurve::Secp256K1,
+        ];
+        ALL
+    }
+}
+
 /// JSON Web Key OKP Elliptic Curve
 ///
 /// Source: <https://www.iana.org/assignments/jose/web-key-elliptic-curve.csv>
@@ -461,6 +569,20 @@ pub enum JsonWebKeyOkpEllipticCurve {
     Unknown(String),
 }
 
+impl JsonWebKeyOkpEllipticCurve {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[JsonWebKeyOkpEllipticCurve] = &[
+            JsonWebKeyOkpEllipticCurve::Ed25519,
+            JsonWebKeyOkpEllipticCurve::Ed448,
+            JsonWebKeyOkpEllipticCurve::X25519,
+            JsonWebKeyOkpEllipticCurve::X448,
+        ];
+        ALL
+    }
+}
+
 /// JSON Web Key Use
 ///
 /// Source: <https://www.iana.org/assignments/jose/web-key-use.csv>
@@ -496,6 +618,15 @@ pub enum JsonWebKeyUse {
     Unknown(String),
 }
 
+impl JsonWebKeyUse {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[JsonWebKeyUse] = &[JsonWebKeyUse::Sig, JsonWebKeyUse::Enc];
+        ALL
+    }
+}
+
 /// JSON Web Key Operation
 ///
 /// Source: <https://www.iana.org/assignments/jose/web-key-operations.csv>
@@ -560,3 +691,21 @@ pub enum JsonWebKeyOperation {
     #[schemars(skip)]
     Unknown(String),
 }
+
+impl JsonWebKeyOperation {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[JsonWebKeyOperation] = &[
+            JsonWebKeyOperation::Sign,
+            JsonWebKeyOperation::Verify,
+            JsonWebKeyOperation::Encrypt,
+            JsonWebKeyOperation::Decrypt,
+            JsonWebKeyOperation::WrapKey,
+            JsonWebKeyOperation::UnwrapKey,
+            JsonWebKeyOperation::DeriveKey,
+            JsonWebKeyOperation::DeriveBits,
+        ];
+        ALL
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hand-written helpers on top of the generated JOSE enums
+
+use crate::jose::{JsonWebKeyEcEllipticCurve, JsonWebKeyType, JsonWebSignatureAlg};
+
+impl JsonWebSignatureAlg {
+    /// The type of key this algorithm is used with
+    ///
+    /// Returns `None` for the `none` algorithm and for unknown algorithms
+    #[must_use]
+    pub fn key_type(&self) -> Option<JsonWebKeyType> {
+        match self {
+            Self::Hs256 | Self::Hs384 | Self::Hs512 => Some(JsonWebKeyType::Oct),
+            Self::Rs256 | Self::Rs384 | Self::Rs512 | Self::Ps256 | Self::Ps384 | Self::Ps512 => {
+                Some(JsonWebKeyType::Rsa)
+            }
+            Self::Es256 | Self::Es384 | Self::Es512 | Self::Es256K => Some(JsonWebKeyType::Ec),
+            Self::EdDsa => Some(JsonWebKeyType::Okp),
+            Self::None | Self::Unknown(_) => None,
+        }
+    }
+
+    /// The elliptic curve this algorithm is used with
+    ///
+    /// Returns `None` for algorithms which are not ECDSA-based
+    #[must_use]
+    pub fn curve(&self) -> Option<JsonWebKeyEcEllipticCurve> {
+        match self {
+            Self::Es256 => Some(JsonWebKeyEcEllipticCurve::P256),
+            Self::Es384 => Some(JsonWebKeyEcEllipticCurve::P384),
+            Self::Es512 => Some(JsonWebKeyEcEllipticCurve::P521),
+            Self::Es256K => Some(JsonWebKeyEcEllipticCurve::Secp256K1),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_type_and_curve() {
+        assert_eq!(
+            JsonWebSignatureAlg::Rs256.key_type(),
+            Some(JsonWebKeyType::Rsa)
+        );
+        assert_eq!(JsonWebSignatureAlg::Rs256.curve(), None);
+
+        assert_eq!(
+            JsonWebSignatureAlg::Es256.key_type(),
+            Some(JsonWebKeyType::Ec)
+        );
+        assert_eq!(
+            JsonWebSignatureAlg::Es256.curve(),
+            Some(JsonWebKeyEcEllipticCurve::P256)
+        );
+
+        assert_eq!(
+            JsonWebSignatureAlg::EdDsa.key_type(),
+            Some(JsonWebKeyType::Okp)
+        );
+        assert_eq!(JsonWebSignatureAlg::EdDsa.curve(), None);
+
+        assert_eq!(JsonWebSignatureAlg::None.key_type(), None);
+        assert_eq!(
+            JsonWebSignatureAlg::Unknown("XYZ".to_owned()).key_type(),
+            None
+        );
+    }
+}
@@ -25,6 +25,7 @@
 #![allow(clippy::module_name_repetitions)]
 
 pub mod jose;
+mod jose_ext;
 pub mod oauth;
 
 pub use parse_display::ParseError;
@@ -61,6 +61,19 @@ pub enum OAuthAccessTokenType {
     Unknown(String),
 }
 
+impl OAuthAccessTokenType {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[OAuthAccessTokenType] = &[
+            OAuthAccessTokenType::Bearer,
+            OAuthAccessTokenType::Na,
+            OAuthAccessTokenType::PoP,
+        ];
+        ALL
+    }
+}
+
 /// OAuth Authorization Endpoint Response Type
 ///
 /// Source: <https://www.iana.org/assignments/oauth-parameters/endpoint.csv>
@@ -120,6 +133,24 @@ pub enum OAuthAuthorizationEndpointResponseType {
     Token,
 }
 
+impl OAuthAuthorizationEndpointResponseType {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[OAuthAuthorizationEndpointResponseType] = &[
+            OAuthAuthorizationEndpointResponseType::Code,
+            OAuthAuthorizationEndpointResponseType::CodeIdToken,
+            OAuthAuthorizationEndpointResponseType::CodeIdTokenToken,
+            OAuthAuthorizationEndpointResponseType::CodeToken,
+            OAuthAuthorizationEndpointResponseType::IdToken,
+            OAuthAuthorizationEndpointResponseType::IdTokenToken,
+            OAuthAuthorizationEndpointResponseType::None,
+            OAuthAuthorizationEndpointResponseType::Token,
+        ];
+        ALL
+    }
+}
+
 /// OAuth Token Type Hint
 ///
 /// Source: <https://www.iana.org/assignments/oauth-parameters/token-type-hint.csv>
@@ -160,6 +191,19 @@ pub enum OAuthTokenTypeHint {
     Unknown(String),
 }
 
+impl OAuthTokenTypeHint {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[OAuthTokenTypeHint] = &[
+            OAuthTokenTypeHint::AccessToken,
+            OAuthTokenTypeHint::RefreshToken,
+            OAuthTokenTypeHint::Pct,
+        ];
+        ALL
+    }
+}
+
 /// OAuth Token Endpoint Authentication Method
 ///
 /// Source: <https://www.iana.org/assignments/oauth-parameters/token-endpoint-auth-method.csv>
@@ -220,6 +264,23 @@ pub enum OAuthClientAuthenticationMethod {
     Unknown(String),
 }
 
+impl OAuthClientAuthenticationMethod {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[OAuthClientAuthenticationMethod] = &[
+            OAuthClientAuthenticationMethod::None,
+            OAuthClientAuthenticationMethod::ClientSecretPost,
+            OAuthClientAuthenticationMethod::ClientSecretBasic,
+            OAuthClientAuthenticationMethod::ClientSecretJwt,
+            OAuthClientAuthenticationMethod::PrivateKeyJwt,
+            OAuthClientAuthenticationMethod::TlsClientAuth,
+            OAuthClientAuthenticationMethod::SelfSignedTlsClientAuth,
+        ];
+        ALL
+    }
+}
+
 /// PKCE Code Challenge Method
 ///
 /// Source: <https://www.iana.org/assignments/oauth-parameters/pkce-code-challenge-method.csv>
@@ -254,3 +315,15 @@ pub enum PkceCodeChallengeMethod {
     #[schemars(skip)]
     Unknown(String),
 }
+
+impl PkceCodeChallengeMethod {
+    /// All the known values of this enum
+    #[must_use]
+    pub fn all() -> &'static [Self] {
+        const ALL: &[PkceCodeChallengeMethod] = &[
+            PkceCodeChallengeMethod::Plain,
+            PkceCodeChallengeMethod::S256,
+        ];
+        ALL
+    }
+}
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use digest::Digest;
-use mas_iana::jose::{JsonWebKeyEcEllipticCurve, JsonWebSignatureAlg};
+use mas_iana::jose::{JsonWebKeyEcEllipticCurve, JsonWebKeyOkpEllipticCurve, JsonWebSignatureAlg};
 use sha2::{Sha256, Sha384, Sha512};
 use thiserror::Error;
 
@@ -34,9 +34,18 @@ pub enum AsymmetricKeyFromJwkError {
         inner: elliptic_curve::Error,
     },
 
+    #[error("Invalid Ed25519 parameters")]
+    Ed25519 {
+        #[from]
+        inner: ed25519_dalek::SignatureError,
+    },
+
     #[error("Unsupported algorithm {alg}")]
     UnsupportedAlgorithm { alg: JsonWebSignatureAlg },
 
+    #[error("Unsupported OKP curve {crv}")]
+    UnsupportedOkpCurve { crv: JsonWebKeyOkpEllipticCurve },
+
     #[error("Key not suitable for algorithm {alg}")]
     KeyNotSuitable { alg: JsonWebSignatureAlg },
 }
@@ -52,7 +61,9 @@ pub enum AsymmetricSigningKey {
     Ps512(super::Ps512SigningKey),
     Es256(super::Es256SigningKey),
     Es384(super::Es384SigningKey),
+    Es512(super::Es512SigningKey),
     Es256K(super::Es256KSigningKey),
+    Ed25519(super::Ed25519SigningKey),
 }
 
 impl AsymmetricSigningKey {
@@ -105,11 +116,21 @@ impl AsymmetricSigningKey {
         Self::Es384(ecdsa::SigningKey::from(key))
     }
 
+    #[must_use]
+    pub fn es512(key: super::Es512SigningKey) -> Self {
+        Self::Es512(key)
+    }
+
     #[must_use]
     pub fn es256k(key: elliptic_curve::SecretKey<k256::Secp256k1>) -> Self {
         Self::Es256K(ecdsa::SigningKey::from(key))
     }
 
+    #[must_use]
+    pub fn ed25519(key: ed25519_dalek::SigningKey) -> Self {
+        Self::Ed25519(key)
+    }
+
     pub fn from_jwk_and_alg(
         params: &JsonWebKeyPrivateParameters,
         alg: &JsonWebSignatureAlg,
@@ -140,7 +161,7 @@ impl AsymmetricSigningKey {
             (JsonWebKeyPrivateParameters::Ec(params), JsonWebSignatureAlg::Es512)
                 if params.crv == JsonWebKeyEcEllipticCurve::P521 =>
             {
-                Err(AsymmetricKeyFromJwkError::UnsupportedAlgorithm { alg: alg.clone() })
+                Ok(Self::es512(params.try_into()?))
             }
 
             (JsonWebKeyPrivateParameters::Ec(params), JsonWebSignatureAlg::Es256K)
@@ -149,8 +170,16 @@ impl AsymmetricSigningKey {
                 Ok(Self::es256k(params.try_into()?))
             }
 
-            (JsonWebKeyPrivateParameters::Okp(_params), JsonWebSignatureAlg::EdDsa) => {
-                Err(AsymmetricKeyFromJwkError::UnsupportedAlgorithm { alg: alg.clone() })
+            (JsonWebKeyPrivateParameters::Okp(params), JsonWebSignatureAlg::EdDsa)
+                if params.crv == JsonWebKeyOkpEllipticCurve::Ed25519 =>
+            {
+                Ok(Self::ed25519(params.try_into()?))
+            }
+
+            (JsonWebKeyPrivateParameters::Okp(params), JsonWebSignatureAlg::EdDsa) => {
+                Err(AsymmetricKeyFromJwkError::UnsupportedOkpCurve {
+                    crv: params.crv.clone(),
+                })
             }
 
             _ => Err(AsymmetricKeyFromJwkError::KeyNotSuitable { alg: alg.clone() }),
@@ -206,16 +235,28 @@ impl From<super::Es384SigningKey> for AsymmetricSigningKey {
     }
 }
 
+impl From<super::Es512SigningKey> for AsymmetricSigningKey {
+    fn from(key: super::Es512SigningKey) -> Self {
+        Self::Es512(key)
+    }
+}
+
 impl From<super::Es256KSigningKey> for AsymmetricSigningKey {
     fn from(key: super::Es256KSigningKey) -> Self {
         Self::Es256K(key)
     }
 }
 
+impl From<super::Ed25519SigningKey> for AsymmetricSigningKey {
+    fn from(key: super::Ed25519SigningKey) -> Self {
+        Self::Ed25519(key)
+    }
+}
+
 impl signature::RandomizedSigner<Signature> for AsymmetricSigningKey {
     fn try_sign_with_rng(
         &self,
-        rng: impl rand::CryptoRng + rand::RngCore,
+        mut rng: impl rand::CryptoRng + rand::RngCore,
         msg: &[u8],
     ) -> Result<Signature, signature::Error> {
         match self {
@@ -251,10 +292,22 @@ impl signature::RandomizedSigner<Signature> for AsymmetricSigningKey {
                 let signature = key.try_sign_with_rng(rng, msg)?;
                 Ok(Signature::from_signature(&signature))
             }
+            Self::Es512(key) => {
+                // `p521` implements the same newer `signature` traits as `ed25519-dalek`
+                let signature =
+                    p521::ecdsa::signature::RandomizedSigner::try_sign_with_rng(key, &mut rng, msg)
+                        .map_err(|_| signature::Error::new())?;
+                Ok(Signature::new(signature.to_bytes().to_vec()))
+            }
             Self::Es256K(key) => {
                 let signature = key.try_sign_with_rng(rng, msg)?;
                 Ok(Signature::from_signature(&signature))
             }
+            Self::Ed25519(key) => {
+                // Ed25519 signatures are deterministic, they don't need the RNG
+                let signature = ed25519_dalek::Signer::sign(key, msg);
+                Ok(Signature::new(signature.to_bytes().to_vec()))
+            }
         }
     }
 }
@@ -270,7 +323,9 @@ pub enum AsymmetricVerifyingKey {
     Ps512(super::Ps512VerifyingKey),
     Es256(super::Es256VerifyingKey),
     Es384(super::Es384VerifyingKey),
+    Es512(super::Es512VerifyingKey),
     Es256K(super::Es256KVerifyingKey),
+    Ed25519(super::Ed25519VerifyingKey),
 }
 
 impl AsymmetricVerifyingKey {
@@ -314,11 +369,21 @@ impl AsymmetricVerifyingKey {
         Self::Es384(ecdsa::VerifyingKey::from(key))
     }
 
+    #[must_use]
+    pub fn es512(key: super::Es512VerifyingKey) -> Self {
+        Self::Es512(key)
+    }
+
     #[must_use]
     pub fn es256k(key: elliptic_curve::PublicKey<k256::Secp256k1>) -> Self {
         Self::Es256K(ecdsa::VerifyingKey::from(key))
     }
 
+    #[must_use]
+    pub fn ed25519(key: ed25519_dalek::VerifyingKey) -> Self {
+        Self::Ed25519(key)
+    }
+
     pub fn from_jwk_and_alg(
         params: &JsonWebKeyPublicParameters,
         alg: &JsonWebSignatureAlg,
@@ -349,7 +414,7 @@ impl AsymmetricVerifyingKey {
             (JsonWebKeyPublicParameters::Ec(params), JsonWebSignatureAlg::Es512)
                 if params.crv == JsonWebKeyEcEllipticCurve::P521 =>
             {
-                Err(AsymmetricKeyFromJwkError::UnsupportedAlgorithm { alg: alg.clone() })
+                Ok(Self::es512(params.try_into()?))
             }
 
             (JsonWebKeyPublicParameters::Ec(params), JsonWebSignatureAlg::Es256K)
@@ -358,8 +423,16 @@ impl AsymmetricVerifyingKey {
                 Ok(Self::es256k(params.try_into()?))
             }
 
-            (JsonWebKeyPublicParameters::Okp(_params), JsonWebSignatureAlg::EdDsa) => {
-                Err(AsymmetricKeyFromJwkError::UnsupportedAlgorithm { alg: alg.clone() })
+            (JsonWebKeyPublicParameters::Okp(params), JsonWebSignatureAlg::EdDsa)
+                if params.crv == JsonWebKeyOkpEllipticCurve::Ed25519 =>
+            {
+                Ok(Self::ed25519(params.try_into()?))
+            }
+
+            (JsonWebKeyPublicParameters::Okp(params), JsonWebSignatureAlg::EdDsa) => {
+                Err(AsymmetricKeyFromJwkError::UnsupportedOkpCurve {
+                    crv: params.crv.clone(),
+                })
             }
 
             _ => Err(AsymmetricKeyFromJwkError::KeyNotSuitable { alg: alg.clone() }),
@@ -415,12 +488,24 @@ impl From<super::Es384VerifyingKey> for AsymmetricVerifyingKey {
     }
 }
 
+impl From<super::Es512VerifyingKey> for AsymmetricVerifyingKey {
+    fn from(key: super::Es512VerifyingKey) -> Self {
+        Self::Es512(key)
+    }
+}
+
 impl From<super::Es256KVerifyingKey> for AsymmetricVerifyingKey {
     fn from(key: super::Es256KVerifyingKey) -> Self {
         Self::Es256K(key)
     }
 }
 
+impl From<super::Ed25519VerifyingKey> for AsymmetricVerifyingKey {
+    fn from(key: super::Ed25519VerifyingKey) -> Self {
+        Self::Ed25519(key)
+    }
+}
+
 impl signature::Verifier<Signature> for AsymmetricVerifyingKey {
     fn verify(&self, msg: &[u8], signature: &Signature) -> Result<(), ecdsa::Error> {
         match self {
@@ -456,10 +541,24 @@ impl signature::Verifier<Signature> for AsymmetricVerifyingKey {
                 let signature = signature.to_signature()?;
                 key.verify(msg, &signature)
             }
+            Self::Es512(key) => {
+                let signature = p521::ecdsa::Signature::from_slice(signature.as_ref())
+                    .map_err(|_| ecdsa::Error::new())?;
+                p521::ecdsa::signature::Verifier::verify(key, msg, &signature)
+                    .map_err(|_| ecdsa::Error::new())
+            }
             Self::Es256K(key) => {
                 let signature = signature.to_signature()?;
                 key.verify(msg, &signature)
             }
+            Self::Ed25519(key) => {
+                // `ed25519-dalek` implements a newer version of the `signature` traits,
+                // so its errors are mapped to the opaque error we return
+                let signature = ed25519_dalek::Signature::from_slice(signature.as_ref())
+                    .map_err(|_| ecdsa::Error::new())?;
+                ed25519_dalek::Verifier::verify(key, msg, &signature)
+                    .map_err(|_| ecdsa::Error::new())
+            }
         }
     }
 }
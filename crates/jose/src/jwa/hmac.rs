@@ -109,10 +109,11 @@ impl<D: Digest + BlockSizeUser>
         msg: &[u8],
         signature: &Signature<<hmac::SimpleHmac<D> as OutputSizeUser>::OutputSize>,
     ) -> Result<(), signature::Error> {
-        let new_signature = self.try_sign(msg)?;
-        if &new_signature != signature {
-            return Err(signature::Error::new());
-        }
-        Ok(())
+        let mut mac = <hmac::SimpleHmac<D> as Mac>::new_from_slice(&self.key)
+            .map_err(signature::Error::from_source)?;
+        mac.update(msg);
+        // This does a constant-time comparison of the signatures
+        mac.verify_slice(signature.as_ref())
+            .map_err(signature::Error::from_source)
     }
 }
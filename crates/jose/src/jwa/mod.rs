@@ -50,8 +50,16 @@ pub type Es384VerifyingKey = ecdsa::VerifyingKey<p384::NistP384>;
 pub type Es256KSigningKey = ecdsa::SigningKey<k256::Secp256k1>;
 pub type Es256KVerifyingKey = ecdsa::VerifyingKey<k256::Secp256k1>;
 
+// `p521` is built on newer versions of the `ecdsa` and `elliptic-curve` crates,
+// and wraps their key types to sign with SHA-512
+pub type Es512SigningKey = p521::ecdsa::SigningKey;
+pub type Es512VerifyingKey = p521::ecdsa::VerifyingKey;
+
+pub type Ed25519SigningKey = ed25519_dalek::SigningKey;
+pub type Ed25519VerifyingKey = ed25519_dalek::VerifyingKey;
+
 /// All the signing algorithms supported by this crate.
-pub const SUPPORTED_SIGNING_ALGORITHMS: [JsonWebSignatureAlg; 12] = [
+pub const SUPPORTED_SIGNING_ALGORITHMS: [JsonWebSignatureAlg; 14] = [
     JsonWebSignatureAlg::Hs256,
     JsonWebSignatureAlg::Hs384,
     JsonWebSignatureAlg::Hs512,
@@ -63,5 +71,7 @@ pub const SUPPORTED_SIGNING_ALGORITHMS: [JsonWebSignatureAlg; 12] = [
     JsonWebSignatureAlg::Ps512,
     JsonWebSignatureAlg::Es256,
     JsonWebSignatureAlg::Es384,
+    JsonWebSignatureAlg::Es512,
     JsonWebSignatureAlg::Es256K,
+    JsonWebSignatureAlg::EdDsa,
 ];
@@ -26,13 +26,23 @@ pub enum SymmetricKey {
 }
 
 #[derive(Debug, Error)]
-#[error("Invalid algorithm {alg} used for symetric key")]
+#[error("Invalid algorithm {alg} used for symmetric key, supported algorithms are HS256, HS384 and HS512")]
 pub struct InvalidAlgorithm {
     pub alg: JsonWebSignatureAlg,
     pub key: Vec<u8>,
 }
 
 impl SymmetricKey {
+    /// The algorithms which can be used with a symmetric key
+    #[must_use]
+    pub const fn supported_algorithms() -> &'static [JsonWebSignatureAlg] {
+        &[
+            JsonWebSignatureAlg::Hs256,
+            JsonWebSignatureAlg::Hs384,
+            JsonWebSignatureAlg::Hs512,
+        ]
+    }
+
     pub fn new_for_alg(key: Vec<u8>, alg: &JsonWebSignatureAlg) -> Result<Self, InvalidAlgorithm> {
         match alg {
             JsonWebSignatureAlg::Hs256 => Ok(Self::hs256(key)),
@@ -14,6 +14,8 @@
 
 //! Ref: <https://www.rfc-editor.org/rfc/rfc7517.html>
 
+use base64ct::{Base64UrlUnpadded, Encoding};
+use digest::Digest;
 use mas_iana::jose::{
     JsonWebKeyEcEllipticCurve, JsonWebKeyOperation, JsonWebKeyType, JsonWebKeyUse,
     JsonWebSignatureAlg,
@@ -42,6 +44,15 @@ pub trait ParametersInfo {
     fn possible_algs(&self) -> &[JsonWebSignatureAlg];
 }
 
+/// Key parameters from which a JWK Thumbprint can be computed
+///
+/// Ref: <https://www.rfc-editor.org/rfc/rfc7638.html>
+pub trait ThumbprintParameters {
+    /// The required members of the key, serialized as a JSON object without
+    /// whitespace and with the members in lexicographic order
+    fn thumbprint_members(&self) -> String;
+}
+
 /// An utilitary trait to figure out the [`JsonWebKeyEcEllipticCurve`] value for
 /// elliptic curves
 trait JwkEcCurve {
@@ -227,6 +238,21 @@ impl<P> JsonWebKey<P> {
     pub const fn params(&self) -> &P {
         &self.parameters
     }
+
+    /// Compute the JWK Thumbprint of this key with the given hash function, as
+    /// defined by RFC 7638.
+    ///
+    /// Only the required members of the key are hashed, so the `kid`, `use`
+    /// and `alg` members don't affect the result.
+    #[must_use]
+    pub fn thumbprint<D: Digest>(&self, hasher: D) -> String
+    where
+        P: ThumbprintParameters,
+    {
+        let members = self.parameters.thumbprint_members();
+        let hash = hasher.chain_update(members.as_bytes()).finalize();
+        Base64UrlUnpadded::encode_string(&hash)
+    }
 }
 
 impl<P> Constrainable for JsonWebKey<P>
@@ -535,7 +561,52 @@ mod tests {
             keys.next().unwrap().params().ec().unwrap(),
         )
         .unwrap();
-        // 8th is P-521, but we don't support it yet
-        keys.next().unwrap().params().ec().unwrap();
+        // 9th is P-521
+        p521::ecdsa::VerifyingKey::try_from(keys.next().unwrap().params().ec().unwrap()).unwrap();
+        assert!(keys.next().is_none());
+    }
+
+    #[test]
+    fn rsa_thumbprint() {
+        // Example from RFC 7638 section 3.1
+        let jwk = serde_json::json!({
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+            "e": "AQAB",
+            "alg": "RS256",
+            "kid": "2011-04-29"
+        });
+
+        let jwk: PublicJsonWebKey = serde_json::from_value(jwk).unwrap();
+        assert_eq!(
+            jwk.thumbprint(sha2::Sha256::new()),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+
+        // The optional members don't change the thumbprint
+        let jwk = jwk
+            .with_kid("something-else")
+            .with_use(JsonWebKeyUse::Enc)
+            .with_alg(JsonWebSignatureAlg::Ps256);
+        assert_eq!(
+            jwk.thumbprint(sha2::Sha256::new()),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
+
+    #[test]
+    fn okp_thumbprint() {
+        // Example from RFC 8037 appendix A.3
+        let jwk = serde_json::json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"
+        });
+
+        let jwk: PublicJsonWebKey = serde_json::from_value(jwk).unwrap();
+        assert_eq!(
+            jwk.thumbprint(sha2::Sha256::new()),
+            "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k"
+        );
     }
 }
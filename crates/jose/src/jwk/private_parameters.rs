@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use base64ct::{Base64UrlUnpadded, Encoding};
 use mas_iana::jose::{
     JsonWebKeyEcEllipticCurve, JsonWebKeyOkpEllipticCurve, JsonWebKeyType, JsonWebSignatureAlg,
 };
@@ -24,7 +25,7 @@ use serde_with::{
 };
 use thiserror::Error;
 
-use super::{public_parameters::JsonWebKeyPublicParameters, ParametersInfo};
+use super::{public_parameters::JsonWebKeyPublicParameters, ParametersInfo, ThumbprintParameters};
 
 #[serde_as]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
@@ -97,6 +98,29 @@ impl ParametersInfo for JsonWebKeyPrivateParameters {
     }
 }
 
+impl ThumbprintParameters for JsonWebKeyPrivateParameters {
+    fn thumbprint_members(&self) -> String {
+        match self {
+            JsonWebKeyPrivateParameters::Oct(p) => serde_json::json!({
+                "k": Base64UrlUnpadded::encode_string(&p.k),
+                "kty": "oct",
+            })
+            .to_string(),
+
+            // The thumbprint of asymmetric keys only includes the public members
+            JsonWebKeyPrivateParameters::Rsa(p) => {
+                JsonWebKeyPublicParameters::Rsa(p.clone().into()).thumbprint_members()
+            }
+            JsonWebKeyPrivateParameters::Ec(p) => {
+                JsonWebKeyPublicParameters::Ec(p.clone().into()).thumbprint_members()
+            }
+            JsonWebKeyPrivateParameters::Okp(p) => {
+                JsonWebKeyPublicParameters::Okp(p.clone().into()).thumbprint_members()
+            }
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 #[error("can't extract a public key out of a symetric key")]
 pub struct SymetricKeyError;
@@ -366,14 +390,65 @@ mod ec_impls {
     }
 }
 
+mod p521_impls {
+    use mas_iana::jose::JsonWebKeyEcEllipticCurve;
+    use p521::{
+        ecdsa::{SigningKey, VerifyingKey},
+        elliptic_curve::sec1::Coordinates,
+    };
+
+    use super::EcPrivateParameters;
+
+    impl TryFrom<EcPrivateParameters> for SigningKey {
+        type Error = elliptic_curve::Error;
+        fn try_from(value: EcPrivateParameters) -> Result<Self, Self::Error> {
+            Self::try_from(&value)
+        }
+    }
+
+    impl TryFrom<&EcPrivateParameters> for SigningKey {
+        type Error = elliptic_curve::Error;
+
+        fn try_from(value: &EcPrivateParameters) -> Result<Self, Self::Error> {
+            SigningKey::from_slice(&value.d).map_err(|_| elliptic_curve::Error)
+        }
+    }
+
+    impl From<SigningKey> for EcPrivateParameters {
+        fn from(key: SigningKey) -> Self {
+            (&key).into()
+        }
+    }
+
+    impl From<&SigningKey> for EcPrivateParameters {
+        fn from(key: &SigningKey) -> Self {
+            let point = VerifyingKey::from(key).to_encoded_point(false);
+            let (x, y) = match point.coordinates() {
+                Coordinates::Uncompressed { x, y } => (x, y),
+                _ => unreachable!(),
+            };
+            EcPrivateParameters {
+                crv: JsonWebKeyEcEllipticCurve::P521,
+                x: x.to_vec(),
+                y: y.to_vec(),
+                d: key.to_bytes().to_vec(),
+            }
+        }
+    }
+}
+
 #[serde_as]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct OkpPrivateParameters {
-    crv: JsonWebKeyOkpEllipticCurve,
+    pub(crate) crv: JsonWebKeyOkpEllipticCurve,
 
     #[schemars(with = "String")]
     #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
     x: Vec<u8>,
+
+    #[schemars(with = "String")]
+    #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
+    d: Vec<u8>,
 }
 
 impl ParametersInfo for OkpPrivateParameters {
@@ -391,3 +466,41 @@ impl From<OkpPrivateParameters> for super::public_parameters::OkpPublicParameter
         Self::new(params.crv, params.x)
     }
 }
+
+mod okp_impls {
+    use ed25519_dalek::{SignatureError, SigningKey};
+    use mas_iana::jose::JsonWebKeyOkpEllipticCurve;
+
+    use super::OkpPrivateParameters;
+
+    impl TryFrom<OkpPrivateParameters> for SigningKey {
+        type Error = SignatureError;
+        fn try_from(value: OkpPrivateParameters) -> Result<Self, Self::Error> {
+            Self::try_from(&value)
+        }
+    }
+
+    impl TryFrom<&OkpPrivateParameters> for SigningKey {
+        type Error = SignatureError;
+
+        fn try_from(value: &OkpPrivateParameters) -> Result<Self, Self::Error> {
+            SigningKey::try_from(&value.d[..])
+        }
+    }
+
+    impl From<SigningKey> for OkpPrivateParameters {
+        fn from(key: SigningKey) -> Self {
+            (&key).into()
+        }
+    }
+
+    impl From<&SigningKey> for OkpPrivateParameters {
+        fn from(key: &SigningKey) -> Self {
+            OkpPrivateParameters {
+                crv: JsonWebKeyOkpEllipticCurve::Ed25519,
+                x: key.verifying_key().to_bytes().to_vec(),
+                d: key.to_bytes().to_vec(),
+            }
+        }
+    }
+}
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use base64ct::{Base64UrlUnpadded, Encoding};
 use mas_iana::jose::{
     JsonWebKeyEcEllipticCurve, JsonWebKeyOkpEllipticCurve, JsonWebKeyType, JsonWebSignatureAlg,
 };
@@ -23,7 +24,7 @@ use serde_with::{
     serde_as,
 };
 
-use super::ParametersInfo;
+use super::{ParametersInfo, ThumbprintParameters};
 
 #[serde_as]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
@@ -83,6 +84,32 @@ impl ParametersInfo for JsonWebKeyPublicParameters {
     }
 }
 
+impl ThumbprintParameters for JsonWebKeyPublicParameters {
+    fn thumbprint_members(&self) -> String {
+        let members = match self {
+            Self::Rsa(p) => serde_json::json!({
+                "e": Base64UrlUnpadded::encode_string(&p.e),
+                "kty": "RSA",
+                "n": Base64UrlUnpadded::encode_string(&p.n),
+            }),
+            Self::Ec(p) => serde_json::json!({
+                "crv": p.crv,
+                "kty": "EC",
+                "x": Base64UrlUnpadded::encode_string(&p.x),
+                "y": Base64UrlUnpadded::encode_string(&p.y),
+            }),
+            Self::Okp(p) => serde_json::json!({
+                "crv": p.crv,
+                "kty": "OKP",
+                "x": Base64UrlUnpadded::encode_string(&p.x),
+            }),
+        };
+
+        // serde_json sorts the keys of objects, which is what RFC 7638 requires
+        members.to_string()
+    }
+}
+
 #[serde_as]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct RsaPublicParameters {
@@ -157,7 +184,7 @@ impl ParametersInfo for EcPublicParameters {
 #[serde_as]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct OkpPublicParameters {
-    crv: JsonWebKeyOkpEllipticCurve,
+    pub(crate) crv: JsonWebKeyOkpEllipticCurve,
 
     #[schemars(with = "String")]
     #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
@@ -318,3 +345,115 @@ mod ec_impls {
         }
     }
 }
+
+mod p521_impls {
+    use digest::typenum::Unsigned;
+    use mas_iana::jose::JsonWebKeyEcEllipticCurve;
+    use p521::{
+        ecdsa::VerifyingKey,
+        elliptic_curve::{sec1::Coordinates, FieldBytesSize},
+        EncodedPoint, FieldBytes, NistP521,
+    };
+
+    use super::{EcPublicParameters, JsonWebKeyPublicParameters};
+
+    /// Left-pad a coordinate to the size of the field
+    ///
+    /// Some implementations, like Keycloak, strip the leading zero bytes of
+    /// the 66-byte P-521 coordinates.
+    fn pad_coordinate(coordinate: &[u8]) -> Result<FieldBytes, elliptic_curve::Error> {
+        let size = FieldBytesSize::<NistP521>::USIZE;
+        let offset = size
+            .checked_sub(coordinate.len())
+            .ok_or(elliptic_curve::Error)?;
+
+        let mut bytes = FieldBytes::default();
+        bytes[offset..].copy_from_slice(coordinate);
+        Ok(bytes)
+    }
+
+    impl TryFrom<&EcPublicParameters> for VerifyingKey {
+        type Error = elliptic_curve::Error;
+        fn try_from(value: &EcPublicParameters) -> Result<Self, Self::Error> {
+            let x = pad_coordinate(&value.x)?;
+            let y = pad_coordinate(&value.y)?;
+            let pubkey = EncodedPoint::from_affine_coordinates(&x, &y, false);
+            VerifyingKey::from_encoded_point(&pubkey).map_err(|_| elliptic_curve::Error)
+        }
+    }
+
+    impl From<VerifyingKey> for JsonWebKeyPublicParameters {
+        fn from(key: VerifyingKey) -> Self {
+            (&key).into()
+        }
+    }
+
+    impl From<&VerifyingKey> for JsonWebKeyPublicParameters {
+        fn from(key: &VerifyingKey) -> Self {
+            Self::Ec(key.into())
+        }
+    }
+
+    impl From<VerifyingKey> for EcPublicParameters {
+        fn from(key: VerifyingKey) -> Self {
+            (&key).into()
+        }
+    }
+
+    impl From<&VerifyingKey> for EcPublicParameters {
+        fn from(key: &VerifyingKey) -> Self {
+            let point = key.to_encoded_point(false);
+            let (x, y) = match point.coordinates() {
+                Coordinates::Uncompressed { x, y } => (x, y),
+                _ => unreachable!(),
+            };
+            EcPublicParameters {
+                crv: JsonWebKeyEcEllipticCurve::P521,
+                x: x.to_vec(),
+                y: y.to_vec(),
+            }
+        }
+    }
+}
+
+mod okp_impls {
+    use ed25519_dalek::{SignatureError, VerifyingKey};
+    use mas_iana::jose::JsonWebKeyOkpEllipticCurve;
+
+    use super::{JsonWebKeyPublicParameters, OkpPublicParameters};
+
+    impl TryFrom<&OkpPublicParameters> for VerifyingKey {
+        type Error = SignatureError;
+
+        fn try_from(value: &OkpPublicParameters) -> Result<Self, Self::Error> {
+            VerifyingKey::try_from(&value.x[..])
+        }
+    }
+
+    impl From<VerifyingKey> for JsonWebKeyPublicParameters {
+        fn from(key: VerifyingKey) -> Self {
+            (&key).into()
+        }
+    }
+
+    impl From<&VerifyingKey> for JsonWebKeyPublicParameters {
+        fn from(key: &VerifyingKey) -> Self {
+            Self::Okp(key.into())
+        }
+    }
+
+    impl From<VerifyingKey> for OkpPublicParameters {
+        fn from(key: VerifyingKey) -> Self {
+            (&key).into()
+        }
+    }
+
+    impl From<&VerifyingKey> for OkpPublicParameters {
+        fn from(key: &VerifyingKey) -> Self {
+            Self {
+                crv: JsonWebKeyOkpEllipticCurve::Ed25519,
+                x: key.to_bytes().to_vec(),
+            }
+        }
+    }
+}
@@ -219,11 +219,27 @@ asymetric_jwt_test!(ps384, Ps384, PS384_JWT);
 asymetric_jwt_test!(ps512, Ps512, PS512_JWT);
 asymetric_jwt_test!(es256, Es256, ES256_JWT);
 asymetric_jwt_test!(es384, Es384, ES384_JWT);
-asymetric_jwt_test!(es512, Es512, ES512_JWT, supported = false);
+asymetric_jwt_test!(es512, Es512, ES512_JWT);
 asymetric_jwt_test!(es256k, Es256K, ES256K_JWT);
 asymetric_jwt_test!(eddsa_ed25519, EdDsa, EDDSA_ED25519_JWT, supported = false);
 asymetric_jwt_test!(eddsa_ed448, EdDsa, EDDSA_ED448_JWT, supported = false);
 
+#[test]
+fn test_symmetric_key_wrong_algorithm() {
+    use mas_iana::jose::JsonWebSignatureAlg;
+    use mas_jose::jwa::SymmetricKey;
+
+    for alg in SymmetricKey::supported_algorithms() {
+        assert!(SymmetricKey::new_for_alg(oct_key(), alg).is_ok());
+    }
+
+    let err = match SymmetricKey::new_for_alg(oct_key(), &JsonWebSignatureAlg::Rs256) {
+        Ok(_) => panic!("RS256 should not be usable with a symmetric key"),
+        Err(err) => err,
+    };
+    assert_eq!(err.alg, JsonWebSignatureAlg::Rs256);
+}
+
 #[test]
 fn test_private_to_public_jwks() {
     let priv_jwks = private_jwks();
@@ -231,3 +247,102 @@ fn test_private_to_public_jwks() {
 
     assert_eq!(pub_jwks, public_jwks());
 }
+
+#[test]
+fn test_thumbprints() {
+    use mas_jose::constraints::Constrainable;
+    use sha2::Digest;
+
+    // The key IDs of the test keys are their RFC 7638 thumbprint
+    for key in private_jwks().iter() {
+        assert_eq!(
+            Some(key.thumbprint(sha2::Sha256::new()).as_str()),
+            key.kid()
+        );
+    }
+
+    for key in public_jwks().iter() {
+        assert_eq!(
+            Some(key.thumbprint(sha2::Sha256::new()).as_str()),
+            key.kid()
+        );
+    }
+}
+
+/// The generic EdDSA tests above can't pick a key only from the algorithm, as
+/// the test key sets have both an Ed25519 and an Ed448 key. Only Ed25519 is
+/// supported, so those tests select the key by its ID.
+mod eddsa_ed25519_support {
+    use mas_iana::jose::{JsonWebKeyOkpEllipticCurve, JsonWebSignatureAlg};
+    use mas_jose::{
+        constraints::{Constraint, ConstraintSet},
+        jwa::AsymmetricKeyFromJwkError,
+        jwt::{JsonWebSignatureHeader, Jwt},
+    };
+
+    use super::*;
+
+    static ED25519_KID: &str = "iXkiyhEh6E7U-aX0fg7w-esHWqPvvxWd6gH1JG2u7N0";
+
+    #[test]
+    fn verify_jwt() {
+        let jwks = public_jwks();
+        let jwt: Jwt<'_, Payload> = Jwt::try_from(EDDSA_ED25519_JWT).unwrap();
+
+        let key = jwks.find_key(&jwt.header().into()).unwrap();
+
+        let key = mas_jose::jwa::AsymmetricVerifyingKey::from_jwk_and_alg(
+            key.params(),
+            &JsonWebSignatureAlg::EdDsa,
+        )
+        .unwrap();
+
+        jwt.verify(&key).unwrap();
+    }
+
+    #[test]
+    fn sign_and_verify_jwt() {
+        let alg = JsonWebSignatureAlg::EdDsa;
+        let payload = Payload {
+            hello: "world".to_string(),
+        };
+        let header = JsonWebSignatureHeader::new(alg.clone()).with_kid(ED25519_KID);
+
+        let jwks = private_jwks();
+        let key = jwks
+            .find_key(&ConstraintSet::new([
+                Constraint::alg(&alg),
+                Constraint::kid(ED25519_KID),
+            ]))
+            .unwrap();
+
+        let key =
+            mas_jose::jwa::AsymmetricSigningKey::from_jwk_and_alg(key.params(), &alg).unwrap();
+
+        let jwt: Jwt<'_, Payload> = Jwt::sign(header, payload, &key).unwrap();
+        let jwt: Jwt<'_, Payload> = Jwt::try_from(jwt.as_str()).unwrap();
+
+        let jwks = public_jwks();
+        let key = jwks.find_key(&jwt.header().into()).unwrap();
+
+        let key =
+            mas_jose::jwa::AsymmetricVerifyingKey::from_jwk_and_alg(key.params(), &alg).unwrap();
+
+        jwt.verify(&key).unwrap();
+    }
+
+    #[test]
+    fn ed448_is_unsupported() {
+        let alg = JsonWebSignatureAlg::EdDsa;
+        let jwks = public_jwks();
+        let jwt: Jwt<'_, Payload> = Jwt::try_from(EDDSA_ED448_JWT).unwrap();
+        let key = jwks.find_key(&jwt.header().into()).unwrap();
+
+        assert!(matches!(
+            mas_jose::jwa::AsymmetricVerifyingKey::from_jwk_and_alg(key.params(), &alg),
+            Err(AsymmetricKeyFromJwkError::UnsupportedOkpCurve {
+                crv: JsonWebKeyOkpEllipticCurve::Ed448
+            })
+        ));
+    }
+}
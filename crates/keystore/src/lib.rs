@@ -25,7 +25,7 @@
 
 use std::{ops::Deref, sync::Arc};
 
-use der::{zeroize::Zeroizing, Decode};
+use der::{asn1::OctetStringRef, zeroize::Zeroizing, Decode, Encode};
 use elliptic_curve::pkcs8::EncodePrivateKey;
 use mas_iana::jose::{JsonWebKeyType, JsonWebSignatureAlg};
 pub use mas_jose::jwk::{JsonWebKey, JsonWebKeySet};
@@ -62,6 +62,12 @@ pub enum LoadError {
         inner: rsa::errors::Error,
     },
 
+    #[error("Invalid Ed25519 private key")]
+    Ed25519 {
+        #[from]
+        inner: ed25519_dalek::SignatureError,
+    },
+
     #[error("Failed to decode PKCS1-encoded RSA key")]
     Pkcs1 {
         #[from]
@@ -143,12 +149,26 @@ pub enum PrivateKey {
     EcP256(Box<elliptic_curve::SecretKey<p256::NistP256>>),
     EcP384(Box<elliptic_curve::SecretKey<p384::NistP384>>),
     EcK256(Box<elliptic_curve::SecretKey<k256::Secp256k1>>),
+    Ed25519(Box<ed25519_dalek::SigningKey>),
 }
 
+/// The OID of Ed25519 keys, as defined in RFC8410
+const ED25519_OID: const_oid::ObjectIdentifier =
+    const_oid::ObjectIdentifier::new_unwrap("1.3.101.112");
+
 /// Error returned when the key can't be used for the requested algorithm
 #[derive(Debug, Error)]
-#[error("Wrong algorithm for key")]
-pub struct WrongAlgorithmError;
+#[error("Algorithm {alg} can't be used with this {kty} key")]
+pub struct WrongAlgorithmError {
+    /// The requested algorithm
+    pub alg: JsonWebSignatureAlg,
+
+    /// The type of the key
+    pub kty: JsonWebKeyType,
+
+    /// The algorithms which can be used with this key
+    pub supported: &'static [JsonWebSignatureAlg],
+}
 
 impl PrivateKey {
     fn from_pkcs1_private_key(pkcs1_key: &pkcs1::RsaPrivateKey) -> Result<Self, LoadError> {
@@ -178,10 +198,29 @@ impl PrivateKey {
                 k256::Secp256k1::OID => Ok(Self::EcK256(Box::new(info.try_into()?))),
                 oid => Err(LoadError::UnknownEllipticCurveOid { oid }),
             },
+            ED25519_OID => {
+                // The key is wrapped in an extra OCTET STRING, see RFC8410 section 7
+                let key = OctetStringRef::from_der(info.private_key)?;
+                let key = ed25519_dalek::SigningKey::try_from(key.as_bytes())?;
+                Ok(Self::Ed25519(Box::new(key)))
+            }
             oid => Err(LoadError::UnknownAlgorithmOid { oid }),
         }
     }
 
+    fn ed25519_to_pkcs8(
+        key: &ed25519_dalek::SigningKey,
+    ) -> Result<pkcs8::SecretDocument, anyhow::Error> {
+        let secret_key = Zeroizing::new(key.to_bytes());
+        let private_key = Zeroizing::new(OctetStringRef::new(&secret_key[..])?.to_vec()?);
+        let algorithm = pkcs8::AlgorithmIdentifier {
+            oid: ED25519_OID,
+            parameters: None,
+        };
+        let info = PrivateKeyInfo::new(algorithm, &private_key);
+        Ok(info.try_into()?)
+    }
+
     fn from_ec_private_key(key: sec1::EcPrivateKey) -> Result<Self, LoadError> {
         let curve = key
             .parameters
@@ -200,7 +239,7 @@ impl PrivateKey {
     /// Serialize the key as a DER document
     ///
     /// It will use the most common format depending on the key type: PKCS1 for
-    /// RSA keys and SEC1 for elliptic curve keys
+    /// RSA keys, SEC1 for elliptic curve keys and PKCS8 for Ed25519 keys
     ///
     /// # Errors
     ///
@@ -211,6 +250,7 @@ impl PrivateKey {
             PrivateKey::EcP256(key) => key.to_sec1_der()?,
             PrivateKey::EcP384(key) => key.to_sec1_der()?,
             PrivateKey::EcK256(key) => key.to_sec1_der()?,
+            PrivateKey::Ed25519(key) => Self::ed25519_to_pkcs8(key)?.to_bytes(),
         };
 
         Ok(der)
@@ -227,6 +267,7 @@ impl PrivateKey {
             PrivateKey::EcP256(key) => key.to_pkcs8_der()?,
             PrivateKey::EcP384(key) => key.to_pkcs8_der()?,
             PrivateKey::EcK256(key) => key.to_pkcs8_der()?,
+            PrivateKey::Ed25519(key) => Self::ed25519_to_pkcs8(key)?,
         };
 
         Ok(der.to_bytes())
@@ -235,7 +276,7 @@ impl PrivateKey {
     /// Serialize the key as a PEM document
     ///
     /// It will use the most common format depending on the key type: PKCS1 for
-    /// RSA keys and SEC1 for elliptic curve keys
+    /// RSA keys, SEC1 for elliptic curve keys and PKCS8 for Ed25519 keys
     ///
     /// # Errors
     ///
@@ -249,6 +290,9 @@ impl PrivateKey {
             PrivateKey::EcP256(key) => key.to_sec1_pem(line_ending)?,
             PrivateKey::EcP384(key) => key.to_sec1_pem(line_ending)?,
             PrivateKey::EcK256(key) => key.to_sec1_pem(line_ending)?,
+            PrivateKey::Ed25519(key) => {
+                Self::ed25519_to_pkcs8(key)?.to_pem(PrivateKeyInfo::PEM_LABEL, line_ending)?
+            }
         };
 
         Ok(pem)
@@ -426,6 +470,33 @@ impl PrivateKey {
         }
     }
 
+    /// The signature algorithms which can be used with this key
+    #[must_use]
+    pub fn supported_algorithms(&self) -> &'static [JsonWebSignatureAlg] {
+        match self {
+            PrivateKey::Rsa(_) => &[
+                JsonWebSignatureAlg::Rs256,
+                JsonWebSignatureAlg::Rs384,
+                JsonWebSignatureAlg::Rs512,
+                JsonWebSignatureAlg::Ps256,
+                JsonWebSignatureAlg::Ps384,
+                JsonWebSignatureAlg::Ps512,
+            ],
+            PrivateKey::EcP256(_) => &[JsonWebSignatureAlg::Es256],
+            PrivateKey::EcP384(_) => &[JsonWebSignatureAlg::Es384],
+            PrivateKey::EcK256(_) => &[JsonWebSignatureAlg::Es256K],
+            PrivateKey::Ed25519(_) => &[JsonWebSignatureAlg::EdDsa],
+        }
+    }
+
+    fn wrong_algorithm(&self, alg: &JsonWebSignatureAlg) -> WrongAlgorithmError {
+        WrongAlgorithmError {
+            alg: alg.clone(),
+            kty: self.kty(),
+            supported: self.supported_algorithms(),
+        }
+    }
+
     /// Get an [`AsymmetricVerifyingKey`] out of this key, for the specified
     /// [`JsonWebSignatureAlg`]
     ///
@@ -446,7 +517,7 @@ impl PrivateKey {
                     JsonWebSignatureAlg::Ps256 => AsymmetricVerifyingKey::ps256(key),
                     JsonWebSignatureAlg::Ps384 => AsymmetricVerifyingKey::ps384(key),
                     JsonWebSignatureAlg::Ps512 => AsymmetricVerifyingKey::ps512(key),
-                    _ => return Err(WrongAlgorithmError),
+                    _ => return Err(self.wrong_algorithm(alg)),
                 }
             }
 
@@ -462,7 +533,11 @@ impl PrivateKey {
                 AsymmetricVerifyingKey::es256k(key.public_key())
             }
 
-            _ => return Err(WrongAlgorithmError),
+            (Self::Ed25519(key), JsonWebSignatureAlg::EdDsa) => {
+                AsymmetricVerifyingKey::ed25519(key.verifying_key())
+            }
+
+            _ => return Err(self.wrong_algorithm(alg)),
         };
 
         Ok(key)
@@ -488,7 +563,7 @@ impl PrivateKey {
                     JsonWebSignatureAlg::Ps256 => AsymmetricSigningKey::ps256(key),
                     JsonWebSignatureAlg::Ps384 => AsymmetricSigningKey::ps384(key),
                     JsonWebSignatureAlg::Ps512 => AsymmetricSigningKey::ps512(key),
-                    _ => return Err(WrongAlgorithmError),
+                    _ => return Err(self.wrong_algorithm(alg)),
                 }
             }
 
@@ -504,7 +579,11 @@ impl PrivateKey {
                 AsymmetricSigningKey::es256k(*key.clone())
             }
 
-            _ => return Err(WrongAlgorithmError),
+            (Self::Ed25519(key), JsonWebSignatureAlg::EdDsa) => {
+                AsymmetricSigningKey::ed25519(*key.clone())
+            }
+
+            _ => return Err(self.wrong_algorithm(alg)),
         };
 
         Ok(key)
@@ -537,6 +616,12 @@ impl PrivateKey {
         let key = elliptic_curve::SecretKey::random(rng);
         Self::EcK256(Box::new(key))
     }
+
+    /// Generate an Ed25519 key
+    pub fn generate_ed25519<R: RngCore + CryptoRng>(mut rng: R) -> Self {
+        let key = ed25519_dalek::SigningKey::generate(&mut rng);
+        Self::Ed25519(Box::new(key))
+    }
 }
 
 impl From<&PrivateKey> for JsonWebKeyPublicParameters {
@@ -546,6 +631,7 @@ impl From<&PrivateKey> for JsonWebKeyPublicParameters {
             PrivateKey::EcP256(key) => key.public_key().into(),
             PrivateKey::EcP384(key) => key.public_key().into(),
             PrivateKey::EcK256(key) => key.public_key().into(),
+            PrivateKey::Ed25519(key) => key.verifying_key().into(),
         }
     }
 }
@@ -557,23 +643,12 @@ impl ParametersInfo for PrivateKey {
             PrivateKey::EcP256(_) | PrivateKey::EcP384(_) | PrivateKey::EcK256(_) => {
                 JsonWebKeyType::Ec
             }
+            PrivateKey::Ed25519(_) => JsonWebKeyType::Okp,
         }
     }
 
-    fn possible_algs(&self) -> &'static [JsonWebSignatureAlg] {
-        match self {
-            PrivateKey::Rsa(_) => &[
-                JsonWebSignatureAlg::Rs256,
-                JsonWebSignatureAlg::Rs384,
-                JsonWebSignatureAlg::Rs512,
-                JsonWebSignatureAlg::Ps256,
-                JsonWebSignatureAlg::Ps384,
-                JsonWebSignatureAlg::Ps512,
-            ],
-            PrivateKey::EcP256(_) => &[JsonWebSignatureAlg::Es256],
-            PrivateKey::EcP384(_) => &[JsonWebSignatureAlg::Es384],
-            PrivateKey::EcK256(_) => &[JsonWebSignatureAlg::Es256K],
-        }
+    fn possible_algs(&self) -> &[JsonWebSignatureAlg] {
+        self.supported_algorithms()
     }
 }
 
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use der::pem::LineEnding;
-use mas_iana::jose::JsonWebSignatureAlg;
+use mas_iana::jose::{JsonWebKeyType, JsonWebSignatureAlg};
 use mas_jose::{
     jwk::ParametersInfo,
     jwt::{JsonWebSignatureHeader, Jwt},
@@ -127,6 +127,8 @@ plain_test!(plain_ec_k256_sec1_pem, EcK256, "ec-k256.sec1.pem");
 plain_test!(plain_ec_k256_sec1_der, EcK256, "ec-k256.sec1.der");
 plain_test!(plain_ec_k256_pkcs8_pem, EcK256, "ec-k256.pkcs8.pem");
 plain_test!(plain_ec_k256_pkcs8_der, EcK256, "ec-k256.pkcs8.der");
+plain_test!(plain_ed25519_pkcs8_pem, Ed25519, "ed25519.pkcs8.pem");
+plain_test!(plain_ed25519_pkcs8_der, Ed25519, "ed25519.pkcs8.der");
 
 enc_test!(enc_rsa_pkcs8_pem, Rsa, "rsa.pkcs8.encrypted.pem");
 enc_test!(enc_rsa_pkcs8_der, Rsa, "rsa.pkcs8.encrypted.der");
@@ -136,6 +138,16 @@ enc_test!(enc_ec_p384_pkcs8_pem, EcP384, "ec-p384.pkcs8.encrypted.pem");
 enc_test!(enc_ec_p384_pkcs8_der, EcP384, "ec-p384.pkcs8.encrypted.der");
 enc_test!(enc_ec_k256_pkcs8_pem, EcK256, "ec-k256.pkcs8.encrypted.pem");
 enc_test!(enc_ec_k256_pkcs8_der, EcK256, "ec-k256.pkcs8.encrypted.der");
+enc_test!(
+    enc_ed25519_pkcs8_pem,
+    Ed25519,
+    "ed25519.pkcs8.encrypted.pem"
+);
+enc_test!(
+    enc_ed25519_pkcs8_der,
+    Ed25519,
+    "ed25519.pkcs8.encrypted.der"
+);
 
 // Test PEM/DER serialization. For some reason, DER serialization of EC keys
 // have some extra bytes
@@ -147,6 +159,8 @@ pem_test!(serialize_ec_p384_sec1_pem, "ec-p384.sec1");
 der_test!(serialize_ec_p384_sec1_der, "ec-p384.sec1", compare = false);
 pem_test!(serialize_ec_k256_sec1_pem, "ec-k256.sec1");
 der_test!(serialize_ec_k256_sec1_der, "ec-k256.sec1", compare = false);
+pem_test!(serialize_ed25519_pkcs8_pem, "ed25519.pkcs8");
+der_test!(serialize_ed25519_pkcs8_der, "ed25519.pkcs8");
 
 #[test]
 fn load_encrypted_as_unencrypted_error() {
@@ -170,6 +184,28 @@ fn load_unencrypted_as_encrypted_error() {
         .is_unencrypted());
 }
 
+#[test]
+fn wrong_algorithm_error() {
+    let pem = include_str!("./keys/ec-p256.sec1.pem");
+    let key = PrivateKey::load_pem(pem).unwrap();
+    assert_eq!(key.supported_algorithms(), &[JsonWebSignatureAlg::Es256]);
+
+    // The signing key isn't `Debug`, so `unwrap_err` can't be used here
+    let Err(err) = key.signing_key_for_alg(&JsonWebSignatureAlg::Rs256) else {
+        panic!("an EC key can't be used for RS256");
+    };
+    assert_eq!(err.alg, JsonWebSignatureAlg::Rs256);
+    assert_eq!(err.supported, &[JsonWebSignatureAlg::Es256]);
+    assert_eq!(
+        err.to_string(),
+        "Algorithm RS256 can't be used with this EC key"
+    );
+
+    assert!(key
+        .verifying_key_for_alg(&JsonWebSignatureAlg::Es384)
+        .is_err());
+}
+
 #[test]
 fn generate_sign_and_verify() {
     // Use a seeded RNG to keep the snapshot stable
@@ -222,3 +258,64 @@ fn generate_sign_and_verify() {
         token.verify_with_jwks(&jwks).unwrap();
     }
 }
+
+#[test]
+fn generate_ed25519_sign_and_verify() {
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+    let alg = JsonWebSignatureAlg::EdDsa;
+
+    let key = PrivateKey::generate_ed25519(&mut rng);
+    assert_eq!(key.supported_algorithms(), &[JsonWebSignatureAlg::EdDsa]);
+
+    // The key survives a round trip through PEM
+    let pem = key.to_pem(LineEnding::LF).unwrap();
+    let key = PrivateKey::load_pem(&pem).unwrap();
+    assert!(matches!(key, PrivateKey::Ed25519(_)), "wrong key type");
+
+    let keyset = Keystore::new(JsonWebKeySet::new(vec![JsonWebKey::new(key)]));
+    assert_eq!(keyset.available_signing_algorithms(), vec![alg.clone()]);
+
+    // The public key is published as an OKP key
+    let jwks = keyset.public_jwks();
+    let public = jwks.iter().next().unwrap();
+    assert_eq!(public.params().kty(), JsonWebKeyType::Okp);
+    assert!(public.params().okp().is_some());
+
+    let key = keyset.signing_key_for_algorithm(&alg).unwrap();
+    let signer = key.params().signing_key_for_alg(&alg).unwrap();
+    let header = JsonWebSignatureHeader::new(alg);
+    let token = Jwt::sign_with_rng(&mut rng, header, "", &signer).unwrap();
+    token.verify_with_jwks(&jwks).unwrap();
+}
+
+#[test]
+fn verify_during_key_rotation() {
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+    let alg = JsonWebSignatureAlg::Es256;
+
+    let old = PrivateKey::generate_ec_p256(&mut rng);
+    let new = PrivateKey::generate_ec_p256(&mut rng);
+    let signer = new.signing_key_for_alg(&alg).unwrap();
+
+    // Both the old and the new key are published while rotating
+    let jwks = Keystore::new(JsonWebKeySet::new(vec![
+        JsonWebKey::new(old).with_kid("old"),
+        JsonWebKey::new(new).with_kid("new"),
+    ]))
+    .public_jwks();
+
+    // The `kid` selects the right key
+    let header = JsonWebSignatureHeader::new(alg.clone()).with_kid("new");
+    let token = Jwt::sign_with_rng(&mut rng, header, "", &signer).unwrap();
+    token.verify_with_jwks(&jwks).unwrap();
+
+    // Without a `kid`, every key matching the algorithm is tried
+    let header = JsonWebSignatureHeader::new(alg.clone());
+    let token = Jwt::sign_with_rng(&mut rng, header, "", &signer).unwrap();
+    token.verify_with_jwks(&jwks).unwrap();
+
+    // A `kid` pointing to the wrong key doesn't fall back to the other keys
+    let header = JsonWebSignatureHeader::new(alg).with_kid("old");
+    let token = Jwt::sign_with_rng(&mut rng, header, "", &signer).unwrap();
+    assert!(token.verify_with_jwks(&jwks).is_err());
+}
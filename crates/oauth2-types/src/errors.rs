@@ -356,6 +356,22 @@ impl ClientErrorCode {
             ClientErrorCode::Unknown(_) => "",
         }
     }
+
+    /// Whether this error means that the authorization server can't proceed
+    /// without the end-user interacting with it.
+    ///
+    /// Those are the errors returned to requests with `prompt=none`, so the
+    /// client can retry the authorization request interactively.
+    #[must_use]
+    pub fn is_interaction_required(&self) -> bool {
+        matches!(
+            self,
+            ClientErrorCode::InteractionRequired
+                | ClientErrorCode::LoginRequired
+                | ClientErrorCode::AccountSelectionRequired
+                | ClientErrorCode::ConsentRequired
+        )
+    }
 }
 
 #[cfg(test)]
@@ -548,4 +564,16 @@ mod tests {
             ClientErrorCode::Unknown("unknown_error_code".to_owned())
         );
     }
+
+    #[test]
+    fn interaction_required_client_error_codes() {
+        assert!(ClientErrorCode::InteractionRequired.is_interaction_required());
+        assert!(ClientErrorCode::LoginRequired.is_interaction_required());
+        assert!(ClientErrorCode::AccountSelectionRequired.is_interaction_required());
+        assert!(ClientErrorCode::ConsentRequired.is_interaction_required());
+
+        assert!(!ClientErrorCode::AccessDenied.is_interaction_required());
+        assert!(!ClientErrorCode::ServerError.is_interaction_required());
+        assert!(!ClientErrorCode::Unknown("login_required".to_owned()).is_interaction_required());
+    }
 }
@@ -22,6 +22,7 @@ use data_encoding::BASE64URL_NOPAD;
 use mas_iana::oauth::PkceCodeChallengeMethod;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
 use thiserror::Error;
 
 /// Errors that can occur when verifying a code challenge.
@@ -88,7 +89,8 @@ pub trait CodeChallengeMethodExt {
     where
         Self: Sized,
     {
-        if self.compute_challenge(verifier)? == challenge {
+        let computed = self.compute_challenge(verifier)?;
+        if bool::from(computed.as_bytes().ct_eq(challenge.as_bytes())) {
             Ok(())
         } else {
             Err(CodeChallengeError::VerificationFailed)
@@ -116,6 +118,16 @@ impl CodeChallengeMethodExt for PkceCodeChallengeMethod {
     }
 }
 
+/// Check a `code_verifier` presented in a token request against the
+/// `code_challenge` and method stored from the authorization request.
+///
+/// This is a shorthand for [`CodeChallengeMethodExt::verify`] which doesn't
+/// tell why the verification failed.
+#[must_use]
+pub fn verify(method: &PkceCodeChallengeMethod, challenge: &str, verifier: &str) -> bool {
+    method.verify(challenge, verifier).is_ok()
+}
+
 /// The code challenge data added to an authorization request.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct AuthorizationRequest {
@@ -172,4 +184,27 @@ mod tests {
             Err(CodeChallengeError::InvalidCharacters),
         );
     }
+
+    #[test]
+    fn test_pkce_verify_helper() {
+        // This challenge comes from the RFC7636 appendices
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+        assert!(verify(&PkceCodeChallengeMethod::S256, challenge, verifier));
+        assert!(verify(&PkceCodeChallengeMethod::Plain, verifier, verifier));
+
+        // Mismatches
+        assert!(!verify(&PkceCodeChallengeMethod::S256, verifier, verifier));
+        assert!(!verify(
+            &PkceCodeChallengeMethod::Plain,
+            challenge,
+            verifier
+        ));
+        assert!(!verify(
+            &PkceCodeChallengeMethod::S256,
+            challenge,
+            "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
+        ));
+    }
 }
@@ -635,6 +635,17 @@ impl AccessTokenResponse {
         self.expires_in = Some(expires_in);
         self
     }
+
+    /// Get the time at which the access token expires, given the time at
+    /// which this response was obtained.
+    ///
+    /// Returns `None` if the response has no `expires_in`, or if the
+    /// expiration time can't be represented.
+    #[must_use]
+    pub fn expires_at(&self, obtained_at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.expires_in
+            .and_then(|expires_in| obtained_at.checked_add_signed(expires_in))
+    }
 }
 
 impl fmt::Debug for AccessTokenResponse {
@@ -779,6 +790,26 @@ mod tests {
         assert_serde_json(&req, expected);
     }
 
+    #[test]
+    fn access_token_response_expires_at() {
+        let obtained_at = DateTime::parse_from_rfc3339("2023-01-25T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let response = AccessTokenResponse::new("abcd".to_owned());
+        assert_eq!(response.expires_at(obtained_at), None);
+
+        let response = response.with_expires_in(Duration::minutes(5));
+        assert_eq!(
+            response.expires_at(obtained_at),
+            Some(obtained_at + Duration::minutes(5))
+        );
+
+        // A huge `expires_in` from the server must not panic
+        let response = response.with_expires_in(Duration::max_value());
+        assert_eq!(response.expires_at(obtained_at), None);
+    }
+
     #[test]
     fn serialize_grant_type() {
         assert_eq!(
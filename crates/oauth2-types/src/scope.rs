@@ -166,6 +166,22 @@ impl Scope {
     pub fn insert(&mut self, value: ScopeToken) -> bool {
         self.0.insert(value)
     }
+
+    /// The tokens of this `Scope` which are not part of the `already_granted`
+    /// one.
+    ///
+    /// This is useful to only ask the user to consent to the tokens they did
+    /// not consent to before.
+    #[must_use]
+    pub fn difference(&self, already_granted: &Scope) -> Scope {
+        self.0.difference(&already_granted.0).cloned().collect()
+    }
+
+    /// Whether all the tokens of this `Scope` are part of the `other` one.
+    #[must_use]
+    pub fn is_subset(&self, other: &Scope) -> bool {
+        self.0.is_subset(&other.0)
+    }
 }
 
 impl std::fmt::Display for Scope {
@@ -252,4 +268,33 @@ mod tests {
         assert!(Scope::from_str("http://example.com").is_ok());
         assert!(Scope::from_str("urn:matrix:org.matrix.msc2967.client:*").is_ok());
     }
+
+    #[test]
+    fn scope_difference() {
+        let granted = Scope::from_str("openid profile").unwrap();
+
+        // Identical scopes
+        let requested = Scope::from_str("profile openid").unwrap();
+        assert!(requested.difference(&granted).is_empty());
+        assert!(requested.is_subset(&granted));
+
+        // The requested scope is a superset of the granted one
+        let requested = Scope::from_str("openid profile email").unwrap();
+        assert_eq!(
+            requested.difference(&granted),
+            Scope::from_str("email").unwrap()
+        );
+        assert!(!requested.is_subset(&granted));
+        assert!(granted.is_subset(&requested));
+
+        // The requested scope is a subset of the granted one
+        let requested = Scope::from_str("openid").unwrap();
+        assert!(requested.difference(&granted).is_empty());
+        assert!(requested.is_subset(&granted));
+
+        // Disjoint scopes
+        let requested = Scope::from_str("email address").unwrap();
+        assert_eq!(requested.difference(&granted), requested);
+        assert!(!requested.is_subset(&granted));
+    }
 }
@@ -19,6 +19,7 @@ use std::{str::Utf8Error, sync::Arc};
 use headers::authorization::InvalidBearerToken;
 use http::{header::ToStrError, StatusCode};
 use mas_http::{catch_http_codes, form_urlencoded_request, json_request, json_response};
+use mas_iana::jose::JsonWebSignatureAlg;
 use mas_jose::{
     claims::ClaimError,
     jwa::InvalidAlgorithm,
@@ -261,6 +262,11 @@ pub enum AuthorizationError {
     #[error(transparent)]
     UrlEncoded(#[from] serde_urlencoded::ser::Error),
 
+    /// The `max_age` is not a positive number of seconds that fits in a
+    /// `u32`.
+    #[error("max_age must be between 1 and {} seconds", u32::MAX)]
+    InvalidMaxAge,
+
     /// An error occurred making the PAR request.
     #[error(transparent)]
     PushedAuthorization(#[from] PushedAuthorizationError),
@@ -637,6 +643,28 @@ pub enum IdTokenError {
     /// one we got before.
     #[error("wrong authentication time")]
     WrongAuthTime,
+
+    /// The nonce of the ID Token is not the one that was sent with the
+    /// authorization request.
+    #[error("wrong nonce: expected {expected:?}, got {got:?}")]
+    NonceMismatch {
+        /// The nonce that was sent with the authorization request.
+        expected: String,
+
+        /// The nonce in the ID Token.
+        got: String,
+    },
+
+    /// A token hash claim of the ID Token doesn't match the token it is
+    /// supposed to be the hash of.
+    #[error("the {claim:?} claim doesn't match the {alg} hash of the token")]
+    TokenHashMismatch {
+        /// The name of the claim, either `at_hash` or `c_hash`.
+        claim: &'static str,
+
+        /// The algorithm used to compute the hash.
+        alg: JsonWebSignatureAlg,
+    },
 }
 
 /// An error that can be returned by an OpenID Provider.
@@ -16,18 +16,24 @@
 //!
 //! [Authorization Code flow]: https://openid.net/specs/openid-connect-core-1_0.html#CodeFlowAuth
 
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroU32,
+};
+
 use base64ct::{Base64UrlUnpadded, Encoding};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use http::header::CONTENT_TYPE;
+use language_tags::LanguageTag;
 use mas_http::{CatchHttpCodesLayer, FormUrlencodedRequestLayer, JsonResponseLayer};
 use mas_iana::oauth::{OAuthAuthorizationEndpointResponseType, PkceCodeChallengeMethod};
-use mas_jose::claims::{self, TokenHash};
+use mas_jose::{claims, jwt::Jwt};
 use oauth2_types::{
     pkce,
     prelude::CodeChallengeMethodExt,
     requests::{
         AccessTokenRequest, AccessTokenResponse, AuthorizationCodeGrant, AuthorizationRequest,
-        Prompt, PushedAuthorizationResponse,
+        Prompt, PushedAuthorizationResponse, ResponseMode,
     },
     scope::Scope,
 };
@@ -36,6 +42,7 @@ use rand::{
     Rng,
 };
 use serde::Serialize;
+use serde_json::Value;
 use serde_with::skip_serializing_none;
 use tower::{Layer, Service, ServiceExt};
 use url::Url;
@@ -43,16 +50,20 @@ use url::Url;
 use super::jose::JwtVerificationData;
 use crate::{
     error::{
-        AuthorizationError, IdTokenError, PushedAuthorizationError, TokenAuthorizationCodeError,
+        AuthorizationError, IdTokenError, JwtVerificationError, PushedAuthorizationError,
+        TokenAuthorizationCodeError,
     },
     http_service::HttpService,
-    requests::{jose::verify_id_token, token::request_access_token},
+    requests::{
+        jose::{verify_id_token, verify_nonce, verify_token_hash},
+        token::request_access_token,
+    },
     types::{
         client_credentials::ClientCredentials,
         scope::{ScopeExt, ScopeToken},
         IdToken,
     },
-    utils::{http_all_error_status_codes, http_error_mapper},
+    utils::{http_all_error_status_codes, http_error_mapper, ResourceIndicators},
 };
 
 /// The data necessary to build an authorization request.
@@ -64,6 +75,14 @@ pub struct AuthorizationRequestData<'a> {
     /// The PKCE methods supported by the issuer, from its metadata.
     pub code_challenge_methods_supported: Option<&'a [PkceCodeChallengeMethod]>,
 
+    /// Whether to fall back to the `plain` PKCE method if the issuer doesn't
+    /// support `S256`.
+    ///
+    /// The `plain` method doesn't protect against an attacker that can read
+    /// the authorization request, so this should only be enabled for issuers
+    /// that don't support anything else.
+    pub allow_plain_pkce: bool,
+
     /// The scope to authorize.
     ///
     /// If the OpenID Connect scope token (`openid`) is not included, it will be
@@ -76,7 +95,49 @@ pub struct AuthorizationRequestData<'a> {
     pub redirect_uri: &'a Url,
 
     /// Optional hints for the action to be performed.
+    ///
+    /// Combined with [`Prompt::None`], the issuer will not display any user
+    /// interface, which allows to silently refresh the end-user's session. If
+    /// the end-user needs to interact with the issuer, the redirect URI will
+    /// receive an error for which
+    /// [`ClientErrorCode::is_interaction_required()`] returns `true`, and the
+    /// request can be retried interactively.
+    ///
+    /// [`ClientErrorCode::is_interaction_required()`]: oauth2_types::errors::ClientErrorCode::is_interaction_required
     pub prompt: Option<&'a [Prompt]>,
+
+    /// The maximum allowable elapsed time since the end-user last actively
+    /// authenticated with the issuer.
+    ///
+    /// If more time has elapsed, the issuer will ask the end-user to
+    /// re-authenticate, or return an error if [`Prompt::None`] is used. It is
+    /// sent to the issuer as a number of seconds, and must be at least one
+    /// second.
+    pub max_age: Option<Duration>,
+
+    /// How the authorization response should be returned to the redirect URI.
+    ///
+    /// If this is `None`, the default response mode for the `code` response
+    /// type, `query`, is used.
+    pub response_mode: Option<&'a ResponseMode>,
+
+    /// Hint about the login identifier the end-user might use to log in.
+    pub login_hint: Option<&'a str>,
+
+    /// The end-user's preferred languages and scripts for the user interface.
+    pub ui_locales: Option<&'a [LanguageTag]>,
+
+    /// The requested Authentication Context Class Reference values.
+    pub acr_values: Option<&'a HashSet<String>>,
+
+    /// The [resource indicators] of the protected resources where the access
+    /// token is going to be used.
+    ///
+    /// They are sent with both the authorization request and the token
+    /// request.
+    ///
+    /// [resource indicators]: https://www.rfc-editor.org/rfc/rfc8707
+    pub resource: Option<&'a [Url]>,
 }
 
 /// The data necessary to validate a response from the Token endpoint in the
@@ -94,6 +155,9 @@ pub struct AuthorizationValidationData {
 
     /// A string to correlate the authorization request to the token request.
     pub code_challenge_verifier: Option<String>,
+
+    /// The resource indicators that were sent with the authorization request.
+    pub resource: Option<Vec<Url>>,
 }
 
 #[skip_serializing_none]
@@ -103,6 +167,8 @@ struct FullAuthorizationRequest {
     inner: AuthorizationRequest,
     #[serde(flatten)]
     pkce: Option<pkce::AuthorizationRequest>,
+    #[serde(flatten)]
+    resource: Option<ResourceIndicators>,
 }
 
 /// Build the authorization request.
@@ -113,25 +179,49 @@ fn build_authorization_request(
     let AuthorizationRequestData {
         client_id,
         code_challenge_methods_supported,
+        allow_plain_pkce,
         scope,
         redirect_uri,
         prompt,
+        max_age,
+        response_mode,
+        login_hint,
+        ui_locales,
+        acr_values,
+        resource,
     } = authorization_data;
     let mut scope = scope.clone();
 
+    let max_age = max_age
+        .map(|max_age| {
+            u32::try_from(max_age.num_seconds())
+                .ok()
+                .and_then(NonZeroU32::new)
+                .ok_or(AuthorizationError::InvalidMaxAge)
+        })
+        .transpose()?;
+
     // Generate a random CSRF "state" token and a nonce.
     let state = Alphanumeric.sample_string(rng, 16);
     let nonce = Alphanumeric.sample_string(rng, 16);
 
-    // Use PKCE, whenever possible.
-    let (pkce, code_challenge_verifier) = if code_challenge_methods_supported
-        .iter()
-        .any(|methods| methods.contains(&PkceCodeChallengeMethod::S256))
+    // Use PKCE, whenever possible. Prefer S256, and only fall back to plain if
+    // allowed.
+    let code_challenge_methods_supported = code_challenge_methods_supported.unwrap_or_default();
+    let method = if code_challenge_methods_supported.contains(&PkceCodeChallengeMethod::S256) {
+        Some(PkceCodeChallengeMethod::S256)
+    } else if allow_plain_pkce
+        && code_challenge_methods_supported.contains(&PkceCodeChallengeMethod::Plain)
     {
+        Some(PkceCodeChallengeMethod::Plain)
+    } else {
+        None
+    };
+
+    let (pkce, code_challenge_verifier) = if let Some(method) = method {
         let mut verifier = [0u8; 32];
         rng.fill(&mut verifier);
 
-        let method = PkceCodeChallengeMethod::S256;
         let verifier = Base64UrlUnpadded::encode_string(&verifier);
         let code_challenge = method.compute_challenge(&verifier)?.into();
 
@@ -154,20 +244,21 @@ fn build_authorization_request(
             redirect_uri: Some(redirect_uri.clone()),
             scope,
             state: Some(state.clone()),
-            response_mode: None,
+            response_mode: response_mode.cloned(),
             nonce: Some(nonce.clone()),
             display: None,
             prompt: prompt.map(ToOwned::to_owned),
-            max_age: None,
-            ui_locales: None,
+            max_age,
+            ui_locales: ui_locales.map(ToOwned::to_owned),
             id_token_hint: None,
-            login_hint: None,
-            acr_values: None,
+            login_hint: login_hint.map(ToOwned::to_owned),
+            acr_values: acr_values.cloned(),
             request: None,
             request_uri: None,
             registration: None,
         },
         pkce,
+        resource: resource.map(|resource| ResourceIndicators(resource.to_owned())),
     };
 
     let auth_data = AuthorizationValidationData {
@@ -175,6 +266,7 @@ fn build_authorization_request(
         nonce,
         redirect_uri: redirect_uri.clone(),
         code_challenge_verifier,
+        resource: resource.map(ToOwned::to_owned),
     };
 
     Ok((auth_request, auth_data))
@@ -226,21 +318,23 @@ pub fn build_authorization_url(
 
     let authorization_query = serde_urlencoded::to_string(authorization_request)?;
 
-    let mut authorization_url = authorization_endpoint;
+    let authorization_url = append_query(authorization_endpoint, &authorization_query);
 
-    // Add our parameters to the query, because the URL might already have one.
-    let mut full_query = authorization_url
-        .query()
-        .map(ToOwned::to_owned)
-        .unwrap_or_default();
-    if !full_query.is_empty() {
-        full_query.push('&');
-    }
-    full_query.push_str(&authorization_query);
+    Ok((authorization_url, validation_data))
+}
 
-    authorization_url.set_query(Some(&full_query));
+/// Add the parameters of `query` to the query of `url`.
+///
+/// The URL might already have a query, so the existing parameters are kept
+/// before ours. Its fragment, if any, is left untouched.
+fn append_query(mut url: Url, query: &str) -> Url {
+    let mut serializer = form_urlencoded::Serializer::new(String::new());
+    serializer.extend_pairs(url.query_pairs());
+    serializer.extend_pairs(form_urlencoded::parse(query.as_bytes()));
+    let full_query = serializer.finish();
 
-    Ok((authorization_url, validation_data))
+    url.set_query(Some(&full_query));
+    url
 }
 
 /// Make a [Pushed Authorization Request] and build the URL for authenticating
@@ -336,19 +430,7 @@ pub async fn build_par_authorization_url(
         ("client_id", &client_id),
     ])?;
 
-    let mut authorization_url = authorization_endpoint;
-
-    // Add our parameters to the query, because the URL might already have one.
-    let mut full_query = authorization_url
-        .query()
-        .map(ToOwned::to_owned)
-        .unwrap_or_default();
-    if !full_query.is_empty() {
-        full_query.push('&');
-    }
-    full_query.push_str(&authorization_query);
-
-    authorization_url.set_query(Some(&full_query));
+    let authorization_url = append_query(authorization_endpoint, &authorization_query);
 
     Ok((authorization_url, validation_data))
 }
@@ -382,6 +464,12 @@ pub async fn build_par_authorization_url(
 ///   If it is not provided, the ID Token won't be verified. Note that in the
 ///   OpenID Connect specification, this verification is required.
 ///
+/// * `check_unverified_nonce` - Whether to check the `nonce` of the ID Token,
+///   if there is one, when `id_token_verification_data` is not provided.
+///
+///   The signature of the ID Token is not verified in that case, so this is
+///   only a defense in depth measure.
+///
 /// * `now` - The current time.
 ///
 /// * `rng` - A random number generator.
@@ -399,6 +487,7 @@ pub async fn access_token_with_authorization_code(
     code: String,
     validation_data: AuthorizationValidationData,
     id_token_verification_data: Option<JwtVerificationData<'_>>,
+    check_unverified_nonce: bool,
     now: DateTime<Utc>,
     rng: &mut impl Rng,
 ) -> Result<(AccessTokenResponse, Option<IdToken<'static>>), TokenAuthorizationCodeError> {
@@ -413,45 +502,97 @@ pub async fn access_token_with_authorization_code(
             redirect_uri: Some(validation_data.redirect_uri),
             code_verifier: validation_data.code_challenge_verifier,
         }),
+        validation_data.resource,
         now,
         rng,
     )
     .await?;
 
-    let id_token = if let Some(verification_data) = id_token_verification_data {
-        let signing_alg = verification_data.signing_algorithm;
+    if id_token_verification_data.is_none() && check_unverified_nonce {
+        if let Some(id_token) = &token_response.id_token {
+            verify_unsigned_id_token_nonce(id_token, &validation_data.nonce)?;
+        }
+    }
 
-        let id_token = token_response
-            .id_token
-            .as_deref()
-            .ok_or(IdTokenError::MissingIdToken)?;
+    let id_token = id_token_verification_data
+        .map(|verification_data| {
+            verify_authorization_code_id_token(
+                &token_response,
+                &code,
+                &validation_data.nonce,
+                verification_data,
+                now,
+            )
+        })
+        .transpose()?;
 
-        let id_token = verify_id_token(id_token, verification_data, None, now)?;
+    Ok((token_response, id_token))
+}
 
-        let mut claims = id_token.payload().clone();
+/// Check the nonce of an ID Token, without verifying its signature.
+fn verify_unsigned_id_token_nonce(id_token: &str, nonce: &str) -> Result<(), IdTokenError> {
+    let id_token: Jwt<HashMap<String, Value>> =
+        id_token.try_into().map_err(JwtVerificationError::from)?;
 
-        // Access token hash must match.
-        claims::AT_HASH
-            .extract_optional_with_options(
-                &mut claims,
-                TokenHash::new(signing_alg, &token_response.access_token),
-            )
-            .map_err(IdTokenError::from)?;
+    let mut claims = id_token.payload().clone();
+    verify_nonce(&mut claims, nonce)?;
+
+    Ok(())
+}
+
+/// Verify the ID Token of a response from the Token endpoint in the
+/// Authorization Code flow.
+///
+/// This is done by [`access_token_with_authorization_code()`] when it is given
+/// some [`JwtVerificationData`], but this can also be used on its own, for
+/// example to retry the verification after refreshing the issuer's JWKS.
+///
+/// # Arguments
+///
+/// * `token_response` - The response from the Token endpoint.
+///
+/// * `code` - The authorization code that was exchanged.
+///
+/// * `nonce` - The nonce that was sent with the authorization request.
+///
+/// * `verification_data` - The data necessary to verify the ID Token.
+///
+/// * `now` - The current time.
+///
+/// # Errors
+///
+/// Returns an error if the ID Token is missing or if its verification fails.
+pub fn verify_authorization_code_id_token(
+    token_response: &AccessTokenResponse,
+    code: &str,
+    nonce: &str,
+    verification_data: JwtVerificationData<'_>,
+    now: DateTime<Utc>,
+) -> Result<IdToken<'static>, IdTokenError> {
+    let signing_alg = verification_data.signing_algorithm;
 
-        // Code hash must match.
-        claims::C_HASH
-            .extract_optional_with_options(&mut claims, TokenHash::new(signing_alg, &code))
-            .map_err(IdTokenError::from)?;
+    let id_token = token_response
+        .id_token
+        .as_deref()
+        .ok_or(IdTokenError::MissingIdToken)?;
 
-        // Nonce must match.
-        claims::NONCE
-            .extract_required_with_options(&mut claims, validation_data.nonce.as_str())
-            .map_err(IdTokenError::from)?;
+    let id_token = verify_id_token(id_token, verification_data, None, now)?;
 
-        Some(id_token.into_owned())
-    } else {
-        None
-    };
+    let mut claims = id_token.payload().clone();
 
-    Ok((token_response, id_token))
+    // Access token hash must match.
+    verify_token_hash(
+        &mut claims,
+        &claims::AT_HASH,
+        signing_alg,
+        &token_response.access_token,
+    )?;
+
+    // Code hash must match.
+    verify_token_hash(&mut claims, &claims::C_HASH, signing_alg, code)?;
+
+    // Nonce must match.
+    verify_nonce(&mut claims, nonce)?;
+
+    Ok(id_token.into_owned())
 }
@@ -68,6 +68,7 @@ pub async fn access_token_with_client_credentials(
         client_credentials,
         token_endpoint,
         AccessTokenRequest::ClientCredentials(ClientCredentialsGrant { scope }),
+        None,
         now,
         rng,
     )
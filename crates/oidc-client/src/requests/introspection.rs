@@ -101,7 +101,8 @@ impl<'a> From<ClientCredentials> for IntrospectionAuthentication<'a> {
 ///
 /// * `authentication` - The method used to authenticate the request.
 ///
-/// * `revocation_endpoint` - The URL of the issuer's Revocation endpoint.
+/// * `introspection_endpoint` - The URL of the issuer's Introspection
+///   endpoint.
 ///
 /// * `token` - The token to introspect.
 ///
@@ -17,11 +17,11 @@
 use std::collections::HashMap;
 
 use bytes::Bytes;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use mas_http::JsonResponseLayer;
 use mas_iana::jose::JsonWebSignatureAlg;
 use mas_jose::{
-    claims::{self, TimeOptions},
+    claims::{self, Claim, ClaimError, TimeOptions, TokenHash, TokenHashError},
     jwk::PublicJsonWebKeySet,
     jwt::Jwt,
 };
@@ -77,6 +77,21 @@ pub struct JwtVerificationData<'a> {
     /// The JWA that should have been used to sign the JWT, as set during
     /// client registration.
     pub signing_algorithm: &'a JsonWebSignatureAlg,
+
+    /// The tolerated clock skew with the issuer when checking the time claims
+    /// of an ID Token.
+    ///
+    /// [`JwtVerificationData::default_leeway()`] should be used unless the
+    /// issuer is known to need something else.
+    pub leeway: Duration,
+}
+
+impl JwtVerificationData<'_> {
+    /// The default tolerated clock skew with the issuer, one minute.
+    #[must_use]
+    pub fn default_leeway() -> Duration {
+        Duration::minutes(1)
+    }
 }
 
 /// Decode and verify a signed JWT.
@@ -118,6 +133,7 @@ pub fn verify_signed_jwt<'a>(
         jwks,
         client_id,
         signing_algorithm,
+        leeway: _,
     } = verification_data;
 
     let jwt: Jwt<HashMap<String, Value>> = jwt.try_into()?;
@@ -149,6 +165,10 @@ pub fn verify_signed_jwt<'a>(
 ///
 /// * The `iat` claim must be present must be in the past.
 ///
+/// * If the `nbf` claim is present, it must be in the past.
+///
+/// The time claims are checked with the leeway of the `verification_data`.
+///
 /// * The `sub` claim must be present.
 ///
 /// If an authorization ID token is provided, these extra checks are performed:
@@ -180,12 +200,15 @@ pub fn verify_id_token<'a>(
 
     let mut claims = id_token.payload().clone();
 
-    let time_options = TimeOptions::new(now);
+    let time_options = TimeOptions::new(now).leeway(verification_data.leeway);
     // Must not have expired.
     claims::EXP.extract_required_with_options(&mut claims, &time_options)?;
 
     // `iat` claim must be present.
-    claims::IAT.extract_required_with_options(&mut claims, time_options)?;
+    claims::IAT.extract_required_with_options(&mut claims, &time_options)?;
+
+    // Must be valid already, if there is a `nbf` claim.
+    claims::NBF.extract_optional_with_options(&mut claims, time_options)?;
 
     // Subject identifier must be present.
     let sub = claims::SUB.extract_required(&mut claims)?;
@@ -215,3 +238,48 @@ pub fn verify_id_token<'a>(
 
     Ok(id_token)
 }
+
+/// Check that the `nonce` claim of an ID Token matches the one that was sent
+/// with the authorization request.
+pub(crate) fn verify_nonce(
+    claims: &mut HashMap<String, Value>,
+    expected: &str,
+) -> Result<(), IdTokenError> {
+    // Extract the claim without validating it, to be able to report the mismatch
+    let got: String = Claim::<String>::new("nonce").extract_required(claims)?;
+
+    if got == expected {
+        Ok(())
+    } else {
+        Err(IdTokenError::NonceMismatch {
+            expected: expected.to_owned(),
+            got,
+        })
+    }
+}
+
+/// Check a token hash claim of an ID Token, if it is present.
+///
+/// A mismatch is reported with the algorithm used, but not the token.
+pub(crate) fn verify_token_hash(
+    claims: &mut HashMap<String, Value>,
+    claim: &Claim<String, TokenHash>,
+    signing_algorithm: &JsonWebSignatureAlg,
+    token: &str,
+) -> Result<(), IdTokenError> {
+    match claim.extract_optional_with_options(claims, TokenHash::new(signing_algorithm, token)) {
+        Ok(_) => Ok(()),
+        Err(ClaimError::ValidationError { claim, source })
+            if matches!(
+                source.downcast_ref::<TokenHashError>(),
+                Some(TokenHashError::HashMismatch)
+            ) =>
+        {
+            Err(IdTokenError::TokenHashMismatch {
+                claim,
+                alg: signing_algorithm.clone(),
+            })
+        }
+        Err(e) => Err(e.into()),
+    }
+}
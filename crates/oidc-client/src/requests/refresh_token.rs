@@ -17,7 +17,7 @@
 //! [Refresh Tokens]: https://openid.net/specs/openid-connect-core-1_0.html#RefreshTokens
 
 use chrono::{DateTime, Utc};
-use mas_jose::claims::{self, TokenHash};
+use mas_jose::claims;
 use oauth2_types::{
     requests::{AccessTokenRequest, AccessTokenResponse, RefreshTokenGrant},
     scope::Scope,
@@ -29,14 +29,18 @@ use super::jose::JwtVerificationData;
 use crate::{
     error::{IdTokenError, TokenRefreshError},
     http_service::HttpService,
-    requests::{jose::verify_id_token, token::request_access_token},
+    requests::{
+        jose::{verify_id_token, verify_token_hash},
+        token::request_access_token,
+    },
     types::{client_credentials::ClientCredentials, IdToken},
 };
 
-/// Exchange an authorization code for an access token.
+/// Exchange a refresh token for a new access token.
 ///
-/// This should be used as the first step for logging in, and to request a
-/// token with a new scope.
+/// This should be used to keep access to the issuer's APIs after the access
+/// token expired. The issuer might also rotate the refresh token and the ID
+/// Token in the response.
 ///
 /// # Arguments
 ///
@@ -58,7 +62,7 @@ use crate::{
 ///   the response.
 ///
 ///   The signing algorithm corresponds to the `id_token_signed_response_alg`
-/// field in the client metadata.
+///   field in the client metadata.
 ///
 ///   If it is not provided, the ID Token won't be verified.
 ///
@@ -96,6 +100,7 @@ pub async fn refresh_access_token(
             refresh_token,
             scope,
         }),
+        None,
         now,
         rng,
     )
@@ -112,12 +117,12 @@ pub async fn refresh_access_token(
         let mut claims = id_token.payload().clone();
 
         // Access token hash must match.
-        claims::AT_HASH
-            .extract_optional_with_options(
-                &mut claims,
-                TokenHash::new(signing_alg, &token_response.access_token),
-            )
-            .map_err(IdTokenError::from)?;
+        verify_token_hash(
+            &mut claims,
+            &claims::AT_HASH,
+            signing_alg,
+            &token_response.access_token,
+        )?;
 
         Some(id_token.into_owned())
     } else {
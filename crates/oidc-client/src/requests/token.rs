@@ -18,6 +18,8 @@ use chrono::{DateTime, Utc};
 use mas_http::{CatchHttpCodesLayer, FormUrlencodedRequestLayer, JsonResponseLayer};
 use oauth2_types::requests::{AccessTokenRequest, AccessTokenResponse};
 use rand::Rng;
+use serde::Serialize;
+use serde_with::skip_serializing_none;
 use tower::{Layer, Service, ServiceExt};
 use url::Url;
 
@@ -25,9 +27,18 @@ use crate::{
     error::TokenRequestError,
     http_service::HttpService,
     types::client_credentials::ClientCredentials,
-    utils::{http_all_error_status_codes, http_error_mapper},
+    utils::{http_all_error_status_codes, http_error_mapper, ResourceIndicators},
 };
 
+#[skip_serializing_none]
+#[derive(Serialize)]
+struct TokenRequest {
+    #[serde(flatten)]
+    inner: AccessTokenRequest,
+    #[serde(flatten)]
+    resource: Option<ResourceIndicators>,
+}
+
 /// Request an access token.
 ///
 /// # Arguments
@@ -41,6 +52,9 @@ use crate::{
 ///
 /// * `request` - The request to make at the Token endpoint.
 ///
+/// * `resource` - The [resource indicators] of the protected resources where
+///   the access token is going to be used.
+///
 /// * `now` - The current time.
 ///
 /// * `rng` - A random number generator.
@@ -48,17 +62,25 @@ use crate::{
 /// # Errors
 ///
 /// Returns an error if the request fails or the response is invalid.
+///
+/// [resource indicators]: https://www.rfc-editor.org/rfc/rfc8707
 #[tracing::instrument(skip_all, fields(token_endpoint, request))]
 pub async fn request_access_token(
     http_service: &HttpService,
     client_credentials: ClientCredentials,
     token_endpoint: &Url,
     request: AccessTokenRequest,
+    resource: Option<Vec<Url>>,
     now: DateTime<Utc>,
     rng: &mut impl Rng,
 ) -> Result<AccessTokenResponse, TokenRequestError> {
     tracing::debug!(?request, "Requesting access token...");
 
+    let request = TokenRequest {
+        inner: request,
+        resource: resource.map(ResourceIndicators),
+    };
+
     let token_request = http::Request::post(token_endpoint.as_str()).body(request)?;
 
     let token_request = client_credentials.apply_to_request(token_request, now, rng)?;
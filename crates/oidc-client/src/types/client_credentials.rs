@@ -179,6 +179,26 @@ impl ClientCredentials {
         }
     }
 
+    /// Get the authentication method used by these `ClientCredentials`.
+    #[must_use]
+    pub fn authentication_method(&self) -> OAuthClientAuthenticationMethod {
+        match self {
+            ClientCredentials::None { .. } => OAuthClientAuthenticationMethod::None,
+            ClientCredentials::ClientSecretBasic { .. } => {
+                OAuthClientAuthenticationMethod::ClientSecretBasic
+            }
+            ClientCredentials::ClientSecretPost { .. } => {
+                OAuthClientAuthenticationMethod::ClientSecretPost
+            }
+            ClientCredentials::ClientSecretJwt { .. } => {
+                OAuthClientAuthenticationMethod::ClientSecretJwt
+            }
+            ClientCredentials::PrivateKeyJwt { .. } => {
+                OAuthClientAuthenticationMethod::PrivateKeyJwt
+            }
+        }
+    }
+
     /// Apply these `ClientCredentials` to the given request.
     pub(crate) fn apply_to_request<T: Serialize>(
         self,
@@ -523,6 +543,39 @@ mod test {
         );
     }
 
+    #[test]
+    fn authentication_method() {
+        let credentials = ClientCredentials::ClientSecretBasic {
+            client_id: CLIENT_ID.to_owned(),
+            client_secret: CLIENT_SECRET.to_owned(),
+        };
+        assert_eq!(
+            credentials.authentication_method(),
+            OAuthClientAuthenticationMethod::ClientSecretBasic
+        );
+
+        let credentials = ClientCredentials::ClientSecretPost {
+            client_id: CLIENT_ID.to_owned(),
+            client_secret: CLIENT_SECRET.to_owned(),
+        };
+        assert_eq!(
+            credentials.authentication_method(),
+            OAuthClientAuthenticationMethod::ClientSecretPost
+        );
+
+        let credentials = ClientCredentials::ClientSecretJwt {
+            client_id: CLIENT_ID.to_owned(),
+            client_secret: CLIENT_SECRET.to_owned(),
+            signing_algorithm: JsonWebSignatureAlg::Hs256,
+            token_endpoint: Url::parse("http://localhost").unwrap(),
+        };
+        assert_eq!(
+            credentials.authentication_method(),
+            OAuthClientAuthenticationMethod::ClientSecretJwt
+        );
+        assert!(CLIENT_SUPPORTED_AUTH_METHODS.contains(&credentials.authentication_method()));
+    }
+
     #[tokio::test]
     async fn build_request_none() {
         let credentials = ClientCredentials::None {
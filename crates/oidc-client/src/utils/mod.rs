@@ -16,6 +16,8 @@ use std::ops::RangeBounds;
 
 use bytes::Buf;
 use http::{Response, StatusCode};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use url::Url;
 
 use crate::error::ErrorBody;
 
@@ -39,3 +41,25 @@ pub fn http_all_error_status_codes() -> impl RangeBounds<StatusCode> {
 
     client_errors_start_code..=server_errors_end_code
 }
+
+/// A list of [resource indicators].
+///
+/// When flattened in a form-encoded request, each resource is serialized as a
+/// separate `resource` parameter.
+///
+/// [resource indicators]: https://www.rfc-editor.org/rfc/rfc8707
+#[derive(Debug, Clone)]
+pub struct ResourceIndicators(pub Vec<Url>);
+
+impl Serialize for ResourceIndicators {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for resource in &self.0 {
+            map.serialize_entry("resource", resource.as_str())?;
+        }
+        map.end()
+    }
+}
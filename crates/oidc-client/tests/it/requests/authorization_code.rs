@@ -19,10 +19,11 @@ use std::{
 
 use assert_matches::assert_matches;
 use chrono::Duration;
+use language_tags::LanguageTag;
 use mas_iana::oauth::{
     OAuthAccessTokenType, OAuthClientAuthenticationMethod, PkceCodeChallengeMethod,
 };
-use mas_jose::{claims::ClaimError, jwk::PublicJsonWebKeySet};
+use mas_jose::jwk::PublicJsonWebKeySet;
 use mas_oidc_client::{
     error::{
         AuthorizationError, IdTokenError, PushedAuthorizationError, TokenAuthorizationCodeError,
@@ -30,13 +31,16 @@ use mas_oidc_client::{
     requests::{
         authorization_code::{
             access_token_with_authorization_code, build_authorization_url,
-            build_par_authorization_url, AuthorizationRequestData, AuthorizationValidationData,
+            build_par_authorization_url, verify_authorization_code_id_token,
+            AuthorizationRequestData, AuthorizationValidationData,
         },
         jose::JwtVerificationData,
     },
-    types::scope::{ScopeExt, ScopeToken},
+    types::scope::{Scope, ScopeExt, ScopeToken},
+};
+use oauth2_types::requests::{
+    AccessTokenResponse, Prompt, PushedAuthorizationResponse, ResponseMode,
 };
-use oauth2_types::requests::{AccessTokenResponse, PushedAuthorizationResponse};
 use rand::SeedableRng;
 use tokio::sync::oneshot;
 use url::Url;
@@ -50,25 +54,57 @@ use crate::{
     CODE_VERIFIER, ID_TOKEN_SIGNING_ALG, NONCE, REDIRECT_URI, REQUEST_URI,
 };
 
-#[test]
-fn pass_authorization_url() {
-    let issuer = Url::parse("http://localhost/").unwrap();
-    let authorization_endpoint = issuer.join("authorize").unwrap();
-    let redirect_uri = Url::parse(REDIRECT_URI).unwrap();
-    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+/// The data shared by the tests building an authorization URL
+struct AuthorizationUrlBuilder {
+    authorization_endpoint: Url,
+    redirect_uri: Url,
+    scope: Scope,
+}
 
-    let (url, validation_data) = build_authorization_url(
-        authorization_endpoint,
+impl Default for AuthorizationUrlBuilder {
+    fn default() -> Self {
+        Self {
+            authorization_endpoint: Url::parse("http://localhost/authorize").unwrap(),
+            redirect_uri: Url::parse(REDIRECT_URI).unwrap(),
+            scope: [ScopeToken::Openid].into_iter().collect(),
+        }
+    }
+}
+
+impl AuthorizationUrlBuilder {
+    /// The default request data, to be overridden by each test with the struct
+    /// update syntax
+    fn data(&self) -> AuthorizationRequestData<'_> {
         AuthorizationRequestData {
             client_id: CLIENT_ID,
             code_challenge_methods_supported: Some(&[PkceCodeChallengeMethod::S256]),
-            scope: &[ScopeToken::Openid].into_iter().collect(),
-            redirect_uri: &redirect_uri,
+            allow_plain_pkce: false,
+            scope: &self.scope,
+            redirect_uri: &self.redirect_uri,
             prompt: None,
-        },
-        &mut rng,
-    )
-    .unwrap();
+            max_age: None,
+            response_mode: None,
+            login_hint: None,
+            ui_locales: None,
+            acr_values: None,
+            resource: None,
+        }
+    }
+
+    fn build(
+        &self,
+        data: AuthorizationRequestData<'_>,
+    ) -> Result<(Url, AuthorizationValidationData), AuthorizationError> {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+        build_authorization_url(self.authorization_endpoint.clone(), data, &mut rng)
+    }
+}
+
+#[test]
+fn pass_authorization_url() {
+    let builder = AuthorizationUrlBuilder::default();
+
+    let (url, validation_data) = builder.build(builder.data()).unwrap();
 
     assert_eq!(validation_data.state, "OrJ8xbWovSpJUTKz");
     assert_eq!(
@@ -88,6 +124,193 @@ fn pass_authorization_url() {
     let code_challenge = query_pairs.get("code_challenge").unwrap();
     assert!(code_challenge.len() >= 43);
     assert_eq!(query_pairs.get("code_challenge_method").unwrap(), "S256");
+    assert!(!query_pairs.contains_key("response_mode"));
+    assert!(!query_pairs.contains_key("login_hint"));
+    assert!(!query_pairs.contains_key("ui_locales"));
+    assert!(!query_pairs.contains_key("acr_values"));
+}
+
+#[test]
+fn pass_authorization_url_with_existing_query_and_fragment() {
+    let builder = AuthorizationUrlBuilder {
+        authorization_endpoint: Url::parse("https://idp/authorize?foo=bar&#frag").unwrap(),
+        ..AuthorizationUrlBuilder::default()
+    };
+
+    let (url, validation_data) = builder.build(builder.data()).unwrap();
+
+    assert_eq!(url.path(), "/authorize");
+    assert_eq!(url.fragment(), Some("frag"));
+
+    let query = url.query().unwrap();
+    assert!(query.starts_with("foo=bar&"));
+    assert!(!query.contains("&&"));
+
+    let query_pairs = url.query_pairs().collect::<HashMap<_, _>>();
+    assert_eq!(query_pairs.get("foo").unwrap(), "bar");
+    assert_eq!(query_pairs.get("client_id").unwrap(), CLIENT_ID);
+    assert_eq!(query_pairs.get("redirect_uri").unwrap(), REDIRECT_URI);
+    assert_eq!(*query_pairs.get("state").unwrap(), validation_data.state);
+}
+
+#[test]
+fn pass_authorization_url_without_pkce() {
+    let builder = AuthorizationUrlBuilder::default();
+
+    // The issuer doesn't advertise support for S256, and the plain fallback is
+    // disabled
+    let (url, validation_data) = builder
+        .build(AuthorizationRequestData {
+            code_challenge_methods_supported: Some(&[PkceCodeChallengeMethod::Plain]),
+            ..builder.data()
+        })
+        .unwrap();
+
+    assert_eq!(validation_data.code_challenge_verifier, None);
+
+    let query_pairs = url.query_pairs().collect::<HashMap<_, _>>();
+    assert!(!query_pairs.contains_key("code_challenge"));
+    assert!(!query_pairs.contains_key("code_challenge_method"));
+}
+
+#[test]
+fn pass_authorization_url_with_plain_pkce() {
+    let builder = AuthorizationUrlBuilder::default();
+
+    // The issuer only advertises support for plain
+    let (url, validation_data) = builder
+        .build(AuthorizationRequestData {
+            code_challenge_methods_supported: Some(&[PkceCodeChallengeMethod::Plain]),
+            allow_plain_pkce: true,
+            ..builder.data()
+        })
+        .unwrap();
+
+    let verifier = validation_data.code_challenge_verifier.unwrap();
+    let query_pairs = url.query_pairs().collect::<HashMap<_, _>>();
+    assert_eq!(*query_pairs.get("code_challenge").unwrap(), verifier);
+    assert_eq!(query_pairs.get("code_challenge_method").unwrap(), "plain");
+}
+
+#[test]
+fn pass_authorization_url_without_pkce_support() {
+    let builder = AuthorizationUrlBuilder::default();
+
+    // The issuer doesn't advertise any PKCE method
+    let (url, validation_data) = builder
+        .build(AuthorizationRequestData {
+            code_challenge_methods_supported: None,
+            allow_plain_pkce: true,
+            ..builder.data()
+        })
+        .unwrap();
+
+    assert_eq!(validation_data.code_challenge_verifier, None);
+    assert!(!url.query_pairs().any(|(key, _)| key == "code_challenge"));
+}
+
+#[test]
+fn pass_authorization_url_with_response_mode() {
+    let builder = AuthorizationUrlBuilder::default();
+
+    let (url, _validation_data) = builder
+        .build(AuthorizationRequestData {
+            response_mode: Some(&ResponseMode::FormPost),
+            ..builder.data()
+        })
+        .unwrap();
+
+    let query_pairs = url.query_pairs().collect::<HashMap<_, _>>();
+    assert_eq!(query_pairs.get("response_mode").unwrap(), "form_post");
+}
+
+#[test]
+fn pass_authorization_url_with_silent_auth() {
+    let builder = AuthorizationUrlBuilder::default();
+
+    let (url, _validation_data) = builder
+        .build(AuthorizationRequestData {
+            prompt: Some(&[Prompt::None]),
+            max_age: Some(Duration::hours(1)),
+            ..builder.data()
+        })
+        .unwrap();
+
+    let query_pairs = url.query_pairs().collect::<HashMap<_, _>>();
+    assert_eq!(query_pairs.get("prompt").unwrap(), "none");
+    assert_eq!(query_pairs.get("max_age").unwrap(), "3600");
+}
+
+#[test]
+fn fail_authorization_url_invalid_max_age() {
+    let builder = AuthorizationUrlBuilder::default();
+
+    let error = builder
+        .build(AuthorizationRequestData {
+            max_age: Some(Duration::zero()),
+            ..builder.data()
+        })
+        .unwrap_err();
+
+    assert_matches!(error, AuthorizationError::InvalidMaxAge);
+}
+
+#[test]
+fn pass_authorization_url_with_hints() {
+    let builder = AuthorizationUrlBuilder::default();
+    let ui_locales = [
+        LanguageTag::parse("fr").unwrap(),
+        LanguageTag::parse("en-GB").unwrap(),
+    ];
+    let acr_values = ["urn:mace:incommon:iap:silver".to_owned()].into();
+
+    let (url, _validation_data) = builder
+        .build(AuthorizationRequestData {
+            login_hint: Some("alice@example.com"),
+            ui_locales: Some(&ui_locales),
+            acr_values: Some(&acr_values),
+            ..builder.data()
+        })
+        .unwrap();
+
+    let query_pairs = url.query_pairs().collect::<HashMap<_, _>>();
+    assert_eq!(query_pairs.get("login_hint").unwrap(), "alice@example.com");
+    assert_eq!(query_pairs.get("ui_locales").unwrap(), "fr en-GB");
+    assert_eq!(
+        query_pairs.get("acr_values").unwrap(),
+        "urn:mace:incommon:iap:silver"
+    );
+}
+
+#[test]
+fn pass_authorization_url_with_resource() {
+    let builder = AuthorizationUrlBuilder::default();
+    let resource = [
+        Url::parse("https://matrix.example.com/").unwrap(),
+        Url::parse("https://api.example.com/").unwrap(),
+    ];
+
+    let (url, validation_data) = builder
+        .build(AuthorizationRequestData {
+            resource: Some(&resource),
+            ..builder.data()
+        })
+        .unwrap();
+
+    let resource_params: Vec<_> = url
+        .query_pairs()
+        .filter(|(key, _)| key == "resource")
+        .map(|(_, value)| value.into_owned())
+        .collect();
+    assert_eq!(
+        resource_params,
+        ["https://matrix.example.com/", "https://api.example.com/"]
+    );
+    assert!(url.query().unwrap().contains(
+        "resource=https%3A%2F%2Fmatrix.example.com%2F&resource=https%3A%2F%2Fapi.example.com%2F"
+    ));
+
+    assert_eq!(validation_data.resource.unwrap(), resource);
 }
 
 #[tokio::test]
@@ -97,7 +320,7 @@ async fn pass_pushed_authorization_request() {
         client_credentials(OAuthClientAuthenticationMethod::None, &issuer, None);
     let authorization_endpoint = issuer.join("authorize").unwrap();
     let par_endpoint = issuer.join("par").unwrap();
-    let redirect_uri = Url::parse(REDIRECT_URI).unwrap();
+    let builder = AuthorizationUrlBuilder::default();
     let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
 
     let (sender, receiver) = oneshot::channel();
@@ -130,13 +353,7 @@ async fn pass_pushed_authorization_request() {
         client_credentials,
         &par_endpoint,
         authorization_endpoint,
-        AuthorizationRequestData {
-            client_id: CLIENT_ID,
-            code_challenge_methods_supported: Some(&[PkceCodeChallengeMethod::S256]),
-            scope: &[ScopeToken::Openid].into_iter().collect(),
-            redirect_uri: &redirect_uri,
-            prompt: None,
-        },
+        builder.data(),
         now(),
         &mut rng,
     )
@@ -174,7 +391,7 @@ async fn fail_pushed_authorization_request_404() {
         client_credentials(OAuthClientAuthenticationMethod::None, &issuer, None);
     let authorization_endpoint = issuer.join("authorize").unwrap();
     let par_endpoint = issuer.join("par").unwrap();
-    let redirect_uri = Url::parse(REDIRECT_URI).unwrap();
+    let builder = AuthorizationUrlBuilder::default();
     let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
 
     let error = build_par_authorization_url(
@@ -182,13 +399,7 @@ async fn fail_pushed_authorization_request_404() {
         client_credentials,
         &par_endpoint,
         authorization_endpoint,
-        AuthorizationRequestData {
-            client_id: CLIENT_ID,
-            code_challenge_methods_supported: Some(&[PkceCodeChallengeMethod::S256]),
-            scope: &[ScopeToken::Openid].into_iter().collect(),
-            redirect_uri: &redirect_uri,
-            prompt: None,
-        },
+        builder.data(),
         now(),
         &mut rng,
     )
@@ -260,6 +471,7 @@ async fn pass_access_token_with_authorization_code() {
         nonce: NONCE.to_owned(),
         redirect_uri,
         code_challenge_verifier: Some(CODE_VERIFIER.to_owned()),
+        resource: None,
     };
 
     let (id_token, jwks) = id_token(issuer.as_str());
@@ -268,6 +480,7 @@ async fn pass_access_token_with_authorization_code() {
         jwks: &jwks,
         client_id: &CLIENT_ID.to_owned(),
         signing_algorithm: &ID_TOKEN_SIGNING_ALG,
+        leeway: JwtVerificationData::default_leeway(),
     };
 
     Mock::given(method("POST"))
@@ -293,6 +506,7 @@ async fn pass_access_token_with_authorization_code() {
         AUTHORIZATION_CODE.to_owned(),
         validation_data,
         Some(id_token_verification_data),
+        false,
         now(),
         &mut rng,
     )
@@ -305,6 +519,66 @@ async fn pass_access_token_with_authorization_code() {
     assert_eq!(response_id_token.unwrap().as_str(), id_token.as_str());
 }
 
+#[tokio::test]
+async fn pass_access_token_with_authorization_code_and_resource() {
+    let (http_service, mock_server, issuer) = init_test().await;
+    let client_credentials =
+        client_credentials(OAuthClientAuthenticationMethod::None, &issuer, None);
+    let token_endpoint = issuer.join("token").unwrap();
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+
+    let redirect_uri = Url::parse(REDIRECT_URI).unwrap();
+    let validation_data = AuthorizationValidationData {
+        state: "some_state".to_owned(),
+        nonce: NONCE.to_owned(),
+        redirect_uri,
+        code_challenge_verifier: Some(CODE_VERIFIER.to_owned()),
+        resource: Some(vec![
+            Url::parse("https://matrix.example.com/").unwrap(),
+            Url::parse("https://api.example.com/").unwrap(),
+        ]),
+    };
+
+    Mock::given(method("POST"))
+        .and(path("/token"))
+        .and(is_valid_token_endpoint_request)
+        .and(|req: &Request| {
+            let resource: Vec<_> = form_urlencoded::parse(&req.body)
+                .filter(|(key, _)| key == "resource")
+                .map(|(_, value)| value.into_owned())
+                .collect();
+            resource == ["https://matrix.example.com/", "https://api.example.com/"]
+        })
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(AccessTokenResponse {
+                access_token: ACCESS_TOKEN.to_owned(),
+                refresh_token: None,
+                id_token: None,
+                token_type: OAuthAccessTokenType::Bearer,
+                expires_in: None,
+                scope: None,
+            }),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let (response, _) = access_token_with_authorization_code(
+        &http_service,
+        client_credentials,
+        &token_endpoint,
+        AUTHORIZATION_CODE.to_owned(),
+        validation_data,
+        None,
+        false,
+        now(),
+        &mut rng,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(response.access_token, ACCESS_TOKEN);
+}
+
 #[tokio::test]
 async fn fail_access_token_with_authorization_code_wrong_nonce() {
     let (http_service, mock_server, issuer) = init_test().await;
@@ -319,6 +593,7 @@ async fn fail_access_token_with_authorization_code_wrong_nonce() {
         nonce: "wrong_nonce".to_owned(),
         redirect_uri,
         code_challenge_verifier: Some(CODE_VERIFIER.to_owned()),
+        resource: None,
     };
 
     let (id_token, jwks) = id_token(issuer.as_str());
@@ -327,6 +602,7 @@ async fn fail_access_token_with_authorization_code_wrong_nonce() {
         jwks: &jwks,
         client_id: &CLIENT_ID.to_owned(),
         signing_algorithm: &ID_TOKEN_SIGNING_ALG,
+        leeway: JwtVerificationData::default_leeway(),
     };
 
     Mock::given(method("POST"))
@@ -352,19 +628,117 @@ async fn fail_access_token_with_authorization_code_wrong_nonce() {
         AUTHORIZATION_CODE.to_owned(),
         validation_data,
         Some(id_token_verification_data),
+        false,
         now(),
         &mut rng,
     )
     .await
     .unwrap_err();
 
-    assert_matches!(
+    let error = assert_matches!(error, TokenAuthorizationCodeError::IdToken(e) => e);
+    let (expected, got) = assert_matches!(
+        error,
+        IdTokenError::NonceMismatch { expected, got } => (expected, got)
+    );
+    assert_eq!(expected, "wrong_nonce");
+    assert_eq!(got, NONCE);
+}
+
+#[tokio::test]
+async fn fail_access_token_with_authorization_code_wrong_unverified_nonce() {
+    let (http_service, mock_server, issuer) = init_test().await;
+    let client_credentials =
+        client_credentials(OAuthClientAuthenticationMethod::None, &issuer, None);
+    let token_endpoint = issuer.join("token").unwrap();
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+
+    let redirect_uri = Url::parse(REDIRECT_URI).unwrap();
+    let validation_data = AuthorizationValidationData {
+        state: "some_state".to_owned(),
+        nonce: "wrong_nonce".to_owned(),
+        redirect_uri,
+        code_challenge_verifier: Some(CODE_VERIFIER.to_owned()),
+        resource: None,
+    };
+
+    let (id_token, _jwks) = id_token(issuer.as_str());
+
+    Mock::given(method("POST"))
+        .and(path("/token"))
+        .and(is_valid_token_endpoint_request)
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(AccessTokenResponse {
+                access_token: ACCESS_TOKEN.to_owned(),
+                refresh_token: None,
+                id_token: Some(id_token.into_string()),
+                token_type: OAuthAccessTokenType::Bearer,
+                expires_in: None,
+                scope: Some([ScopeToken::Openid].into_iter().collect()),
+            }),
+        )
+        .mount(&mock_server)
+        .await;
+
+    // Without verification data, the nonce is still checked
+    let error = access_token_with_authorization_code(
+        &http_service,
+        client_credentials,
+        &token_endpoint,
+        AUTHORIZATION_CODE.to_owned(),
+        validation_data,
+        None,
+        true,
+        now(),
+        &mut rng,
+    )
+    .await
+    .unwrap_err();
+
+    let error = assert_matches!(error, TokenAuthorizationCodeError::IdToken(e) => e);
+    let (expected, got) = assert_matches!(
+        error,
+        IdTokenError::NonceMismatch { expected, got } => (expected, got)
+    );
+    assert_eq!(expected, "wrong_nonce");
+    assert_eq!(got, NONCE);
+}
+
+#[test]
+fn fail_verify_authorization_code_id_token_wrong_access_token_hash() {
+    let issuer = Url::parse("http://localhost/").unwrap();
+    let (id_token, jwks) = id_token(issuer.as_str());
+    let id_token_verification_data = JwtVerificationData {
+        issuer: issuer.as_str(),
+        jwks: &jwks,
+        client_id: &CLIENT_ID.to_owned(),
+        signing_algorithm: &ID_TOKEN_SIGNING_ALG,
+        leeway: JwtVerificationData::default_leeway(),
+    };
+
+    // The `at_hash` claim of the ID Token is for another access token
+    let token_response = AccessTokenResponse {
+        access_token: "another_access_token".to_owned(),
+        refresh_token: None,
+        id_token: Some(id_token.into_string()),
+        token_type: OAuthAccessTokenType::Bearer,
+        expires_in: None,
+        scope: None,
+    };
+
+    let error = verify_authorization_code_id_token(
+        &token_response,
+        AUTHORIZATION_CODE,
+        NONCE,
+        id_token_verification_data,
+        now(),
+    )
+    .unwrap_err();
+
+    let alg = assert_matches!(
         error,
-        TokenAuthorizationCodeError::IdToken(IdTokenError::Claim(ClaimError::ValidationError {
-            claim: "nonce",
-            ..
-        }))
+        IdTokenError::TokenHashMismatch { claim: "at_hash", alg } => alg
     );
+    assert_eq!(alg, ID_TOKEN_SIGNING_ALG);
 }
 
 #[tokio::test]
@@ -382,6 +756,7 @@ async fn fail_access_token_with_authorization_code_no_id_token() {
         nonce: nonce.clone(),
         redirect_uri,
         code_challenge_verifier: Some(CODE_VERIFIER.to_owned()),
+        resource: None,
     };
 
     let id_token_verification_data = JwtVerificationData {
@@ -389,6 +764,7 @@ async fn fail_access_token_with_authorization_code_no_id_token() {
         jwks: &PublicJsonWebKeySet::default(),
         client_id: &CLIENT_ID.to_owned(),
         signing_algorithm: &ID_TOKEN_SIGNING_ALG,
+        leeway: JwtVerificationData::default_leeway(),
     };
 
     Mock::given(method("POST"))
@@ -414,6 +790,7 @@ async fn fail_access_token_with_authorization_code_no_id_token() {
         AUTHORIZATION_CODE.to_owned(),
         validation_data,
         Some(id_token_verification_data),
+        false,
         now(),
         &mut rng,
     )
@@ -34,6 +34,7 @@ use crate::{keystore, now, CLIENT_ID, ID_TOKEN_SIGNING_ALG, SUBJECT_IDENTIFIER};
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum IdTokenFlag {
     WrongExpiration,
+    ExpiredSince(Duration),
     WrongSubject,
 }
 
@@ -66,15 +67,12 @@ fn id_token(
 
     claims::IAT.insert(&mut claims, now).unwrap();
 
-    if flag == Some(IdTokenFlag::WrongExpiration) {
-        claims::EXP
-            .insert(&mut claims, now - Duration::hours(1))
-            .unwrap();
-    } else {
-        claims::EXP
-            .insert(&mut claims, now + Duration::hours(1))
-            .unwrap();
-    }
+    let exp = match flag {
+        Some(IdTokenFlag::WrongExpiration) => now - Duration::hours(1),
+        Some(IdTokenFlag::ExpiredSince(duration)) => now - duration,
+        _ => now + Duration::hours(1),
+    };
+    claims::EXP.insert(&mut claims, exp).unwrap();
 
     if let Some(auth_time) = auth_time {
         claims::AUTH_TIME.insert(&mut claims, auth_time).unwrap();
@@ -100,6 +98,7 @@ async fn pass_verify_id_token() {
         jwks: &jwks,
         client_id: &CLIENT_ID.to_owned(),
         signing_algorithm: &ID_TOKEN_SIGNING_ALG,
+        leeway: JwtVerificationData::default_leeway(),
     };
 
     verify_id_token(
@@ -123,6 +122,7 @@ async fn fail_verify_id_token_wrong_issuer() {
         jwks: &jwks,
         client_id: &CLIENT_ID.to_owned(),
         signing_algorithm: &ID_TOKEN_SIGNING_ALG,
+        leeway: JwtVerificationData::default_leeway(),
     };
 
     let error = verify_id_token(id_token.as_str(), verification_data, None, now).unwrap_err();
@@ -147,6 +147,7 @@ async fn fail_verify_id_token_wrong_audience() {
         jwks: &jwks,
         client_id: &"wrong_client_id".to_owned(),
         signing_algorithm: &ID_TOKEN_SIGNING_ALG,
+        leeway: JwtVerificationData::default_leeway(),
     };
 
     let error = verify_id_token(id_token.as_str(), verification_data, None, now).unwrap_err();
@@ -171,6 +172,7 @@ async fn fail_verify_id_token_wrong_signing_algorithm() {
         jwks: &jwks,
         client_id: &CLIENT_ID.to_owned(),
         signing_algorithm: &JsonWebSignatureAlg::Unknown("wrong_algorithm".to_owned()),
+        leeway: JwtVerificationData::default_leeway(),
     };
 
     let error = verify_id_token(id_token.as_str(), verification_data, None, now).unwrap_err();
@@ -192,6 +194,7 @@ async fn fail_verify_id_token_wrong_expiration() {
         jwks: &jwks,
         client_id: &CLIENT_ID.to_owned(),
         signing_algorithm: &ID_TOKEN_SIGNING_ALG,
+        leeway: JwtVerificationData::default_leeway(),
     };
 
     let error = verify_id_token(id_token.as_str(), verification_data, None, now).unwrap_err();
@@ -199,6 +202,53 @@ async fn fail_verify_id_token_wrong_expiration() {
     assert_matches!(error, IdTokenError::Claim(_));
 }
 
+#[tokio::test]
+async fn pass_verify_id_token_expired_within_leeway() {
+    let issuer = "http://localhost/";
+    let (id_token, jwks) = id_token(
+        issuer,
+        Some(IdTokenFlag::ExpiredSince(Duration::seconds(30))),
+        None,
+    );
+    let now = now();
+
+    let verification_data = JwtVerificationData {
+        issuer,
+        jwks: &jwks,
+        client_id: &CLIENT_ID.to_owned(),
+        signing_algorithm: &ID_TOKEN_SIGNING_ALG,
+        leeway: Duration::seconds(60),
+    };
+
+    verify_id_token(id_token.as_str(), verification_data, None, now).unwrap();
+}
+
+#[tokio::test]
+async fn fail_verify_id_token_expired_beyond_leeway() {
+    let issuer = "http://localhost/";
+    let (id_token, jwks) = id_token(
+        issuer,
+        Some(IdTokenFlag::ExpiredSince(Duration::seconds(90))),
+        None,
+    );
+    let now = now();
+
+    let verification_data = JwtVerificationData {
+        issuer,
+        jwks: &jwks,
+        client_id: &CLIENT_ID.to_owned(),
+        signing_algorithm: &ID_TOKEN_SIGNING_ALG,
+        leeway: Duration::seconds(60),
+    };
+
+    let error = verify_id_token(id_token.as_str(), verification_data, None, now).unwrap_err();
+
+    assert_matches!(
+        error,
+        IdTokenError::Claim(ClaimError::ValidationError { claim: "exp", .. })
+    );
+}
+
 #[tokio::test]
 async fn fail_verify_id_token_wrong_subject() {
     let issuer = "http://localhost/";
@@ -211,6 +261,7 @@ async fn fail_verify_id_token_wrong_subject() {
         jwks: &jwks,
         client_id: &CLIENT_ID.to_owned(),
         signing_algorithm: &ID_TOKEN_SIGNING_ALG,
+        leeway: JwtVerificationData::default_leeway(),
     };
 
     let error = verify_id_token(
@@ -236,6 +287,7 @@ async fn fail_verify_id_token_wrong_auth_time() {
         jwks: &jwks,
         client_id: &CLIENT_ID.to_owned(),
         signing_algorithm: &ID_TOKEN_SIGNING_ALG,
+        leeway: JwtVerificationData::default_leeway(),
     };
 
     let error = verify_id_token(
@@ -16,7 +16,7 @@ use std::collections::HashMap;
 
 use assert_matches::assert_matches;
 use mas_iana::oauth::{OAuthAccessTokenType, OAuthClientAuthenticationMethod};
-use mas_oidc_client::requests::refresh_token::refresh_access_token;
+use mas_oidc_client::requests::{jose::JwtVerificationData, refresh_token::refresh_access_token};
 use oauth2_types::requests::AccessTokenResponse;
 use rand::SeedableRng;
 use wiremock::{
@@ -24,7 +24,10 @@ use wiremock::{
     Mock, Request, ResponseTemplate,
 };
 
-use crate::{client_credentials, init_test, now, ACCESS_TOKEN, CLIENT_ID, REFRESH_TOKEN};
+use crate::{
+    client_credentials, id_token, init_test, now, ACCESS_TOKEN, CLIENT_ID, ID_TOKEN_SIGNING_ALG,
+    REFRESH_TOKEN,
+};
 
 #[tokio::test]
 async fn pass_refresh_access_token() {
@@ -97,3 +100,55 @@ async fn pass_refresh_access_token() {
     assert_eq!(response.refresh_token, None);
     assert_matches!(response_id_token, None);
 }
+
+#[tokio::test]
+async fn pass_refresh_access_token_with_id_token() {
+    let (http_service, mock_server, issuer) = init_test().await;
+    let client_credentials =
+        client_credentials(OAuthClientAuthenticationMethod::None, &issuer, None);
+    let token_endpoint = issuer.join("token").unwrap();
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+
+    let (auth_id_token, _) = id_token(issuer.as_str());
+    let (id_token, jwks) = id_token(issuer.as_str());
+    let id_token_verification_data = JwtVerificationData {
+        issuer: issuer.as_str(),
+        jwks: &jwks,
+        client_id: &CLIENT_ID.to_owned(),
+        signing_algorithm: &ID_TOKEN_SIGNING_ALG,
+        leeway: JwtVerificationData::default_leeway(),
+    };
+
+    Mock::given(method("POST"))
+        .and(path("/token"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(AccessTokenResponse {
+                access_token: ACCESS_TOKEN.to_owned(),
+                refresh_token: Some(REFRESH_TOKEN.to_owned()),
+                id_token: Some(id_token.to_string()),
+                token_type: OAuthAccessTokenType::Bearer,
+                expires_in: None,
+                scope: None,
+            }),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let (response, response_id_token) = refresh_access_token(
+        &http_service,
+        client_credentials,
+        &token_endpoint,
+        REFRESH_TOKEN.to_owned(),
+        None,
+        Some(id_token_verification_data),
+        Some(&auth_id_token),
+        now(),
+        &mut rng,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(response.access_token, ACCESS_TOKEN);
+    assert_eq!(response.refresh_token.as_deref(), Some(REFRESH_TOKEN));
+    assert_matches!(response_id_token, Some(_));
+}
@@ -14,8 +14,11 @@
 
 use std::collections::HashMap;
 
+use assert_matches::assert_matches;
+use http::StatusCode;
 use mas_iana::oauth::{OAuthClientAuthenticationMethod, OAuthTokenTypeHint};
-use mas_oidc_client::requests::revocation::revoke_token;
+use mas_oidc_client::{error::TokenRevokeError, requests::revocation::revoke_token};
+use oauth2_types::errors::ClientErrorCode;
 use rand::SeedableRng;
 use wiremock::{
     matchers::{method, path},
@@ -80,3 +83,39 @@ async fn pass_revoke_token() {
     .await
     .unwrap();
 }
+
+#[tokio::test]
+async fn fail_revoke_token_http_error() {
+    let (http_service, mock_server, issuer) = init_test().await;
+    let client_credentials =
+        client_credentials(OAuthClientAuthenticationMethod::None, &issuer, None);
+    let revocation_endpoint = issuer.join("revoke").unwrap();
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+
+    Mock::given(method("POST"))
+        .and(path("/revoke"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+            "error": "invalid_client",
+            "error_description": "Unknown client",
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let error = revoke_token(
+        &http_service,
+        client_credentials,
+        &revocation_endpoint,
+        ACCESS_TOKEN.to_owned(),
+        None,
+        crate::now(),
+        &mut rng,
+    )
+    .await
+    .unwrap_err();
+
+    let http_error = assert_matches!(error, TokenRevokeError::Http(e) => e);
+    assert_eq!(http_error.status, StatusCode::UNAUTHORIZED);
+    let body = http_error.body.unwrap();
+    assert_eq!(body.error, ClientErrorCode::InvalidClient);
+    assert_eq!(body.error_description.as_deref(), Some("Unknown client"));
+}
@@ -0,0 +1,248 @@
+// Copyright 2022 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Unpacking of OPA bundles, as produced by `opa build -t wasm`
+
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error("failed to read bundle archive")]
+    Archive(#[from] std::io::Error),
+
+    #[error("invalid entry path in bundle")]
+    InvalidPath,
+
+    #[error("failed to parse bundled data at {path}")]
+    Data {
+        path: String,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("bundle does not contain a WASM module")]
+    MissingModule,
+
+    #[error("bundle contains more than one WASM module")]
+    MultipleModules,
+}
+
+/// Extract the WASM module and the merged data documents from a gzipped
+/// tarball
+///
+/// If set, `overrides` is merged over the data documents found in the bundle.
+pub(crate) fn unpack(
+    bundle: &[u8],
+    overrides: Option<Value>,
+) -> Result<(Vec<u8>, Value), BundleError> {
+    let mut archive = tar::Archive::new(GzDecoder::new(bundle));
+    let mut module = None;
+    let mut data = Value::Object(serde_json::Map::new());
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = std::str::from_utf8(&entry.path_bytes())
+            .map_err(|_| BundleError::InvalidPath)?
+            .to_owned();
+
+        // Entries are usually prefixed with a slash, sometimes with "./"
+        let segments: Vec<&str> = path
+            .split('/')
+            .filter(|s| !s.is_empty() && *s != ".")
+            .collect();
+
+        let Some((file_name, prefix)) = segments.split_last() else {
+            return Err(BundleError::InvalidPath);
+        };
+
+        let is_module = file_name.rsplit_once('.').map_or(false, |(_, extension)| {
+            extension.eq_ignore_ascii_case("wasm")
+        });
+
+        if is_module {
+            if module.is_some() {
+                return Err(BundleError::MultipleModules);
+            }
+
+            let mut buf = Vec::new();
+            entry.read_to_end(&mut buf)?;
+            module = Some(buf);
+        } else if *file_name == "data.json" {
+            let document: Value =
+                serde_json::from_reader(&mut entry).map_err(|source| BundleError::Data {
+                    path: path.clone(),
+                    source,
+                })?;
+
+            // Data documents are rooted at the path of their directory
+            let document = prefix.iter().rev().fold(document, |document, segment| {
+                let mut map = serde_json::Map::new();
+                map.insert((*segment).to_owned(), document);
+                Value::Object(map)
+            });
+
+            merge(&mut data, document);
+        }
+    }
+
+    if let Some(overrides) = overrides {
+        merge(&mut data, overrides);
+    }
+
+    let module = module.ok_or(BundleError::MissingModule)?;
+    Ok((module, data))
+}
+
+/// Deep-merge `other` into `base`, values from `other` taking precedence
+pub(crate) fn merge(base: &mut Value, other: Value) {
+    match (base, other) {
+        (Value::Object(base), Value::Object(other)) => {
+            for (key, value) in other {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, other) => *base = other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    /// Build a gzipped tarball from a list of paths and contents
+    fn build_bundle(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (path, content) in files {
+            // OPA uses absolute paths, which `Header::set_path` refuses, so the
+            // name is written directly
+            let mut header = tar::Header::new_gnu();
+            header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_size(content.len().try_into().unwrap());
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, content.as_bytes()).unwrap();
+        }
+
+        let mut encoder = builder.into_inner().unwrap();
+        encoder.flush().unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn sample_bundle() -> Vec<u8> {
+        build_bundle(&[
+            ("/policy.wasm", "\0asm"),
+            ("/data.json", r#"{"allowed_domains": ["element.io"]}"#),
+            ("./passwords/data.json", r#"{"min_length": 8}"#),
+        ])
+    }
+
+    #[test]
+    fn test_unpack() {
+        let (module, data) = unpack(&sample_bundle(), None).unwrap();
+        assert_eq!(module, b"\0asm");
+        assert_eq!(
+            data,
+            serde_json::json!({
+                "allowed_domains": ["element.io"],
+                "passwords": {
+                    "min_length": 8,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_unpack_with_overrides() {
+        let overrides = serde_json::json!({
+            "passwords": {
+                "min_length": 12,
+            },
+        });
+
+        let (_module, data) = unpack(&sample_bundle(), Some(overrides)).unwrap();
+        assert_eq!(
+            data,
+            serde_json::json!({
+                "allowed_domains": ["element.io"],
+                "passwords": {
+                    "min_length": 12,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_unpack_errors() {
+        let bundle = build_bundle(&[("/data.json", "{}")]);
+        assert!(matches!(
+            unpack(&bundle, None),
+            Err(BundleError::MissingModule)
+        ));
+
+        let bundle = build_bundle(&[("/a.wasm", "\0asm"), ("/b.wasm", "\0asm")]);
+        assert!(matches!(
+            unpack(&bundle, None),
+            Err(BundleError::MultipleModules)
+        ));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut base = serde_json::json!({
+            "allowed_domains": ["element.io"],
+            "passwords": {
+                "min_length": 8,
+                "require_number": true,
+            },
+        });
+
+        merge(
+            &mut base,
+            serde_json::json!({
+                "allowed_domains": ["example.com"],
+                "passwords": {
+                    "min_length": 12,
+                },
+            }),
+        );
+
+        assert_eq!(
+            base,
+            serde_json::json!({
+                "allowed_domains": ["example.com"],
+                "passwords": {
+                    "min_length": 12,
+                    "require_number": true,
+                },
+            })
+        );
+    }
+}
@@ -17,13 +17,34 @@
 #![warn(clippy::pedantic)]
 #![allow(clippy::missing_errors_doc)]
 
+mod bundle;
+
+use std::{
+    collections::HashSet,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
 use mas_data_model::{AuthorizationGrant, User};
 use oauth2_types::registration::VerifiedClientMetadata;
 use opa_wasm::Runtime;
-use serde::Deserialize;
+use opentelemetry::{
+    metrics::{Counter, Histogram},
+    Context, KeyValue,
+};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio::io::{AsyncRead, AsyncReadExt};
-use wasmtime::{Config, Engine, Module, Store};
+use wasmtime::{Config, Engine, Module, Store, Trap};
+
+pub use self::bundle::BundleError;
+
+/// How often the engine epoch is incremented when an evaluation budget is set
+const EPOCH_TICK: Duration = Duration::from_millis(10);
 
 #[derive(Debug, Error)]
 pub enum LoadError {
@@ -42,6 +63,12 @@ pub enum LoadError {
     #[error("failed to instantiate a test instance")]
     Instantiate(#[source] InstanciateError),
 
+    #[error("failed to load policy bundle")]
+    Bundle(#[source] BundleError),
+
+    #[error("failed to start the evaluation budget ticker")]
+    Ticker(#[source] std::io::Error),
+
     #[cfg(feature = "cache")]
     #[error("could not load wasmtime cache configuration")]
     CacheSetup(#[source] anyhow::Error),
@@ -59,27 +86,224 @@ pub enum InstanciateError {
     LoadData(#[source] anyhow::Error),
 }
 
+#[derive(Debug, Clone)]
+struct PolicyMetrics {
+    evaluation_duration: Histogram<f64>,
+    evaluations: Counter<u64>,
+}
+
+impl PolicyMetrics {
+    fn new() -> Self {
+        let meter = opentelemetry::global::meter("mas-policy");
+
+        let evaluation_duration = meter
+            .f64_histogram("policy.evaluation_duration_seconds")
+            .with_description("The policy evaluation latencies in seconds.")
+            .init();
+        let evaluations = meter
+            .u64_counter("policy.evaluations_total")
+            .with_description("Total number of policy evaluations, by outcome.")
+            .init();
+
+        Self {
+            evaluation_duration,
+            evaluations,
+        }
+    }
+
+    fn record(&self, entrypoint: &str, duration: Duration, result: &EvaluationResult) {
+        let cx = Context::current();
+        let entrypoint = KeyValue::new("entrypoint", entrypoint.to_owned());
+        let outcome = if result.valid() { "valid" } else { "invalid" };
+
+        self.evaluation_duration
+            .record(&cx, duration.as_secs_f64(), &[entrypoint.clone()]);
+        self.evaluations
+            .add(&cx, 1, &[entrypoint, KeyValue::new("outcome", outcome)]);
+    }
+}
+
+/// Background thread incrementing the epoch of an [`Engine`], stopped on drop
+///
+/// This runs on a dedicated thread rather than as a task on the async runtime,
+/// so that the deadline is enforced even if the runtime is single-threaded or
+/// busy running the evaluation itself.
+#[derive(Debug)]
+struct EpochTicker {
+    stop: Arc<AtomicBool>,
+}
+
+impl EpochTicker {
+    fn spawn(engine: Engine) -> Result<Self, LoadError> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        std::thread::Builder::new()
+            .name("policy-epoch-ticker".to_owned())
+            .spawn(move || loop {
+                std::thread::sleep(EPOCH_TICK);
+                if thread_stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                engine.increment_epoch();
+            })
+            .map_err(LoadError::Ticker)?;
+
+        Ok(Self { stop })
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
 pub struct PolicyFactory {
     engine: Engine,
     module: Module,
+    ticker: Option<Arc<EpochTicker>>,
+    evaluation_budget: Option<Duration>,
+    metrics: PolicyMetrics,
     data: serde_json::Value,
     register_entrypoint: String,
     client_registration_entrypoint: String,
     authorization_grant_endpoint: String,
+    email_entrypoint: String,
 }
 
 impl PolicyFactory {
     #[tracing::instrument(skip(source), err)]
     pub async fn load(
-        mut source: impl AsyncRead + std::marker::Unpin,
+        source: impl AsyncRead + std::marker::Unpin,
         data: serde_json::Value,
         register_entrypoint: String,
         client_registration_entrypoint: String,
         authorization_grant_endpoint: String,
+        email_entrypoint: String,
+        evaluation_budget: Option<Duration>,
+    ) -> Result<Self, LoadError> {
+        let (engine, module) = Self::compile(source, evaluation_budget.is_some()).await?;
+        let ticker = evaluation_budget
+            .map(|_| EpochTicker::spawn(engine.clone()).map(Arc::new))
+            .transpose()?;
+
+        let factory = Self {
+            engine,
+            module,
+            ticker,
+            evaluation_budget,
+            metrics: PolicyMetrics::new(),
+            data,
+            register_entrypoint,
+            client_registration_entrypoint,
+            authorization_grant_endpoint,
+            email_entrypoint,
+        };
+
+        // Try to instanciate
+        factory
+            .instantiate()
+            .await
+            .map_err(LoadError::Instantiate)?;
+
+        Ok(factory)
+    }
+
+    /// Load the policy from an OPA bundle, a gzipped tarball containing the
+    /// WASM module and `data.json` documents.
+    ///
+    /// If set, the `data` passed here is merged over the data bundled with the
+    /// policy.
+    #[tracing::instrument(skip(source, data), err)]
+    pub async fn load_bundle(
+        mut source: impl AsyncRead + std::marker::Unpin,
+        data: Option<serde_json::Value>,
+        register_entrypoint: String,
+        client_registration_entrypoint: String,
+        authorization_grant_endpoint: String,
+        email_entrypoint: String,
+        evaluation_budget: Option<Duration>,
     ) -> Result<Self, LoadError> {
+        let mut buf = Vec::new();
+        source.read_to_end(&mut buf).await?;
+
+        // Decompressing is CPU-bound, so spawn that in a blocking task
+        let (module, data) = tokio::task::spawn_blocking(move || bundle::unpack(&buf, data))
+            .await?
+            .map_err(LoadError::Bundle)?;
+
+        Self::load(
+            &module[..],
+            data,
+            register_entrypoint,
+            client_registration_entrypoint,
+            authorization_grant_endpoint,
+            email_entrypoint,
+            evaluation_budget,
+        )
+        .await
+    }
+
+    /// Recompile the policy from a new WASM module, and swap it in place of
+    /// the current one if a test instance can be created from it.
+    ///
+    /// On error, the factory keeps using the previous module. Already
+    /// instantiated [`Policy`] are not affected by the reload: they keep the
+    /// previous engine ticking until they are dropped.
+    #[tracing::instrument(skip(self, source), err)]
+    pub async fn reload(
+        &mut self,
+        source: impl AsyncRead + std::marker::Unpin,
+    ) -> Result<(), LoadError> {
+        let (engine, module) = Self::compile(source, self.evaluation_budget.is_some()).await?;
+        let ticker = self
+            .evaluation_budget
+            .map(|_| EpochTicker::spawn(engine.clone()).map(Arc::new))
+            .transpose()?;
+
+        let candidate = Self {
+            engine,
+            module,
+            ticker,
+            evaluation_budget: self.evaluation_budget,
+            metrics: self.metrics.clone(),
+            data: self.data.clone(),
+            register_entrypoint: self.register_entrypoint.clone(),
+            client_registration_entrypoint: self.client_registration_entrypoint.clone(),
+            authorization_grant_endpoint: self.authorization_grant_endpoint.clone(),
+            email_entrypoint: self.email_entrypoint.clone(),
+        };
+
+        // Try to instanciate before swapping the module
+        candidate
+            .instantiate()
+            .await
+            .map_err(LoadError::Instantiate)?;
+
+        self.engine = candidate.engine;
+        self.module = candidate.module;
+        self.ticker = candidate.ticker;
+
+        Ok(())
+    }
+
+    /// Replace the data passed to the policy, without recompiling the module.
+    ///
+    /// Only the [`Policy`] instantiated after this call use the new data.
+    /// Already instantiated ones keep using the previous data.
+    pub fn set_data(&mut self, data: serde_json::Value) {
+        self.data = data;
+    }
+
+    async fn compile(
+        mut source: impl AsyncRead + std::marker::Unpin,
+        epoch_interruption: bool,
+    ) -> Result<(Engine, Module), LoadError> {
         let mut config = Config::default();
         config.async_support(true);
         config.cranelift_opt_level(wasmtime::OptLevel::Speed);
+        config.epoch_interruption(epoch_interruption);
 
         #[cfg(feature = "cache")]
         config
@@ -99,38 +323,48 @@ impl PolicyFactory {
         .await?
         .map_err(LoadError::Compilation)?;
 
-        let factory = Self {
-            engine,
-            module,
-            data,
-            register_entrypoint,
-            client_registration_entrypoint,
-            authorization_grant_endpoint,
-        };
-
-        // Try to instanciate
-        factory
-            .instantiate()
-            .await
-            .map_err(LoadError::Instantiate)?;
-
-        Ok(factory)
+        Ok((engine, module))
     }
 
     #[tracing::instrument(skip(self), err)]
     pub async fn instantiate(&self) -> Result<Policy, InstanciateError> {
         let mut store = Store::new(&self.engine, ());
+        let epoch_deadline = self.evaluation_budget.map(epoch_deadline);
+        if let Some(epoch_deadline) = epoch_deadline {
+            store.set_epoch_deadline(epoch_deadline);
+        }
+
         let runtime = Runtime::new(&mut store, &self.module)
             .await
             .map_err(InstanciateError::Runtime)?;
 
         // Check that we have the required entrypoints
-        let entrypoints = runtime.entrypoints();
+        self.check_entrypoints(&runtime.entrypoints())?;
+
+        let instance = runtime
+            .with_data(&mut store, &self.data)
+            .await
+            .map_err(InstanciateError::LoadData)?;
+
+        Ok(Policy {
+            store,
+            instance,
+            epoch_deadline,
+            metrics: self.metrics.clone(),
+            register_entrypoint: self.register_entrypoint.clone(),
+            client_registration_entrypoint: self.client_registration_entrypoint.clone(),
+            authorization_grant_endpoint: self.authorization_grant_endpoint.clone(),
+            email_entrypoint: self.email_entrypoint.clone(),
+            _ticker: self.ticker.clone(),
+        })
+    }
 
+    fn check_entrypoints(&self, entrypoints: &HashSet<&str>) -> Result<(), InstanciateError> {
         for e in [
             self.register_entrypoint.as_str(),
             self.client_registration_entrypoint.as_str(),
             self.authorization_grant_endpoint.as_str(),
+            self.email_entrypoint.as_str(),
         ] {
             if !entrypoints.contains(e) {
                 return Err(InstanciateError::MissingEntrypoint {
@@ -139,18 +373,52 @@ impl PolicyFactory {
             }
         }
 
-        let instance = runtime
-            .with_data(&mut store, &self.data)
+        Ok(())
+    }
+
+    /// Check that the policy can still be instantiated, and that it exposes
+    /// the configured entrypoints.
+    ///
+    /// This is cheaper than [`PolicyFactory::instantiate`], as it doesn't load
+    /// the policy data, and is meant to be called by health checks.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn healthcheck(&self) -> Result<(), InstanciateError> {
+        let mut store = Store::new(&self.engine, ());
+        if let Some(epoch_deadline) = self.evaluation_budget.map(epoch_deadline) {
+            store.set_epoch_deadline(epoch_deadline);
+        }
+
+        let runtime = Runtime::new(&mut store, &self.module)
             .await
-            .map_err(InstanciateError::LoadData)?;
+            .map_err(InstanciateError::Runtime)?;
 
-        Ok(Policy {
-            store,
-            instance,
-            register_entrypoint: self.register_entrypoint.clone(),
-            client_registration_entrypoint: self.client_registration_entrypoint.clone(),
-            authorization_grant_endpoint: self.authorization_grant_endpoint.clone(),
-        })
+        self.check_entrypoints(&runtime.entrypoints())
+    }
+
+    /// List the entrypoints exposed by the compiled policy, sorted by name.
+    ///
+    /// This instantiates a throwaway runtime, without checking that the
+    /// configured entrypoints are present, which makes it useful to diagnose a
+    /// [`InstanciateError::MissingEntrypoint`] error.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn available_entrypoints(&self) -> Result<Vec<String>, InstanciateError> {
+        let mut store = Store::new(&self.engine, ());
+        if let Some(epoch_deadline) = self.evaluation_budget.map(epoch_deadline) {
+            store.set_epoch_deadline(epoch_deadline);
+        }
+
+        let runtime = Runtime::new(&mut store, &self.module)
+            .await
+            .map_err(InstanciateError::Runtime)?;
+
+        let mut entrypoints: Vec<String> = runtime
+            .entrypoints()
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .collect();
+        entrypoints.sort();
+
+        Ok(entrypoints)
     }
 }
 
@@ -158,6 +426,7 @@ impl PolicyFactory {
 pub struct Violation {
     pub msg: String,
     pub field: Option<String>,
+    pub code: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -173,30 +442,76 @@ impl EvaluationResult {
     }
 }
 
+/// Compute the number of epoch ticks corresponding to an evaluation budget
+fn epoch_deadline(budget: Duration) -> u64 {
+    let ticks = (budget.as_nanos() / EPOCH_TICK.as_nanos()).max(1);
+    u64::try_from(ticks).unwrap_or(u64::MAX)
+}
+
+/// Informations about the client making a registration request
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct RegistrationContext {
+    /// The IP address of the requester
+    pub ip: Option<IpAddr>,
+
+    /// The user agent of the requester
+    pub user_agent: Option<String>,
+}
+
 pub struct Policy {
     store: Store<()>,
     instance: opa_wasm::Policy<opa_wasm::DefaultContext>,
+    epoch_deadline: Option<u64>,
+    metrics: PolicyMetrics,
     register_entrypoint: String,
     client_registration_entrypoint: String,
     authorization_grant_endpoint: String,
+    email_entrypoint: String,
+
+    /// Keeps the epoch of the engine this policy was instantiated from
+    /// ticking, even if the factory got reloaded since then
+    _ticker: Option<Arc<EpochTicker>>,
 }
 
 #[derive(Debug, Error)]
-#[error("failed to evaluate policy")]
 pub enum EvaluationError {
+    #[error("failed to evaluate policy")]
     Serialization(#[from] serde_json::Error),
-    Evaluation(#[from] anyhow::Error),
+
+    #[error("failed to evaluate policy")]
+    Evaluation(#[source] anyhow::Error),
+
+    #[error("policy evaluation exceeded its budget")]
+    Timeout,
+}
+
+impl From<anyhow::Error> for EvaluationError {
+    fn from(err: anyhow::Error) -> Self {
+        if matches!(err.downcast_ref::<Trap>(), Some(Trap::Interrupt)) {
+            Self::Timeout
+        } else {
+            Self::Evaluation(err)
+        }
+    }
 }
 
 impl Policy {
+    /// Reset the evaluation budget before running an entrypoint
+    fn reset_budget(&mut self) {
+        if let Some(epoch_deadline) = self.epoch_deadline {
+            self.store.set_epoch_deadline(epoch_deadline);
+        }
+    }
+
     #[tracing::instrument(skip(self, password))]
     pub async fn evaluate_register(
         &mut self,
         username: &str,
         password: &str,
         email: &str,
+        context: Option<&RegistrationContext>,
     ) -> Result<EvaluationResult, EvaluationError> {
-        let input = serde_json::json!({
+        let mut input = serde_json::json!({
             "user": {
                 "username": username,
                 "password": password,
@@ -204,10 +519,18 @@ impl Policy {
             }
         });
 
+        if let Some(context) = context {
+            input["requester"] = serde_json::to_value(context)?;
+        }
+
+        self.reset_budget();
+        let start = Instant::now();
         let [res]: [EvaluationResult; 1] = self
             .instance
             .evaluate(&mut self.store, &self.register_entrypoint, &input)
             .await?;
+        self.metrics
+            .record(&self.register_entrypoint, start.elapsed(), &res);
 
         Ok(res)
     }
@@ -222,6 +545,8 @@ impl Policy {
             "client_metadata": client_metadata,
         });
 
+        self.reset_budget();
+        let start = Instant::now();
         let [res]: [EvaluationResult; 1] = self
             .instance
             .evaluate(
@@ -230,6 +555,8 @@ impl Policy {
                 &input,
             )
             .await?;
+        self.metrics
+            .record(&self.client_registration_entrypoint, start.elapsed(), &res);
 
         Ok(res)
     }
@@ -247,10 +574,72 @@ impl Policy {
             "user": user,
         });
 
+        self.evaluate_authorization_grant_input(&input).await
+    }
+
+    /// Evaluate multiple authorization grants of the same user.
+    ///
+    /// The user is only serialized once, which makes it cheaper than calling
+    /// [`Policy::evaluate_authorization_grant`] for each grant. The results
+    /// are in the same order as the grants.
+    #[tracing::instrument(skip_all, fields(authorization_grants.count = authorization_grants.len()))]
+    pub async fn evaluate_authorization_grants(
+        &mut self,
+        authorization_grants: &[AuthorizationGrant],
+        user: &User,
+    ) -> Result<Vec<EvaluationResult>, EvaluationError> {
+        let user = serde_json::to_value(user)?;
+        let mut input = serde_json::json!({
+            "authorization_grant": null,
+            "user": user,
+        });
+
+        let mut results = Vec::with_capacity(authorization_grants.len());
+        for authorization_grant in authorization_grants {
+            input["authorization_grant"] = serde_json::to_value(authorization_grant)?;
+            results.push(self.evaluate_authorization_grant_input(&input).await?);
+        }
+
+        Ok(results)
+    }
+
+    async fn evaluate_authorization_grant_input(
+        &mut self,
+        input: &serde_json::Value,
+    ) -> Result<EvaluationResult, EvaluationError> {
+        self.reset_budget();
+        let start = Instant::now();
+        let [res]: [EvaluationResult; 1] = self
+            .instance
+            .evaluate(&mut self.store, &self.authorization_grant_endpoint, input)
+            .await?;
+        self.metrics
+            .record(&self.authorization_grant_endpoint, start.elapsed(), &res);
+
+        Ok(res)
+    }
+
+    /// Evaluate an email address a user wants to add to their account.
+    #[tracing::instrument(skip(self))]
+    pub async fn evaluate_email(
+        &mut self,
+        email: &str,
+        user: &User,
+    ) -> Result<EvaluationResult, EvaluationError> {
+        let user = serde_json::to_value(user)?;
+        let input = serde_json::json!({
+            "email": email,
+            "user": user,
+        });
+
+        self.reset_budget();
+        let start = Instant::now();
         let [res]: [EvaluationResult; 1] = self
             .instance
-            .evaluate(&mut self.store, &self.authorization_grant_endpoint, &input)
+            .evaluate(&mut self.store, &self.email_entrypoint, &input)
             .await?;
+        self.metrics
+            .record(&self.email_entrypoint, start.elapsed(), &res);
 
         Ok(res)
     }
@@ -258,8 +647,69 @@ impl Policy {
 
 #[cfg(test)]
 mod tests {
+    use chrono::TimeZone;
+    use rand::SeedableRng;
+
     use super::*;
 
+    #[allow(clippy::disallowed_types)]
+    fn policy_path() -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join("..")
+            .join("policies")
+            .join("policy.wasm")
+    }
+
+    async fn load_policy_factory() -> PolicyFactory {
+        let file = tokio::fs::File::open(policy_path()).await.unwrap();
+
+        PolicyFactory::load(
+            file,
+            serde_json::json!({}),
+            "register/violation".to_owned(),
+            "client_registration/violation".to_owned(),
+            "authorization_grant/violation".to_owned(),
+            "email/violation".to_owned(),
+            None,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_evaluation_timeout() {
+        // A module exporting a function which loops forever:
+        // (module (func (export "loop") (loop (br 0))))
+        const LOOP_MODULE: &[u8] = &[
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x08, 0x01, 0x04, b'l', b'o', b'o', b'p', 0x00, 0x00, // export section
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x03, 0x40, 0x0c, 0x00, 0x0b, 0x0b, // code section
+        ];
+
+        let (engine, module) = PolicyFactory::compile(LOOP_MODULE, true).await.unwrap();
+        let _ticker = EpochTicker::spawn(engine.clone()).unwrap();
+
+        let mut store = Store::new(&engine, ());
+        store.set_epoch_deadline(epoch_deadline(Duration::from_millis(50)));
+
+        let instance = wasmtime::Instance::new_async(&mut store, &module, &[])
+            .await
+            .unwrap();
+        let func = instance
+            .get_typed_func::<(), ()>(&mut store, "loop")
+            .unwrap();
+
+        // This runs on a single-threaded runtime, which the ticker must not rely on
+        let err = func.call_async(&mut store, ()).await.unwrap_err();
+        assert!(matches!(
+            EvaluationError::from(err),
+            EvaluationError::Timeout
+        ));
+    }
+
     #[tokio::test]
     async fn test_register() {
         let data = serde_json::json!({
@@ -267,43 +717,260 @@ mod tests {
             "banned_domains": ["staging.element.io"],
         });
 
-        #[allow(clippy::disallowed_types)]
-        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("..")
-            .join("..")
-            .join("policies")
-            .join("policy.wasm");
+        let mut factory = load_policy_factory().await;
+        factory.set_data(data);
+
+        let mut policy = factory.instantiate().await.unwrap();
+
+        let res = policy
+            .evaluate_register("hello", "hunter2", "hello@example.com", None)
+            .await
+            .unwrap();
+        assert!(!res.valid());
+
+        let res = policy
+            .evaluate_register("hello", "hunter2", "hello@foo.element.io", None)
+            .await
+            .unwrap();
+        assert!(res.valid());
+
+        let res = policy
+            .evaluate_register("hello", "hunter2", "hello@staging.element.io", None)
+            .await
+            .unwrap();
+        assert!(!res.valid());
 
-        let file = tokio::fs::File::open(path).await.unwrap();
+        // Policies not using the requester context are unaffected by it
+        let context = RegistrationContext {
+            ip: Some(std::net::Ipv4Addr::LOCALHOST.into()),
+            user_agent: Some("Mozilla/5.0".to_owned()),
+        };
+        let res = policy
+            .evaluate_register("hello", "hunter2", "hello@foo.element.io", Some(&context))
+            .await
+            .unwrap();
+        assert!(res.valid());
+    }
+
+    #[test]
+    fn test_violation_deserialization() {
+        let violation: Violation = serde_json::from_value(serde_json::json!({
+            "msg": "email domain not allowed",
+            "field": "email",
+            "code": "email_domain_banned",
+        }))
+        .unwrap();
+        assert_eq!(violation.code.as_deref(), Some("email_domain_banned"));
+
+        // Policies which don't set a code are still supported
+        let violation: Violation =
+            serde_json::from_value(serde_json::json!({ "msg": "username too short" })).unwrap();
+        assert_eq!(violation.field, None);
+        assert_eq!(violation.code, None);
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let mut factory = load_policy_factory().await;
 
-        let factory = PolicyFactory::load(
+        // A broken module should be rejected and keep the old one around
+        let res = factory.reload(&b"not a wasm module"[..]).await;
+        assert!(matches!(res, Err(LoadError::Compilation(_))));
+        factory.instantiate().await.unwrap();
+
+        let file = tokio::fs::File::open(policy_path()).await.unwrap();
+        factory.reload(file).await.unwrap();
+        factory.instantiate().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_ticker_alive() {
+        let file = tokio::fs::File::open(policy_path()).await.unwrap();
+        let mut factory = PolicyFactory::load(
             file,
-            data,
+            serde_json::json!({}),
             "register/violation".to_owned(),
             "client_registration/violation".to_owned(),
             "authorization_grant/violation".to_owned(),
+            "email/violation".to_owned(),
+            Some(Duration::from_secs(1)),
         )
         .await
         .unwrap();
 
-        let mut policy = factory.instantiate().await.unwrap();
+        let old_policy = factory.instantiate().await.unwrap();
+        let old_ticker = Arc::downgrade(factory.ticker.as_ref().unwrap());
 
-        let res = policy
-            .evaluate_register("hello", "hunter2", "hello@example.com")
+        let file = tokio::fs::File::open(policy_path()).await.unwrap();
+        factory.reload(file).await.unwrap();
+
+        // The policy instantiated before the reload keeps its engine ticking
+        let ticker = old_ticker.upgrade().unwrap();
+        assert!(!ticker.stop.load(Ordering::Relaxed));
+        drop(ticker);
+
+        // Until it is dropped
+        drop(old_policy);
+        assert!(old_ticker.upgrade().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_set_data() {
+        let mut factory = load_policy_factory().await;
+
+        let mut old_policy = factory.instantiate().await.unwrap();
+
+        factory.set_data(serde_json::json!({
+            "banned_domains": ["example.com"],
+        }));
+        let mut new_policy = factory.instantiate().await.unwrap();
+
+        let res = new_policy
+            .evaluate_register("hello", "hunter2", "hello@example.com", None)
             .await
             .unwrap();
         assert!(!res.valid());
 
+        // The policy instantiated before keeps the old data
+        let res = old_policy
+            .evaluate_register("hello", "hunter2", "hello@example.com", None)
+            .await
+            .unwrap();
+        assert!(res.valid());
+    }
+
+    #[tokio::test]
+    async fn test_healthcheck() {
+        let mut factory = load_policy_factory().await;
+
+        factory.healthcheck().await.unwrap();
+
+        // Point one of the entrypoints to something which doesn't exist
+        factory.register_entrypoint = "register/missing".to_owned();
+        assert!(matches!(
+            factory.healthcheck().await,
+            Err(InstanciateError::MissingEntrypoint { entrypoint }) if entrypoint == "register/missing"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_email() {
+        let now = chrono::Utc.timestamp_opt(1_516_239_022, 0).unwrap();
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let user = &User::samples(now, &mut rng)[0];
+
+        let mut factory = load_policy_factory().await;
+        factory.set_data(serde_json::json!({
+            "allowed_domains": ["element.io", "*.element.io"],
+            "banned_domains": ["staging.element.io"],
+        }));
+        let mut policy = factory.instantiate().await.unwrap();
+
         let res = policy
-            .evaluate_register("hello", "hunter2", "hello@foo.element.io")
+            .evaluate_email("hello@element.io", user)
             .await
             .unwrap();
         assert!(res.valid());
 
         let res = policy
-            .evaluate_register("hello", "hunter2", "hello@staging.element.io")
+            .evaluate_email("hello@example.com", user)
             .await
             .unwrap();
         assert!(!res.valid());
+
+        let res = policy
+            .evaluate_email("hello@staging.element.io", user)
+            .await
+            .unwrap();
+        assert!(!res.valid());
+
+        // Policies without the email entrypoint are rejected
+        factory.email_entrypoint = "email/missing".to_owned();
+        assert!(matches!(
+            factory.healthcheck().await,
+            Err(InstanciateError::MissingEntrypoint { .. })
+        ));
+        assert!(matches!(
+            factory.instantiate().await,
+            Err(InstanciateError::MissingEntrypoint { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_authorization_grants() {
+        let now = chrono::Utc.timestamp_opt(1_516_239_022, 0).unwrap();
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let user = &User::samples(now, &mut rng)[0];
+
+        let client = mas_data_model::Client {
+            id: user.id,
+            client_id: "client".to_owned(),
+            encrypted_client_secret: None,
+            redirect_uris: Vec::new(),
+            response_types: Vec::new(),
+            grant_types: Vec::new(),
+            contacts: Vec::new(),
+            client_name: None,
+            logo_uri: None,
+            client_uri: None,
+            policy_uri: None,
+            tos_uri: None,
+            jwks: None,
+            id_token_signed_response_alg: None,
+            userinfo_signed_response_alg: None,
+            token_endpoint_auth_method: None,
+            token_endpoint_auth_signing_alg: None,
+            initiate_login_uri: None,
+        };
+
+        let grant = |scope: &str| AuthorizationGrant {
+            id: user.id,
+            stage: mas_data_model::AuthorizationGrantStage::Pending,
+            code: None,
+            client: client.clone(),
+            redirect_uri: "https://example.com/callback".parse().unwrap(),
+            scope: scope.parse().unwrap(),
+            state: None,
+            nonce: None,
+            max_age: None,
+            response_mode: oauth2_types::requests::ResponseMode::Query,
+            response_type_id_token: false,
+            created_at: now,
+            requires_consent: false,
+        };
+
+        let factory = load_policy_factory().await;
+        let mut policy = factory.instantiate().await.unwrap();
+
+        let results = policy
+            .evaluate_authorization_grants(
+                &[
+                    grant("openid email"),
+                    grant("openid profile"),
+                    grant("openid"),
+                ],
+                user,
+            )
+            .await
+            .unwrap();
+
+        // The results are in the same order as the grants
+        assert_eq!(results.len(), 3);
+        assert!(results[0].valid());
+        assert!(!results[1].valid());
+        assert!(results[2].valid());
+    }
+
+    #[tokio::test]
+    async fn test_available_entrypoints() {
+        let factory = load_policy_factory().await;
+
+        let entrypoints = factory.available_entrypoints().await.unwrap();
+        assert!(entrypoints.contains(&"register/violation".to_owned()));
+        assert!(entrypoints.contains(&"email/violation".to_owned()));
+
+        let mut sorted = entrypoints.clone();
+        sorted.sort();
+        assert_eq!(entrypoints, sorted);
     }
 }
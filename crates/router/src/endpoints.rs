@@ -412,6 +412,14 @@ impl SimpleRoute for AccountPassword {
     const PATH: &'static str = "/account/password";
 }
 
+/// `GET /account/session`
+#[derive(Default, Debug, Clone)]
+pub struct AccountSession;
+
+impl SimpleRoute for AccountSession {
+    const PATH: &'static str = "/account/session";
+}
+
 /// `GET|POST /account/emails`
 #[derive(Default, Debug, Clone)]
 pub struct AccountEmails;
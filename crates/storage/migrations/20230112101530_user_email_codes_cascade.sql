@@ -0,0 +1,21 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Make sure the confirmation codes go away with the email they belong to
+ALTER TABLE "user_email_confirmation_codes"
+  DROP CONSTRAINT "user_email_confirmation_codes_user_email_id_fkey",
+  ADD CONSTRAINT "user_email_confirmation_codes_user_email_id_fkey"
+    FOREIGN KEY ("user_email_id")
+    REFERENCES "user_emails" ("user_email_id")
+    ON DELETE CASCADE;
@@ -0,0 +1,28 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+CREATE TABLE "user_failed_logins" (
+  "user_failed_login_id" UUID NOT NULL
+    CONSTRAINT "user_failed_logins_pkey"
+    PRIMARY KEY,
+
+  -- This is not a reference to the users table, so that attempts against
+  -- unknown usernames are tracked as well
+  "username" TEXT NOT NULL,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX "user_failed_logins_username_created_at_idx"
+  ON "user_failed_logins" ("username", "created_at");
@@ -0,0 +1,32 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Keeps track of the client assertions which were already used, to prevent
+-- them from being replayed
+CREATE TABLE "oauth2_consumed_client_assertions" (
+  "oauth2_client_id" UUID NOT NULL
+    CONSTRAINT "oauth2_consumed_client_assertions_oauth2_client_id_fkey"
+    REFERENCES "oauth2_clients" ("oauth2_client_id")
+    ON DELETE CASCADE,
+
+  "jti" TEXT NOT NULL,
+  "consumed_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "expires_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+  CONSTRAINT "oauth2_consumed_client_assertions_pkey"
+    PRIMARY KEY ("oauth2_client_id", "jti")
+);
+
+CREATE INDEX "oauth2_consumed_client_assertions_expires_at_idx"
+  ON "oauth2_consumed_client_assertions" ("expires_at");
@@ -0,0 +1,22 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Whether to query the userinfo endpoint of the upstream provider after the
+-- code exchange
+ALTER TABLE "upstream_oauth_providers"
+  ADD COLUMN "fetch_userinfo" BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The claims returned by the userinfo endpoint, if it was queried
+ALTER TABLE "upstream_oauth_authorization_sessions"
+  ADD COLUMN "userinfo" JSONB;
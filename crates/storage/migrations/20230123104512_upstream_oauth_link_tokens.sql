@@ -0,0 +1,21 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+
+-- Keep the tokens returned by the upstream provider, encrypted
+ALTER TABLE "upstream_oauth_links"
+  ADD COLUMN "encrypted_access_token" TEXT,
+  ADD COLUMN "encrypted_refresh_token" TEXT,
+  ADD COLUMN "access_token_expires_at" TIMESTAMP WITH TIME ZONE,
+  ADD COLUMN "tokens_updated_at" TIMESTAMP WITH TIME ZONE;
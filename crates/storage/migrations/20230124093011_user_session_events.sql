@@ -0,0 +1,37 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+
+-- Append-only history of what happened to browser sessions
+CREATE TABLE "user_session_events" (
+  "user_session_event_id" UUID NOT NULL
+    CONSTRAINT "user_session_events_pkey"
+    PRIMARY KEY,
+
+  "user_session_id" UUID NOT NULL
+    CONSTRAINT "user_session_events_user_session_id_fkey"
+    REFERENCES "user_sessions" ("user_session_id"),
+
+  -- Denormalized from the session, to list the events of a user
+  "user_id" UUID NOT NULL
+    CONSTRAINT "user_session_events_user_id_fkey"
+    REFERENCES "users" ("user_id"),
+
+  "kind" TEXT NOT NULL,
+  "metadata" JSONB NOT NULL,
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX "user_session_events_user_id_idx"
+  ON "user_session_events" ("user_id");
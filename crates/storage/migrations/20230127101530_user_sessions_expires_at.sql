@@ -0,0 +1,24 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+
+-- When the browser session stops being valid. Sessions started before this
+-- column was added never expire.
+ALTER TABLE "user_sessions"
+  ADD COLUMN "expires_at" TIMESTAMP WITH TIME ZONE;
+
+-- Used to purge the expired sessions
+CREATE INDEX "user_sessions_expires_at_idx"
+  ON "user_sessions" ("expires_at")
+  WHERE "expires_at" IS NOT NULL;
@@ -0,0 +1,23 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+
+-- Keep the history of browser sessions once they are deleted
+ALTER TABLE "user_session_events"
+  ALTER COLUMN "user_session_id" DROP NOT NULL,
+  DROP CONSTRAINT "user_session_events_user_session_id_fkey",
+  ADD CONSTRAINT "user_session_events_user_session_id_fkey"
+    FOREIGN KEY ("user_session_id")
+    REFERENCES "user_sessions" ("user_session_id")
+    ON DELETE SET NULL;
@@ -0,0 +1,59 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers to get a connection from the pool without waiting forever when it
+//! is exhausted
+
+use std::time::Duration;
+
+use sqlx::{pool::PoolConnection, PgPool, Postgres, Transaction};
+
+use crate::DatabaseError;
+
+/// Acquire a connection from the pool, giving up after `timeout`
+///
+/// # Errors
+///
+/// Returns [`DatabaseError::AcquireTimeout`] if no connection was available
+/// in time, or a [`DatabaseError::Driver`] if acquiring the connection failed
+#[tracing::instrument(skip(pool), err)]
+pub async fn acquire_with_timeout(
+    pool: &PgPool,
+    timeout: Duration,
+) -> Result<PoolConnection<Postgres>, DatabaseError> {
+    let conn = tokio::time::timeout(timeout, pool.acquire())
+        .await
+        .map_err(|_| DatabaseError::AcquireTimeout { timeout })??;
+
+    Ok(conn)
+}
+
+/// Start a transaction on a connection from the pool, giving up on acquiring
+/// the connection after `timeout`
+///
+/// # Errors
+///
+/// Returns [`DatabaseError::AcquireTimeout`] if no connection was available
+/// in time, or a [`DatabaseError::Driver`] if starting the transaction failed
+#[tracing::instrument(skip(pool), err)]
+pub async fn begin_with_timeout(
+    pool: &PgPool,
+    timeout: Duration,
+) -> Result<Transaction<'static, Postgres>, DatabaseError> {
+    let txn = tokio::time::timeout(timeout, pool.begin())
+        .await
+        .map_err(|_| DatabaseError::AcquireTimeout { timeout })??;
+
+    Ok(txn)
+}
@@ -40,6 +40,9 @@ struct CompatAccessTokenLookup {
     compat_session_device_id: String,
     user_id: Uuid,
     user_username: String,
+    user_admin: bool,
+    user_display_name: Option<String>,
+    user_deactivated_at: Option<DateTime<Utc>>,
     user_email_id: Option<Uuid>,
     user_email: Option<String>,
     user_email_created_at: Option<DateTime<Utc>>,
@@ -66,6 +69,9 @@ pub async fn lookup_active_compat_access_token(
                 cs.device_id       AS "compat_session_device_id",
                  u.user_id         AS "user_id!",
                  u.username        AS "user_username!",
+                 u.admin           AS "user_admin!",
+                 u.display_name    AS "user_display_name",
+                 u.deactivated_at  AS "user_deactivated_at",
                 ue.user_email_id   AS "user_email_id?",
                 ue.email           AS "user_email?",
                 ue.created_at      AS "user_email_created_at?",
@@ -80,8 +86,9 @@ pub async fn lookup_active_compat_access_token(
               ON ue.user_email_id = u.primary_user_email_id
 
             WHERE ct.access_token = $1
-              AND (ct.expires_at < $2 OR ct.expires_at IS NULL)
+              AND (ct.expires_at > $2 OR ct.expires_at IS NULL)
               AND cs.finished_at IS NULL 
+              AND u.deactivated_at IS NULL
         "#,
         token,
         clock.now(),
@@ -127,6 +134,9 @@ pub async fn lookup_active_compat_access_token(
         username: res.user_username,
         sub: user_id.to_string(),
         primary_email,
+        display_name: res.user_display_name,
+        deactivated_at: res.user_deactivated_at,
+        admin: res.user_admin,
     };
 
     let id = res.compat_session_id.into();
@@ -152,6 +162,7 @@ pub struct CompatRefreshTokenLookup {
     compat_refresh_token_id: Uuid,
     compat_refresh_token: String,
     compat_refresh_token_created_at: DateTime<Utc>,
+    compat_refresh_token_consumed_at: Option<DateTime<Utc>>,
     compat_access_token_id: Uuid,
     compat_access_token: String,
     compat_access_token_created_at: DateTime<Utc>,
@@ -162,12 +173,87 @@ pub struct CompatRefreshTokenLookup {
     compat_session_device_id: String,
     user_id: Uuid,
     user_username: String,
+    user_admin: bool,
+    user_display_name: Option<String>,
+    user_deactivated_at: Option<DateTime<Utc>>,
     user_email_id: Option<Uuid>,
     user_email: Option<String>,
     user_email_created_at: Option<DateTime<Utc>>,
     user_email_confirmed_at: Option<DateTime<Utc>>,
 }
 
+impl CompatRefreshTokenLookup {
+    fn into_parts(
+        self,
+    ) -> Result<(CompatRefreshToken, CompatAccessToken, CompatSession), DatabaseError> {
+        let refresh_token = CompatRefreshToken {
+            id: self.compat_refresh_token_id.into(),
+            token: self.compat_refresh_token,
+            created_at: self.compat_refresh_token_created_at,
+            consumed_at: self.compat_refresh_token_consumed_at,
+        };
+
+        let access_token = CompatAccessToken {
+            id: self.compat_access_token_id.into(),
+            token: self.compat_access_token,
+            created_at: self.compat_access_token_created_at,
+            expires_at: self.compat_access_token_expires_at,
+        };
+
+        let user_id = Ulid::from(self.user_id);
+        let primary_email = match (
+            self.user_email_id,
+            self.user_email,
+            self.user_email_created_at,
+            self.user_email_confirmed_at,
+        ) {
+            (Some(id), Some(email), Some(created_at), confirmed_at) => Some(UserEmail {
+                id: id.into(),
+                email,
+                created_at,
+                confirmed_at,
+            }),
+            (None, None, None, None) => None,
+            _ => {
+                return Err(DatabaseInconsistencyError::on("users")
+                    .column("primary_user_email_id")
+                    .row(user_id)
+                    .into())
+            }
+        };
+
+        let user = User {
+            id: user_id,
+            username: self.user_username,
+            sub: user_id.to_string(),
+            primary_email,
+            display_name: self.user_display_name,
+            deactivated_at: self.user_deactivated_at,
+            admin: self.user_admin,
+        };
+
+        let session_id = self.compat_session_id.into();
+        let device = Device::try_from(self.compat_session_device_id).map_err(|e| {
+            DatabaseInconsistencyError::on("compat_sessions")
+                .column("device_id")
+                .row(session_id)
+                .source(e)
+        })?;
+
+        let session = CompatSession {
+            id: session_id,
+            user,
+            device,
+            created_at: self.compat_session_created_at,
+            finished_at: self.compat_session_finished_at,
+        };
+
+        Ok((refresh_token, access_token, session))
+    }
+}
+
+/// Lookup a refresh token which was not consumed yet, belonging to an active
+/// compat session
 #[tracing::instrument(skip_all, err)]
 #[allow(clippy::type_complexity)]
 pub async fn lookup_active_compat_refresh_token(
@@ -181,6 +267,7 @@ pub async fn lookup_active_compat_refresh_token(
                 cr.compat_refresh_token_id,
                 cr.refresh_token   AS "compat_refresh_token",
                 cr.created_at      AS "compat_refresh_token_created_at",
+                cr.consumed_at     AS "compat_refresh_token_consumed_at",
                 ct.compat_access_token_id,
                 ct.access_token    AS "compat_access_token",
                 ct.created_at      AS "compat_access_token_created_at",
@@ -191,6 +278,9 @@ pub async fn lookup_active_compat_refresh_token(
                 cs.device_id       AS "compat_session_device_id",
                 u.user_id,
                 u.username         AS "user_username!",
+                u.admin            AS "user_admin!",
+                u.display_name     AS "user_display_name",
+                u.deactivated_at   AS "user_deactivated_at",
                 ue.user_email_id   AS "user_email_id?",
                 ue.email           AS "user_email?",
                 ue.created_at      AS "user_email_created_at?",
@@ -209,6 +299,7 @@ pub async fn lookup_active_compat_refresh_token(
             WHERE cr.refresh_token = $1
               AND cr.consumed_at IS NULL
               AND cs.finished_at IS NULL
+              AND u.deactivated_at IS NULL
         "#,
         token,
     )
@@ -219,65 +310,68 @@ pub async fn lookup_active_compat_refresh_token(
 
     let Some(res) = res else { return Ok(None); };
 
-    let refresh_token = CompatRefreshToken {
-        id: res.compat_refresh_token_id.into(),
-        token: res.compat_refresh_token,
-        created_at: res.compat_refresh_token_created_at,
-    };
-
-    let access_token = CompatAccessToken {
-        id: res.compat_access_token_id.into(),
-        token: res.compat_access_token,
-        created_at: res.compat_access_token_created_at,
-        expires_at: res.compat_access_token_expires_at,
-    };
+    res.into_parts().map(Some)
+}
 
-    let user_id = Ulid::from(res.user_id);
-    let primary_email = match (
-        res.user_email_id,
-        res.user_email,
-        res.user_email_created_at,
-        res.user_email_confirmed_at,
-    ) {
-        (Some(id), Some(email), Some(created_at), confirmed_at) => Some(UserEmail {
-            id: id.into(),
-            email,
-            created_at,
-            confirmed_at,
-        }),
-        (None, None, None, None) => None,
-        _ => {
-            return Err(DatabaseInconsistencyError::on("users")
-                .column("primary_user_email_id")
-                .row(user_id)
-                .into())
-        }
-    };
+/// Lookup a refresh token, regardless of whether it was consumed or its
+/// session ended.
+///
+/// This is used to detect refresh token reuse: the caller is responsible for
+/// checking the state of the token and of its session.
+#[tracing::instrument(skip_all, err)]
+#[allow(clippy::type_complexity)]
+pub async fn lookup_compat_refresh_token(
+    executor: impl PgExecutor<'_>,
+    token: &str,
+) -> Result<Option<(CompatRefreshToken, CompatAccessToken, CompatSession)>, DatabaseError> {
+    let res = sqlx::query_as!(
+        CompatRefreshTokenLookup,
+        r#"
+            SELECT
+                cr.compat_refresh_token_id,
+                cr.refresh_token   AS "compat_refresh_token",
+                cr.created_at      AS "compat_refresh_token_created_at",
+                cr.consumed_at     AS "compat_refresh_token_consumed_at",
+                ct.compat_access_token_id,
+                ct.access_token    AS "compat_access_token",
+                ct.created_at      AS "compat_access_token_created_at",
+                ct.expires_at      AS "compat_access_token_expires_at",
+                cs.compat_session_id,
+                cs.created_at      AS "compat_session_created_at",
+                cs.finished_at     AS "compat_session_finished_at",
+                cs.device_id       AS "compat_session_device_id",
+                u.user_id,
+                u.username         AS "user_username!",
+                u.admin            AS "user_admin!",
+                u.display_name     AS "user_display_name",
+                u.deactivated_at   AS "user_deactivated_at",
+                ue.user_email_id   AS "user_email_id?",
+                ue.email           AS "user_email?",
+                ue.created_at      AS "user_email_created_at?",
+                ue.confirmed_at    AS "user_email_confirmed_at?"
 
-    let user = User {
-        id: user_id,
-        username: res.user_username,
-        sub: user_id.to_string(),
-        primary_email,
-    };
+            FROM compat_refresh_tokens cr
+            INNER JOIN compat_sessions cs
+              USING (compat_session_id)
+            INNER JOIN compat_access_tokens ct
+              USING (compat_access_token_id)
+            INNER JOIN users u
+              USING (user_id)
+            LEFT JOIN user_emails ue
+              ON ue.user_email_id = u.primary_user_email_id
 
-    let session_id = res.compat_session_id.into();
-    let device = Device::try_from(res.compat_session_device_id).map_err(|e| {
-        DatabaseInconsistencyError::on("compat_sessions")
-            .column("device_id")
-            .row(session_id)
-            .source(e)
-    })?;
+            WHERE cr.refresh_token = $1
+        "#,
+        token,
+    )
+    .fetch_one(executor)
+    .instrument(info_span!("Fetch compat refresh token"))
+    .await
+    .to_option()?;
 
-    let session = CompatSession {
-        id: session_id,
-        user,
-        device,
-        created_at: res.compat_session_created_at,
-        finished_at: res.compat_session_finished_at,
-    };
+    let Some(res) = res else { return Ok(None); };
 
-    Ok(Some((refresh_token, access_token, session)))
+    res.into_parts().map(Some)
 }
 
 #[tracing::instrument(
@@ -400,6 +494,7 @@ pub async fn add_compat_refresh_token(
         id,
         token,
         created_at,
+        consumed_at: None,
     })
 }
 
@@ -471,6 +566,74 @@ pub async fn consume_compat_refresh_token(
     DatabaseError::ensure_affected_rows(&res, 1)
 }
 
+/// End a compat session and invalidate all of its access and refresh tokens
+#[tracing::instrument(
+    skip_all,
+    fields(
+        compat_session.id = %session.id,
+        compat_session.device.id = session.device.as_str(),
+        user.id = %session.user.id,
+    ),
+    err,
+)]
+pub async fn revoke_compat_session(
+    conn: impl Acquire<'_, Database = Postgres> + Send,
+    clock: &Clock,
+    mut session: CompatSession,
+) -> Result<CompatSession, DatabaseError> {
+    let mut txn = conn.begin().await?;
+    let finished_at = clock.now();
+
+    sqlx::query!(
+        r#"
+            UPDATE compat_access_tokens
+            SET expires_at = $2
+            WHERE compat_session_id = $1
+              AND (expires_at IS NULL OR expires_at > $2)
+        "#,
+        Uuid::from(session.id),
+        finished_at,
+    )
+    .execute(&mut txn)
+    .instrument(info_span!("Expire compat access tokens"))
+    .await?;
+
+    sqlx::query!(
+        r#"
+            UPDATE compat_refresh_tokens
+            SET consumed_at = $2
+            WHERE compat_session_id = $1
+              AND consumed_at IS NULL
+        "#,
+        Uuid::from(session.id),
+        finished_at,
+    )
+    .execute(&mut txn)
+    .instrument(info_span!("Consume compat refresh tokens"))
+    .await?;
+
+    let res = sqlx::query!(
+        r#"
+            UPDATE compat_sessions
+            SET finished_at = $2
+            WHERE compat_session_id = $1
+        "#,
+        Uuid::from(session.id),
+        finished_at,
+    )
+    .execute(&mut txn)
+    .instrument(info_span!("Finish compat session"))
+    .await?;
+
+    DatabaseError::ensure_affected_rows(&res, 1)?;
+
+    txn.commit().await?;
+
+    session.finished_at = Some(finished_at);
+
+    Ok(session)
+}
+
 #[tracing::instrument(
     skip_all,
     fields(
@@ -528,29 +691,23 @@ struct CompatSsoLoginLookup {
     compat_session_device_id: Option<String>,
     user_id: Option<Uuid>,
     user_username: Option<String>,
+    user_admin: Option<bool>,
+    user_display_name: Option<String>,
+    user_deactivated_at: Option<DateTime<Utc>>,
     user_email_id: Option<Uuid>,
     user_email: Option<String>,
     user_email_created_at: Option<DateTime<Utc>>,
     user_email_confirmed_at: Option<DateTime<Utc>>,
 }
 
-impl TryFrom<CompatSsoLoginLookup> for CompatSsoLogin {
-    type Error = DatabaseInconsistencyError;
-
-    fn try_from(res: CompatSsoLoginLookup) -> Result<Self, Self::Error> {
-        let id = res.compat_sso_login_id.into();
-        let redirect_uri = Url::parse(&res.compat_sso_login_redirect_uri).map_err(|e| {
-            DatabaseInconsistencyError::on("compat_sso_logins")
-                .column("redirect_uri")
-                .row(id)
-                .source(e)
-        })?;
-
+impl CompatSsoLoginLookup {
+    /// Take the user of the compat session out of the lookup, if there is one
+    fn take_user(&mut self) -> Result<Option<User>, DatabaseInconsistencyError> {
         let primary_email = match (
-            res.user_email_id,
-            res.user_email,
-            res.user_email_created_at,
-            res.user_email_confirmed_at,
+            self.user_email_id.take(),
+            self.user_email.take(),
+            self.user_email_created_at.take(),
+            self.user_email_confirmed_at.take(),
         ) {
             (Some(id), Some(email), Some(created_at), confirmed_at) => Some(UserEmail {
                 id: id.into(),
@@ -564,21 +721,47 @@ impl TryFrom<CompatSsoLoginLookup> for CompatSsoLogin {
             }
         };
 
-        let user = match (res.user_id, res.user_username, primary_email) {
-            (Some(id), Some(username), primary_email) => {
+        let user = match (
+            self.user_id.take(),
+            self.user_username.take(),
+            self.user_admin.take(),
+            primary_email,
+        ) {
+            (Some(id), Some(username), Some(admin), primary_email) => {
                 let id = Ulid::from(id);
                 Some(User {
                     id,
                     username,
                     sub: id.to_string(),
                     primary_email,
+                    display_name: self.user_display_name.take(),
+                    deactivated_at: self.user_deactivated_at.take(),
+                    admin,
                 })
             }
 
-            (None, None, None) => None,
+            (None, None, None, None) => None,
             _ => return Err(DatabaseInconsistencyError::on("compat_sessions").column("user_id")),
         };
 
+        Ok(user)
+    }
+}
+
+impl TryFrom<CompatSsoLoginLookup> for CompatSsoLogin {
+    type Error = DatabaseInconsistencyError;
+
+    fn try_from(mut res: CompatSsoLoginLookup) -> Result<Self, Self::Error> {
+        let id = res.compat_sso_login_id.into();
+        let redirect_uri = Url::parse(&res.compat_sso_login_redirect_uri).map_err(|e| {
+            DatabaseInconsistencyError::on("compat_sso_logins")
+                .column("redirect_uri")
+                .row(id)
+                .source(e)
+        })?;
+
+        let user = res.take_user()?;
+
         let session = match (
             res.compat_session_id,
             res.compat_session_device_id,
@@ -667,6 +850,9 @@ pub async fn get_compat_sso_login_by_id(
                 cs.device_id       AS "compat_session_device_id?",
                 u.user_id          AS "user_id?",
                 u.username         AS "user_username?",
+                u.admin            AS "user_admin?",
+                u.display_name     AS "user_display_name?",
+                u.deactivated_at   AS "user_deactivated_at?",
                 ue.user_email_id   AS "user_email_id?",
                 ue.email           AS "user_email?",
                 ue.created_at      AS "user_email_created_at?",
@@ -725,6 +911,9 @@ pub async fn get_paginated_user_compat_sso_logins(
                 cs.device_id       AS "compat_session_device_id",
                 u.user_id          AS "user_id",
                 u.username         AS "user_username",
+                u.admin            AS "user_admin",
+                u.display_name     AS "user_display_name",
+                u.deactivated_at   AS "user_deactivated_at",
                 ue.user_email_id   AS "user_email_id",
                 ue.email           AS "user_email",
                 ue.created_at      AS "user_email_created_at",
@@ -781,6 +970,9 @@ pub async fn get_compat_sso_login_by_token(
                 cs.device_id       AS "compat_session_device_id?",
                 u.user_id          AS "user_id?",
                 u.username         AS "user_username?",
+                u.admin            AS "user_admin?",
+                u.display_name     AS "user_display_name?",
+                u.deactivated_at   AS "user_deactivated_at?",
                 ue.user_email_id   AS "user_email_id?",
                 ue.email           AS "user_email?",
                 ue.created_at      AS "user_email_created_at?",
@@ -946,3 +1138,134 @@ pub async fn mark_compat_sso_login_as_exchanged(
     compat_sso_login.state = state;
     Ok(compat_sso_login)
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rand::SeedableRng;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::user::{add_user, deactivate_user};
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_refresh_token_reuse(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let device = Device::generate(&mut rng);
+        let session = start_compat_session(&mut conn, &mut rng, &clock, user, device).await?;
+        let access_token = add_compat_access_token(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &session,
+            "mct_access".to_owned(),
+            None,
+        )
+        .await?;
+        let refresh_token = add_compat_refresh_token(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &session,
+            &access_token,
+            "mcr_refresh".to_owned(),
+        )
+        .await?;
+
+        let (found, _, _) = lookup_active_compat_refresh_token(&mut conn, "mcr_refresh")
+            .await?
+            .expect("the refresh token should be active");
+        assert_eq!(found.id, refresh_token.id);
+        assert!(!found.consumed());
+
+        consume_compat_refresh_token(&mut conn, &clock, refresh_token).await?;
+
+        // A consumed token is not active anymore, but can still be found to
+        // detect its reuse
+        assert!(lookup_active_compat_refresh_token(&mut conn, "mcr_refresh")
+            .await?
+            .is_none());
+        let (found, _, session) = lookup_compat_refresh_token(&mut conn, "mcr_refresh")
+            .await?
+            .expect("the refresh token should be found");
+        assert!(found.consumed());
+        assert!(session.finished_at.is_none());
+        assert!(
+            lookup_active_compat_access_token(&mut conn, &clock, "mct_access")
+                .await?
+                .is_some()
+        );
+
+        revoke_compat_session(&mut conn, &clock, session).await?;
+
+        let (_, _, session) = lookup_compat_refresh_token(&mut conn, "mcr_refresh")
+            .await?
+            .expect("the refresh token should be found");
+        assert_eq!(session.finished_at, Some(clock.now()));
+        assert!(
+            lookup_active_compat_access_token(&mut conn, &clock, "mct_access")
+                .await?
+                .is_none()
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_deactivated_user_tokens(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let device = Device::generate(&mut rng);
+        let session =
+            start_compat_session(&mut conn, &mut rng, &clock, user.clone(), device).await?;
+        let access_token = add_compat_access_token(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &session,
+            "mct_access".to_owned(),
+            None,
+        )
+        .await?;
+        add_compat_refresh_token(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &session,
+            &access_token,
+            "mcr_refresh".to_owned(),
+        )
+        .await?;
+
+        deactivate_user(&mut conn, &clock, user).await?;
+
+        assert!(
+            lookup_active_compat_access_token(&mut conn, &clock, "mct_access")
+                .await?
+                .is_none()
+        );
+        assert!(lookup_active_compat_refresh_token(&mut conn, "mcr_refresh")
+            .await?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_lookup_unknown_refresh_token(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut conn = pool.acquire().await?;
+
+        assert!(lookup_compat_refresh_token(&mut conn, "mcr_unknown")
+            .await?
+            .is_none());
+
+        Ok(())
+    }
+}
@@ -79,6 +79,11 @@ pub enum DatabaseError {
     /// rows
     #[error("Expected {expected} rows to be affected, but {actual} rows were affected")]
     RowsAffected { expected: u64, actual: u64 },
+
+    /// An error which happens when no connection could be acquired from the
+    /// pool in time
+    #[error("Timed out after {timeout:?} while acquiring a database connection")]
+    AcquireTimeout { timeout: std::time::Duration },
 }
 
 impl DatabaseError {
@@ -161,25 +166,55 @@ impl DatabaseInconsistencyError {
     }
 }
 
+/// The source of the current time. The default one follows the system clock,
+/// and a mocked one can be frozen at an arbitrary time in tests.
 #[derive(Default, Debug, Clone, Copy)]
 pub struct Clock {
-    _private: (),
+    fixed: Option<DateTime<Utc>>,
 }
 
 impl Clock {
+    /// Create a [`Clock`] which always returns the given time
+    #[must_use]
+    pub const fn mock(now: DateTime<Utc>) -> Self {
+        Self { fixed: Some(now) }
+    }
+
+    /// Move a mocked clock forward by the given duration
+    ///
+    /// # Panics
+    ///
+    /// If the clock is not a mocked one
+    pub fn advance(&mut self, duration: chrono::Duration) {
+        let now = self.fixed.as_mut().expect("can't advance a real clock");
+        *now += duration;
+    }
+
     #[must_use]
     pub fn now(&self) -> DateTime<Utc> {
+        if let Some(now) = self.fixed {
+            return now;
+        }
+
         // This is the clock used elsewhere, it's fine to call Utc::now here
         #[allow(clippy::disallowed_methods)]
         Utc::now()
     }
 }
 
+mod acquire;
 pub mod compat;
+mod migrations;
 pub mod oauth2;
 pub(crate) mod pagination;
 pub mod upstream_oauth2;
 pub mod user;
 
+pub use self::{
+    acquire::{acquire_with_timeout, begin_with_timeout},
+    migrations::pending_migrations,
+    pagination::PaginationOrder,
+};
+
 /// Embedded migrations, allowing them to run on startup
 pub static MIGRATOR: Migrator = sqlx::migrate!();
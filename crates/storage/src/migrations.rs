@@ -0,0 +1,97 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Inspect which of the embedded migrations were applied to the database
+
+use std::collections::HashSet;
+
+use sqlx::{
+    migrate::{Migrate, MigrateError},
+    PgConnection,
+};
+
+use crate::MIGRATOR;
+
+/// List the versions of the embedded migrations which are not applied to the
+/// database yet
+///
+/// This doesn't modify the database, so it can be used by readiness checks.
+///
+/// # Errors
+///
+/// Returns an error if the list of applied migrations could not be fetched
+#[tracing::instrument(skip_all, err)]
+pub async fn pending_migrations(conn: &mut PgConnection) -> Result<Vec<i64>, MigrateError> {
+    // The migrations table doesn't exist on a fresh database
+    let has_migrations_table: bool =
+        sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(&mut *conn)
+            .await?;
+
+    let mut applied: HashSet<i64> = if has_migrations_table {
+        conn.list_applied_migrations()
+            .await?
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
+    // A migration which failed halfway is listed too, but it is not applied
+    if has_migrations_table {
+        if let Some(dirty) = conn.dirty_version().await? {
+            applied.remove(&dirty);
+        }
+    }
+
+    let pending = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect();
+
+    Ok(pending)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test(migrations = false)]
+    async fn test_pending_migrations(pool: PgPool) -> Result<(), MigrateError> {
+        let mut conn = pool.acquire().await?;
+
+        // Nothing is applied on a fresh database
+        let all: Vec<i64> = MIGRATOR.iter().map(|migration| migration.version).collect();
+        assert!(!all.is_empty());
+        assert_eq!(pending_migrations(&mut conn).await?, all);
+
+        MIGRATOR.run(&mut conn).await?;
+        assert!(pending_migrations(&mut conn).await?.is_empty());
+
+        // A migration which failed halfway is still pending
+        let last = *all.last().unwrap();
+        sqlx::query("UPDATE _sqlx_migrations SET success = FALSE WHERE version = $1")
+            .bind(last)
+            .execute(&mut conn)
+            .await?;
+        assert_eq!(pending_migrations(&mut conn).await?, vec![last]);
+
+        Ok(())
+    }
+}
@@ -28,7 +28,9 @@ use url::Url;
 use uuid::Uuid;
 
 use super::client::lookup_client;
-use crate::{Clock, DatabaseError, DatabaseInconsistencyError, LookupResultExt};
+use crate::{
+    user::session_metadata, Clock, DatabaseError, DatabaseInconsistencyError, LookupResultExt,
+};
 
 #[tracing::instrument(
     skip_all,
@@ -152,8 +154,13 @@ struct GrantLookup {
     oauth2_session_id: Option<Uuid>,
     user_session_id: Option<Uuid>,
     user_session_created_at: Option<DateTime<Utc>>,
+    user_session_ip_address: Option<String>,
+    user_session_user_agent: Option<String>,
     user_id: Option<Uuid>,
     user_username: Option<String>,
+    user_admin: Option<bool>,
+    user_display_name: Option<String>,
+    user_deactivated_at: Option<DateTime<Utc>>,
     user_session_last_authentication_id: Option<Uuid>,
     user_session_last_authentication_created_at: Option<DateTime<Utc>>,
     user_email_id: Option<Uuid>,
@@ -223,6 +230,7 @@ impl GrantLookup {
             self.user_session_created_at,
             self.user_id,
             self.user_username,
+            self.user_admin,
             last_authentication,
             primary_email,
         ) {
@@ -232,6 +240,7 @@ impl GrantLookup {
                 Some(user_session_created_at),
                 Some(user_id),
                 Some(user_username),
+                Some(user_admin),
                 last_authentication,
                 primary_email,
             ) => {
@@ -241,13 +250,24 @@ impl GrantLookup {
                     username: user_username,
                     sub: user_id.to_string(),
                     primary_email,
+                    display_name: self.user_display_name,
+                    deactivated_at: self.user_deactivated_at,
+                    admin: user_admin,
                 };
 
+                let user_session_id = Ulid::from(user_session_id);
+                let metadata = session_metadata(
+                    user_session_id,
+                    self.user_session_ip_address,
+                    self.user_session_user_agent,
+                )?;
+
                 let browser_session = BrowserSession {
-                    id: user_session_id.into(),
+                    id: user_session_id,
                     user,
                     created_at: user_session_created_at,
                     last_authentication,
+                    metadata,
                 };
 
                 let client = client.clone();
@@ -262,7 +282,7 @@ impl GrantLookup {
 
                 Some(session)
             }
-            (None, None, None, None, None, None, None) => None,
+            (None, None, None, None, None, None, None, None) => None,
             _ => {
                 return Err(
                     DatabaseInconsistencyError::on("oauth2_authorization_grants")
@@ -437,8 +457,13 @@ pub async fn get_grant_by_id(
                 os.oauth2_session_id       AS "oauth2_session_id?",
                 us.user_session_id         AS "user_session_id?",
                 us.created_at              AS "user_session_created_at?",
+                us.ip_address              AS "user_session_ip_address?",
+                us.user_agent              AS "user_session_user_agent?",
                  u.user_id                 AS "user_id?",
                  u.username                AS "user_username?",
+                 u.admin                   AS "user_admin?",
+                 u.display_name            AS "user_display_name?",
+                 u.deactivated_at          AS "user_deactivated_at?",
                 usa.user_session_authentication_id AS "user_session_last_authentication_id?",
                 usa.created_at             AS "user_session_last_authentication_created_at?",
                 ue.user_email_id           AS "user_email_id?",
@@ -506,8 +531,13 @@ pub async fn lookup_grant_by_code(
                 os.oauth2_session_id       AS "oauth2_session_id?",
                 us.user_session_id         AS "user_session_id?",
                 us.created_at              AS "user_session_created_at?",
+                us.ip_address              AS "user_session_ip_address?",
+                us.user_agent              AS "user_session_user_agent?",
                  u.user_id                 AS "user_id?",
                  u.username                AS "user_username?",
+                 u.admin                   AS "user_admin?",
+                 u.display_name            AS "user_display_name?",
+                 u.deactivated_at          AS "user_deactivated_at?",
                 usa.user_session_authentication_id AS "user_session_last_authentication_id?",
                 usa.created_at             AS "user_session_last_authentication_created_at?",
                 ue.user_email_id           AS "user_email_id?",
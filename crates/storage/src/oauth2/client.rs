@@ -14,6 +14,7 @@
 
 use std::{collections::HashMap, string::ToString};
 
+use chrono::{DateTime, Utc};
 use mas_data_model::{Client, JwksOrJwksUri};
 use mas_iana::{
     jose::JsonWebSignatureAlg,
@@ -530,9 +531,117 @@ pub async fn insert_client_from_config(
     Ok(())
 }
 
+/// Mark a client assertion as consumed, so that it can't be used again
+///
+/// Returns `false` if an assertion with the same `jti` was already consumed by
+/// this client.
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %client.id,
+        client_assertion.jti = jti,
+    ),
+    err,
+)]
+pub async fn consume_client_assertion(
+    executor: impl PgExecutor<'_>,
+    clock: &Clock,
+    client: &Client,
+    jti: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<bool, DatabaseError> {
+    let consumed_at = clock.now();
+    let res = sqlx::query!(
+        r#"
+            INSERT INTO oauth2_consumed_client_assertions
+                (oauth2_client_id, jti, consumed_at, expires_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (oauth2_client_id, jti) DO NOTHING
+        "#,
+        Uuid::from(client.id),
+        jti,
+        consumed_at,
+        expires_at,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(res.rows_affected() == 1)
+}
+
+/// Forget about the consumed client assertions which are now expired
+pub async fn cleanup_consumed_client_assertions(
+    executor: impl PgExecutor<'_>,
+    clock: &Clock,
+) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query!(
+        r#"
+            DELETE FROM oauth2_consumed_client_assertions
+            WHERE expires_at < $1
+        "#,
+        clock.now(),
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(res.rows_affected())
+}
+
 pub async fn truncate_clients(executor: impl PgExecutor<'_>) -> Result<(), sqlx::Error> {
     sqlx::query!("TRUNCATE oauth2_client_redirect_uris, oauth2_clients CASCADE")
         .execute(executor)
         .await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use rand::SeedableRng;
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_consume_client_assertion(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let mut conn = pool.acquire().await?;
+
+        let client_id = Ulid::from_datetime_with_source(clock.now().into(), &mut rng);
+        insert_client_from_config(
+            &mut conn,
+            &mut rng,
+            &clock,
+            client_id,
+            OAuthClientAuthenticationMethod::PrivateKeyJwt,
+            None,
+            None,
+            None,
+            &[],
+        )
+        .await?;
+        let client = lookup_client(&mut conn, client_id).await?.unwrap();
+
+        let expires_at = clock.now() + Duration::minutes(5);
+        assert!(consume_client_assertion(&mut conn, &clock, &client, "jti", expires_at).await?);
+
+        // The same assertion can't be used twice
+        assert!(!consume_client_assertion(&mut conn, &clock, &client, "jti", expires_at).await?);
+
+        // It is still remembered until it expires
+        clock.advance(Duration::minutes(4));
+        assert_eq!(
+            cleanup_consumed_client_assertions(&mut conn, &clock).await?,
+            0
+        );
+
+        clock.advance(Duration::minutes(2));
+        assert_eq!(
+            cleanup_consumed_client_assertions(&mut conn, &clock).await?,
+            1
+        );
+
+        Ok(())
+    }
+}
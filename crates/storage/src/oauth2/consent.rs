@@ -12,15 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::str::FromStr;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+};
 
+use chrono::{DateTime, Utc};
 use mas_data_model::{Client, User};
 use oauth2_types::scope::{Scope, ScopeToken};
 use rand::Rng;
-use sqlx::PgExecutor;
+use sqlx::{PgConnection, PgExecutor};
 use ulid::Ulid;
 use uuid::Uuid;
 
+use super::client::lookup_clients;
 use crate::{Clock, DatabaseError, DatabaseInconsistencyError};
 
 #[tracing::instrument(
@@ -62,6 +67,85 @@ pub async fn fetch_client_consent(
     Ok(scope)
 }
 
+/// The scope a user consented to for a client, along with when the consent
+/// was first given and last refreshed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientConsent {
+    pub scope: Scope,
+    pub created_at: DateTime<Utc>,
+    pub refreshed_at: Option<DateTime<Utc>>,
+}
+
+struct ClientConsentLookup {
+    scope_token: String,
+    created_at: DateTime<Utc>,
+    refreshed_at: Option<DateTime<Utc>>,
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %user.id,
+        %client.id,
+    ),
+    err,
+)]
+pub async fn fetch_client_consent_with_metadata(
+    executor: impl PgExecutor<'_>,
+    user: &User,
+    client: &Client,
+) -> Result<Option<ClientConsent>, DatabaseError> {
+    let res = sqlx::query_as!(
+        ClientConsentLookup,
+        r#"
+            SELECT scope_token, created_at, refreshed_at
+            FROM oauth2_consents
+            WHERE user_id = $1 AND oauth2_client_id = $2
+        "#,
+        Uuid::from(user.id),
+        Uuid::from(client.id),
+    )
+    .fetch_all(executor)
+    .await?;
+
+    let created_at = res.iter().map(|row| row.created_at).min();
+    let refreshed_at = res.iter().filter_map(|row| row.refreshed_at).max();
+
+    let Some(created_at) = created_at else { return Ok(None) };
+
+    let scope: Result<Scope, _> = res
+        .into_iter()
+        .map(|row| ScopeToken::from_str(&row.scope_token))
+        .collect();
+
+    let scope = scope.map_err(|e| {
+        DatabaseInconsistencyError::on("oauth2_consents")
+            .column("scope_token")
+            .source(e)
+    })?;
+
+    Ok(Some(ClientConsent {
+        scope,
+        created_at,
+        refreshed_at,
+    }))
+}
+
+/// The scope tokens touched by [`insert_client_consent`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientConsentChanges {
+    /// The tokens the user didn't consent to before
+    pub added: Scope,
+
+    /// The tokens the user already consented to, which got refreshed
+    pub refreshed: Scope,
+}
+
+struct InsertedConsentLookup {
+    scope_token: String,
+    inserted: bool,
+}
+
 #[tracing::instrument(
     skip_all,
     fields(
@@ -78,24 +162,30 @@ pub async fn insert_client_consent(
     user: &User,
     client: &Client,
     scope: &Scope,
-) -> Result<(), sqlx::Error> {
+) -> Result<ClientConsentChanges, DatabaseError> {
     let now = clock.now();
-    let (tokens, ids): (Vec<String>, Vec<Uuid>) = scope
-        .iter()
+
+    // The same row can't be affected twice by the `ON CONFLICT` clause of a
+    // single statement, so make sure each token is only inserted once
+    let tokens: BTreeSet<String> = scope.iter().map(ToString::to_string).collect();
+    let (tokens, ids): (Vec<String>, Vec<Uuid>) = tokens
+        .into_iter()
         .map(|token| {
             (
-                token.to_string(),
+                token,
                 Uuid::from(Ulid::from_datetime_with_source(now.into(), &mut rng)),
             )
         })
         .unzip();
 
-    sqlx::query!(
+    let res = sqlx::query_as!(
+        InsertedConsentLookup,
         r#"
             INSERT INTO oauth2_consents
                 (oauth2_consent_id, user_id, oauth2_client_id, scope_token, created_at)
             SELECT id, $2, $3, scope_token, $5 FROM UNNEST($1::uuid[], $4::text[]) u(id, scope_token)
             ON CONFLICT (user_id, oauth2_client_id, scope_token) DO UPDATE SET refreshed_at = $5
+            RETURNING scope_token, refreshed_at IS NULL AS "inserted!"
         "#,
         &ids,
         Uuid::from(user.id),
@@ -103,8 +193,172 @@ pub async fn insert_client_consent(
         &tokens,
         now,
     )
+    .fetch_all(executor)
+    .await?;
+
+    let mut added = Vec::new();
+    let mut refreshed = Vec::new();
+    for row in res {
+        let token = ScopeToken::from_str(&row.scope_token).map_err(|e| {
+            DatabaseInconsistencyError::on("oauth2_consents")
+                .column("scope_token")
+                .source(e)
+        })?;
+
+        if row.inserted {
+            added.push(token);
+        } else {
+            refreshed.push(token);
+        }
+    }
+
+    Ok(ClientConsentChanges {
+        added: added.into_iter().collect(),
+        refreshed: refreshed.into_iter().collect(),
+    })
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %user.id,
+        %client.id,
+    ),
+    err,
+)]
+pub async fn revoke_client_consent(
+    executor: impl PgExecutor<'_>,
+    user: &User,
+    client: &Client,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+            DELETE FROM oauth2_consents
+            WHERE user_id = $1 AND oauth2_client_id = $2
+        "#,
+        Uuid::from(user.id),
+        Uuid::from(client.id),
+    )
     .execute(executor)
     .await?;
 
     Ok(())
 }
+
+struct ConsentLookup {
+    oauth2_client_id: Uuid,
+    scope_token: String,
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %user.id,
+    ),
+    err,
+)]
+pub async fn get_all_user_consents(
+    conn: &mut PgConnection,
+    user: &User,
+) -> Result<Vec<(Client, Scope)>, DatabaseError> {
+    let res = sqlx::query_as!(
+        ConsentLookup,
+        r#"
+            SELECT oauth2_client_id, scope_token
+            FROM oauth2_consents
+            WHERE user_id = $1
+        "#,
+        Uuid::from(user.id),
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut scopes: BTreeMap<Ulid, Vec<ScopeToken>> = BTreeMap::new();
+    for row in res {
+        let client_id = Ulid::from(row.oauth2_client_id);
+        let token = ScopeToken::from_str(&row.scope_token).map_err(|e| {
+            DatabaseInconsistencyError::on("oauth2_consents")
+                .column("scope_token")
+                .source(e)
+        })?;
+        scopes.entry(client_id).or_default().push(token);
+    }
+
+    let clients = lookup_clients(&mut *conn, scopes.keys().copied()).await?;
+
+    let consents: Result<Vec<_>, DatabaseInconsistencyError> = scopes
+        .into_iter()
+        .map(|(client_id, tokens)| {
+            let client = clients
+                .get(&client_id)
+                .ok_or_else(|| {
+                    DatabaseInconsistencyError::on("oauth2_consents").column("oauth2_client_id")
+                })?
+                .clone();
+
+            Ok((client, tokens.into_iter().collect()))
+        })
+        .collect();
+
+    Ok(consents?)
+}
+
+#[cfg(test)]
+mod tests {
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
+    use rand::SeedableRng;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        oauth2::client::{insert_client_from_config, lookup_client},
+        user::add_user,
+    };
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_insert_client_consent(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let client_id = Ulid::from_datetime_with_source(clock.now().into(), &mut rng);
+        insert_client_from_config(
+            &mut conn,
+            &mut rng,
+            &clock,
+            client_id,
+            OAuthClientAuthenticationMethod::None,
+            None,
+            None,
+            None,
+            &[],
+        )
+        .await?;
+        let client = lookup_client(&mut conn, client_id).await?.unwrap();
+
+        let scope: Scope = "openid profile".parse().unwrap();
+        let changes =
+            insert_client_consent(&mut conn, &mut rng, &clock, &user, &client, &scope).await?;
+        assert_eq!(changes.added, scope);
+        assert!(changes.refreshed.is_empty());
+
+        // Overlapping scope: only the new token is added
+        let scope: Scope = "profile email".parse().unwrap();
+        let changes =
+            insert_client_consent(&mut conn, &mut rng, &clock, &user, &client, &scope).await?;
+        assert_eq!(changes.added, "email".parse::<Scope>().unwrap());
+        assert_eq!(changes.refreshed, "profile".parse::<Scope>().unwrap());
+
+        // No spurious rows were inserted
+        let consent = fetch_client_consent(&mut conn, &user, &client).await?;
+        assert_eq!(consent, "openid profile email".parse::<Scope>().unwrap());
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM oauth2_consents")
+            .fetch_one(&mut conn)
+            .await?;
+        assert_eq!(count, 3);
+
+        Ok(())
+    }
+}
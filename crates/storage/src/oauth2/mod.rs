@@ -82,6 +82,7 @@ struct OAuthSessionLookup {
 )]
 pub async fn get_paginated_user_oauth_sessions(
     conn: &mut PgConnection,
+    clock: &Clock,
     user: &User,
     before: Option<Ulid>,
     after: Option<Ulid>,
@@ -134,7 +135,7 @@ pub async fn get_paginated_user_oauth_sessions(
     // ideal
     let mut browser_sessions: HashMap<Ulid, BrowserSession> = HashMap::new();
     for id in browser_session_ids {
-        let v = lookup_active_session(&mut *conn, id)
+        let v = lookup_active_session(&mut *conn, clock, id)
             .await?
             .ok_or_else(|| {
                 DatabaseInconsistencyError::on("oauth2_sessions").column("user_session_id")
@@ -22,7 +22,7 @@ use ulid::Ulid;
 use uuid::Uuid;
 
 use super::client::lookup_client;
-use crate::{Clock, DatabaseError, DatabaseInconsistencyError};
+use crate::{user::session_metadata, Clock, DatabaseError, DatabaseInconsistencyError};
 
 #[tracing::instrument(
     skip_all,
@@ -85,8 +85,13 @@ struct OAuth2RefreshTokenLookup {
     oauth2_session_scope: String,
     user_session_id: Uuid,
     user_session_created_at: DateTime<Utc>,
+    user_session_ip_address: Option<String>,
+    user_session_user_agent: Option<String>,
     user_id: Uuid,
     user_username: String,
+    user_admin: bool,
+    user_display_name: Option<String>,
+    user_deactivated_at: Option<DateTime<Utc>>,
     user_session_last_authentication_id: Option<Uuid>,
     user_session_last_authentication_created_at: Option<DateTime<Utc>>,
     user_email_id: Option<Uuid>,
@@ -117,8 +122,13 @@ pub async fn lookup_active_refresh_token(
                 os.scope             AS "oauth2_session_scope!",
                 us.user_session_id   AS "user_session_id!",
                 us.created_at        AS "user_session_created_at!",
+                us.ip_address        AS "user_session_ip_address",
+                us.user_agent        AS "user_session_user_agent",
                  u.user_id           AS "user_id!",
                  u.username          AS "user_username!",
+                 u.admin             AS "user_admin!",
+                 u.display_name      AS "user_display_name",
+                 u.deactivated_at    AS "user_deactivated_at",
                 usa.user_session_authentication_id AS "user_session_last_authentication_id?",
                 usa.created_at       AS "user_session_last_authentication_created_at?",
                 ue.user_email_id     AS "user_email_id?",
@@ -144,6 +154,7 @@ pub async fn lookup_active_refresh_token(
               AND rt.revoked_at  IS NULL
               AND us.finished_at IS NULL
               AND os.finished_at IS NULL
+              AND u.deactivated_at IS NULL
 
             ORDER BY usa.created_at DESC
             LIMIT 1
@@ -216,6 +227,9 @@ pub async fn lookup_active_refresh_token(
         username: res.user_username,
         sub: user_id.to_string(),
         primary_email,
+        display_name: res.user_display_name,
+        deactivated_at: res.user_deactivated_at,
+        admin: res.user_admin,
     };
 
     let last_authentication = match (
@@ -230,11 +244,19 @@ pub async fn lookup_active_refresh_token(
         _ => return Err(DatabaseInconsistencyError::on("user_session_authentications").into()),
     };
 
+    let user_session_id = Ulid::from(res.user_session_id);
+    let metadata = session_metadata(
+        user_session_id,
+        res.user_session_ip_address,
+        res.user_session_user_agent,
+    )?;
+
     let browser_session = BrowserSession {
-        id: res.user_session_id.into(),
+        id: user_session_id,
         created_at: res.user_session_created_at,
         user,
         last_authentication,
+        metadata,
     };
 
     let scope = res.oauth2_session_scope.parse().map_err(|e| {
@@ -21,6 +21,51 @@ use uuid::Uuid;
 #[error("Either 'first' or 'last' must be specified")]
 pub struct InvalidPagination;
 
+/// The order in which the items of a paginated query are sorted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PaginationOrder {
+    /// Oldest items first
+    #[default]
+    Ascending,
+
+    /// Newest items first
+    Descending,
+}
+
+impl PaginationOrder {
+    /// The comparison operator used to select the items after a cursor
+    fn after_operator(self) -> &'static str {
+        match self {
+            Self::Ascending => " > ",
+            Self::Descending => " < ",
+        }
+    }
+
+    /// The comparison operator used to select the items before a cursor
+    fn before_operator(self) -> &'static str {
+        match self {
+            Self::Ascending => " < ",
+            Self::Descending => " > ",
+        }
+    }
+
+    /// The `ORDER BY` direction used to fetch the items in this order
+    fn direction(self) -> &'static str {
+        match self {
+            Self::Ascending => " ASC",
+            Self::Descending => " DESC",
+        }
+    }
+
+    /// The `ORDER BY` direction used to fetch the items in the reverse order
+    fn reverse_direction(self) -> &'static str {
+        match self {
+            Self::Ascending => " DESC",
+            Self::Descending => " ASC",
+        }
+    }
+}
+
 /// Add cursor-based pagination to a query, as used in paginated GraphQL
 /// connections
 pub fn generate_pagination<'a, DB>(
@@ -31,6 +76,37 @@ pub fn generate_pagination<'a, DB>(
     first: Option<usize>,
     last: Option<usize>,
 ) -> Result<(), InvalidPagination>
+where
+    DB: Database,
+    Uuid: sqlx::Type<DB> + sqlx::Encode<'a, DB>,
+    i64: sqlx::Type<DB> + sqlx::Encode<'a, DB>,
+{
+    generate_ordered_pagination(
+        query,
+        id_field,
+        PaginationOrder::Ascending,
+        before,
+        after,
+        first,
+        last,
+    )
+}
+
+/// Add cursor-based pagination to a query, with the items sorted in the given
+/// order.
+///
+/// The `before` and `after` cursors are relative to that order, so with a
+/// [`PaginationOrder::Descending`] order, `after` selects the items with a
+/// lower ID than the cursor.
+pub fn generate_ordered_pagination<'a, DB>(
+    query: &mut QueryBuilder<'a, DB>,
+    id_field: &'static str,
+    order: PaginationOrder,
+    before: Option<Ulid>,
+    after: Option<Ulid>,
+    first: Option<usize>,
+    last: Option<usize>,
+) -> Result<(), InvalidPagination>
 where
     DB: Database,
     Uuid: sqlx::Type<DB> + sqlx::Encode<'a, DB>,
@@ -40,22 +116,22 @@ where
     // 1. Start from the greedy query: SELECT * FROM table
 
     // 2. If the after argument is provided, add `id > parsed_cursor` to the `WHERE`
-    // clause
+    // clause (`id < parsed_cursor` in descending order)
     if let Some(after) = after {
         query
             .push(" AND ")
             .push(id_field)
-            .push(" > ")
+            .push(order.after_operator())
             .push_bind(Uuid::from(after));
     }
 
     // 3. If the before argument is provided, add `id < parsed_cursor` to the
-    // `WHERE` clause
+    // `WHERE` clause (`id > parsed_cursor` in descending order)
     if let Some(before) = before {
         query
             .push(" AND ")
             .push(id_field)
-            .push(" < ")
+            .push(order.before_operator())
             .push_bind(Uuid::from(before));
     }
 
@@ -65,7 +141,8 @@ where
         query
             .push(" ORDER BY ")
             .push(id_field)
-            .push(" ASC LIMIT ")
+            .push(order.direction())
+            .push(" LIMIT ")
             .push_bind((count + 1) as i64);
     // 5. If the first argument is provided, add `ORDER BY id DESC LIMIT last+1`
     // to the query
@@ -73,7 +150,8 @@ where
         query
             .push(" ORDER BY ")
             .push(id_field)
-            .push(" DESC LIMIT ")
+            .push(order.reverse_direction())
+            .push(" LIMIT ")
             .push_bind((count + 1) as i64);
     } else {
         return Err(InvalidPagination);
@@ -120,6 +198,16 @@ pub trait QueryBuilderExt {
         first: Option<usize>,
         last: Option<usize>,
     ) -> Result<&mut Self, InvalidPagination>;
+
+    fn generate_ordered_pagination(
+        &mut self,
+        id_field: &'static str,
+        order: PaginationOrder,
+        before: Option<Ulid>,
+        after: Option<Ulid>,
+        first: Option<usize>,
+        last: Option<usize>,
+    ) -> Result<&mut Self, InvalidPagination>;
 }
 
 impl<'a, DB> QueryBuilderExt for QueryBuilder<'a, DB>
@@ -139,4 +227,69 @@ where
         generate_pagination(self, id_field, before, after, first, last)?;
         Ok(self)
     }
+
+    fn generate_ordered_pagination(
+        &mut self,
+        id_field: &'static str,
+        order: PaginationOrder,
+        before: Option<Ulid>,
+        after: Option<Ulid>,
+        first: Option<usize>,
+        last: Option<usize>,
+    ) -> Result<&mut Self, InvalidPagination> {
+        generate_ordered_pagination(self, id_field, order, before, after, first, last)?;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Postgres;
+
+    use super::*;
+
+    #[test]
+    fn test_descending_pagination_sql() {
+        let cursor = Ulid::from_parts(1000, 0);
+
+        let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM t WHERE TRUE");
+        generate_ordered_pagination(
+            &mut query,
+            "id",
+            PaginationOrder::Descending,
+            Some(cursor),
+            Some(cursor),
+            Some(10),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            query.sql(),
+            "SELECT * FROM t WHERE TRUE AND id < $1 AND id > $2 ORDER BY id DESC LIMIT $3"
+        );
+
+        let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM t WHERE TRUE");
+        generate_ordered_pagination(
+            &mut query,
+            "id",
+            PaginationOrder::Descending,
+            None,
+            None,
+            None,
+            Some(10),
+        )
+        .unwrap();
+        assert_eq!(
+            query.sql(),
+            "SELECT * FROM t WHERE TRUE ORDER BY id ASC LIMIT $1"
+        );
+
+        // The ascending order is the one used by `generate_pagination`
+        let mut query = QueryBuilder::<Postgres>::new("SELECT * FROM t WHERE TRUE");
+        generate_pagination(&mut query, "id", None, Some(cursor), Some(10), None).unwrap();
+        assert_eq!(
+            query.sql(),
+            "SELECT * FROM t WHERE TRUE AND id > $1 ORDER BY id ASC LIMIT $2"
+        );
+    }
 }
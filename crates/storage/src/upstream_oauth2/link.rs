@@ -13,15 +13,20 @@
 // limitations under the License.
 
 use chrono::{DateTime, Utc};
-use mas_data_model::{UpstreamOAuthLink, UpstreamOAuthProvider, User};
+use mas_data_model::{
+    BrowserSession, SessionMetadata, UpstreamOAuthAuthorizationSession, UpstreamOAuthLink,
+    UpstreamOAuthProvider, User,
+};
 use rand::Rng;
-use sqlx::{PgExecutor, QueryBuilder};
+use sqlx::{Acquire, PgConnection, PgExecutor, Postgres, QueryBuilder};
 use tracing::{info_span, Instrument};
 use ulid::Ulid;
 use uuid::Uuid;
 
+use super::consume_session;
 use crate::{
     pagination::{process_page, QueryBuilderExt},
+    user::{add_user, authenticate_session_with_upstream, lookup_user, start_session},
     Clock, DatabaseError, LookupResultExt,
 };
 
@@ -116,6 +121,12 @@ pub async fn lookup_link_by_subject(
     Ok(res)
 }
 
+/// Add a new link for a subject on a provider
+///
+/// # Errors
+///
+/// Returns [`DatabaseError::RowsAffected`] if there already is a link for this
+/// subject on this provider, or any other database error
 #[tracing::instrument(
     skip_all,
     fields(
@@ -138,7 +149,7 @@ pub async fn add_link(
     let id = Ulid::from_datetime_with_source(created_at.into(), &mut rng);
     tracing::Span::current().record("upstream_oauth_link.id", tracing::field::display(id));
 
-    sqlx::query!(
+    let res = sqlx::query!(
         r#"
             INSERT INTO upstream_oauth_links (
                 upstream_oauth_link_id,
@@ -147,6 +158,7 @@ pub async fn add_link(
                 subject,
                 created_at
             ) VALUES ($1, $2, NULL, $3, $4)
+            ON CONFLICT (upstream_oauth_provider_id, subject) DO NOTHING
         "#,
         Uuid::from(id),
         Uuid::from(upstream_oauth_provider.id),
@@ -156,6 +168,9 @@ pub async fn add_link(
     .execute(executor)
     .await?;
 
+    // There already is a link for this subject on this provider
+    DatabaseError::ensure_affected_rows(&res, 1)?;
+
     Ok(UpstreamOAuthLink {
         id,
         provider_id: upstream_oauth_provider.id,
@@ -165,6 +180,53 @@ pub async fn add_link(
     })
 }
 
+/// Get the link for a subject on a provider, creating it if it doesn't exist
+///
+/// This handles the case where the link is concurrently created by another
+/// request, in which case the existing link is returned.
+///
+/// # Errors
+///
+/// Returns [`DatabaseError`] if any of the underlying queries failed
+#[tracing::instrument(
+    skip_all,
+    fields(
+        upstream_oauth_link.subject = subject,
+        %upstream_oauth_provider.id,
+    ),
+    err,
+)]
+pub async fn lookup_or_add_link(
+    conn: &mut PgConnection,
+    mut rng: impl Rng + Send,
+    clock: &Clock,
+    upstream_oauth_provider: &UpstreamOAuthProvider,
+    subject: String,
+) -> Result<UpstreamOAuthLink, DatabaseError> {
+    let link = lookup_link_by_subject(&mut *conn, upstream_oauth_provider, &subject).await?;
+    if let Some(link) = link {
+        return Ok(link);
+    }
+
+    let res = add_link(
+        &mut *conn,
+        &mut rng,
+        clock,
+        upstream_oauth_provider,
+        subject.clone(),
+    )
+    .await;
+    match res {
+        // The link was created in the meantime by another request
+        Err(DatabaseError::RowsAffected { actual: 0, .. }) => {
+            lookup_link_by_subject(&mut *conn, upstream_oauth_provider, &subject)
+                .await?
+                .ok_or(DatabaseError::invalid_operation())
+        }
+        res => res,
+    }
+}
+
 #[tracing::instrument(
     skip_all,
     fields(
@@ -240,3 +302,534 @@ pub async fn get_paginated_user_links(
     let page: Vec<_> = page.into_iter().map(Into::into).collect();
     Ok((has_previous_page, has_next_page, page))
 }
+
+/// The tokens the upstream provider returned for a link, encrypted with the
+/// site-wide encrypter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpstreamOAuthLinkTokens {
+    pub encrypted_access_token: String,
+    pub encrypted_refresh_token: Option<String>,
+    pub access_token_expires_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+struct LinkTokensLookup {
+    encrypted_access_token: Option<String>,
+    encrypted_refresh_token: Option<String>,
+    access_token_expires_at: Option<DateTime<Utc>>,
+    tokens_updated_at: Option<DateTime<Utc>>,
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %upstream_oauth_link.id,
+        %upstream_oauth_link.subject,
+    ),
+    err,
+)]
+pub async fn store_upstream_tokens(
+    executor: impl PgExecutor<'_>,
+    clock: &Clock,
+    upstream_oauth_link: &UpstreamOAuthLink,
+    encrypted_access_token: &str,
+    encrypted_refresh_token: Option<&str>,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<(), DatabaseError> {
+    let updated_at = clock.now();
+
+    let res = sqlx::query!(
+        r#"
+            UPDATE upstream_oauth_links
+            SET encrypted_access_token = $2,
+                encrypted_refresh_token = $3,
+                access_token_expires_at = $4,
+                tokens_updated_at = $5
+            WHERE upstream_oauth_link_id = $1
+        "#,
+        Uuid::from(upstream_oauth_link.id),
+        encrypted_access_token,
+        encrypted_refresh_token,
+        expires_at,
+        updated_at,
+    )
+    .execute(executor)
+    .await?;
+
+    DatabaseError::ensure_affected_rows(&res, 1)
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %upstream_oauth_link.id,
+        %upstream_oauth_link.subject,
+    ),
+    err,
+)]
+pub async fn lookup_upstream_tokens(
+    executor: impl PgExecutor<'_>,
+    upstream_oauth_link: &UpstreamOAuthLink,
+) -> Result<Option<UpstreamOAuthLinkTokens>, DatabaseError> {
+    let res = sqlx::query_as!(
+        LinkTokensLookup,
+        r#"
+            SELECT
+                encrypted_access_token,
+                encrypted_refresh_token,
+                access_token_expires_at,
+                tokens_updated_at
+            FROM upstream_oauth_links
+            WHERE upstream_oauth_link_id = $1
+        "#,
+        Uuid::from(upstream_oauth_link.id),
+    )
+    .fetch_one(executor)
+    .await
+    .to_option()?;
+
+    // The tokens may have never been stored for this link
+    let res = res.and_then(|res| {
+        Some(UpstreamOAuthLinkTokens {
+            encrypted_access_token: res.encrypted_access_token?,
+            encrypted_refresh_token: res.encrypted_refresh_token,
+            access_token_expires_at: res.access_token_expires_at,
+            updated_at: res.tokens_updated_at?,
+        })
+    });
+
+    Ok(res)
+}
+
+/// Consume the upstream authorization session and authenticate the browser
+/// session with the link
+///
+/// # Errors
+///
+/// Returns [`DatabaseError::InvalidOperation`] if the upstream authorization
+/// session was already consumed, or any other database error
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %upstream_oauth_link.id,
+        %upstream_oauth_authorization_session.id,
+        %user_session.id,
+    ),
+    err,
+)]
+pub async fn authenticate_with_link(
+    conn: &mut PgConnection,
+    mut rng: impl Rng + Send,
+    clock: &Clock,
+    mut user_session: BrowserSession,
+    upstream_oauth_link: &UpstreamOAuthLink,
+    upstream_oauth_authorization_session: UpstreamOAuthAuthorizationSession,
+) -> Result<BrowserSession, DatabaseError> {
+    if upstream_oauth_authorization_session.consumed() {
+        return Err(DatabaseError::invalid_operation());
+    }
+
+    consume_session(&mut *conn, clock, upstream_oauth_authorization_session).await?;
+    authenticate_session_with_upstream(
+        &mut *conn,
+        &mut rng,
+        clock,
+        &mut user_session,
+        upstream_oauth_link,
+    )
+    .await?;
+
+    Ok(user_session)
+}
+
+/// Register a new user, associate the upstream link to them, and start a
+/// browser session authenticated with that link
+///
+/// # Errors
+///
+/// Returns [`DatabaseError::InvalidOperation`] if the link is already
+/// associated to a user, or any other database error
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %upstream_oauth_link.id,
+        %upstream_oauth_authorization_session.id,
+        user.username = username,
+    ),
+    err,
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn register_and_link(
+    conn: impl Acquire<'_, Database = Postgres> + Send,
+    mut rng: impl Rng + Send,
+    clock: &Clock,
+    username: &str,
+    upstream_oauth_link: &UpstreamOAuthLink,
+    upstream_oauth_authorization_session: UpstreamOAuthAuthorizationSession,
+    metadata: Option<SessionMetadata>,
+    max_session_age: Option<chrono::Duration>,
+) -> Result<BrowserSession, DatabaseError> {
+    if upstream_oauth_link.user_id.is_some() {
+        return Err(DatabaseError::invalid_operation());
+    }
+
+    let mut txn = conn.begin().await?;
+
+    let user = add_user(&mut txn, &mut rng, clock, username, None).await?;
+    associate_link_to_user(&mut txn, upstream_oauth_link, &user).await?;
+    let session = start_session(&mut txn, &mut rng, clock, user, metadata, max_session_age).await?;
+    let session = authenticate_with_link(
+        &mut txn,
+        &mut rng,
+        clock,
+        session,
+        upstream_oauth_link,
+        upstream_oauth_authorization_session,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(session)
+}
+
+/// Start a browser session for the user associated to the upstream link,
+/// authenticated with that link
+///
+/// # Errors
+///
+/// Returns [`DatabaseError::InvalidOperation`] if the link isn't associated to
+/// a user or if that user is deactivated, or any other database error
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %upstream_oauth_link.id,
+        %upstream_oauth_authorization_session.id,
+    ),
+    err,
+)]
+pub async fn login_and_link(
+    conn: impl Acquire<'_, Database = Postgres> + Send,
+    mut rng: impl Rng + Send,
+    clock: &Clock,
+    upstream_oauth_link: &UpstreamOAuthLink,
+    upstream_oauth_authorization_session: UpstreamOAuthAuthorizationSession,
+    metadata: Option<SessionMetadata>,
+    max_session_age: Option<chrono::Duration>,
+) -> Result<BrowserSession, DatabaseError> {
+    let user_id = upstream_oauth_link
+        .user_id
+        .ok_or(DatabaseError::invalid_operation())?;
+
+    let mut txn = conn.begin().await?;
+
+    let user = lookup_user(&mut txn, user_id).await?;
+    if !user.is_active() {
+        return Err(DatabaseError::invalid_operation());
+    }
+
+    let session = start_session(&mut txn, &mut rng, clock, user, metadata, max_session_age).await?;
+    let session = authenticate_with_link(
+        &mut txn,
+        &mut rng,
+        clock,
+        session,
+        upstream_oauth_link,
+        upstream_oauth_authorization_session,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(session)
+}
+
+/// Associate the upstream link to the user of an existing browser session,
+/// and authenticate that session with the link
+///
+/// # Errors
+///
+/// Returns [`DatabaseError::InvalidOperation`] if the link is already
+/// associated to a user, or any other database error
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %upstream_oauth_link.id,
+        %upstream_oauth_authorization_session.id,
+        %user_session.id,
+    ),
+    err,
+)]
+pub async fn link_to_session(
+    conn: impl Acquire<'_, Database = Postgres> + Send,
+    mut rng: impl Rng + Send,
+    clock: &Clock,
+    user_session: BrowserSession,
+    upstream_oauth_link: &UpstreamOAuthLink,
+    upstream_oauth_authorization_session: UpstreamOAuthAuthorizationSession,
+) -> Result<BrowserSession, DatabaseError> {
+    if upstream_oauth_link.user_id.is_some() {
+        return Err(DatabaseError::invalid_operation());
+    }
+
+    let mut txn = conn.begin().await?;
+
+    associate_link_to_user(&mut txn, upstream_oauth_link, &user_session.user).await?;
+    let session = authenticate_with_link(
+        &mut txn,
+        &mut rng,
+        clock,
+        user_session,
+        upstream_oauth_link,
+        upstream_oauth_authorization_session,
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(session)
+}
+
+#[cfg(test)]
+mod tests {
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
+    use rand::SeedableRng;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        upstream_oauth2::{add_provider, add_session, lookup_session, AddProviderParams},
+        user::{deactivate_user, get_session_events},
+    };
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_concurrent_link_creation(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+
+        let params = AddProviderParams::new(
+            "https://example.com/".to_owned(),
+            "openid".parse().unwrap(),
+            OAuthClientAuthenticationMethod::None,
+            "client".to_owned(),
+        );
+        let provider = add_provider(&pool, &mut rng, &clock, params).await?;
+
+        // Two callbacks for the same subject run at the same time, and both
+        // don't see a link yet
+        let mut first = pool.begin().await?;
+        let mut second = pool.begin().await?;
+        assert!(lookup_link_by_subject(&mut first, &provider, "subject")
+            .await?
+            .is_none());
+        assert!(lookup_link_by_subject(&mut second, &provider, "subject")
+            .await?
+            .is_none());
+
+        let link = add_link(
+            &mut first,
+            &mut rng,
+            &clock,
+            &provider,
+            "subject".to_owned(),
+        )
+        .await?;
+        first.commit().await?;
+
+        // Adding the link again fails without aborting the transaction
+        assert!(matches!(
+            add_link(
+                &mut second,
+                &mut rng,
+                &clock,
+                &provider,
+                "subject".to_owned()
+            )
+            .await,
+            Err(DatabaseError::RowsAffected { actual: 0, .. })
+        ));
+
+        // ...and the existing link is used instead
+        let other = lookup_or_add_link(
+            &mut second,
+            &mut rng,
+            &clock,
+            &provider,
+            "subject".to_owned(),
+        )
+        .await?;
+        assert_eq!(other.id, link.id);
+        second.commit().await?;
+
+        // Only one link was created
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM upstream_oauth_links")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(count, 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_register_and_login_with_link(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let params = AddProviderParams::new(
+            "https://example.com/".to_owned(),
+            "openid".parse().unwrap(),
+            OAuthClientAuthenticationMethod::None,
+            "client".to_owned(),
+        );
+        let provider = add_provider(&mut conn, &mut rng, &clock, params).await?;
+        let link = add_link(&mut conn, &mut rng, &clock, &provider, "subject".to_owned()).await?;
+
+        // A link without a user can't be used to log in
+        let upstream_session = add_session(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &provider,
+            "state".to_owned(),
+            None,
+            "nonce".to_owned(),
+        )
+        .await?;
+        assert!(login_and_link(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &link,
+            upstream_session.clone(),
+            None,
+            None,
+        )
+        .await
+        .is_err());
+
+        let metadata = SessionMetadata {
+            ip: Some("192.0.2.1".parse().unwrap()),
+            user_agent: Some("Mozilla/5.0".to_owned()),
+        };
+        let session = register_and_link(
+            &mut conn,
+            &mut rng,
+            &clock,
+            "alice",
+            &link,
+            upstream_session.clone(),
+            Some(metadata.clone()),
+            None,
+        )
+        .await?;
+        assert_eq!(session.user.username, "alice");
+        assert!(session.last_authentication.is_some());
+        assert_eq!(session.metadata, metadata);
+
+        // The link is now associated to the user, and the session consumed
+        let link = lookup_link(&mut conn, link.id).await?.unwrap();
+        assert_eq!(link.user_id, Some(session.user.id));
+        let (_, upstream_session) = lookup_session(&mut conn, upstream_session.id)
+            .await?
+            .unwrap();
+        assert!(upstream_session.consumed());
+
+        // The consumed session can't be used again
+        assert!(matches!(
+            authenticate_with_link(
+                &mut conn,
+                &mut rng,
+                &clock,
+                session.clone(),
+                &link,
+                upstream_session,
+            )
+            .await,
+            Err(DatabaseError::InvalidOperation { .. })
+        ));
+
+        // A link associated to a user can't be used to register again
+        let upstream_session = add_session(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &provider,
+            "state2".to_owned(),
+            None,
+            "nonce".to_owned(),
+        )
+        .await?;
+        assert!(register_and_link(
+            &mut conn,
+            &mut rng,
+            &clock,
+            "bob",
+            &link,
+            upstream_session.clone(),
+            None,
+            None,
+        )
+        .await
+        .is_err());
+
+        // But it can be used to log in
+        let other_session = login_and_link(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &link,
+            upstream_session,
+            None,
+            None,
+        )
+        .await?;
+        assert_eq!(other_session.user.id, session.user.id);
+        assert_ne!(other_session.id, session.id);
+
+        // Both sessions were recorded as started and authenticated
+        let (_, _, events) =
+            get_session_events(&mut conn, &session.user, None, None, Some(10), None).await?;
+        let mut events: Vec<_> = events
+            .into_iter()
+            .map(|event| (event.session_id, event.kind.as_str()))
+            .collect();
+        events.sort_unstable();
+        let mut expected = vec![
+            (Some(session.id), "started"),
+            (Some(session.id), "authenticated"),
+            (Some(other_session.id), "started"),
+            (Some(other_session.id), "authenticated"),
+        ];
+        expected.sort_unstable();
+        assert_eq!(events, expected);
+
+        // Unless the user was deactivated
+        deactivate_user(&mut conn, &clock, session.user).await?;
+        let upstream_session = add_session(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &provider,
+            "state3".to_owned(),
+            None,
+            "nonce".to_owned(),
+        )
+        .await?;
+        assert!(matches!(
+            login_and_link(
+                &mut conn,
+                &mut rng,
+                &clock,
+                &link,
+                upstream_session,
+                None,
+                None
+            )
+            .await,
+            Err(DatabaseError::InvalidOperation { .. })
+        ));
+
+        Ok(())
+    }
+}
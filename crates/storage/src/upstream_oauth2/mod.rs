@@ -18,11 +18,15 @@ mod session;
 
 pub use self::{
     link::{
-        add_link, associate_link_to_user, get_paginated_user_links, lookup_link,
-        lookup_link_by_subject,
+        add_link, associate_link_to_user, authenticate_with_link, get_paginated_user_links,
+        link_to_session, login_and_link, lookup_link, lookup_link_by_subject, lookup_or_add_link,
+        lookup_upstream_tokens, register_and_link, store_upstream_tokens, UpstreamOAuthLinkTokens,
+    },
+    provider::{
+        add_provider, get_paginated_providers, get_providers, lookup_provider, AddProviderParams,
     },
-    provider::{add_provider, get_paginated_providers, get_providers, lookup_provider},
     session::{
-        add_session, complete_session, consume_session, lookup_session, lookup_session_on_link,
+        add_session, complete_session, consume_session, lookup_session, lookup_session_by_state,
+        lookup_session_on_link, purge_stale_upstream_sessions,
     },
 };
@@ -28,15 +28,21 @@ use crate::{
 };
 
 #[derive(sqlx::FromRow)]
-struct ProviderLookup {
-    upstream_oauth_provider_id: Uuid,
-    issuer: String,
-    scope: String,
-    client_id: String,
-    encrypted_client_secret: Option<String>,
-    token_endpoint_signing_alg: Option<String>,
-    token_endpoint_auth_method: String,
-    created_at: DateTime<Utc>,
+pub(super) struct ProviderLookup {
+    pub(super) upstream_oauth_provider_id: Uuid,
+    pub(super) issuer: String,
+    pub(super) human_name: Option<String>,
+    pub(super) scope: String,
+    pub(super) client_id: String,
+    pub(super) encrypted_client_secret: Option<String>,
+    pub(super) token_endpoint_signing_alg: Option<String>,
+    pub(super) token_endpoint_auth_method: String,
+    pub(super) id_token_signed_response_alg: Option<String>,
+    pub(super) fetch_userinfo: bool,
+    pub(super) username_claim: Option<String>,
+    pub(super) allow_plain_pkce: bool,
+    pub(super) trust_email_verified: bool,
+    pub(super) created_at: DateTime<Utc>,
 }
 
 impl TryFrom<ProviderLookup> for UpstreamOAuthProvider {
@@ -65,15 +71,31 @@ impl TryFrom<ProviderLookup> for UpstreamOAuthProvider {
                     .row(id)
                     .source(e)
             })?;
+        let id_token_signed_response_alg = value
+            .id_token_signed_response_alg
+            .map(|x| x.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("upstream_oauth_providers")
+                    .column("id_token_signed_response_alg")
+                    .row(id)
+                    .source(e)
+            })?;
 
         Ok(UpstreamOAuthProvider {
             id,
             issuer: value.issuer,
+            human_name: value.human_name,
             scope,
             client_id: value.client_id,
             encrypted_client_secret: value.encrypted_client_secret,
             token_endpoint_auth_method,
             token_endpoint_signing_alg,
+            id_token_signed_response_alg,
+            fetch_userinfo: value.fetch_userinfo,
+            username_claim: value.username_claim,
+            allow_plain_pkce: value.allow_plain_pkce,
+            trust_email_verified: value.trust_email_verified,
             created_at: value.created_at,
         })
     }
@@ -94,11 +116,17 @@ pub async fn lookup_provider(
             SELECT
                 upstream_oauth_provider_id,
                 issuer,
+                human_name,
                 scope,
                 client_id,
                 encrypted_client_secret,
                 token_endpoint_signing_alg,
                 token_endpoint_auth_method,
+                id_token_signed_response_alg,
+                fetch_userinfo,
+                username_claim,
+                allow_plain_pkce,
+                trust_email_verified,
                 created_at
             FROM upstream_oauth_providers
             WHERE upstream_oauth_provider_id = $1
@@ -117,27 +145,80 @@ pub async fn lookup_provider(
     Ok(res)
 }
 
+/// The settings of a new upstream OAuth 2.0 provider, passed to
+/// [`add_provider`]
+#[derive(Debug, Clone)]
+pub struct AddProviderParams {
+    pub issuer: String,
+    pub human_name: Option<String>,
+    pub scope: Scope,
+    pub token_endpoint_auth_method: OAuthClientAuthenticationMethod,
+    pub token_endpoint_signing_alg: Option<JsonWebSignatureAlg>,
+    pub id_token_signed_response_alg: Option<JsonWebSignatureAlg>,
+    pub fetch_userinfo: bool,
+    pub username_claim: Option<String>,
+    pub allow_plain_pkce: bool,
+    pub trust_email_verified: bool,
+    pub client_id: String,
+    pub encrypted_client_secret: Option<String>,
+}
+
+impl AddProviderParams {
+    /// Create the settings of a provider with all the optional ones left to
+    /// their default
+    #[must_use]
+    pub fn new(
+        issuer: String,
+        scope: Scope,
+        token_endpoint_auth_method: OAuthClientAuthenticationMethod,
+        client_id: String,
+    ) -> Self {
+        Self {
+            issuer,
+            human_name: None,
+            scope,
+            token_endpoint_auth_method,
+            token_endpoint_signing_alg: None,
+            id_token_signed_response_alg: None,
+            fetch_userinfo: false,
+            username_claim: None,
+            allow_plain_pkce: false,
+            trust_email_verified: false,
+            client_id,
+            encrypted_client_secret: None,
+        }
+    }
+}
+
 #[tracing::instrument(
     skip_all,
     fields(
         upstream_oauth_provider.id,
-        upstream_oauth_provider.issuer = %issuer,
-        upstream_oauth_provider.client_id = %client_id,
+        upstream_oauth_provider.issuer = %params.issuer,
+        upstream_oauth_provider.client_id = %params.client_id,
     ),
     err,
 )]
-#[allow(clippy::too_many_arguments)]
 pub async fn add_provider(
     executor: impl PgExecutor<'_>,
     mut rng: impl Rng + Send,
     clock: &Clock,
-    issuer: String,
-    scope: Scope,
-    token_endpoint_auth_method: OAuthClientAuthenticationMethod,
-    token_endpoint_signing_alg: Option<JsonWebSignatureAlg>,
-    client_id: String,
-    encrypted_client_secret: Option<String>,
+    params: AddProviderParams,
 ) -> Result<UpstreamOAuthProvider, sqlx::Error> {
+    let AddProviderParams {
+        issuer,
+        human_name,
+        scope,
+        token_endpoint_auth_method,
+        token_endpoint_signing_alg,
+        id_token_signed_response_alg,
+        fetch_userinfo,
+        username_claim,
+        allow_plain_pkce,
+        trust_email_verified,
+        client_id,
+        encrypted_client_secret,
+    } = params;
     let created_at = clock.now();
     let id = Ulid::from_datetime_with_source(created_at.into(), &mut rng);
     tracing::Span::current().record("upstream_oauth_provider.id", tracing::field::display(id));
@@ -147,19 +228,33 @@ pub async fn add_provider(
             INSERT INTO upstream_oauth_providers (
                 upstream_oauth_provider_id,
                 issuer,
+                human_name,
                 scope,
                 token_endpoint_auth_method,
                 token_endpoint_signing_alg,
+                id_token_signed_response_alg,
+                fetch_userinfo,
+                username_claim,
+                allow_plain_pkce,
+                trust_email_verified,
                 client_id,
                 encrypted_client_secret,
                 created_at
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
         "#,
         Uuid::from(id),
         &issuer,
+        human_name.as_deref(),
         scope.to_string(),
         token_endpoint_auth_method.to_string(),
         token_endpoint_signing_alg.as_ref().map(ToString::to_string),
+        id_token_signed_response_alg
+            .as_ref()
+            .map(ToString::to_string),
+        fetch_userinfo,
+        username_claim.as_deref(),
+        allow_plain_pkce,
+        trust_email_verified,
         &client_id,
         encrypted_client_secret.as_deref(),
         created_at,
@@ -170,11 +265,17 @@ pub async fn add_provider(
     Ok(UpstreamOAuthProvider {
         id,
         issuer,
+        human_name,
         scope,
         client_id,
         encrypted_client_secret,
         token_endpoint_signing_alg,
         token_endpoint_auth_method,
+        id_token_signed_response_alg,
+        fetch_userinfo,
+        username_claim,
+        allow_plain_pkce,
+        trust_email_verified,
         created_at,
     })
 }
@@ -192,11 +293,17 @@ pub async fn get_paginated_providers(
             SELECT
                 upstream_oauth_provider_id,
                 issuer,
+                human_name,
                 scope,
                 client_id,
                 encrypted_client_secret,
                 token_endpoint_signing_alg,
                 token_endpoint_auth_method,
+                id_token_signed_response_alg,
+                fetch_userinfo,
+                username_claim,
+                allow_plain_pkce,
+                trust_email_verified,
                 created_at
             FROM upstream_oauth_providers
             WHERE 1 = 1
@@ -231,11 +338,17 @@ pub async fn get_providers(
             SELECT
                 upstream_oauth_provider_id,
                 issuer,
+                human_name,
                 scope,
                 client_id,
                 encrypted_client_secret,
                 token_endpoint_signing_alg,
                 token_endpoint_auth_method,
+                id_token_signed_response_alg,
+                fetch_userinfo,
+                username_claim,
+                allow_plain_pkce,
+                trust_email_verified,
                 created_at
             FROM upstream_oauth_providers
         "#,
@@ -246,3 +359,65 @@ pub async fn get_providers(
     let res: Result<Vec<_>, _> = res.into_iter().map(TryInto::try_into).collect();
     Ok(res?)
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_allow_plain_pkce(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let params = AddProviderParams {
+            allow_plain_pkce: true,
+            ..AddProviderParams::new(
+                "https://example.com/".to_owned(),
+                "openid".parse().unwrap(),
+                OAuthClientAuthenticationMethod::None,
+                "client".to_owned(),
+            )
+        };
+        let provider = add_provider(&mut conn, &mut rng, &clock, params).await?;
+        assert!(provider.allow_plain_pkce);
+
+        let provider = lookup_provider(&mut conn, provider.id).await?.unwrap();
+        assert!(provider.allow_plain_pkce);
+        assert!(!provider.trust_email_verified);
+
+        // Existing providers don't fall back to plain PKCE
+        let id = Ulid::from_datetime_with_source(clock.now().into(), &mut rng);
+        sqlx::query!(
+            r#"
+                INSERT INTO upstream_oauth_providers (
+                    upstream_oauth_provider_id,
+                    issuer,
+                    scope,
+                    token_endpoint_auth_method,
+                    client_id,
+                    created_at
+                ) VALUES ($1, 'https://example.org/', 'openid', 'none', 'client', $2)
+            "#,
+            Uuid::from(id),
+            clock.now(),
+        )
+        .execute(&mut conn)
+        .await?;
+
+        let providers = get_providers(&mut conn).await?;
+        assert_eq!(providers.len(), 2);
+        assert_eq!(
+            providers
+                .iter()
+                .filter(|provider| provider.allow_plain_pkce)
+                .count(),
+            1
+        );
+
+        Ok(())
+    }
+}
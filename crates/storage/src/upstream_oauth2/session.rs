@@ -12,14 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use mas_data_model::{UpstreamOAuthAuthorizationSession, UpstreamOAuthLink, UpstreamOAuthProvider};
 use rand::Rng;
 use sqlx::PgExecutor;
 use ulid::Ulid;
 use uuid::Uuid;
 
-use crate::{Clock, DatabaseError, DatabaseInconsistencyError, LookupResultExt};
+use super::provider::ProviderLookup;
+use crate::{Clock, DatabaseError, LookupResultExt};
 
 struct SessionAndProviderLookup {
     upstream_oauth_authorization_session_id: Uuid,
@@ -29,15 +30,22 @@ struct SessionAndProviderLookup {
     code_challenge_verifier: Option<String>,
     nonce: String,
     id_token: Option<String>,
+    userinfo: Option<serde_json::Value>,
     created_at: DateTime<Utc>,
     completed_at: Option<DateTime<Utc>>,
     consumed_at: Option<DateTime<Utc>>,
     provider_issuer: String,
+    provider_human_name: Option<String>,
     provider_scope: String,
     provider_client_id: String,
     provider_encrypted_client_secret: Option<String>,
     provider_token_endpoint_auth_method: String,
     provider_token_endpoint_signing_alg: Option<String>,
+    provider_id_token_signed_response_alg: Option<String>,
+    provider_fetch_userinfo: bool,
+    provider_username_claim: Option<String>,
+    provider_allow_plain_pkce: bool,
+    provider_trust_email_verified: bool,
     provider_created_at: DateTime<Utc>,
 }
 
@@ -62,15 +70,22 @@ pub async fn lookup_session(
                 ua.code_challenge_verifier,
                 ua.nonce,
                 ua.id_token,
+                ua.userinfo,
                 ua.created_at,
                 ua.completed_at,
                 ua.consumed_at,
                 up.issuer AS "provider_issuer",
+                up.human_name AS "provider_human_name",
                 up.scope AS "provider_scope",
                 up.client_id AS "provider_client_id",
                 up.encrypted_client_secret AS "provider_encrypted_client_secret",
                 up.token_endpoint_auth_method AS "provider_token_endpoint_auth_method",
                 up.token_endpoint_signing_alg AS "provider_token_endpoint_signing_alg",
+                up.id_token_signed_response_alg AS "provider_id_token_signed_response_alg",
+                up.fetch_userinfo AS "provider_fetch_userinfo",
+                up.username_claim AS "provider_username_claim",
+                up.allow_plain_pkce AS "provider_allow_plain_pkce",
+                up.trust_email_verified AS "provider_trust_email_verified",
                 up.created_at AS "provider_created_at"
             FROM upstream_oauth_authorization_sessions ua
             INNER JOIN upstream_oauth_providers up
@@ -85,38 +100,23 @@ pub async fn lookup_session(
 
     let Some(res) = res else { return Ok(None) };
 
-    let id = res.upstream_oauth_provider_id.into();
-    let provider = UpstreamOAuthProvider {
-        id,
+    let provider: UpstreamOAuthProvider = ProviderLookup {
+        upstream_oauth_provider_id: res.upstream_oauth_provider_id,
         issuer: res.provider_issuer,
-        scope: res.provider_scope.parse().map_err(|e| {
-            DatabaseInconsistencyError::on("upstream_oauth_providers")
-                .column("scope")
-                .row(id)
-                .source(e)
-        })?,
+        human_name: res.provider_human_name,
+        scope: res.provider_scope,
         client_id: res.provider_client_id,
         encrypted_client_secret: res.provider_encrypted_client_secret,
-        token_endpoint_auth_method: res.provider_token_endpoint_auth_method.parse().map_err(
-            |e| {
-                DatabaseInconsistencyError::on("upstream_oauth_providers")
-                    .column("token_endpoint_auth_method")
-                    .row(id)
-                    .source(e)
-            },
-        )?,
-        token_endpoint_signing_alg: res
-            .provider_token_endpoint_signing_alg
-            .map(|x| x.parse())
-            .transpose()
-            .map_err(|e| {
-                DatabaseInconsistencyError::on("upstream_oauth_providers")
-                    .column("token_endpoint_signing_alg")
-                    .row(id)
-                    .source(e)
-            })?,
+        token_endpoint_signing_alg: res.provider_token_endpoint_signing_alg,
+        token_endpoint_auth_method: res.provider_token_endpoint_auth_method,
+        id_token_signed_response_alg: res.provider_id_token_signed_response_alg,
+        fetch_userinfo: res.provider_fetch_userinfo,
+        username_claim: res.provider_username_claim,
+        allow_plain_pkce: res.provider_allow_plain_pkce,
+        trust_email_verified: res.provider_trust_email_verified,
         created_at: res.provider_created_at,
-    };
+    }
+    .try_into()?;
 
     let session = UpstreamOAuthAuthorizationSession {
         id: res.upstream_oauth_authorization_session_id.into(),
@@ -126,6 +126,7 @@ pub async fn lookup_session(
         code_challenge_verifier: res.code_challenge_verifier,
         nonce: res.nonce,
         id_token: res.id_token,
+        userinfo: res.userinfo,
         created_at: res.created_at,
         completed_at: res.completed_at,
         consumed_at: res.consumed_at,
@@ -172,8 +173,9 @@ pub async fn add_session(
                 created_at,
                 completed_at,
                 consumed_at,
-                id_token
-            ) VALUES ($1, $2, $3, $4, $5, $6, NULL, NULL, NULL)
+                id_token,
+                userinfo
+            ) VALUES ($1, $2, $3, $4, $5, $6, NULL, NULL, NULL, NULL)
         "#,
         Uuid::from(id),
         Uuid::from(upstream_oauth_provider.id),
@@ -193,6 +195,7 @@ pub async fn add_session(
         code_challenge_verifier,
         nonce,
         id_token: None,
+        userinfo: None,
         created_at,
         completed_at: None,
         consumed_at: None,
@@ -214,6 +217,7 @@ pub async fn complete_session(
     mut upstream_oauth_authorization_session: UpstreamOAuthAuthorizationSession,
     upstream_oauth_link: &UpstreamOAuthLink,
     id_token: Option<String>,
+    userinfo: Option<serde_json::Value>,
 ) -> Result<UpstreamOAuthAuthorizationSession, sqlx::Error> {
     let completed_at = clock.now();
     sqlx::query!(
@@ -221,12 +225,14 @@ pub async fn complete_session(
             UPDATE upstream_oauth_authorization_sessions
             SET upstream_oauth_link_id = $1,
                 completed_at = $2,
-                id_token = $3
-            WHERE upstream_oauth_authorization_session_id = $4
+                id_token = $3,
+                userinfo = $4
+            WHERE upstream_oauth_authorization_session_id = $5
         "#,
         Uuid::from(upstream_oauth_link.id),
         completed_at,
         id_token,
+        userinfo,
         Uuid::from(upstream_oauth_authorization_session.id),
     )
     .execute(executor)
@@ -234,6 +240,7 @@ pub async fn complete_session(
 
     upstream_oauth_authorization_session.completed_at = Some(completed_at);
     upstream_oauth_authorization_session.id_token = id_token;
+    upstream_oauth_authorization_session.userinfo = userinfo;
 
     Ok(upstream_oauth_authorization_session)
 }
@@ -269,6 +276,33 @@ pub async fn consume_session(
     Ok(upstream_oauth_authorization_session)
 }
 
+/// Delete the authorization sessions which were abandoned, i.e. never
+/// completed, consumed or linked, and created more than `older_than` ago.
+///
+/// Returns the number of deleted sessions
+#[tracing::instrument(skip_all, err)]
+pub async fn purge_stale_upstream_sessions(
+    executor: impl PgExecutor<'_>,
+    clock: &Clock,
+    older_than: Duration,
+) -> Result<u64, DatabaseError> {
+    let threshold = clock.now() - older_than;
+    let res = sqlx::query!(
+        r#"
+            DELETE FROM upstream_oauth_authorization_sessions
+            WHERE completed_at IS NULL
+              AND consumed_at IS NULL
+              AND upstream_oauth_link_id IS NULL
+              AND created_at < $1
+        "#,
+        threshold,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(res.rows_affected())
+}
+
 struct SessionLookup {
     upstream_oauth_authorization_session_id: Uuid,
     upstream_oauth_provider_id: Uuid,
@@ -277,6 +311,7 @@ struct SessionLookup {
     code_challenge_verifier: Option<String>,
     nonce: String,
     id_token: Option<String>,
+    userinfo: Option<serde_json::Value>,
     created_at: DateTime<Utc>,
     completed_at: Option<DateTime<Utc>>,
     consumed_at: Option<DateTime<Utc>>,
@@ -307,6 +342,7 @@ pub async fn lookup_session_on_link(
                 code_challenge_verifier,
                 nonce,
                 id_token,
+                userinfo,
                 created_at,
                 completed_at,
                 consumed_at
@@ -331,8 +367,133 @@ pub async fn lookup_session_on_link(
         code_challenge_verifier: res.code_challenge_verifier,
         nonce: res.nonce,
         id_token: res.id_token,
+        userinfo: res.userinfo,
+        created_at: res.created_at,
+        completed_at: res.completed_at,
+        consumed_at: res.consumed_at,
+    }))
+}
+
+/// Lookup a session by the state it was started with
+///
+/// The lookup is scoped to the provider, to avoid mixing up sessions from
+/// different providers
+#[tracing::instrument(
+    skip_all,
+    fields(
+        upstream_oauth_authorization_session.id,
+        %upstream_oauth_provider.id,
+        %upstream_oauth_provider.issuer,
+    ),
+    err,
+)]
+pub async fn lookup_session_by_state(
+    executor: impl PgExecutor<'_>,
+    upstream_oauth_provider: &UpstreamOAuthProvider,
+    state: &str,
+) -> Result<Option<UpstreamOAuthAuthorizationSession>, DatabaseError> {
+    // The state column has a unique constraint, which is backed by an index
+    let res = sqlx::query_as!(
+        SessionLookup,
+        r#"
+            SELECT
+                upstream_oauth_authorization_session_id,
+                upstream_oauth_provider_id,
+                upstream_oauth_link_id,
+                state,
+                code_challenge_verifier,
+                nonce,
+                id_token,
+                userinfo,
+                created_at,
+                completed_at,
+                consumed_at
+            FROM upstream_oauth_authorization_sessions
+            WHERE state = $1
+              AND upstream_oauth_provider_id = $2
+        "#,
+        state,
+        Uuid::from(upstream_oauth_provider.id),
+    )
+    .fetch_one(executor)
+    .await
+    .to_option()?;
+
+    let Some(res) = res else { return Ok(None) };
+
+    tracing::Span::current().record(
+        "upstream_oauth_authorization_session.id",
+        tracing::field::display(Ulid::from(res.upstream_oauth_authorization_session_id)),
+    );
+
+    Ok(Some(UpstreamOAuthAuthorizationSession {
+        id: res.upstream_oauth_authorization_session_id.into(),
+        provider_id: res.upstream_oauth_provider_id.into(),
+        link_id: res.upstream_oauth_link_id.map(Ulid::from),
+        state: res.state,
+        code_challenge_verifier: res.code_challenge_verifier,
+        nonce: res.nonce,
+        id_token: res.id_token,
+        userinfo: res.userinfo,
         created_at: res.created_at,
         completed_at: res.completed_at,
         consumed_at: res.consumed_at,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
+    use rand::SeedableRng;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::upstream_oauth2::{add_provider, AddProviderParams};
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_lookup_session_by_state(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let params = |issuer: &str| {
+            AddProviderParams::new(
+                issuer.to_owned(),
+                "openid".parse().unwrap(),
+                OAuthClientAuthenticationMethod::None,
+                "client".to_owned(),
+            )
+        };
+        let provider =
+            add_provider(&mut conn, &mut rng, &clock, params("https://example.com/")).await?;
+        let other_provider =
+            add_provider(&mut conn, &mut rng, &clock, params("https://example.org/")).await?;
+
+        let session = add_session(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &provider,
+            "state".to_owned(),
+            None,
+            "nonce".to_owned(),
+        )
+        .await?;
+
+        let found = lookup_session_by_state(&mut conn, &provider, "state")
+            .await?
+            .unwrap();
+        assert_eq!(found.id, session.id);
+        assert_eq!(found.provider_id, provider.id);
+
+        // The lookup is scoped to the provider
+        assert!(lookup_session_by_state(&mut conn, &other_provider, "state")
+            .await?
+            .is_none());
+        assert!(lookup_session_by_state(&mut conn, &provider, "other-state")
+            .await?
+            .is_none());
+
+        Ok(())
+    }
+}
@@ -12,14 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use chrono::{DateTime, Utc};
 use mas_data_model::{Authentication, BrowserSession, Password, UpstreamOAuthLink};
 use rand::Rng;
-use sqlx::PgExecutor;
+use sqlx::{Acquire, PgExecutor, Postgres};
 use ulid::Ulid;
 use uuid::Uuid;
 
-use crate::Clock;
+use super::{record_session_event, SessionEventKind};
+use crate::{Clock, DatabaseError};
 
+/// Mark a browser session as authenticated by a password, and record a
+/// [`SessionEventKind::Authenticated`] event for it
 #[tracing::instrument(
     skip_all,
     fields(
@@ -31,12 +35,13 @@ use crate::Clock;
     err,
 )]
 pub async fn authenticate_session_with_password(
-    executor: impl PgExecutor<'_>,
+    conn: impl Acquire<'_, Database = Postgres> + Send,
     mut rng: impl Rng + Send,
     clock: &Clock,
     user_session: &mut BrowserSession,
     user_password: &Password,
-) -> Result<(), sqlx::Error> {
+) -> Result<(), DatabaseError> {
+    let mut txn = conn.begin().await?;
     let created_at = clock.now();
     let id = Ulid::from_datetime_with_source(created_at.into(), &mut rng);
     tracing::Span::current().record(
@@ -44,8 +49,13 @@ pub async fn authenticate_session_with_password(
         tracing::field::display(id),
     );
 
+    // Successfully authenticating also clears the failed login attempts
     sqlx::query!(
         r#"
+            WITH cleared AS (
+                DELETE FROM user_failed_logins
+                WHERE username = $4
+            )
             INSERT INTO user_session_authentications
                 (user_session_authentication_id, user_session_id, created_at)
             VALUES ($1, $2, $3)
@@ -53,15 +63,30 @@ pub async fn authenticate_session_with_password(
         Uuid::from(id),
         Uuid::from(user_session.id),
         created_at,
+        &user_session.user.username,
     )
-    .execute(executor)
+    .execute(&mut txn)
     .await?;
 
     user_session.last_authentication = Some(Authentication { id, created_at });
 
+    record_session_event(
+        &mut txn,
+        &mut rng,
+        clock,
+        user_session,
+        SessionEventKind::Authenticated,
+        serde_json::json!({ "method": "password" }),
+    )
+    .await?;
+
+    txn.commit().await?;
+
     Ok(())
 }
 
+/// Mark a browser session as authenticated by an upstream OAuth 2.0 link, and
+/// record a [`SessionEventKind::Authenticated`] event for it
 #[tracing::instrument(
     skip_all,
     fields(
@@ -73,12 +98,13 @@ pub async fn authenticate_session_with_password(
     err,
 )]
 pub async fn authenticate_session_with_upstream(
-    executor: impl PgExecutor<'_>,
+    conn: impl Acquire<'_, Database = Postgres> + Send,
     mut rng: impl Rng + Send,
     clock: &Clock,
     user_session: &mut BrowserSession,
     upstream_oauth_link: &UpstreamOAuthLink,
-) -> Result<(), sqlx::Error> {
+) -> Result<(), DatabaseError> {
+    let mut txn = conn.begin().await?;
     let created_at = clock.now();
     let id = Ulid::from_datetime_with_source(created_at.into(), &mut rng);
     tracing::Span::current().record(
@@ -96,10 +122,89 @@ pub async fn authenticate_session_with_upstream(
         Uuid::from(user_session.id),
         created_at,
     )
-    .execute(executor)
+    .execute(&mut txn)
     .await?;
 
     user_session.last_authentication = Some(Authentication { id, created_at });
 
+    record_session_event(
+        &mut txn,
+        &mut rng,
+        clock,
+        user_session,
+        SessionEventKind::Authenticated,
+        serde_json::json!({
+            "method": "upstream_oauth2",
+            "upstream_oauth_link_id": upstream_oauth_link.id,
+        }),
+    )
+    .await?;
+
+    txn.commit().await?;
+
     Ok(())
 }
+
+/// Record a failed login attempt against a username
+///
+/// The attempts are tracked by username and not by user, so that unknown
+/// usernames get locked out the same way as existing users.
+#[tracing::instrument(
+    skip_all,
+    fields(
+        user.username = username,
+        user_failed_login.id,
+    ),
+    err,
+)]
+pub async fn record_failed_login(
+    executor: impl PgExecutor<'_>,
+    mut rng: impl Rng + Send,
+    clock: &Clock,
+    username: &str,
+) -> Result<(), DatabaseError> {
+    let created_at = clock.now();
+    let id = Ulid::from_datetime_with_source(created_at.into(), &mut rng);
+    tracing::Span::current().record("user_failed_login.id", tracing::field::display(id));
+
+    sqlx::query!(
+        r#"
+            INSERT INTO user_failed_logins
+                (user_failed_login_id, username, created_at)
+            VALUES ($1, $2, $3)
+        "#,
+        Uuid::from(id),
+        username,
+        created_at,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Count the failed login attempts against a username since the given time
+#[tracing::instrument(
+    skip_all,
+    fields(user.username = username),
+    err,
+)]
+pub async fn failed_login_count_since(
+    executor: impl PgExecutor<'_>,
+    username: &str,
+    since: DateTime<Utc>,
+) -> Result<i64, DatabaseError> {
+    let res = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*) as "count!"
+            FROM user_failed_logins
+            WHERE username = $1 AND created_at >= $2
+        "#,
+        username,
+        since,
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(res)
+}
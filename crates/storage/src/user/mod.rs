@@ -14,44 +14,116 @@
 
 use chrono::{DateTime, Utc};
 use mas_data_model::{
-    Authentication, BrowserSession, User, UserEmail, UserEmailVerification,
-    UserEmailVerificationState,
+    Authentication, BrowserSession, SessionMetadata, User, UserEmail, UserEmailVerification,
+    UserEmailVerificationLookup, UserEmailVerificationState,
 };
 use rand::Rng;
-use sqlx::{PgExecutor, QueryBuilder};
+use sqlx::{Acquire, PgExecutor, Postgres, QueryBuilder};
+use thiserror::Error;
 use tracing::{info_span, Instrument};
 use ulid::Ulid;
 use uuid::Uuid;
 
 use crate::{
-    pagination::{process_page, QueryBuilderExt},
+    pagination::{process_page, PaginationOrder, QueryBuilderExt},
     Clock, DatabaseError, DatabaseInconsistencyError, LookupResultExt,
 };
 
 mod authentication;
 mod password;
+mod session_event;
+mod verification_code;
 
 pub use self::{
-    authentication::{authenticate_session_with_password, authenticate_session_with_upstream},
+    authentication::{
+        authenticate_session_with_password, authenticate_session_with_upstream,
+        failed_login_count_since, record_failed_login,
+    },
     password::{add_user_password, lookup_user_password},
+    session_event::{
+        get_session_events, record_session_event, InvalidSessionEventKind, SessionEvent,
+        SessionEventKind,
+    },
+    verification_code::{generate_verification_code, CodeKind},
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, sqlx::FromRow)]
 struct UserLookup {
     user_id: Uuid,
     user_username: String,
+    user_admin: bool,
+    user_display_name: Option<String>,
+    user_deactivated_at: Option<DateTime<Utc>>,
     user_email_id: Option<Uuid>,
     user_email: Option<String>,
     user_email_created_at: Option<DateTime<Utc>>,
     user_email_confirmed_at: Option<DateTime<Utc>>,
 }
 
+impl TryInto<User> for UserLookup {
+    type Error = DatabaseInconsistencyError;
+
+    fn try_into(self) -> Result<User, Self::Error> {
+        let id = Ulid::from(self.user_id);
+        let primary_email = match (
+            self.user_email_id,
+            self.user_email,
+            self.user_email_created_at,
+            self.user_email_confirmed_at,
+        ) {
+            (Some(id), Some(email), Some(created_at), confirmed_at) => Some(UserEmail {
+                id: id.into(),
+                email,
+                created_at,
+                confirmed_at,
+            }),
+            (None, None, None, None) => None,
+            _ => {
+                return Err(DatabaseInconsistencyError::on("users")
+                    .column("primary_user_email_id")
+                    .row(id))
+            }
+        };
+
+        Ok(User {
+            id,
+            username: self.user_username,
+            sub: id.to_string(),
+            primary_email,
+            display_name: self.user_display_name,
+            deactivated_at: self.user_deactivated_at,
+            admin: self.user_admin,
+        })
+    }
+}
+
+/// Parse the metadata columns of the `user_sessions` table
+pub(crate) fn session_metadata(
+    user_session_id: Ulid,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+) -> Result<SessionMetadata, DatabaseInconsistencyError> {
+    let ip = ip_address.map(|ip| ip.parse()).transpose().map_err(|e| {
+        DatabaseInconsistencyError::on("user_sessions")
+            .column("ip_address")
+            .row(user_session_id)
+            .source(e)
+    })?;
+
+    Ok(SessionMetadata { ip, user_agent })
+}
+
 #[derive(sqlx::FromRow)]
 struct SessionLookup {
     user_session_id: Uuid,
     user_id: Uuid,
     username: String,
+    user_admin: bool,
+    user_display_name: Option<String>,
+    user_deactivated_at: Option<DateTime<Utc>>,
     created_at: DateTime<Utc>,
+    user_session_ip_address: Option<String>,
+    user_session_user_agent: Option<String>,
     last_authentication_id: Option<Uuid>,
     last_authd_at: Option<DateTime<Utc>>,
     user_email_id: Option<Uuid>,
@@ -88,6 +160,9 @@ impl TryInto<BrowserSession> for SessionLookup {
         let user = User {
             id,
             username: self.username,
+            display_name: self.user_display_name,
+            deactivated_at: self.user_deactivated_at,
+            admin: self.user_admin,
             sub: id.to_string(),
             primary_email,
         };
@@ -105,11 +180,19 @@ impl TryInto<BrowserSession> for SessionLookup {
             }
         };
 
+        let id = Ulid::from(self.user_session_id);
+        let metadata = session_metadata(
+            id,
+            self.user_session_ip_address,
+            self.user_session_user_agent,
+        )?;
+
         Ok(BrowserSession {
-            id: self.user_session_id.into(),
+            id,
             user,
             created_at: self.created_at,
             last_authentication,
+            metadata,
         })
     }
 }
@@ -121,6 +204,7 @@ impl TryInto<BrowserSession> for SessionLookup {
 )]
 pub async fn lookup_active_session(
     executor: impl PgExecutor<'_>,
+    clock: &Clock,
     id: Ulid,
 ) -> Result<Option<BrowserSession>, DatabaseError> {
     let res = sqlx::query_as!(
@@ -130,7 +214,12 @@ pub async fn lookup_active_session(
                 s.user_session_id,
                 u.user_id,
                 u.username,
+                u.admin AS "user_admin",
+                u.display_name AS "user_display_name",
+                u.deactivated_at AS "user_deactivated_at",
                 s.created_at,
+                s.ip_address AS "user_session_ip_address",
+                s.user_agent AS "user_session_user_agent",
                 a.user_session_authentication_id AS "last_authentication_id?",
                 a.created_at                     AS "last_authd_at?",
                 ue.user_email_id   AS "user_email_id?",
@@ -144,11 +233,15 @@ pub async fn lookup_active_session(
                 USING (user_session_id)
             LEFT JOIN user_emails ue
               ON ue.user_email_id = u.primary_user_email_id
-            WHERE s.user_session_id = $1 AND s.finished_at IS NULL
+            WHERE s.user_session_id = $1
+              AND s.finished_at IS NULL
+              AND u.deactivated_at IS NULL
+              AND (s.expires_at IS NULL OR s.expires_at > $2)
             ORDER BY a.created_at DESC
             LIMIT 1
         "#,
         Uuid::from(id),
+        clock.now(),
     )
     .fetch_one(executor)
     .await
@@ -167,9 +260,12 @@ pub async fn lookup_active_session(
     ),
     err,
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn get_paginated_user_sessions(
     executor: impl PgExecutor<'_>,
+    clock: &Clock,
     user: &User,
+    order: PaginationOrder,
     before: Option<Ulid>,
     after: Option<Ulid>,
     first: Option<usize>,
@@ -181,7 +277,12 @@ pub async fn get_paginated_user_sessions(
                 s.user_session_id,
                 u.user_id,
                 u.username,
+                u.admin AS "user_admin",
+                u.display_name AS "user_display_name",
+                u.deactivated_at AS "user_deactivated_at",
                 s.created_at,
+                s.ip_address AS "user_session_ip_address",
+                s.user_agent AS "user_session_user_agent",
                 a.user_session_authentication_id AS "last_authentication_id",
                 a.created_at                     AS "last_authd_at",
                 ue.user_email_id   AS "user_email_id",
@@ -199,9 +300,11 @@ pub async fn get_paginated_user_sessions(
     );
 
     query
-        .push(" WHERE s.finished_at IS NULL AND s.user_id = ")
+        .push(" WHERE s.finished_at IS NULL AND (s.expires_at IS NULL OR s.expires_at > ")
+        .push_bind(clock.now())
+        .push(") AND s.user_id = ")
         .push_bind(Uuid::from(user.id))
-        .generate_pagination("s.user_session_id", before, after, first, last)?;
+        .generate_ordered_pagination("s.user_session_id", order, before, after, first, last)?;
 
     let span = info_span!("Fetch paginated user emails", db.statement = query.sql());
     let page: Vec<SessionLookup> = query
@@ -216,6 +319,8 @@ pub async fn get_paginated_user_sessions(
     Ok((has_previous_page, has_next_page, page?))
 }
 
+/// Start a new browser session for a user, and record a
+/// [`SessionEventKind::Started`] event for it
 #[tracing::instrument(
     skip_all,
     fields(
@@ -225,25 +330,34 @@ pub async fn get_paginated_user_sessions(
     err,
 )]
 pub async fn start_session(
-    executor: impl PgExecutor<'_>,
+    conn: impl Acquire<'_, Database = Postgres> + Send,
     mut rng: impl Rng + Send,
     clock: &Clock,
     user: User,
-) -> Result<BrowserSession, sqlx::Error> {
+    metadata: Option<SessionMetadata>,
+    max_age: Option<chrono::Duration>,
+) -> Result<BrowserSession, DatabaseError> {
+    let mut txn = conn.begin().await?;
     let created_at = clock.now();
     let id = Ulid::from_datetime_with_source(created_at.into(), &mut rng);
     tracing::Span::current().record("user_session.id", tracing::field::display(id));
+    let metadata = metadata.unwrap_or_default();
+    let expires_at = max_age.map(|max_age| created_at + max_age);
 
     sqlx::query!(
         r#"
-            INSERT INTO user_sessions (user_session_id, user_id, created_at)
-            VALUES ($1, $2, $3)
+            INSERT INTO user_sessions
+                (user_session_id, user_id, created_at, ip_address, user_agent, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
         "#,
         Uuid::from(id),
         Uuid::from(user.id),
         created_at,
+        metadata.ip.map(|ip| ip.to_string()),
+        metadata.user_agent.as_deref(),
+        expires_at,
     )
-    .execute(executor)
+    .execute(&mut txn)
     .await?;
 
     let session = BrowserSession {
@@ -251,8 +365,21 @@ pub async fn start_session(
         user,
         created_at,
         last_authentication: None,
+        metadata,
     };
 
+    record_session_event(
+        &mut txn,
+        &mut rng,
+        clock,
+        &session,
+        SessionEventKind::Started,
+        serde_json::json!({}),
+    )
+    .await?;
+
+    txn.commit().await?;
+
     Ok(session)
 }
 
@@ -263,15 +390,52 @@ pub async fn start_session(
 )]
 pub async fn count_active_sessions(
     executor: impl PgExecutor<'_>,
+    clock: &Clock,
     user: &User,
 ) -> Result<i64, DatabaseError> {
     let res = sqlx::query_scalar!(
         r#"
             SELECT COUNT(*) as "count!"
             FROM user_sessions s
-            WHERE s.user_id = $1 AND s.finished_at IS NULL
+            WHERE s.user_id = $1
+              AND s.finished_at IS NULL
+              AND (s.expires_at IS NULL OR s.expires_at > $2)
         "#,
         Uuid::from(user.id),
+        clock.now(),
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(res)
+}
+
+#[tracing::instrument(skip_all, err)]
+pub async fn count_users(executor: impl PgExecutor<'_>) -> Result<i64, DatabaseError> {
+    let res = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*) as "count!"
+            FROM users
+        "#,
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(res)
+}
+
+#[tracing::instrument(skip_all, fields(%since), err)]
+pub async fn count_users_created_since(
+    executor: impl PgExecutor<'_>,
+    since: DateTime<Utc>,
+) -> Result<i64, DatabaseError> {
+    let res = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*) as "count!"
+            FROM users
+            WHERE created_at >= $1
+        "#,
+        since,
     )
     .fetch_one(executor)
     .await?;
@@ -292,6 +456,7 @@ pub async fn add_user(
     mut rng: impl Rng + Send,
     clock: &Clock,
     username: &str,
+    display_name: Option<String>,
 ) -> Result<User, sqlx::Error> {
     let created_at = clock.now();
     let id = Ulid::from_datetime_with_source(created_at.into(), &mut rng);
@@ -299,11 +464,12 @@ pub async fn add_user(
 
     sqlx::query!(
         r#"
-            INSERT INTO users (user_id, username, created_at)
-            VALUES ($1, $2, $3)
+            INSERT INTO users (user_id, username, display_name, created_at)
+            VALUES ($1, $2, $3, $4)
         "#,
         Uuid::from(id),
         username,
+        display_name.as_deref(),
         created_at,
     )
     .execute(executor)
@@ -313,35 +479,353 @@ pub async fn add_user(
         id,
         username: username.to_owned(),
         sub: id.to_string(),
+        display_name,
         primary_email: None,
+        deactivated_at: None,
+        admin: false,
     })
 }
 
+#[tracing::instrument(
+    skip_all,
+    fields(%user.id),
+    err,
+)]
+pub async fn deactivate_user(
+    executor: impl PgExecutor<'_>,
+    clock: &Clock,
+    mut user: User,
+) -> Result<User, DatabaseError> {
+    let deactivated_at = clock.now();
+    let res = sqlx::query!(
+        r#"
+            UPDATE users
+            SET deactivated_at = $2
+            WHERE user_id = $1
+        "#,
+        Uuid::from(user.id),
+        deactivated_at,
+    )
+    .execute(executor)
+    .instrument(info_span!("Deactivate user"))
+    .await?;
+
+    DatabaseError::ensure_affected_rows(&res, 1)?;
+
+    user.deactivated_at = Some(deactivated_at);
+
+    Ok(user)
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(%user.id),
+    err,
+)]
+pub async fn reactivate_user(
+    executor: impl PgExecutor<'_>,
+    mut user: User,
+) -> Result<User, DatabaseError> {
+    let res = sqlx::query!(
+        r#"
+            UPDATE users
+            SET deactivated_at = NULL
+            WHERE user_id = $1
+        "#,
+        Uuid::from(user.id),
+    )
+    .execute(executor)
+    .instrument(info_span!("Reactivate user"))
+    .await?;
+
+    DatabaseError::ensure_affected_rows(&res, 1)?;
+
+    user.deactivated_at = None;
+
+    Ok(user)
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(%user.id),
+    err,
+)]
+pub async fn set_user_display_name(
+    executor: impl PgExecutor<'_>,
+    mut user: User,
+    display_name: Option<String>,
+) -> Result<User, DatabaseError> {
+    let res = sqlx::query!(
+        r#"
+            UPDATE users
+            SET display_name = $2
+            WHERE user_id = $1
+        "#,
+        Uuid::from(user.id),
+        display_name.as_deref(),
+    )
+    .execute(executor)
+    .instrument(info_span!("Set user display name"))
+    .await?;
+
+    DatabaseError::ensure_affected_rows(&res, 1)?;
+
+    user.display_name = display_name;
+
+    Ok(user)
+}
+
+#[derive(Debug, Error)]
+#[error("username {username:?} is already taken")]
+pub struct UsernameTakenError {
+    pub username: String,
+}
+
+/// Change the username of a user, keeping track of the previous one.
+///
+/// The `sub` is derived from the user ID, so it stays the same.
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %user.id,
+        user.username = user.username,
+        user.new_username = new_username,
+    ),
+    err,
+)]
+pub async fn rename_user(
+    conn: impl Acquire<'_, Database = Postgres> + Send,
+    mut rng: impl Rng + Send,
+    clock: &Clock,
+    mut user: User,
+    new_username: &str,
+) -> Result<User, DatabaseError> {
+    let mut txn = conn.begin().await?;
+    let created_at = clock.now();
+
+    // Usernames are unique, with the same case-sensitive rule as registration
+    let res = sqlx::query!(
+        r#"
+            UPDATE users
+            SET username = $2
+            WHERE user_id = $1
+        "#,
+        Uuid::from(user.id),
+        new_username,
+    )
+    .execute(&mut txn)
+    .instrument(info_span!("Rename user"))
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db_error)
+            if db_error.constraint() == Some("users_username_unique") =>
+        {
+            DatabaseError::to_invalid_operation(UsernameTakenError {
+                username: new_username.to_owned(),
+            })
+        }
+        _ => DatabaseError::from(e),
+    })?;
+
+    DatabaseError::ensure_affected_rows(&res, 1)?;
+
+    let id = Ulid::from_datetime_with_source(created_at.into(), &mut rng);
+    sqlx::query!(
+        r#"
+            INSERT INTO user_username_history
+                (user_username_history_id, user_id, username, created_at)
+            VALUES ($1, $2, $3, $4)
+        "#,
+        Uuid::from(id),
+        Uuid::from(user.id),
+        &user.username,
+        created_at,
+    )
+    .execute(&mut txn)
+    .instrument(info_span!("Record previous username"))
+    .await?;
+
+    txn.commit().await?;
+
+    user.username = new_username.to_owned();
+
+    Ok(user)
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(%user.id, user.admin = admin),
+    err,
+)]
+pub async fn set_user_admin(
+    executor: impl PgExecutor<'_>,
+    mut user: User,
+    admin: bool,
+) -> Result<User, DatabaseError> {
+    let res = sqlx::query!(
+        r#"
+            UPDATE users
+            SET admin = $2
+            WHERE user_id = $1
+        "#,
+        Uuid::from(user.id),
+        admin,
+    )
+    .execute(executor)
+    .instrument(info_span!("Set user admin"))
+    .await?;
+
+    DatabaseError::ensure_affected_rows(&res, 1)?;
+
+    user.admin = admin;
+
+    Ok(user)
+}
+
+/// End a browser session, and record a [`SessionEventKind::Ended`] event for
+/// it
+///
+/// # Errors
+///
+/// Returns [`DatabaseError::RowsAffected`] if the session was already ended,
+/// e.g. concurrently by another request, or any other database error
 #[tracing::instrument(
     skip_all,
     fields(%user_session.id),
     err,
 )]
 pub async fn end_session(
-    executor: impl PgExecutor<'_>,
+    conn: impl Acquire<'_, Database = Postgres> + Send,
+    rng: impl Rng + Send,
     clock: &Clock,
     user_session: &BrowserSession,
 ) -> Result<(), DatabaseError> {
+    let mut txn = conn.begin().await?;
     let now = clock.now();
     let res = sqlx::query!(
         r#"
             UPDATE user_sessions
             SET finished_at = $1
-            WHERE user_session_id = $2
+            WHERE user_session_id = $2 AND finished_at IS NULL
         "#,
         now,
         Uuid::from(user_session.id),
     )
-    .execute(executor)
+    .execute(&mut txn)
     .instrument(info_span!("End session"))
     .await?;
 
-    DatabaseError::ensure_affected_rows(&res, 1)
+    DatabaseError::ensure_affected_rows(&res, 1)?;
+
+    record_session_event(
+        &mut txn,
+        rng,
+        clock,
+        user_session,
+        SessionEventKind::Ended,
+        serde_json::json!({}),
+    )
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(())
+}
+
+/// End all the active browser sessions of a user, and record a
+/// [`SessionEventKind::Ended`] event for each of them
+///
+/// Returns the number of sessions which were ended
+#[tracing::instrument(
+    skip_all,
+    fields(%user.id),
+    err,
+)]
+pub async fn end_all_sessions(
+    conn: impl Acquire<'_, Database = Postgres> + Send,
+    mut rng: impl Rng + Send,
+    clock: &Clock,
+    user: &User,
+) -> Result<u64, DatabaseError> {
+    let mut txn = conn.begin().await?;
+    let now = clock.now();
+
+    let ended = sqlx::query_scalar!(
+        r#"
+            UPDATE user_sessions
+            SET finished_at = $1
+            WHERE user_id = $2 AND finished_at IS NULL
+            RETURNING user_session_id
+        "#,
+        now,
+        Uuid::from(user.id),
+    )
+    .fetch_all(&mut txn)
+    .instrument(info_span!("End all sessions"))
+    .await?;
+
+    let event_ids: Vec<Uuid> = ended
+        .iter()
+        .map(|_| Uuid::from(Ulid::from_datetime_with_source(now.into(), &mut rng)))
+        .collect();
+
+    sqlx::query!(
+        r#"
+            INSERT INTO user_session_events
+                (user_session_event_id, user_session_id, user_id, kind, metadata, created_at)
+            SELECT id, user_session_id, $3, $4, '{}', $5
+            FROM UNNEST($1::uuid[], $2::uuid[]) e(id, user_session_id)
+        "#,
+        &event_ids,
+        &ended,
+        Uuid::from(user.id),
+        SessionEventKind::Ended.as_str(),
+        now,
+    )
+    .execute(&mut txn)
+    .instrument(info_span!("Record ended sessions"))
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(event_ids.len() as u64)
+}
+
+/// Delete the browser sessions which expired before now, along with their
+/// authentications
+///
+/// Sessions which are still referenced by an OAuth 2.0 session are kept. Their
+/// events are kept as well, detached from the deleted session.
+#[tracing::instrument(skip_all, err)]
+pub async fn purge_expired_sessions(
+    executor: impl PgExecutor<'_>,
+    clock: &Clock,
+) -> Result<u64, DatabaseError> {
+    let res = sqlx::query!(
+        r#"
+            WITH expired AS (
+                SELECT s.user_session_id
+                FROM user_sessions s
+                WHERE s.expires_at < $1
+                  AND NOT EXISTS (
+                    SELECT 1 FROM oauth2_sessions os
+                    WHERE os.user_session_id = s.user_session_id
+                  )
+            ),
+            deleted_authentications AS (
+                DELETE FROM user_session_authentications
+                WHERE user_session_id IN (SELECT user_session_id FROM expired)
+            )
+            DELETE FROM user_sessions
+            WHERE user_session_id IN (SELECT user_session_id FROM expired)
+        "#,
+        clock.now(),
+    )
+    .execute(executor)
+    .instrument(info_span!("Purge expired sessions"))
+    .await?;
+
+    Ok(res.rows_affected())
 }
 
 #[tracing::instrument(
@@ -359,6 +843,9 @@ pub async fn lookup_user_by_username(
             SELECT
                 u.user_id,
                 u.username       AS user_username,
+                u.admin          AS user_admin,
+                u.display_name   AS user_display_name,
+                u.deactivated_at AS user_deactivated_at,
                 ue.user_email_id AS "user_email_id?",
                 ue.email         AS "user_email?",
                 ue.created_at    AS "user_email_created_at?",
@@ -379,34 +866,7 @@ pub async fn lookup_user_by_username(
 
     let Some(res) = res else { return Ok(None) };
 
-    let id = Ulid::from(res.user_id);
-    let primary_email = match (
-        res.user_email_id,
-        res.user_email,
-        res.user_email_created_at,
-        res.user_email_confirmed_at,
-    ) {
-        (Some(id), Some(email), Some(created_at), confirmed_at) => Some(UserEmail {
-            id: id.into(),
-            email,
-            created_at,
-            confirmed_at,
-        }),
-        (None, None, None, None) => None,
-        _ => {
-            return Err(DatabaseInconsistencyError::on("users")
-                .column("primary_user_email_id")
-                .row(id)
-                .into())
-        }
-    };
-
-    Ok(Some(User {
-        id,
-        username: res.user_username,
-        sub: id.to_string(),
-        primary_email,
-    }))
+    Ok(Some(res.try_into()?))
 }
 
 #[tracing::instrument(
@@ -421,6 +881,9 @@ pub async fn lookup_user(executor: impl PgExecutor<'_>, id: Ulid) -> Result<User
             SELECT
                 u.user_id,
                 u.username       AS user_username,
+                u.admin          AS user_admin,
+                u.display_name   AS user_display_name,
+                u.deactivated_at AS user_deactivated_at,
                 ue.user_email_id AS "user_email_id?",
                 ue.email         AS "user_email?",
                 ue.created_at    AS "user_email_created_at?",
@@ -438,34 +901,104 @@ pub async fn lookup_user(executor: impl PgExecutor<'_>, id: Ulid) -> Result<User
     .instrument(info_span!("Fetch user"))
     .await?;
 
-    let id = Ulid::from(res.user_id);
-    let primary_email = match (
-        res.user_email_id,
-        res.user_email,
-        res.user_email_created_at,
-        res.user_email_confirmed_at,
-    ) {
-        (Some(id), Some(email), Some(created_at), confirmed_at) => Some(UserEmail {
-            id: id.into(),
-            email,
-            created_at,
-            confirmed_at,
-        }),
-        (None, None, None, None) => None,
-        _ => {
-            return Err(DatabaseInconsistencyError::on("users")
-                .column("primary_user_email_id")
-                .row(id)
-                .into())
-        }
-    };
+    Ok(res.try_into()?)
+}
 
-    Ok(User {
-        id,
-        username: res.user_username,
-        sub: id.to_string(),
-        primary_email,
-    })
+#[tracing::instrument(
+    skip_all,
+    fields(users.count = ids.len()),
+    err,
+)]
+pub async fn lookup_users(
+    executor: impl PgExecutor<'_>,
+    ids: &[Ulid],
+) -> Result<Vec<User>, DatabaseError> {
+    let ids: Vec<Uuid> = ids.iter().copied().map(Uuid::from).collect();
+    let res = sqlx::query_as!(
+        UserLookup,
+        r#"
+            SELECT
+                u.user_id,
+                u.username       AS user_username,
+                u.admin          AS user_admin,
+                u.display_name   AS user_display_name,
+                u.deactivated_at AS user_deactivated_at,
+                ue.user_email_id AS "user_email_id?",
+                ue.email         AS "user_email?",
+                ue.created_at    AS "user_email_created_at?",
+                ue.confirmed_at  AS "user_email_confirmed_at?"
+            FROM users u
+
+            LEFT JOIN user_emails ue
+              ON ue.user_email_id = u.primary_user_email_id
+
+            WHERE u.user_id = ANY($1)
+        "#,
+        &ids,
+    )
+    .fetch_all(executor)
+    .instrument(info_span!("Fetch users"))
+    .await?;
+
+    let users: Result<Vec<User>, _> = res.into_iter().map(TryInto::try_into).collect();
+    Ok(users?)
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(user.username.prefix = prefix),
+    err,
+)]
+pub async fn search_users_by_username(
+    executor: impl PgExecutor<'_>,
+    prefix: &str,
+    before: Option<Ulid>,
+    after: Option<Ulid>,
+    first: Option<usize>,
+    last: Option<usize>,
+) -> Result<(bool, bool, Vec<User>), DatabaseError> {
+    // Escape the LIKE wildcards so they are matched literally
+    let prefix = prefix
+        .replace('\\', r"\\")
+        .replace('%', r"\%")
+        .replace('_', r"\_");
+
+    let mut query = QueryBuilder::new(
+        r#"
+            SELECT
+                u.user_id,
+                u.username       AS user_username,
+                u.admin          AS user_admin,
+                u.display_name   AS user_display_name,
+                u.deactivated_at AS user_deactivated_at,
+                ue.user_email_id AS user_email_id,
+                ue.email         AS user_email,
+                ue.created_at    AS user_email_created_at,
+                ue.confirmed_at  AS user_email_confirmed_at
+            FROM users u
+
+            LEFT JOIN user_emails ue
+              ON ue.user_email_id = u.primary_user_email_id
+        "#,
+    );
+
+    query
+        .push(" WHERE u.username ILIKE ")
+        .push_bind(prefix)
+        .push(" || '%'")
+        .generate_pagination("u.user_id", before, after, first, last)?;
+
+    let span = info_span!("Search users by username", db.statement = query.sql());
+    let page: Vec<UserLookup> = query
+        .build_query_as()
+        .fetch_all(executor)
+        .instrument(span)
+        .await?;
+
+    let (has_previous_page, has_next_page, page) = process_page(page, first, last)?;
+
+    let page: Result<Vec<_>, _> = page.into_iter().map(TryInto::try_into).collect();
+    Ok((has_previous_page, has_next_page, page?))
 }
 
 #[tracing::instrument(
@@ -540,6 +1073,40 @@ pub async fn get_user_emails(
     Ok(res.into_iter().map(Into::into).collect())
 }
 
+/// Get the email addresses of a user which were confirmed, ordered by address
+#[tracing::instrument(
+    skip_all,
+    fields(%user.id, %user.username),
+    err,
+)]
+pub async fn get_confirmed_user_emails(
+    executor: impl PgExecutor<'_>,
+    user: &User,
+) -> Result<Vec<UserEmail>, sqlx::Error> {
+    let res = sqlx::query_as!(
+        UserEmailLookup,
+        r#"
+            SELECT
+                ue.user_email_id,
+                ue.email        AS "user_email",
+                ue.created_at   AS "user_email_created_at",
+                ue.confirmed_at AS "user_email_confirmed_at"
+            FROM user_emails ue
+
+            WHERE ue.user_id = $1
+              AND ue.confirmed_at IS NOT NULL
+
+            ORDER BY ue.email ASC
+        "#,
+        Uuid::from(user.id),
+    )
+    .fetch_all(executor)
+    .instrument(info_span!("Fetch confirmed user emails"))
+    .await?;
+
+    Ok(res.into_iter().map(Into::into).collect())
+}
+
 #[tracing::instrument(
     skip_all,
     fields(%user.id, %user.username),
@@ -572,6 +1139,7 @@ pub async fn count_user_emails(
 pub async fn get_paginated_user_emails(
     executor: impl PgExecutor<'_>,
     user: &User,
+    order: PaginationOrder,
     before: Option<Ulid>,
     after: Option<Ulid>,
     first: Option<usize>,
@@ -591,7 +1159,7 @@ pub async fn get_paginated_user_emails(
     query
         .push(" WHERE ue.user_id = ")
         .push_bind(Uuid::from(user.id))
-        .generate_pagination("ue.user_email_id", before, after, first, last)?;
+        .generate_ordered_pagination("ue.user_email_id", order, before, after, first, last)?;
 
     let span = info_span!("Fetch paginated user sessions", db.statement = query.sql());
     let page: Vec<UserEmailLookup> = query
@@ -700,22 +1268,28 @@ pub async fn add_user_email(
 pub async fn set_user_email_as_primary(
     executor: impl PgExecutor<'_>,
     user_email: &UserEmail,
-) -> Result<(), sqlx::Error> {
-    sqlx::query!(
+) -> Result<(), DatabaseError> {
+    // Only confirmed emails can be used as primary email
+    if user_email.confirmed_at.is_none() {
+        return Err(DatabaseError::invalid_operation());
+    }
+
+    let res = sqlx::query!(
         r#"
             UPDATE users
             SET primary_user_email_id = user_emails.user_email_id
             FROM user_emails
             WHERE user_emails.user_email_id = $1
               AND users.user_id = user_emails.user_id
+              AND user_emails.confirmed_at IS NOT NULL
         "#,
         Uuid::from(user_email.id),
     )
     .execute(executor)
-    .instrument(info_span!("Add user email"))
+    .instrument(info_span!("Set user email as primary"))
     .await?;
 
-    Ok(())
+    DatabaseError::ensure_affected_rows(&res, 1)
 }
 
 #[tracing::instrument(
@@ -727,9 +1301,24 @@ pub async fn set_user_email_as_primary(
     err,
 )]
 pub async fn remove_user_email(
-    executor: impl PgExecutor<'_>,
+    conn: impl Acquire<'_, Database = Postgres> + Send,
     user_email: UserEmail,
 ) -> Result<(), sqlx::Error> {
+    let mut txn = conn.begin().await?;
+
+    // Unset the primary email of the user if it is the one being removed
+    sqlx::query!(
+        r#"
+            UPDATE users
+            SET primary_user_email_id = NULL
+            WHERE primary_user_email_id = $1
+        "#,
+        Uuid::from(user_email.id),
+    )
+    .execute(&mut txn)
+    .instrument(info_span!("Unset primary user email"))
+    .await?;
+
     sqlx::query!(
         r#"
             DELETE FROM user_emails
@@ -737,13 +1326,45 @@ pub async fn remove_user_email(
         "#,
         Uuid::from(user_email.id),
     )
-    .execute(executor)
+    .execute(&mut txn)
     .instrument(info_span!("Remove user email"))
     .await?;
 
+    txn.commit().await?;
+
     Ok(())
 }
 
+/// Remove the unverified emails which were added more than `older_than` ago,
+/// along with their confirmation codes
+///
+/// Emails used as a primary email are left untouched.
+#[tracing::instrument(skip_all, err)]
+pub async fn purge_unverified_emails(
+    executor: impl PgExecutor<'_>,
+    clock: &Clock,
+    older_than: chrono::Duration,
+) -> Result<u64, DatabaseError> {
+    let threshold = clock.now() - older_than;
+    let res = sqlx::query!(
+        r#"
+            DELETE FROM user_emails ue
+            WHERE ue.confirmed_at IS NULL
+              AND ue.created_at < $1
+              AND NOT EXISTS (
+                SELECT 1 FROM users u
+                WHERE u.primary_user_email_id = ue.user_email_id
+              )
+        "#,
+        threshold,
+    )
+    .execute(executor)
+    .instrument(info_span!("Purge unverified emails"))
+    .await?;
+
+    Ok(res.rows_affected())
+}
+
 #[tracing::instrument(
     skip_all,
     fields(
@@ -869,7 +1490,7 @@ pub async fn lookup_user_email_verification_code(
     clock: &Clock,
     user_email: UserEmail,
     code: &str,
-) -> Result<Option<UserEmailVerification>, DatabaseError> {
+) -> Result<UserEmailVerificationLookup, DatabaseError> {
     let now = clock.now();
 
     let res = sqlx::query_as!(
@@ -893,7 +1514,7 @@ pub async fn lookup_user_email_verification_code(
     .await
     .to_option()?;
 
-    let Some(res) = res else { return Ok(None) };
+    let Some(res) = res else { return Ok(UserEmailVerificationLookup::CodeNotFound) };
 
     let state = if let Some(when) = res.consumed_at {
         UserEmailVerificationState::AlreadyUsed { when }
@@ -905,7 +1526,7 @@ pub async fn lookup_user_email_verification_code(
         UserEmailVerificationState::Valid
     };
 
-    Ok(Some(UserEmailVerification {
+    Ok(UserEmailVerificationLookup::Found(UserEmailVerification {
         id: res.user_email_confirmation_code_id.into(),
         code: res.code,
         email: user_email,
@@ -953,6 +1574,41 @@ pub async fn consume_email_verification(
     Ok(user_email_verification)
 }
 
+/// Check whether a new verification code can be sent for an email, which is
+/// the case if no code was sent for it in the last `min_interval`
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %user_email.id,
+        %user_email.email,
+    ),
+    err,
+)]
+pub async fn can_send_verification_code(
+    executor: impl PgExecutor<'_>,
+    clock: &Clock,
+    user_email: &UserEmail,
+    min_interval: chrono::Duration,
+) -> Result<bool, DatabaseError> {
+    let since = clock.now() - min_interval;
+    let recently_sent = sqlx::query_scalar!(
+        r#"
+            SELECT EXISTS(
+                SELECT 1 FROM user_email_confirmation_codes
+                WHERE user_email_id = $1
+                  AND created_at > $2
+            ) AS "exists!"
+        "#,
+        Uuid::from(user_email.id),
+        since,
+    )
+    .fetch_one(executor)
+    .instrument(info_span!("Check recent user email verification codes"))
+    .await?;
+
+    Ok(!recently_sent)
+}
+
 #[tracing::instrument(
     skip_all,
     fields(
@@ -1002,3 +1658,597 @@ pub async fn add_user_email_verification_code(
 
     Ok(verification)
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use rand::SeedableRng;
+    use sqlx::{PgConnection, PgPool};
+
+    use super::*;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_count_users(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        assert_eq!(count_users(&mut conn).await?, 0);
+
+        add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        add_user(&mut conn, &mut rng, &clock, "bob", None).await?;
+
+        assert_eq!(count_users(&mut conn).await?, 2);
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_count_users_created_since(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let before = clock.now() - Duration::hours(1);
+        add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        add_user(&mut conn, &mut rng, &clock, "bob", None).await?;
+        let after = clock.now() + Duration::hours(1);
+
+        assert_eq!(count_users_created_since(&mut conn, before).await?, 2);
+        assert_eq!(count_users_created_since(&mut conn, after).await?, 0);
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_end_session_twice(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let session = start_session(&mut conn, &mut rng, &clock, user, None, None).await?;
+
+        end_session(&mut conn, &mut rng, &clock, &session).await?;
+        assert!(lookup_active_session(&mut conn, &clock, session.id)
+            .await?
+            .is_none());
+
+        // Ending it a second time is reported
+        assert!(matches!(
+            end_session(&mut conn, &mut rng, &clock, &session).await,
+            Err(DatabaseError::RowsAffected { actual: 0, .. })
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_end_all_sessions(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let alice = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let bob = add_user(&mut conn, &mut rng, &clock, "bob", None).await?;
+        let first = start_session(&mut conn, &mut rng, &clock, alice.clone(), None, None).await?;
+        let second = start_session(&mut conn, &mut rng, &clock, alice.clone(), None, None).await?;
+        let ended = start_session(&mut conn, &mut rng, &clock, alice.clone(), None, None).await?;
+        let other = start_session(&mut conn, &mut rng, &clock, bob.clone(), None, None).await?;
+        end_session(&mut conn, &mut rng, &clock, &ended).await?;
+
+        // Already finished sessions and the ones of other users are left alone
+        let count = end_all_sessions(&mut conn, &mut rng, &clock, &alice).await?;
+        assert_eq!(count, 2);
+        assert_eq!(count_active_sessions(&mut conn, &clock, &alice).await?, 0);
+        assert!(lookup_active_session(&mut conn, &clock, other.id)
+            .await?
+            .is_some());
+
+        // Each ended session is recorded once in the history of the user
+        let ended_sessions = |events: Vec<SessionEvent>| {
+            let mut ended: Vec<_> = events
+                .into_iter()
+                .filter(|event| event.kind == SessionEventKind::Ended)
+                .filter_map(|event| event.session_id)
+                .collect();
+            ended.sort_unstable();
+            ended
+        };
+        let (_, _, events) =
+            get_session_events(&mut conn, &alice, None, None, Some(10), None).await?;
+        let mut expected = vec![first.id, second.id, ended.id];
+        expected.sort_unstable();
+        assert_eq!(ended_sessions(events), expected);
+        let (_, _, events) =
+            get_session_events(&mut conn, &bob, None, None, Some(10), None).await?;
+        assert!(ended_sessions(events).is_empty());
+
+        let count = end_all_sessions(&mut conn, &mut rng, &clock, &alice).await?;
+        assert_eq!(count, 0);
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_deactivated_user_sessions(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let session = start_session(&mut conn, &mut rng, &clock, user, None, None).await?;
+
+        let user = deactivate_user(&mut conn, &clock, session.user.clone()).await?;
+        assert!(lookup_active_session(&mut conn, &clock, session.id)
+            .await?
+            .is_none());
+
+        // The sessions are usable again once the user is reactivated
+        reactivate_user(&mut conn, user).await?;
+        assert!(lookup_active_session(&mut conn, &clock, session.id)
+            .await?
+            .is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_failed_logins(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let mut conn = pool.acquire().await?;
+
+        let since = clock.now();
+        record_failed_login(&mut conn, &mut rng, &clock, "alice").await?;
+        clock.advance(Duration::minutes(1));
+        record_failed_login(&mut conn, &mut rng, &clock, "alice").await?;
+
+        // Unknown usernames are tracked as well
+        record_failed_login(&mut conn, &mut rng, &clock, "bob").await?;
+
+        assert_eq!(
+            failed_login_count_since(&mut conn, "alice", since).await?,
+            2
+        );
+        assert_eq!(failed_login_count_since(&mut conn, "bob", since).await?, 1);
+        assert_eq!(
+            failed_login_count_since(&mut conn, "alice", clock.now()).await?,
+            1
+        );
+
+        // Authenticating with a password clears the failed attempts
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let password = add_user_password(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &user,
+            1,
+            "hash".to_owned(),
+            None,
+        )
+        .await?;
+        let mut session = start_session(&mut conn, &mut rng, &clock, user, None, None).await?;
+        authenticate_session_with_password(&mut conn, &mut rng, &clock, &mut session, &password)
+            .await?;
+
+        assert_eq!(
+            failed_login_count_since(&mut conn, "alice", since).await?,
+            0
+        );
+        assert_eq!(failed_login_count_since(&mut conn, "bob", since).await?, 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_rename_user(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let alice = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        add_user(&mut conn, &mut rng, &clock, "bob", None).await?;
+
+        let alice = rename_user(&mut conn, &mut rng, &clock, alice, "carol").await?;
+        assert_eq!(alice.username, "carol");
+        assert!(!username_exists(&mut conn, "alice").await?);
+        assert!(username_exists(&mut conn, "carol").await?);
+
+        // Taking the username of another user fails cleanly
+        let err = rename_user(&mut conn, &mut rng, &clock, alice.clone(), "bob")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            DatabaseError::InvalidOperation { source: Some(ref source) }
+                if source.is::<UsernameTakenError>()
+        ));
+
+        // Usernames are case-sensitive, like on registration
+        let alice = rename_user(&mut conn, &mut rng, &clock, alice, "Bob").await?;
+        assert_eq!(alice.username, "Bob");
+        assert!(lookup_user_by_username(&mut conn, "carol").await?.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_user_admin(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let alice = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        assert!(!alice.admin);
+        let session = start_session(&mut conn, &mut rng, &clock, alice.clone(), None, None).await?;
+
+        // The flag is read back by all the lookups
+        let alice = set_user_admin(&mut conn, alice, true).await?;
+        assert!(alice.admin);
+        assert!(lookup_user(&mut conn, alice.id).await?.admin);
+        assert!(
+            lookup_user_by_username(&mut conn, "alice")
+                .await?
+                .unwrap()
+                .admin
+        );
+        let session = lookup_active_session(&mut conn, &clock, session.id)
+            .await?
+            .unwrap();
+        assert!(session.user.admin);
+
+        // And can be removed
+        let alice = set_user_admin(&mut conn, alice, false).await?;
+        assert!(!alice.admin);
+        assert!(!lookup_user(&mut conn, alice.id).await?.admin);
+        assert!(
+            !lookup_user_by_username(&mut conn, "alice")
+                .await?
+                .unwrap()
+                .admin
+        );
+        let session = lookup_active_session(&mut conn, &clock, session.id)
+            .await?
+            .unwrap();
+        assert!(!session.user.admin);
+
+        Ok(())
+    }
+
+    /// Check the display name seen by all the lookups
+    async fn assert_display_name(
+        conn: &mut PgConnection,
+        clock: &Clock,
+        session: &BrowserSession,
+        expected: Option<&str>,
+    ) -> Result<(), DatabaseError> {
+        let user = lookup_user(&mut *conn, session.user.id).await?;
+        assert_eq!(user.display_name.as_deref(), expected);
+        let user = lookup_user_by_username(&mut *conn, &session.user.username)
+            .await?
+            .unwrap();
+        assert_eq!(user.display_name.as_deref(), expected);
+        let session = lookup_active_session(&mut *conn, clock, session.id)
+            .await?
+            .unwrap();
+        assert_eq!(session.user.display_name.as_deref(), expected);
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_user_display_name(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let alice = add_user(
+            &mut conn,
+            &mut rng,
+            &clock,
+            "alice",
+            Some("Alice".to_owned()),
+        )
+        .await?;
+        assert_eq!(alice.display_name.as_deref(), Some("Alice"));
+        let session = start_session(&mut conn, &mut rng, &clock, alice.clone(), None, None).await?;
+        assert_display_name(&mut conn, &clock, &session, Some("Alice")).await?;
+
+        let alice = set_user_display_name(&mut conn, alice, Some("Alice A.".to_owned())).await?;
+        assert_eq!(alice.display_name.as_deref(), Some("Alice A."));
+        assert_display_name(&mut conn, &clock, &session, Some("Alice A.")).await?;
+
+        let alice = set_user_display_name(&mut conn, alice, None).await?;
+        assert_eq!(alice.display_name, None);
+        assert_display_name(&mut conn, &clock, &session, None).await?;
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_session_expiry(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let max_age = Some(Duration::hours(1));
+        let expiring =
+            start_session(&mut conn, &mut rng, &clock, user.clone(), None, max_age).await?;
+
+        // Sessions without a maximum age never expire
+        let forever = start_session(&mut conn, &mut rng, &clock, user.clone(), None, None).await?;
+        assert_eq!(count_active_sessions(&mut conn, &clock, &user).await?, 2);
+
+        clock.advance(Duration::hours(2));
+        assert!(lookup_active_session(&mut conn, &clock, expiring.id)
+            .await?
+            .is_none());
+        assert!(lookup_active_session(&mut conn, &clock, forever.id)
+            .await?
+            .is_some());
+        assert_eq!(count_active_sessions(&mut conn, &clock, &user).await?, 1);
+
+        // Only the expired session is purged
+        assert_eq!(purge_expired_sessions(&mut conn, &clock).await?, 1);
+        assert_eq!(purge_expired_sessions(&mut conn, &clock).await?, 0);
+        assert!(lookup_active_session(&mut conn, &clock, forever.id)
+            .await?
+            .is_some());
+
+        // But its events are kept in the history of the user
+        let (_, _, events) =
+            get_session_events(&mut conn, &user, None, None, Some(10), None).await?;
+        let mut events: Vec<_> = events
+            .into_iter()
+            .map(|event| (event.session_id, event.kind))
+            .collect();
+        events.sort_by_key(|(session_id, _)| *session_id);
+        assert_eq!(
+            events,
+            vec![
+                (None, SessionEventKind::Started),
+                (Some(forever.id), SessionEventKind::Started),
+            ]
+        );
+
+        Ok(())
+    }
+
+    /// Fetch a page of session IDs, newest first
+    async fn paginate_sessions(
+        conn: &mut PgConnection,
+        clock: &Clock,
+        user: &User,
+        before: Option<Ulid>,
+        after: Option<Ulid>,
+        first: Option<usize>,
+        last: Option<usize>,
+    ) -> Result<(bool, bool, Vec<Ulid>), DatabaseError> {
+        let (has_previous_page, has_next_page, page) = get_paginated_user_sessions(
+            conn,
+            clock,
+            user,
+            PaginationOrder::Descending,
+            before,
+            after,
+            first,
+            last,
+        )
+        .await?;
+        let page = page.into_iter().map(|session| session.id).collect();
+        Ok((has_previous_page, has_next_page, page))
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_paginate_sessions_newest_first(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let mut ids = Vec::new();
+        for _ in 0..5 {
+            let session =
+                start_session(&mut conn, &mut rng, &clock, user.clone(), None, None).await?;
+            ids.push(session.id);
+            clock.advance(Duration::minutes(1));
+        }
+        ids.reverse();
+
+        // Walk forward from the newest session
+        let (has_previous_page, has_next_page, page) =
+            paginate_sessions(&mut conn, &clock, &user, None, None, Some(2), None).await?;
+        assert!(!has_previous_page);
+        assert!(has_next_page);
+        assert_eq!(page, ids[0..2]);
+
+        let (has_previous_page, has_next_page, page) =
+            paginate_sessions(&mut conn, &clock, &user, None, Some(ids[1]), Some(2), None).await?;
+        assert!(!has_previous_page);
+        assert!(has_next_page);
+        assert_eq!(page, ids[2..4]);
+
+        let (has_previous_page, has_next_page, page) =
+            paginate_sessions(&mut conn, &clock, &user, None, Some(ids[3]), Some(2), None).await?;
+        assert!(!has_previous_page);
+        assert!(!has_next_page);
+        assert_eq!(page, ids[4..5]);
+
+        // Walk backward from the oldest session
+        let (has_previous_page, has_next_page, page) =
+            paginate_sessions(&mut conn, &clock, &user, None, None, None, Some(2)).await?;
+        assert!(has_previous_page);
+        assert!(!has_next_page);
+        assert_eq!(page, ids[3..5]);
+
+        let (has_previous_page, has_next_page, page) =
+            paginate_sessions(&mut conn, &clock, &user, Some(ids[3]), None, None, Some(2)).await?;
+        assert!(has_previous_page);
+        assert!(!has_next_page);
+        assert_eq!(page, ids[1..3]);
+
+        let (has_previous_page, has_next_page, page) =
+            paginate_sessions(&mut conn, &clock, &user, Some(ids[1]), None, None, Some(2)).await?;
+        assert!(!has_previous_page);
+        assert!(!has_next_page);
+        assert_eq!(page, ids[0..1]);
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_paginate_user_emails_newest_first(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let mut emails = Vec::new();
+        for email in ["a@example.com", "b@example.com", "c@example.com"] {
+            let email =
+                add_user_email(&mut conn, &mut rng, &clock, &user, email.to_owned()).await?;
+            emails.push(email.id);
+            clock.advance(Duration::minutes(1));
+        }
+
+        let (_, has_next_page, page) = get_paginated_user_emails(
+            &mut conn,
+            &user,
+            PaginationOrder::Ascending,
+            None,
+            None,
+            Some(2),
+            None,
+        )
+        .await?;
+        assert!(has_next_page);
+        let page: Vec<_> = page.into_iter().map(|e| e.id).collect();
+        assert_eq!(page, emails[0..2]);
+
+        emails.reverse();
+        let (_, has_next_page, page) = get_paginated_user_emails(
+            &mut conn,
+            &user,
+            PaginationOrder::Descending,
+            None,
+            None,
+            Some(2),
+            None,
+        )
+        .await?;
+        assert!(has_next_page);
+        let page: Vec<_> = page.into_iter().map(|e| e.id).collect();
+        assert_eq!(page, emails[0..2]);
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_get_confirmed_user_emails(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        add_user_email(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &user,
+            "b@example.com".to_owned(),
+        )
+        .await?;
+        let confirmed = add_user_email(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &user,
+            "a@example.com".to_owned(),
+        )
+        .await?;
+        mark_user_email_as_verified(&mut conn, &clock, confirmed).await?;
+
+        let emails = get_user_emails(&mut conn, &user).await?;
+        let emails: Vec<_> = emails.iter().map(|e| e.email.as_str()).collect();
+        assert_eq!(emails, ["a@example.com", "b@example.com"]);
+
+        let emails = get_confirmed_user_emails(&mut conn, &user).await?;
+        let emails: Vec<_> = emails.iter().map(|e| e.email.as_str()).collect();
+        assert_eq!(emails, ["a@example.com"]);
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_primary_user_email(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let user_email = add_user_email(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &user,
+            "alice@example.com".to_owned(),
+        )
+        .await?;
+
+        // Unconfirmed emails can't be set as primary
+        assert!(matches!(
+            set_user_email_as_primary(&mut conn, &user_email).await,
+            Err(DatabaseError::InvalidOperation { .. })
+        ));
+
+        let user_email = mark_user_email_as_verified(&mut conn, &clock, user_email).await?;
+        set_user_email_as_primary(&mut conn, &user_email).await?;
+        let user = lookup_user(&mut conn, user.id).await?;
+        assert_eq!(user.primary_email.map(|e| e.id), Some(user_email.id));
+
+        // Removing the email unsets it as primary
+        remove_user_email(&mut conn, user_email).await?;
+        let user = lookup_user(&mut conn, user.id).await?;
+        assert!(user.primary_email.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_can_send_verification_code(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let clock = Clock::default();
+        let mut conn = pool.acquire().await?;
+
+        let user = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let user_email = add_user_email(
+            &mut conn,
+            &mut rng,
+            &clock,
+            &user,
+            "alice@example.com".to_owned(),
+        )
+        .await?;
+
+        let interval = Duration::minutes(1);
+        assert!(can_send_verification_code(&mut conn, &clock, &user_email, interval).await?);
+
+        add_user_email_verification_code(
+            &mut conn,
+            &mut rng,
+            &clock,
+            user_email.clone(),
+            Duration::hours(8),
+            "123456".to_owned(),
+        )
+        .await?;
+        assert!(!can_send_verification_code(&mut conn, &clock, &user_email, interval).await?);
+        assert!(
+            can_send_verification_code(&mut conn, &clock, &user_email, Duration::zero()).await?
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Append-only history of what happened to browser sessions
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use mas_data_model::{BrowserSession, User};
+use rand::Rng;
+use sqlx::{PgExecutor, QueryBuilder};
+use thiserror::Error;
+use tracing::{info_span, Instrument};
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{
+    pagination::{process_page, QueryBuilderExt},
+    Clock, DatabaseError, DatabaseInconsistencyError,
+};
+
+/// What happened to a browser session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEventKind {
+    /// The session was started
+    Started,
+
+    /// The user authenticated in the session
+    Authenticated,
+
+    /// The session was ended
+    Ended,
+}
+
+impl SessionEventKind {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Started => "started",
+            Self::Authenticated => "authenticated",
+            Self::Ended => "ended",
+        }
+    }
+}
+
+impl std::fmt::Display for SessionEventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("invalid session event kind {0:?}")]
+pub struct InvalidSessionEventKind(String);
+
+impl FromStr for SessionEventKind {
+    type Err = InvalidSessionEventKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "started" => Ok(Self::Started),
+            "authenticated" => Ok(Self::Authenticated),
+            "ended" => Ok(Self::Ended),
+            s => Err(InvalidSessionEventKind(s.to_owned())),
+        }
+    }
+}
+
+/// An entry in the history of a browser session
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionEvent {
+    pub id: Ulid,
+
+    /// The session this event is about, unless it was deleted since then
+    pub session_id: Option<Ulid>,
+    pub user_id: Ulid,
+    pub kind: SessionEventKind,
+    pub metadata: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+struct SessionEventLookup {
+    user_session_event_id: Uuid,
+    user_session_id: Option<Uuid>,
+    user_id: Uuid,
+    kind: String,
+    metadata: serde_json::Value,
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<SessionEventLookup> for SessionEvent {
+    type Error = DatabaseInconsistencyError;
+
+    fn try_from(value: SessionEventLookup) -> Result<Self, Self::Error> {
+        let id = Ulid::from(value.user_session_event_id);
+        let kind = value.kind.parse().map_err(|e| {
+            DatabaseInconsistencyError::on("user_session_events")
+                .column("kind")
+                .row(id)
+                .source(e)
+        })?;
+
+        Ok(SessionEvent {
+            id,
+            session_id: value.user_session_id.map(Ulid::from),
+            user_id: Ulid::from(value.user_id),
+            kind,
+            metadata: value.metadata,
+            created_at: value.created_at,
+        })
+    }
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %user_session.id,
+        user.id = %user_session.user.id,
+        user_session_event.id,
+        user_session_event.kind = %kind,
+    ),
+    err,
+)]
+pub async fn record_session_event(
+    executor: impl PgExecutor<'_>,
+    mut rng: impl Rng + Send,
+    clock: &Clock,
+    user_session: &BrowserSession,
+    kind: SessionEventKind,
+    metadata: serde_json::Value,
+) -> Result<SessionEvent, DatabaseError> {
+    let created_at = clock.now();
+    let id = Ulid::from_datetime_with_source(created_at.into(), &mut rng);
+    tracing::Span::current().record("user_session_event.id", tracing::field::display(id));
+
+    sqlx::query!(
+        r#"
+            INSERT INTO user_session_events
+                (user_session_event_id, user_session_id, user_id, kind, metadata, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        Uuid::from(id),
+        Uuid::from(user_session.id),
+        Uuid::from(user_session.user.id),
+        kind.as_str(),
+        &metadata,
+        created_at,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(SessionEvent {
+        id,
+        session_id: Some(user_session.id),
+        user_id: user_session.user.id,
+        kind,
+        metadata,
+        created_at,
+    })
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        %user.id,
+        %user.username,
+    ),
+    err,
+)]
+pub async fn get_session_events(
+    executor: impl PgExecutor<'_>,
+    user: &User,
+    before: Option<Ulid>,
+    after: Option<Ulid>,
+    first: Option<usize>,
+    last: Option<usize>,
+) -> Result<(bool, bool, Vec<SessionEvent>), DatabaseError> {
+    let mut query = QueryBuilder::new(
+        r#"
+            SELECT
+                user_session_event_id,
+                user_session_id,
+                user_id,
+                kind,
+                metadata,
+                created_at
+            FROM user_session_events
+        "#,
+    );
+
+    query
+        .push(" WHERE user_id = ")
+        .push_bind(Uuid::from(user.id))
+        .generate_pagination("user_session_event_id", before, after, first, last)?;
+
+    let span = info_span!(
+        "Fetch paginated user session events",
+        db.statement = query.sql()
+    );
+    let page: Vec<SessionEventLookup> = query
+        .build_query_as()
+        .fetch_all(executor)
+        .instrument(span)
+        .await?;
+
+    let (has_previous_page, has_next_page, page) = process_page(page, first, last)?;
+
+    let page: Result<Vec<_>, _> = page.into_iter().map(TryInto::try_into).collect();
+    Ok((has_previous_page, has_next_page, page?))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use rand::SeedableRng;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::user::{add_user, start_session};
+
+    #[test]
+    fn test_kind_round_trip() {
+        for kind in [
+            SessionEventKind::Started,
+            SessionEventKind::Authenticated,
+            SessionEventKind::Ended,
+        ] {
+            assert_eq!(kind.to_string().parse::<SessionEventKind>().unwrap(), kind);
+        }
+
+        assert!("unknown".parse::<SessionEventKind>().is_err());
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_record_session_event(pool: PgPool) -> Result<(), DatabaseError> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut clock = Clock::mock(Utc.timestamp_opt(1_516_239_022, 0).unwrap());
+        let mut conn = pool.acquire().await?;
+
+        let alice = add_user(&mut conn, &mut rng, &clock, "alice", None).await?;
+        let bob = add_user(&mut conn, &mut rng, &clock, "bob", None).await?;
+        let session = start_session(&mut conn, &mut rng, &clock, alice.clone(), None, None).await?;
+        start_session(&mut conn, &mut rng, &clock, bob.clone(), None, None).await?;
+
+        // Starting the session recorded it
+        let (_, _, mut recorded) =
+            get_session_events(&mut conn, &alice, None, None, Some(10), None).await?;
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].kind, SessionEventKind::Started);
+        clock.advance(Duration::minutes(1));
+
+        for (kind, metadata) in [
+            (
+                SessionEventKind::Authenticated,
+                serde_json::json!({ "method": "password" }),
+            ),
+            (SessionEventKind::Ended, serde_json::json!({})),
+        ] {
+            let event =
+                record_session_event(&mut conn, &mut rng, &clock, &session, kind, metadata).await?;
+            assert_eq!(event.session_id, Some(session.id));
+            assert_eq!(event.user_id, alice.id);
+            recorded.push(event);
+            clock.advance(Duration::minutes(1));
+        }
+
+        // Only the events of the user are listed, oldest first
+        let (has_previous_page, has_next_page, events) =
+            get_session_events(&mut conn, &alice, None, None, Some(10), None).await?;
+        assert!(!has_previous_page);
+        assert!(!has_next_page);
+        assert_eq!(events, recorded);
+
+        let (has_previous_page, _, events) =
+            get_session_events(&mut conn, &alice, None, None, None, Some(1)).await?;
+        assert!(has_previous_page);
+        assert_eq!(events, recorded[2..]);
+
+        Ok(())
+    }
+}
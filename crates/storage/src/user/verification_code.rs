@@ -0,0 +1,87 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use rand::{distributions::Slice, Rng};
+use zeroize::Zeroizing;
+
+const DIGITS: &[char] = &['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
+
+// Leaves out the characters which are easy to mix up: 0/O and 1/I
+const ALPHANUMERIC: &[char] = &[
+    '2', '3', '4', '5', '6', '7', '8', '9', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'J', 'K', 'L',
+    'M', 'N', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z',
+];
+
+/// The format of the verification codes sent by email
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeKind {
+    /// `len` decimal digits, including leading zeros
+    Numeric { len: usize },
+
+    /// `len` uppercase letters and digits
+    Alphanumeric { len: usize },
+}
+
+impl Default for CodeKind {
+    fn default() -> Self {
+        Self::Numeric { len: 6 }
+    }
+}
+
+/// Generate a random verification code of the given kind
+///
+/// The code is wiped from memory once dropped.
+#[must_use]
+pub fn generate_verification_code(rng: impl Rng, kind: CodeKind) -> Zeroizing<String> {
+    let (charset, len) = match kind {
+        CodeKind::Numeric { len } => (DIGITS, len),
+        CodeKind::Alphanumeric { len } => (ALPHANUMERIC, len),
+    };
+
+    // The charsets are never empty, so this can't fail
+    let distribution = Slice::new(charset).unwrap();
+
+    let mut code = Zeroizing::new(String::with_capacity(len));
+    code.extend(rng.sample_iter(distribution).take(len));
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn generate_codes() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+
+        for _ in 0..100 {
+            let code = generate_verification_code(&mut rng, CodeKind::Numeric { len: 6 });
+            assert_eq!(code.len(), 6);
+            assert!(code.chars().all(|c| c.is_ascii_digit()));
+        }
+
+        for _ in 0..100 {
+            let code = generate_verification_code(&mut rng, CodeKind::Alphanumeric { len: 8 });
+            assert_eq!(code.len(), 8);
+            assert!(code.chars().all(|c| ALPHANUMERIC.contains(&c)));
+        }
+
+        // The numeric codes are kept as text, so leading zeros are preserved
+        assert!((0..1000)
+            .map(|_| generate_verification_code(&mut rng, CodeKind::Numeric { len: 6 }))
+            .any(|code| code.starts_with('0')));
+    }
+}
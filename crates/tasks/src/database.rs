@@ -44,6 +44,20 @@ impl Task for CleanupExpired {
                 error!(?error, "failed to cleanup expired tokens");
             }
         }
+
+        let res =
+            mas_storage::oauth2::client::cleanup_consumed_client_assertions(&self.0, &self.1).await;
+        match res {
+            Ok(0) => {
+                debug!("no client assertion to clean up");
+            }
+            Ok(count) => {
+                info!(count, "cleaned up expired client assertions");
+            }
+            Err(error) => {
+                error!(?error, "failed to cleanup expired client assertions");
+            }
+        }
     }
 }
 
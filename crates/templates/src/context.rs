@@ -198,14 +198,49 @@ impl TemplateContext for EmptyContext {
 #[derive(Serialize)]
 pub struct IndexContext {
     discovery_url: Url,
+    password_login_enabled: bool,
+    registration_enabled: bool,
+    providers: Vec<UpstreamOAuthProvider>,
 }
 
 impl IndexContext {
     /// Constructs the context for the index page from the OIDC discovery
     /// document URL
+    ///
+    /// Password login and registration are enabled by default, and no upstream
+    /// OAuth 2.0 providers are advertised.
     #[must_use]
     pub fn new(discovery_url: Url) -> Self {
-        Self { discovery_url }
+        Self {
+            discovery_url,
+            password_login_enabled: true,
+            registration_enabled: true,
+            providers: Vec::new(),
+        }
+    }
+
+    /// Set whether password login is enabled
+    #[must_use]
+    pub fn with_password_login(self, password_login_enabled: bool) -> Self {
+        Self {
+            password_login_enabled,
+            ..self
+        }
+    }
+
+    /// Set whether users can register new accounts
+    #[must_use]
+    pub fn with_registration(self, registration_enabled: bool) -> Self {
+        Self {
+            registration_enabled,
+            ..self
+        }
+    }
+
+    /// Set the upstream OAuth 2.0 providers
+    #[must_use]
+    pub fn with_upstream_providers(self, providers: Vec<UpstreamOAuthProvider>) -> Self {
+        Self { providers, ..self }
     }
 }
 
@@ -214,11 +249,16 @@ impl TemplateContext for IndexContext {
     where
         Self: Sized,
     {
-        vec![Self {
-            discovery_url: "https://example.com/.well-known/openid-configuration"
-                .parse()
-                .unwrap(),
-        }]
+        let discovery_url: Url = "https://example.com/.well-known/openid-configuration"
+            .parse()
+            .unwrap();
+
+        vec![
+            Self::new(discovery_url.clone()),
+            Self::new(discovery_url)
+                .with_password_login(false)
+                .with_registration(false),
+        ]
     }
 }
 
@@ -803,6 +843,8 @@ impl TemplateContext for UpstreamSuggestLink {
 #[derive(Serialize)]
 pub struct UpstreamRegister {
     login_link: String,
+    suggested_username: Option<String>,
+    suggested_email: Option<String>,
 }
 
 impl UpstreamRegister {
@@ -817,7 +859,29 @@ impl UpstreamRegister {
             .relative_url()
             .into();
 
-        Self { login_link }
+        Self {
+            login_link,
+            suggested_username: None,
+            suggested_email: None,
+        }
+    }
+
+    /// Set the username suggested by the upstream provider
+    #[must_use]
+    pub fn with_suggested_username(self, suggested_username: Option<String>) -> Self {
+        Self {
+            suggested_username,
+            ..self
+        }
+    }
+
+    /// Set the email address suggested by the upstream provider
+    #[must_use]
+    pub fn with_suggested_email(self, suggested_email: Option<String>) -> Self {
+        Self {
+            suggested_email,
+            ..self
+        }
     }
 }
 
@@ -827,7 +891,55 @@ impl TemplateContext for UpstreamRegister {
         Self: Sized,
     {
         let id = Ulid::from_datetime_with_source(now.into(), rng);
-        vec![Self::for_link_id(id)]
+        vec![
+            Self::for_link_id(id),
+            Self::for_link_id(id)
+                .with_suggested_username(Some("john".to_owned()))
+                .with_suggested_email(Some("john@example.com".to_owned())),
+        ]
+    }
+}
+
+/// Context used by the `pages/upstream_oauth2/cancelled.html` template
+#[derive(Serialize)]
+pub struct UpstreamCancelledContext {
+    login_link: String,
+    error_description: Option<String>,
+}
+
+impl UpstreamCancelledContext {
+    /// Constructs a new context, linking back to the login page
+    #[must_use]
+    pub fn new(post_auth_action: Option<PostAuthAction>) -> Self {
+        let login_link = mas_router::Login::from(post_auth_action)
+            .relative_url()
+            .into();
+
+        Self {
+            login_link,
+            error_description: None,
+        }
+    }
+
+    /// Set the error description given by the upstream provider
+    #[must_use]
+    pub fn with_error_description(self, error_description: Option<String>) -> Self {
+        Self {
+            error_description,
+            ..self
+        }
+    }
+}
+
+impl TemplateContext for UpstreamCancelledContext {
+    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![
+            Self::new(None),
+            Self::new(None).with_error_description(Some("The user denied the request".to_owned())),
+        ]
     }
 }
 
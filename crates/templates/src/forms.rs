@@ -56,6 +56,21 @@ pub enum FormError {
     /// Password fields don't match
     PasswordMismatch,
 
+    /// The account is locked after too many failed login attempts
+    AccountLocked,
+
+    /// Too many login attempts were made from this client recently
+    RateLimited,
+
+    /// The verification code is not valid
+    InvalidCode,
+
+    /// The verification code expired
+    CodeExpired,
+
+    /// The verification code was already used
+    CodeAlreadyUsed,
+
     /// There was an internal error
     Internal,
 
@@ -148,6 +163,20 @@ impl<K: FormField> FormState<K> {
         self
     }
 
+    /// Add the errors from another form state, on the form and on each field
+    pub fn add_errors_from(&mut self, other: Self) {
+        for (key, field) in other.fields {
+            self.fields
+                .entry(key)
+                .or_default()
+                .errors
+                .extend(field.errors);
+        }
+
+        self.errors.extend(other.errors);
+        self.has_errors |= other.has_errors;
+    }
+
     /// Returns `true` if the form has no error attached to it
     #[must_use]
     pub fn is_valid(&self) -> bool {
@@ -254,4 +283,55 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn form_state_add_errors_from() {
+        let form = TestForm {
+            foo: "john".to_owned(),
+            bar: "hunter2".to_owned(),
+        };
+        let mut state = form
+            .to_form_state()
+            .with_error_on_field(TestFormField::Foo, FieldError::Exists);
+        assert!(!state.is_valid());
+
+        let other = FormState::default()
+            .with_error_on_field(TestFormField::Foo, FieldError::Invalid)
+            .with_error_on_field(TestFormField::Bar, FieldError::Required)
+            .with_error_on_form(FormError::InvalidCredentials);
+        state.add_errors_from(other);
+
+        // Errors are appended to the existing ones, and values are kept
+        let state = serde_json::to_value(state).unwrap();
+        assert_eq!(
+            state,
+            serde_json::json!({
+                "errors": [{"kind": "invalid_credentials"}],
+                "fields": {
+                    "foo": {
+                        "errors": [{"kind": "exists"}, {"kind": "invalid"}],
+                        "value": "john",
+                    },
+                    "bar": {
+                        "errors": [{"kind": "required"}],
+                        "value": null
+                    },
+                }
+            })
+        );
+
+        // Merging a state without errors keeps the form valid
+        let mut state = TestForm {
+            foo: String::new(),
+            bar: String::new(),
+        }
+        .to_form_state();
+        assert!(state.is_valid());
+        state.add_errors_from(FormState::default());
+        assert!(state.is_valid());
+
+        // Merging a state with errors makes it invalid
+        state.add_errors_from(FormState::default().with_error_on_form(FormError::Internal));
+        assert!(!state.is_valid());
+    }
 }
@@ -49,8 +49,8 @@ pub use self::{
         EmailVerificationContext, EmailVerificationPageContext, EmptyContext, ErrorContext,
         FormPostContext, IndexContext, LoginContext, LoginFormField, PolicyViolationContext,
         PostAuthContext, PostAuthContextInner, ReauthContext, ReauthFormField, RegisterContext,
-        RegisterFormField, TemplateContext, UpstreamExistingLinkContext, UpstreamRegister,
-        UpstreamSuggestLink, WithCsrf, WithOptionalSession, WithSession,
+        RegisterFormField, TemplateContext, UpstreamCancelledContext, UpstreamExistingLinkContext,
+        UpstreamRegister, UpstreamSuggestLink, WithCsrf, WithOptionalSession, WithSession,
     },
     forms::{FieldError, FormError, FormField, FormState, ToFormState},
 };
@@ -241,6 +241,9 @@ register_templates! {
 
     /// Render the upstream register screen
     pub fn render_upstream_oauth2_do_register(WithCsrf<UpstreamRegister>) { "pages/upstream_oauth2/do_register.html" }
+
+    /// Render the upstream cancelled authorization screen
+    pub fn render_upstream_oauth2_cancelled(UpstreamCancelledContext) { "pages/upstream_oauth2/cancelled.html" }
 }
 
 impl Templates {
@@ -273,6 +276,7 @@ impl Templates {
         check::render_upstream_oauth2_suggest_link(self, now, rng).await?;
         check::render_upstream_oauth2_do_login(self, now, rng).await?;
         check::render_upstream_oauth2_do_register(self, now, rng).await?;
+        check::render_upstream_oauth2_cancelled(self, now, rng).await?;
         Ok(())
     }
 }
@@ -48,12 +48,12 @@ email_domain_allowed {
 	glob.match(allowed_domain, ["."], domain)
 }
 
-violation[{"field": "email", "msg": "email domain not allowed"}] {
+violation[{"field": "email", "msg": "email domain not allowed", "code": "email_domain_not_allowed"}] {
 	not email_domain_allowed
 }
 
 # Deny emails with their domain in the domains banlist
-violation[{"field": "email", "msg": "email domain not allowed"}] {
+violation[{"field": "email", "msg": "email domain not allowed", "code": "email_domain_banned"}] {
 	[_, domain] := split(input.user.email, "@")
 	some banned_domain in data.banned_domains
 	glob.match(banned_domain, ["."], domain)